@@ -0,0 +1,96 @@
+//! Retrieval benchmarks against a local HTTP provider, see `lassie::testing`.
+//!
+//! Run with `cargo bench --features testing`. Compare the results before and after bumping the
+//! embedded Lassie version to spot regressions in time-to-first-byte and throughput.
+
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use lassie::testing::{Fixture, MockProvider};
+use lassie::{Daemon, DaemonConfig};
+
+const CHUNK_SIZE: usize = 256 * 1024;
+const FILE_SIZE: usize = 64 * CHUNK_SIZE;
+
+/// Pseudo-random content, so that no two chunks are the same.
+#[allow(clippy::cast_possible_truncation)]
+fn file_content(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn fetch(daemon: &Daemon, path: &str) -> Vec<u8> {
+    let mut response = daemon
+        .serve_request(path, &[("Accept", "application/vnd.ipld.car")])
+        .expect("cannot serve the request");
+    assert_eq!(response.status(), 200, "unexpected response status");
+    let mut content = Vec::new();
+    response
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+    content
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let raw = Fixture::raw_block(&file_content(1024));
+    let file = Fixture::unixfs_file(&file_content(FILE_SIZE), CHUNK_SIZE);
+    let raw_root = raw.root().to_string();
+    let file_root = file.root().to_string();
+    let file_len = file.car().len() as u64;
+    let provider = MockProvider::start(vec![raw, file]).expect("cannot start the provider");
+
+    let daemon = Daemon::start(DaemonConfig {
+        disable_listener: true,
+        disable_ipni: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+
+    let query = provider.query();
+    let raw_path = format!("/ipfs/{raw_root}?dag-scope=block&{query}");
+    let all_path = format!("/ipfs/{file_root}?dag-scope=all&{query}");
+    let entity_path = format!("/ipfs/{file_root}?dag-scope=entity&{query}");
+
+    // Fail early when the fixtures are not accepted by Lassie
+    assert!(!fetch(&daemon, &raw_path).is_empty());
+    assert!(!fetch(&daemon, &all_path).is_empty());
+
+    let mut ttfb = c.benchmark_group("ttfb");
+    ttfb.bench_function("raw_block", |b| {
+        b.iter_custom(|iters| {
+            let mut total = Duration::ZERO;
+            for _ in 0..iters {
+                let start = Instant::now();
+                let mut response = daemon
+                    .serve_request(&raw_path, &[("Accept", "application/vnd.ipld.car")])
+                    .expect("cannot serve the request");
+                let mut first = [0u8; 1];
+                response
+                    .read_exact(&mut first)
+                    .expect("cannot read response body");
+                total += start.elapsed();
+                std::io::copy(&mut response, &mut std::io::sink()).unwrap();
+            }
+            total
+        });
+    });
+    ttfb.finish();
+
+    let mut throughput = c.benchmark_group("throughput");
+    throughput.sample_size(20);
+    throughput.throughput(Throughput::Bytes(file_len));
+    throughput.bench_function("car_all", |b| b.iter(|| fetch(&daemon, &all_path)));
+    throughput.bench_function("car_entity", |b| b.iter(|| fetch(&daemon, &entity_path)));
+    throughput.finish();
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
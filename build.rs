@@ -4,7 +4,7 @@ use std::process::Command;
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=go.sum");
-    println!("cargo:rerun-if-changed=go-lib/lassie.go");
+    println!("cargo:rerun-if-changed=go-lib");
 
     let v = get_lassie_version();
     // assert_eq!(
@@ -15,6 +15,26 @@ fn main() {
     println!("cargo:rustc-env=LASSIE_VERSION={v}-rs");
 
     build_lassie();
+    export_metadata();
+}
+
+/// Tell the build scripts of crates depending on us where to find the Go library and its C header.
+/// Cargo exposes these values as `DEP_GOLASSIE_INCLUDE` and `DEP_GOLASSIE_LIB`. There is no
+/// `DEP_GOLASSIE_LIB` on MSVC, the DLL is embedded in our library and there's no import library to
+/// link against.
+fn export_metadata() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    // The header generated by `go build` includes our handwritten header, put them side by side
+    let header = format!("{out_dir}/lassie-ffi.h");
+    std::fs::copy("go-lib/lassie-ffi.h", &header)
+        .unwrap_or_else(|_| panic!("cannot copy lassie-ffi.h to {header}"));
+
+    println!("cargo:include={out_dir}");
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|target_env| target_env == "msvc");
+    if !msvc {
+        println!("cargo:lib={out_dir}");
+    }
 }
 
 #[cfg(not(all(target_os = "windows", target_env = "msvc")))]
@@ -40,7 +60,7 @@ fn build_lassie() {
             "-o",
             out_file,
             "-buildmode=c-archive",
-            "lassie-ffi.go",
+            ".",
         ])
         .env("GOARCH", goarch)
         // We must explicitly enable CGO when cross-compiling
@@ -91,7 +111,7 @@ fn build_lassie() {
             "-o",
             &out_file,
             "-buildmode=c-shared",
-            "lassie-ffi.go",
+            ".",
         ])
         .status()
         .expect(
@@ -101,32 +121,13 @@ fn build_lassie() {
         );
     assert!(status.success(), "`go build` failed");
 
-    eprintln!("Building {out_file}.lib");
-
-    let def_file = format!("{out_dir}\\golassie.def");
-    std::fs::copy("go-lib\\golassie.def", &def_file)
-        .unwrap_or_else(|_| panic!("cannot copy golassie.def to {def_file}"));
-    println!("cargo:rerun-if-changed=go-lib/golassie.def");
-
-    let mut lib_cmd = cc::windows_registry::find(&env::var("TARGET").unwrap(), "lib.exe")
-        .expect("cannot find the path to MSVC link.exe");
-
-    let status = lib_cmd
-        .args([format!("/def:{def_file}"), format!("/out:{out_file}.lib")])
-        .status()
-        .expect("cannot execute 'link.exe'");
-    assert!(status.success(), "`link.exe` failed");
-
-    println!("cargo:rustc-link-search=native={out_dir}");
-
-    // UGLY HACK:
-    // - Rust/Cargo does not support resource files, we must copy the DLL manually
-    // - Cargo does not tell us what is the target output directory. The dir can be `target\debug`,
-    //   `target\x86_64-pc-windows-msvc\debug`, but also some custom dir configured via ENV vars
-    // Related: https://github.com/rust-lang/cargo/issues/5305
-    let dll_out = format!("{out_dir}\\..\\..\\..\\golassie.dll");
-    std::fs::copy(&out_file, &dll_out)
-        .unwrap_or_else(|_| panic!("cannot copy {out_file} to {dll_out}"));
+    // The DLL is embedded in the Rust library and extracted at runtime, see src/golassie.rs.
+    // The hash makes the name of the extracted file unique for each build of the DLL.
+    let dll = std::fs::read(&out_file).unwrap_or_else(|_| panic!("cannot read {out_file}"));
+    let hash = dll.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    println!("cargo:rustc-env=GOLASSIE_DLL_HASH={hash:016x}");
 }
 
 const GO_SUM_LASSIE: &str = "github.com/filecoin-project/lassie v";
@@ -0,0 +1,158 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"bufio"
+	"errors"
+	"net"
+	"net/http"
+	"strings"
+	"sync"
+	"time"
+	"unsafe"
+)
+
+// accessLogMtx protects the registered callback. Callbacks run with the read lock held, so that
+// ClearAccessLogCallback waits for running callbacks before the Rust side frees ctx.
+var accessLogMtx sync.RWMutex
+var accessLogCallback C.access_log_callback_t
+var accessLogCtx unsafe.Pointer
+
+// SetAccessLogCallback registers the function called after every request handled by the daemon,
+// replacing the previous callback.
+//
+//export SetAccessLogCallback
+func SetAccessLogCallback(callback C.access_log_callback_t, ctx unsafe.Pointer) {
+	accessLogMtx.Lock()
+	defer accessLogMtx.Unlock()
+	accessLogCallback = callback
+	accessLogCtx = ctx
+}
+
+// ClearAccessLogCallback unregisters the callback registered with ctx. It does nothing when a
+// different callback has been registered since. When this function returns, the callback is not
+// running and will not be called again.
+//
+//export ClearAccessLogCallback
+func ClearAccessLogCallback(ctx unsafe.Pointer) {
+	accessLogMtx.Lock()
+	defer accessLogMtx.Unlock()
+	if accessLogCtx == ctx {
+		accessLogCallback = nil
+		accessLogCtx = nil
+	}
+}
+
+// withAccessLog reports every request to the registered access log callback once the response
+// is complete. It must wrap all other middlewares, including requireAccessToken, so that rejected
+// requests are reported too.
+func withAccessLog(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		accessLogMtx.RLock()
+		enabled := accessLogCallback != nil
+		accessLogMtx.RUnlock()
+		if !enabled {
+			next.ServeHTTP(res, req)
+			return
+		}
+
+		started := time.Now()
+		w := &accessLogWriter{ResponseWriter: res, status: http.StatusOK}
+		next.ServeHTTP(w, req)
+
+		outcome := C.ACCESS_LOG_SUCCEEDED
+		switch {
+		case w.hijacked:
+			// Lassie hijacks the connection to abort a response that has already started
+			outcome = C.ACCESS_LOG_ABORTED
+		case req.Context().Err() != nil:
+			outcome = C.ACCESS_LOG_CANCELLED
+		case w.status >= 400:
+			outcome = C.ACCESS_LOG_FAILED
+		}
+		reportAccess(req, w, time.Since(started), outcome)
+	})
+}
+
+func reportAccess(req *http.Request, w *accessLogWriter, elapsed time.Duration, outcome int) {
+	retrievalId := C.CString(w.Header().Get(retrievalIdHeader))
+	defer C.free(unsafe.Pointer(retrievalId))
+	remoteAddr := C.CString(req.RemoteAddr)
+	defer C.free(unsafe.Pointer(remoteAddr))
+	method := C.CString(req.Method)
+	defer C.free(unsafe.Pointer(method))
+	path := C.CString(req.URL.Path)
+	defer C.free(unsafe.Pointer(path))
+	cid := C.CString("")
+	if strings.HasPrefix(req.URL.Path, "/ipfs/") {
+		C.free(unsafe.Pointer(cid))
+		cid = C.CString(cidFromPath(req.URL.Path))
+	}
+	defer C.free(unsafe.Pointer(cid))
+
+	record := C.access_log_record_t{
+		retrieval_id: retrievalId,
+		remote_addr:  remoteAddr,
+		method:       method,
+		path:         path,
+		cid:          cid,
+		status:       C.uint16_t(w.status),
+		bytes:        C.uint64_t(w.bytes),
+		duration:     C.int64_t(elapsed),
+		outcome:      C.uint8_t(outcome),
+	}
+
+	accessLogMtx.RLock()
+	defer accessLogMtx.RUnlock()
+	if accessLogCallback != nil {
+		C.call_access_log_callback(accessLogCallback, accessLogCtx, &record)
+	}
+}
+
+// accessLogWriter records the status code and the number of body bytes of a response.
+type accessLogWriter struct {
+	http.ResponseWriter
+	status      int
+	wroteHeader bool
+	bytes       uint64
+	hijacked    bool
+}
+
+func (w *accessLogWriter) WriteHeader(status int) {
+	if !w.wroteHeader {
+		w.wroteHeader = true
+		w.status = status
+	}
+	w.ResponseWriter.WriteHeader(status)
+}
+
+func (w *accessLogWriter) Write(p []byte) (int, error) {
+	w.wroteHeader = true
+	n, err := w.ResponseWriter.Write(p)
+	w.bytes += uint64(n)
+	return n, err
+}
+
+func (w *accessLogWriter) Flush() {
+	if f, ok := w.ResponseWriter.(http.Flusher); ok {
+		f.Flush()
+	}
+}
+
+// Hijack must be forwarded, Lassie hijacks the connection to abort response streams.
+func (w *accessLogWriter) Hijack() (net.Conn, *bufio.ReadWriter, error) {
+	if h, ok := w.ResponseWriter.(http.Hijacker); ok {
+		w.hijacked = true
+		return h.Hijack()
+	}
+	return nil, nil, errors.New("the response writer does not support hijacking")
+}
+
+func (w *accessLogWriter) Unwrap() http.ResponseWriter {
+	return w.ResponseWriter
+}
@@ -0,0 +1,277 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"net"
+	"net/http"
+	"net/http/pprof"
+	"time"
+
+	"github.com/libp2p/go-libp2p/core/host"
+	"github.com/libp2p/go-libp2p/p2p/net/connmgr"
+)
+
+// adminConfig is the configuration dump returned by `GET /config`. Secrets like access tokens are
+// never included.
+type adminConfig struct {
+	Port                           uint16   `json:"port"`
+	TempDir                        string   `json:"temp_dir"`
+	MaxBlocks                      uint64   `json:"max_blocks"`
+	MaxConcurrentRequests          uint32   `json:"max_concurrent_requests"`
+	MaxConcurrentRetrievals        uint32   `json:"max_concurrent_retrievals"`
+	MaxQueuedRetrievals            int64    `json:"max_queued_retrievals"`
+	ProviderTimeout                string   `json:"provider_timeout"`
+	HttpProviderTimeout            string   `json:"http_provider_timeout"`
+	OtlpEndpoint                   string   `json:"otlp_endpoint"`
+	GlobalTimeout                  string   `json:"global_timeout"`
+	AccessTokenConfigured          bool     `json:"access_token_configured"`
+	UserAgent                      string   `json:"user_agent"`
+	DisableListener                bool     `json:"disable_listener"`
+	ListenerFdConfigured           bool     `json:"listener_fd_configured"`
+	ReusePort                      bool     `json:"reuse_port"`
+	IdleShutdown                   string   `json:"idle_shutdown"`
+	EventRecorderURL               string   `json:"event_recorder_url,omitempty"`
+	BootstrapPeers                 []string `json:"bootstrap_peers"`
+	PreconnectProviders            []string `json:"preconnect_providers"`
+	AllowedClientIps               []string `json:"allowed_client_ips"`
+	DisableIpni                    bool     `json:"disable_ipni"`
+	DisableDht                     bool     `json:"disable_dht"`
+	DisableCandidateDiscovery      bool     `json:"disable_candidate_discovery"`
+	DelegatedRoutingURL            string   `json:"delegated_routing_url,omitempty"`
+	ConnMgrLowWater                uint32   `json:"conn_mgr_low_water"`
+	ConnMgrHighWater               uint32   `json:"conn_mgr_high_water"`
+	ConnMgrGracePeriod             string   `json:"conn_mgr_grace_period"`
+	BitswapConcurrency             uint32   `json:"bitswap_concurrency"`
+	BitswapConcurrencyPerRetrieval uint32   `json:"bitswap_concurrency_per_retrieval"`
+	BlockCacheDir                  string   `json:"block_cache_dir,omitempty"`
+	BlockCacheMaxSize              uint64   `json:"block_cache_max_size"`
+	MmapCarStoreSize               uint64   `json:"mmap_car_store_size"`
+	MaxDownloadRate                uint64   `json:"max_download_rate"`
+	MaxDownloadRatePerRetrieval    uint64   `json:"max_download_rate_per_retrieval"`
+	Http1Only                      bool     `json:"http1_only"`
+	HttpMaxConnsPerHost            uint32   `json:"http_max_conns_per_host"`
+	HttpMaxIdleConnsPerHost        uint32   `json:"http_max_idle_conns_per_host"`
+	HttpClientCertFile             string   `json:"http_client_cert_file,omitempty"`
+	HttpInsecureSkipVerify         bool     `json:"http_insecure_skip_verify"`
+	ExtraRootCerts                 []string `json:"extra_root_certs"`
+	Libp2pTransports               uint32   `json:"libp2p_transports"`
+	GoMemoryLimit                  uint64   `json:"go_memory_limit"`
+	GoMaxProcs                     uint32   `json:"go_max_procs"`
+	AdminPprof                     bool     `json:"admin_pprof"`
+	ExtraListeners                 []string `json:"extra_listeners"`
+}
+
+func newAdminConfig(cfg *C.daemon_config_t) adminConfig {
+	return adminConfig{
+		Port:                           uint16(cfg.port),
+		TempDir:                        C.GoString(cfg.temp_dir),
+		MaxBlocks:                      uint64(cfg.max_blocks),
+		MaxConcurrentRequests:          uint32(cfg.max_concurrent_requests),
+		MaxConcurrentRetrievals:        uint32(cfg.max_concurrent_retrievals),
+		MaxQueuedRetrievals:            int64(cfg.max_queued_retrievals),
+		ProviderTimeout:                time.Duration(cfg.provider_timeout).String(),
+		HttpProviderTimeout:            time.Duration(cfg.http_provider_timeout).String(),
+		OtlpEndpoint:                   C.GoString(cfg.otlp_endpoint),
+		GlobalTimeout:                  time.Duration(cfg.global_timeout).String(),
+		AccessTokenConfigured:          C.GoString(cfg.access_token) != "",
+		UserAgent:                      C.GoString(cfg.lassie_user_agent),
+		DisableListener:                bool(cfg.disable_listener),
+		ListenerFdConfigured:           cfg.listener_fd >= 0,
+		ReusePort:                      bool(cfg.reuse_port),
+		IdleShutdown:                   time.Duration(cfg.idle_shutdown).String(),
+		EventRecorderURL:               C.GoString(cfg.event_recorder_url),
+		BootstrapPeers:                 goStrings(cfg.bootstrap_peers, cfg.bootstrap_peers_len),
+		PreconnectProviders:            goStrings(cfg.preconnect_providers, cfg.preconnect_providers_len),
+		AllowedClientIps:               allowedClientIps(cfg),
+		DisableIpni:                    bool(cfg.disable_ipni),
+		DisableDht:                     bool(cfg.disable_dht),
+		DisableCandidateDiscovery:      bool(cfg.disable_candidate_discovery),
+		DelegatedRoutingURL:            C.GoString(cfg.delegated_routing_url),
+		ConnMgrLowWater:                uint32(cfg.conn_mgr_low_water),
+		ConnMgrHighWater:               uint32(cfg.conn_mgr_high_water),
+		ConnMgrGracePeriod:             time.Duration(cfg.conn_mgr_grace_period).String(),
+		BitswapConcurrency:             uint32(cfg.bitswap_concurrency),
+		BitswapConcurrencyPerRetrieval: uint32(cfg.bitswap_concurrency_per_retrieval),
+		BlockCacheDir:                  C.GoString(cfg.block_cache_dir),
+		BlockCacheMaxSize:              uint64(cfg.block_cache_max_size),
+		MmapCarStoreSize:               uint64(cfg.mmap_car_store_size),
+		MaxDownloadRate:                uint64(cfg.max_download_rate),
+		MaxDownloadRatePerRetrieval:    uint64(cfg.max_download_rate_per_retrieval),
+		Http1Only:                      bool(cfg.http1_only),
+		HttpMaxConnsPerHost:            uint32(cfg.http_max_conns_per_host),
+		HttpMaxIdleConnsPerHost:        uint32(cfg.http_max_idle_conns_per_host),
+		HttpClientCertFile:             C.GoString(cfg.http_client_cert_file),
+		HttpInsecureSkipVerify:         bool(cfg.http_insecure_skip_verify),
+		ExtraRootCerts:                 goStrings(cfg.extra_root_certs, cfg.extra_root_certs_len),
+		Libp2pTransports:               uint32(cfg.libp2p_transports),
+		GoMemoryLimit:                  uint64(cfg.go_memory_limit),
+		GoMaxProcs:                     uint32(cfg.go_max_procs),
+		AdminPprof:                     bool(cfg.admin_pprof),
+		ExtraListeners:                 goStrings(cfg.extra_listener_addresses, cfg.extra_listeners_len),
+	}
+}
+
+// allowedClientIps returns nil when all clients are accepted, so that the JSON dump tells "any
+// client" (null) from "no client" ([]).
+func allowedClientIps(cfg *C.daemon_config_t) []string {
+	if !cfg.restrict_client_ips {
+		return nil
+	}
+	return append([]string{}, goStrings(cfg.allowed_client_ips, cfg.allowed_client_ips_len)...)
+}
+
+type adminStats struct {
+	UptimeSeconds    float64 `json:"uptime_seconds"`
+	ActiveRetrievals int     `json:"active_retrievals"`
+	TotalRetrievals  uint64  `json:"total_retrievals"`
+	BytesSent        uint64  `json:"bytes_sent"`
+}
+
+// adminLibp2p describes the running libp2p host, as opposed to adminConfig which reports the
+// settings the host was created with.
+type adminLibp2p struct {
+	PeerId      string   `json:"peer_id"`
+	ListenAddrs []string `json:"listen_addrs"`
+	Connections int      `json:"connections"`
+	// Nil when libp2p runs without a connection manager
+	ConnManager *adminConnManager `json:"conn_manager"`
+}
+
+type adminConnManager struct {
+	LowWater    int    `json:"low_water"`
+	HighWater   int    `json:"high_water"`
+	GracePeriod string `json:"grace_period"`
+}
+
+func newAdminLibp2p(h host.Host) adminLibp2p {
+	info := adminLibp2p{
+		PeerId:      h.ID().String(),
+		ListenAddrs: []string{},
+		Connections: len(h.Network().Conns()),
+	}
+	for _, addr := range h.Network().ListenAddresses() {
+		info.ListenAddrs = append(info.ListenAddrs, addr.String())
+	}
+	if cm, ok := h.ConnManager().(*connmgr.BasicConnMgr); ok {
+		cmInfo := cm.GetInfo()
+		info.ConnManager = &adminConnManager{
+			LowWater:    cmInfo.LowWater,
+			HighWater:   cmInfo.HighWater,
+			GracePeriod: cmInfo.GracePeriod.String(),
+		}
+	}
+	return info
+}
+
+type adminRetrieval struct {
+	Id             string  `json:"id"`
+	Cid            string  `json:"cid"`
+	Provider       string  `json:"provider,omitempty"`
+	ElapsedSeconds float64 `json:"elapsed_seconds"`
+	BytesSent      uint64  `json:"bytes_sent"`
+	BlocksReceived uint64  `json:"blocks_received"`
+}
+
+// newAdminServer creates the control listener configured by admin_network and admin_address.
+// It returns nil when the admin listener is not configured.
+func newAdminServer(ctx context.Context, cfg *C.daemon_config_t, h host.Host) (*http.Server, net.Listener, error) {
+	network := C.GoString(cfg.admin_network)
+	if network == "" {
+		return nil, nil, nil
+	}
+	address := C.GoString(cfg.admin_address)
+	listener, err := net.Listen(network, address)
+	if err != nil {
+		return nil, nil, err
+	}
+	debug(fmt.Sprintf("Admin listener: %s %s", network, listener.Addr()))
+
+	started := time.Now()
+	config := newAdminConfig(cfg)
+
+	mux := http.NewServeMux()
+	mux.HandleFunc("GET /stats", func(res http.ResponseWriter, req *http.Request) {
+		retrievalsMtx.Lock()
+		active := len(retrievals)
+		retrievalsMtx.Unlock()
+		writeJson(res, adminStats{
+			UptimeSeconds:    time.Since(started).Seconds(),
+			ActiveRetrievals: active,
+			TotalRetrievals:  totalRetrievals.Load(),
+			BytesSent:        totalBytesSent.Load(),
+		})
+	})
+	mux.HandleFunc("GET /retrievals", func(res http.ResponseWriter, req *http.Request) {
+		retrievalsMtx.Lock()
+		list := make([]adminRetrieval, 0, len(retrievals))
+		for _, r := range retrievals {
+			list = append(list, adminRetrieval{
+				Id:             r.id,
+				Cid:            r.cid,
+				Provider:       r.provider,
+				ElapsedSeconds: time.Since(r.started).Seconds(),
+				BytesSent:      r.bytesSent.Load(),
+				BlocksReceived: r.blocksReceived.Load(),
+			})
+		}
+		retrievalsMtx.Unlock()
+		writeJson(res, list)
+	})
+	mux.HandleFunc("GET /config", func(res http.ResponseWriter, req *http.Request) {
+		writeJson(res, config)
+	})
+	mux.HandleFunc("GET /libp2p", func(res http.ResponseWriter, req *http.Request) {
+		writeJson(res, newAdminLibp2p(h))
+	})
+	mux.HandleFunc("POST /shutdown", func(res http.ResponseWriter, req *http.Request) {
+		debug("SHUTDOWN REQUESTED VIA THE ADMIN LISTENER")
+		res.WriteHeader(http.StatusAccepted)
+		// Shutting down the admin server waits for this request to finish, we must not block
+		go shutdownFromAdmin()
+	})
+	if cfg.admin_pprof {
+		debug("Serving pprof endpoints on the admin listener")
+		mux.HandleFunc("GET /debug/pprof/", pprof.Index)
+		mux.HandleFunc("GET /debug/pprof/cmdline", pprof.Cmdline)
+		mux.HandleFunc("GET /debug/pprof/profile", pprof.Profile)
+		mux.HandleFunc("GET /debug/pprof/symbol", pprof.Symbol)
+		mux.HandleFunc("POST /debug/pprof/symbol", pprof.Symbol)
+		mux.HandleFunc("GET /debug/pprof/trace", pprof.Trace)
+	}
+
+	server := &http.Server{
+		BaseContext: func(listener net.Listener) context.Context { return ctx },
+		Handler:     requireAccessToken(C.GoString(cfg.admin_access_token), mux),
+	}
+	return server, listener, nil
+}
+
+// shutdownFromAdmin stops the daemon on behalf of the `POST /shutdown` endpoint. The Rust side
+// still owns the daemon handle, we remember the shutdown so that the subsequent StopDaemon call
+// made when the handle is dropped succeeds.
+func shutdownFromAdmin() {
+	mtx.Lock()
+	defer mtx.Unlock()
+	if daemon == nil {
+		return
+	}
+	if err := stopDaemon(); err != nil {
+		debug("CANNOT STOP LASSIE DAEMON", err)
+	}
+	stoppedInternally = true
+}
+
+func writeJson(res http.ResponseWriter, value any) {
+	res.Header().Set("Content-Type", "application/json")
+	if err := json.NewEncoder(res).Encode(value); err != nil {
+		debug("CANNOT WRITE ADMIN RESPONSE", err)
+	}
+}
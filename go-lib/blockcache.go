@@ -0,0 +1,358 @@
+package main
+
+import (
+	"bytes"
+	"container/list"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"io/fs"
+	"os"
+	"path/filepath"
+	"sort"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/ipfs/go-cid"
+	"github.com/ipfs/go-unixfsnode"
+	dagpb "github.com/ipld/go-codec-dagpb"
+	"github.com/ipld/go-ipld-prime/datamodel"
+	"github.com/ipld/go-ipld-prime/linking"
+	cidlink "github.com/ipld/go-ipld-prime/linking/cid"
+	"github.com/ipld/go-ipld-prime/node/basicnode"
+	"github.com/ipld/go-ipld-prime/traversal"
+	"github.com/ipld/go-ipld-prime/traversal/selector"
+)
+
+var errNotCached = errors.New("the block is not cached")
+
+// blockCache is a persistent size-limited blockstore shared by all retrievals. Blocks are stored
+// as individual files named after the hex-encoded multihash, the least recently used blocks are
+// evicted when the cache grows over maxSize.
+type blockCache struct {
+	dir     string
+	maxSize uint64
+
+	mtx     sync.Mutex
+	size    uint64
+	lru     *list.List // of *cacheEntry, most recently used first
+	entries map[string]*list.Element
+}
+
+type cacheEntry struct {
+	key  string
+	size uint64
+}
+
+func newBlockCache(dir string, maxSize uint64) (*blockCache, error) {
+	if err := os.MkdirAll(dir, 0o755); err != nil {
+		return nil, fmt.Errorf("cannot create block cache directory: %w", err)
+	}
+
+	c := &blockCache{
+		dir:     dir,
+		maxSize: maxSize,
+		lru:     list.New(),
+		entries: make(map[string]*list.Element),
+	}
+
+	// Load the blocks stored by previous runs, using the modification time as an approximation
+	// of the last use
+	type storedBlock struct {
+		key     string
+		size    uint64
+		modTime int64
+	}
+	var stored []storedBlock
+	err := filepath.WalkDir(dir, func(path string, entry fs.DirEntry, err error) error {
+		if err != nil || entry.IsDir() || strings.HasPrefix(entry.Name(), ".") {
+			return err
+		}
+		info, err := entry.Info()
+		if err != nil {
+			return err
+		}
+		stored = append(stored, storedBlock{entry.Name(), uint64(info.Size()), info.ModTime().UnixNano()})
+		return nil
+	})
+	if err != nil {
+		return nil, fmt.Errorf("cannot read block cache directory: %w", err)
+	}
+	sort.Slice(stored, func(i, j int) bool { return stored[i].modTime > stored[j].modTime })
+	for _, b := range stored {
+		c.entries[b.key] = c.lru.PushBack(&cacheEntry{b.key, b.size})
+		c.size += b.size
+	}
+	c.mtx.Lock()
+	c.evict()
+	c.mtx.Unlock()
+
+	debug(fmt.Sprintf("Block cache %s: %d blocks, %d bytes", dir, len(stored), c.size))
+	return c, nil
+}
+
+func (c *blockCache) path(key string) string {
+	// Spread the files over 256 subdirectories to keep the directories small
+	return filepath.Join(c.dir, key[len(key)-2:], key)
+}
+
+func cacheKey(id cid.Cid) string {
+	return id.Hash().HexString()
+}
+
+// get returns the cached block data, or nil when the block is not cached.
+func (c *blockCache) get(id cid.Cid) []byte {
+	key := cacheKey(id)
+	c.mtx.Lock()
+	elem, ok := c.entries[key]
+	if ok {
+		c.lru.MoveToFront(elem)
+	}
+	c.mtx.Unlock()
+	if !ok {
+		return nil
+	}
+
+	data, err := os.ReadFile(c.path(key))
+	if err == nil {
+		// Guard against disk corruption, we must never serve invalid blocks
+		var sum cid.Cid
+		sum, err = id.Prefix().Sum(data)
+		if err == nil && !bytes.Equal(sum.Hash(), id.Hash()) {
+			err = fmt.Errorf("hash mismatch")
+		}
+	}
+	if err != nil {
+		debug("BLOCK CACHE READ FAILED", id, err)
+		c.remove(key)
+		return nil
+	}
+	return data
+}
+
+func (c *blockCache) put(id cid.Cid, data []byte) {
+	key := cacheKey(id)
+	size := uint64(len(data))
+	if size > c.maxSize {
+		return
+	}
+	c.mtx.Lock()
+	_, exists := c.entries[key]
+	c.mtx.Unlock()
+	if exists {
+		return
+	}
+
+	path := c.path(key)
+	if err := writeFileAtomic(path, data); err != nil {
+		debug("BLOCK CACHE WRITE FAILED", id, err)
+		return
+	}
+
+	c.mtx.Lock()
+	defer c.mtx.Unlock()
+	if _, exists := c.entries[key]; exists {
+		return
+	}
+	c.entries[key] = c.lru.PushFront(&cacheEntry{key, size})
+	c.size += size
+	c.evict()
+}
+
+func (c *blockCache) remove(key string) {
+	c.mtx.Lock()
+	defer c.mtx.Unlock()
+	if elem, ok := c.entries[key]; ok {
+		c.removeElement(elem)
+	}
+}
+
+// evict removes the least recently used blocks until the cache fits into maxSize.
+// The caller must hold the mutex.
+func (c *blockCache) evict() {
+	for c.size > c.maxSize {
+		c.removeElement(c.lru.Back())
+	}
+}
+
+// The caller must hold the mutex.
+func (c *blockCache) removeElement(elem *list.Element) {
+	entry := c.lru.Remove(elem).(*cacheEntry)
+	delete(c.entries, entry.key)
+	c.size -= entry.size
+	if err := os.Remove(c.path(entry.key)); err != nil && !os.IsNotExist(err) {
+		debug("BLOCK CACHE EVICTION FAILED", entry.key, err)
+	}
+}
+
+func writeFileAtomic(path string, data []byte) error {
+	if err := os.MkdirAll(filepath.Dir(path), 0o755); err != nil {
+		return err
+	}
+	tmp, err := os.CreateTemp(filepath.Dir(path), ".tmp-*")
+	if err != nil {
+		return err
+	}
+	_, err = tmp.Write(data)
+	if closeErr := tmp.Close(); err == nil {
+		err = closeErr
+	}
+	if err == nil {
+		err = os.Rename(tmp.Name(), path)
+	}
+	if err != nil {
+		os.Remove(tmp.Name())
+	}
+	return err
+}
+
+// cachingFetcher wraps the request link system so that blocks are looked up in the cache before
+// Lassie asks the providers for them, and all received blocks are added to the cache. Requests
+// for DAGs held by the cache entirely are served without contacting any provider.
+type cachingFetcher struct {
+	fetcher types.Fetcher
+	cache   *blockCache
+}
+
+func (f cachingFetcher) Fetch(ctx context.Context, request types.RetrievalRequest, opts ...types.FetchOption) (*types.RetrievalStats, error) {
+	lsys := request.LinkSystem
+	readOpener := lsys.StorageReadOpener
+	writeOpener := lsys.StorageWriteOpener
+
+	lsys.StorageReadOpener = func(lctx linking.LinkContext, lnk datamodel.Link) (io.Reader, error) {
+		r, err := readOpener(lctx, lnk)
+		if err == nil {
+			return r, nil
+		}
+		cl, ok := lnk.(cidlink.Link)
+		if !ok {
+			return nil, err
+		}
+		data := f.cache.get(cl.Cid)
+		if data == nil {
+			return nil, err
+		}
+		// The response is built from the blocks written to the request storage, we must add the
+		// cached block there too
+		w, commit, writeErr := writeOpener(lctx)
+		if writeErr != nil {
+			return nil, writeErr
+		}
+		if _, writeErr := w.Write(data); writeErr != nil {
+			return nil, writeErr
+		}
+		if writeErr := commit(lnk); writeErr != nil {
+			return nil, writeErr
+		}
+		return bytes.NewReader(data), nil
+	}
+
+	lsys.StorageWriteOpener = func(lctx linking.LinkContext) (io.Writer, linking.BlockWriteCommitter, error) {
+		w, commit, err := writeOpener(lctx)
+		if err != nil {
+			return nil, nil, err
+		}
+		var buf bytes.Buffer
+		return io.MultiWriter(w, &buf), func(lnk datamodel.Link) error {
+			if err := commit(lnk); err != nil {
+				return err
+			}
+			if cl, ok := lnk.(cidlink.Link); ok {
+				f.cache.put(cl.Cid, buf.Bytes())
+			}
+			return nil
+		}, nil
+	}
+
+	request.LinkSystem = lsys
+	if stats, ok := f.serveCached(ctx, request); ok {
+		return stats, nil
+	}
+	return f.fetcher.Fetch(ctx, request, opts...)
+}
+
+// serveCached writes the blocks selected by request from the cache to the request storage, when
+// the cache holds all of them. Lassie looks up the request link system in Bitswap retrievals
+// only, the HTTP retriever takes every block from the response of the provider.
+//
+// A first traversal reads the cache without writing anything, so that no block reaches the
+// response unless the cache holds the entire DAG.
+func (f cachingFetcher) serveCached(ctx context.Context, request types.RetrievalRequest) (*types.RetrievalStats, bool) {
+	started := time.Now()
+	dryRun := cidlink.DefaultLinkSystem()
+	dryRun.StorageReadOpener = func(_ linking.LinkContext, lnk datamodel.Link) (io.Reader, error) {
+		if cl, ok := lnk.(cidlink.Link); ok {
+			if data := f.cache.get(cl.Cid); data != nil {
+				return bytes.NewReader(data), nil
+			}
+		}
+		return nil, errNotCached
+	}
+	blocks, _, err := traverseRequest(ctx, dryRun, request)
+	if err != nil || (request.MaxBlocks > 0 && blocks > request.MaxBlocks) {
+		return nil, false
+	}
+
+	blocks, size, err := traverseRequest(ctx, request.LinkSystem, request)
+	if err != nil {
+		// E.g. a block was evicted in the meantime, the providers send the rest
+		debug("CANNOT SERVE FROM THE BLOCK CACHE", request.Root, err)
+		return nil, false
+	}
+	debugw("served from the block cache", "cid", request.Root, "blocks", blocks)
+	return &types.RetrievalStats{
+		RootCid:  request.Root,
+		Size:     size,
+		Blocks:   blocks,
+		Duration: time.Since(started),
+	}, true
+}
+
+// traverseRequest walks the DAG selected by request, loading the blocks with lsys. It returns the
+// number and the total size of the loaded blocks.
+func traverseRequest(ctx context.Context, lsys linking.LinkSystem, request types.RetrievalRequest) (uint64, uint64, error) {
+	var blocks, size uint64
+	readOpener := lsys.StorageReadOpener
+	lsys.StorageReadOpener = func(lctx linking.LinkContext, lnk datamodel.Link) (io.Reader, error) {
+		r, err := readOpener(lctx, lnk)
+		if err != nil {
+			return nil, err
+		}
+		data, err := io.ReadAll(r)
+		if err != nil {
+			return nil, err
+		}
+		blocks++
+		size += uint64(len(data))
+		return bytes.NewReader(data), nil
+	}
+	unixfsnode.AddUnixFSReificationToLinkSystem(&lsys)
+
+	sel, err := selector.CompileSelector(request.GetSelector())
+	if err != nil {
+		return 0, 0, err
+	}
+	chooser := dagpb.AddSupportToChooser(basicnode.Chooser)
+	lctx := linking.LinkContext{Ctx: ctx}
+	root := cidlink.Link{Cid: request.Root}
+	prototype, err := chooser(root, lctx)
+	if err != nil {
+		return 0, 0, err
+	}
+	node, err := lsys.Load(lctx, root, prototype)
+	if err != nil {
+		return 0, 0, err
+	}
+	progress := traversal.Progress{Cfg: &traversal.Config{
+		Ctx:                            ctx,
+		LinkSystem:                     lsys,
+		LinkTargetNodePrototypeChooser: chooser,
+	}}
+	err = progress.WalkAdv(node, sel, func(traversal.Progress, datamodel.Node, traversal.VisitReason) error {
+		return nil
+	})
+	return blocks, size, err
+}
@@ -0,0 +1,25 @@
+#include "lassie-ffi.h"
+
+bool call_head_callback(response_sink_t* sink, uint16_t status, const char* headers) {
+	return sink->on_head(sink->ctx, status, headers);
+}
+
+bool call_chunk_callback(response_sink_t* sink, const uint8_t* data, size_t len) {
+	return sink->on_chunk(sink->ctx, data, len);
+}
+
+void call_access_log_callback(access_log_callback_t callback, void* ctx, const access_log_record_t* record) {
+	callback(ctx, record);
+}
+
+void call_retrieval_event_callback(retrieval_event_callback_t callback, void* ctx, const retrieval_event_t* event) {
+	callback(ctx, event);
+}
+
+void call_measurement_callback(measurement_callback_t callback, void* ctx, const measurement_t* measurement) {
+	callback(ctx, measurement);
+}
+
+bool call_provider_score_callback(provider_score_callback_t callback, void* ctx, const char* provider, const char* cid, double* score) {
+	return callback(ctx, provider, cid, score);
+}
@@ -0,0 +1,91 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"fmt"
+	"strings"
+	"time"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/ipfs/go-cid"
+)
+
+// The circuit breaker configuration, protected by providerStatsMtx. The breaker is disabled when
+// breakerFailures is zero.
+var breakerFailures int
+var breakerCoolDown time.Duration
+
+// withCircuitBreaker applies the circuit breaker configured for the daemon and wraps the
+// candidate source created by newCandidateSource. The state of the providers is kept across
+// daemons, like the other provider statistics.
+func withCircuitBreaker(cfg *C.daemon_config_t, source types.CandidateSource) (types.CandidateSource, error) {
+	providerStatsMtx.Lock()
+	breakerFailures = int(cfg.circuit_breaker_failures)
+	breakerCoolDown = time.Duration(cfg.circuit_breaker_cool_down)
+	providerStatsMtx.Unlock()
+	if cfg.circuit_breaker_failures == 0 {
+		return source, nil
+	}
+
+	debug(fmt.Sprintf("Skipping providers for %v after %d consecutive failures", time.Duration(cfg.circuit_breaker_cool_down), cfg.circuit_breaker_failures))
+	source, err := orDefaultCandidateSource(source)
+	if err != nil {
+		return nil, err
+	}
+	return circuitBreakerSource{next: source}, nil
+}
+
+// updateCircuit opens the circuit of a provider that failed breakerFailures consecutive
+// attempts. It must be called with providerStatsMtx held.
+//
+// After the cool-down, the provider gets another chance. The failure count is reset by a success
+// only, so a single failure opens the circuit again.
+func (s *providerStats) updateCircuit(event types.RetrievalEvent) {
+	switch event.Code() {
+	case types.SuccessCode:
+		s.consecutiveFailures = 0
+		s.openUntil = time.Time{}
+	case types.FailedRetrievalCode:
+		// Lassie cancels the slower attempts once a provider succeeds, that's not the provider's
+		// fault
+		if e, ok := event.(eventWithErrorMessage); ok && strings.Contains(e.ErrorMessage(), context.Canceled.Error()) {
+			return
+		}
+		s.consecutiveFailures++
+		if breakerFailures > 0 && s.consecutiveFailures >= breakerFailures {
+			s.openUntil = event.Time().Add(breakerCoolDown)
+		}
+	}
+}
+
+// isCircuitOpen tells whether the candidates of the provider should be skipped right now.
+func isCircuitOpen(provider string) bool {
+	providerStatsMtx.Lock()
+	defer providerStatsMtx.Unlock()
+	s := statsByProvider[provider]
+	return breakerFailures > 0 && s != nil && time.Now().Before(s.openUntil)
+}
+
+// circuitBreakerSource drops the candidates of providers with an open circuit. Providers listed
+// in the `providers=` parameter of a request do not go through the candidate source, they are
+// never skipped.
+type circuitBreakerSource struct {
+	next types.CandidateSource
+}
+
+func (s circuitBreakerSource) FindCandidates(ctx context.Context, c cid.Cid, cb func(types.RetrievalCandidate)) error {
+	return s.next.FindCandidates(ctx, c, func(candidate types.RetrievalCandidate) {
+		provider := candidate.MinerPeer.ID.String()
+		if isCircuitOpen(provider) {
+			debugw("skipping provider with an open circuit", "provider", provider, "cid", c)
+			return
+		}
+		cb(candidate)
+	})
+}
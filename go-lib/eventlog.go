@@ -0,0 +1,103 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"encoding/json"
+	"fmt"
+	"sync"
+	"time"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+)
+
+var eventLogMtx sync.Mutex
+
+// eventLog is the file configured by the last InitDaemon call, if any. Like log_file, it is
+// rotated when it grows over logFileMaxSize.
+var eventLog *rotatingFile
+
+// eventLogRecord is a single line of the event log.
+type eventLogRecord struct {
+	Time        time.Time `json:"ts"`
+	Event       string    `json:"event"`
+	RetrievalId string    `json:"retrieval_id,omitempty"`
+	LassieId    string    `json:"lassie_id"`
+	RootCid     string    `json:"root_cid,omitempty"`
+	Provider    string    `json:"provider,omitempty"`
+	Protocol    string    `json:"protocol,omitempty"`
+	Error       string    `json:"error,omitempty"`
+	Candidates  []string  `json:"candidates,omitempty"`
+	DurationMs  float64   `json:"duration_ms,omitempty"`
+	Bytes       uint64    `json:"bytes,omitempty"`
+	Blocks      uint64    `json:"blocks,omitempty"`
+}
+
+// setupEventLog opens the event log configured in cfg and closes the previous one. The caller
+// must hold the mutex.
+func setupEventLog(cfg *C.daemon_config_t) error {
+	var file *rotatingFile
+	if path := C.GoString(cfg.event_log); path != "" {
+		var err error
+		file, err = openRotatingFile(path, logFileMaxSize, logFileBackups)
+		if err != nil {
+			return err
+		}
+		debug(fmt.Sprintf("Writing retrieval events to %s", path))
+	}
+
+	eventLogMtx.Lock()
+	defer eventLogMtx.Unlock()
+	if eventLog != nil {
+		eventLog.Close()
+	}
+	eventLog = file
+	return nil
+}
+
+// closeEventLog is called when the daemon stops.
+func closeEventLog() {
+	eventLogMtx.Lock()
+	defer eventLogMtx.Unlock()
+	if eventLog != nil {
+		eventLog.Close()
+		eventLog = nil
+	}
+}
+
+// writeEventLog is subscribed to all Lassie retrieval events and appends them to the event log.
+// Like forwardRetrievalEvent, it must be subscribed after onRetrievalEvent.
+func writeEventLog(event types.RetrievalEvent) {
+	eventLogMtx.Lock()
+	defer eventLogMtx.Unlock()
+	if eventLog == nil {
+		return
+	}
+
+	d := describeEvent(event)
+	line, err := json.Marshal(eventLogRecord{
+		Time:        event.Time().UTC(),
+		Event:       string(event.Code()),
+		RetrievalId: d.retrievalId,
+		LassieId:    d.lassieId,
+		RootCid:     d.rootCid,
+		Provider:    d.provider,
+		Protocol:    d.protocol,
+		Error:       d.errorMessage,
+		Candidates:  d.candidates,
+		DurationMs:  float64(d.duration) / float64(time.Millisecond),
+		Bytes:       d.bytes,
+		Blocks:      d.blocks,
+	})
+	if err != nil {
+		debugw("cannot encode retrieval event", "event", event.Code(), "err", err)
+		return
+	}
+	if _, err := eventLog.Write(append(line, '\n')); err != nil {
+		debugw("cannot write to the event log", "err", err)
+	}
+}
@@ -0,0 +1,194 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"sync"
+	"time"
+	"unsafe"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+)
+
+// eventMtx protects the registered callback, see accessLogMtx for the locking scheme.
+var eventMtx sync.RWMutex
+var eventCallback C.retrieval_event_callback_t
+var eventCtx unsafe.Pointer
+
+// SetRetrievalEventCallback registers the function called for every Lassie retrieval event,
+// replacing the previous callback.
+//
+//export SetRetrievalEventCallback
+func SetRetrievalEventCallback(callback C.retrieval_event_callback_t, ctx unsafe.Pointer) {
+	eventMtx.Lock()
+	defer eventMtx.Unlock()
+	eventCallback = callback
+	eventCtx = ctx
+}
+
+// ClearRetrievalEventCallback unregisters the callback registered with ctx. It does nothing when
+// a different callback has been registered since. When this function returns, the callback is
+// not running and will not be called again.
+//
+//export ClearRetrievalEventCallback
+func ClearRetrievalEventCallback(ctx unsafe.Pointer) {
+	eventMtx.Lock()
+	defer eventMtx.Unlock()
+	if eventCtx == ctx {
+		eventCallback = nil
+		eventCtx = nil
+	}
+}
+
+type eventWithErrorMessage interface {
+	ErrorMessage() string
+}
+
+type eventWithDuration interface {
+	Duration() time.Duration
+}
+
+type eventWithCandidates interface {
+	Candidates() []types.RetrievalCandidate
+}
+
+type eventWithReceivedCids interface {
+	ReceivedCidsCount() uint64
+}
+
+// retrievalEventKind maps the Lassie events we forward to RETRIEVAL_EVENT_* values.
+func retrievalEventKind(code types.EventCode) (int, bool) {
+	switch code {
+	case types.StartedFetchCode:
+		return C.RETRIEVAL_EVENT_STARTED, true
+	case types.CandidatesFoundCode:
+		return C.RETRIEVAL_EVENT_CANDIDATES_FOUND, true
+	case types.CandidatesFilteredCode:
+		return C.RETRIEVAL_EVENT_CANDIDATES_FILTERED, true
+	case types.ConnectedToProviderCode:
+		return C.RETRIEVAL_EVENT_CONNECTED, true
+	case types.FirstByteCode:
+		return C.RETRIEVAL_EVENT_FIRST_BYTE, true
+	case types.SuccessCode:
+		return C.RETRIEVAL_EVENT_SUCCESS, true
+	case types.FailedRetrievalCode, types.FailedCode:
+		return C.RETRIEVAL_EVENT_FAILURE, true
+	}
+	return 0, false
+}
+
+// eventDetails are the fields of a Lassie event we report, see forwardRetrievalEvent and
+// writeEventLog.
+type eventDetails struct {
+	retrievalId  string
+	lassieId     string
+	rootCid      string
+	provider     string
+	protocol     string
+	errorMessage string
+	candidates   []string
+	duration     time.Duration
+	bytes        uint64
+	blocks       uint64
+}
+
+// describeEvent extracts the fields we report from a Lassie event. The caller must not hold
+// retrievalsMtx.
+func describeEvent(event types.RetrievalEvent) eventDetails {
+	d := eventDetails{lassieId: event.RetrievalId().String()}
+	retrievalsMtx.Lock()
+	if r, ok := retrievalsByLassieId[d.lassieId]; ok {
+		d.retrievalId = r.id
+		d.rootCid = r.cid
+	}
+	retrievalsMtx.Unlock()
+
+	if e, ok := event.(eventWithRootCid); ok {
+		d.rootCid = e.RootCid().String()
+	}
+	if e, ok := event.(eventWithProviderId); ok && e.ProviderId() != "" {
+		d.provider = e.ProviderId().String()
+	}
+	if e, ok := event.(eventWithProtocol); ok {
+		d.protocol = e.Protocol().String()
+	}
+	if e, ok := event.(eventWithErrorMessage); ok {
+		d.errorMessage = e.ErrorMessage()
+	}
+	if e, ok := event.(eventWithCandidates); ok {
+		for _, c := range e.Candidates() {
+			d.candidates = append(d.candidates, c.MinerPeer.ID.String())
+		}
+	}
+	if e, ok := event.(eventWithDuration); ok {
+		d.duration = e.Duration()
+	}
+	if e, ok := event.(eventWithReceivedBytes); ok {
+		d.bytes = e.ReceivedBytesSize()
+	}
+	if e, ok := event.(eventWithReceivedCids); ok {
+		d.blocks = e.ReceivedCidsCount()
+	}
+	return d
+}
+
+// forwardRetrievalEvent is subscribed to all Lassie retrieval events and passes them to the
+// registered callback. It must be subscribed after onRetrievalEvent, so that the events are
+// already correlated with our retrieval IDs.
+func forwardRetrievalEvent(event types.RetrievalEvent) {
+	eventMtx.RLock()
+	defer eventMtx.RUnlock()
+	if eventCallback == nil {
+		return
+	}
+	kind, ok := retrievalEventKind(event.Code())
+	if !ok {
+		return
+	}
+	d := describeEvent(event)
+
+	cStrings := []*C.char{
+		C.CString(d.retrievalId),
+		C.CString(d.lassieId),
+		C.CString(d.rootCid),
+		C.CString(d.provider),
+		C.CString(d.protocol),
+		C.CString(d.errorMessage),
+	}
+	var candidateList **C.char
+	if len(d.candidates) > 0 {
+		candidateList = (**C.char)(C.malloc(C.size_t(len(d.candidates)) * C.size_t(unsafe.Sizeof(uintptr(0)))))
+		list := unsafe.Slice(candidateList, len(d.candidates))
+		for i, c := range d.candidates {
+			list[i] = C.CString(c)
+			cStrings = append(cStrings, list[i])
+		}
+	}
+	defer func() {
+		for _, s := range cStrings {
+			C.free(unsafe.Pointer(s))
+		}
+		C.free(unsafe.Pointer(candidateList))
+	}()
+
+	record := C.retrieval_event_t{
+		retrieval_id:   cStrings[0],
+		lassie_id:      cStrings[1],
+		root_cid:       cStrings[2],
+		provider:       cStrings[3],
+		protocol:       cStrings[4],
+		error:          cStrings[5],
+		candidates:     candidateList,
+		candidates_len: C.size_t(len(d.candidates)),
+		time:           C.int64_t(event.Time().UnixNano()),
+		duration:       C.int64_t(d.duration),
+		bytes:          C.uint64_t(d.bytes),
+		blocks:         C.uint64_t(d.blocks),
+		kind:           C.uint8_t(kind),
+	}
+	C.call_retrieval_event_callback(eventCallback, eventCtx, &record)
+}
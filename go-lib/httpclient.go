@@ -0,0 +1,178 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"crypto/tls"
+	"crypto/x509"
+	"fmt"
+	"io"
+	"net/http"
+	"os"
+	"sync/atomic"
+	"time"
+)
+
+// defaultTransport is the Go default, captured before we replace http.DefaultTransport.
+var defaultTransport = http.DefaultTransport.(*http.Transport)
+
+// configureHttpTransport applies the configured HTTP version, connection pool limits, client
+// certificate, extra root certificates and provider timeout to outbound HTTP requests.
+//
+// Lassie's HTTP retriever uses http.DefaultClient, which looks up http.DefaultTransport for every
+// request, so replacing the default transport is the only way to configure it. The transport is
+// shared by all HTTP clients of the Go side that don't set their own, e.g. IPNI lookups. It must
+// be configured before creating any client that captures http.DefaultTransport.
+func configureHttpTransport(cfg *C.daemon_config_t) error {
+	// Start from the Go defaults, the previous daemon may have changed them
+	t := defaultTransport.Clone()
+	if cfg.http1_only {
+		debug("HTTP/2 DISABLED FOR OUTBOUND REQUESTS")
+		// A non-nil empty map disables the automatic HTTP/2 upgrade, see net/http docs
+		t.ForceAttemptHTTP2 = false
+		t.TLSNextProto = map[string]func(string, *tls.Conn) http.RoundTripper{}
+	}
+	if cfg.http_max_conns_per_host > 0 {
+		t.MaxConnsPerHost = int(cfg.http_max_conns_per_host)
+	}
+	if cfg.http_max_idle_conns_per_host > 0 {
+		t.MaxIdleConnsPerHost = int(cfg.http_max_idle_conns_per_host)
+		t.MaxIdleConns = max(t.MaxIdleConns, t.MaxIdleConnsPerHost)
+	}
+	if certFile := C.GoString(cfg.http_client_cert_file); certFile != "" {
+		cert, err := tls.LoadX509KeyPair(certFile, C.GoString(cfg.http_client_key_file))
+		if err != nil {
+			return fmt.Errorf("cannot load the client certificate: %w", err)
+		}
+		debug(fmt.Sprintf("Presenting the TLS client certificate from %s", certFile))
+		tlsConfig(t).Certificates = []tls.Certificate{cert}
+	}
+	if cfg.http_insecure_skip_verify {
+		debug("TLS CERTIFICATE VERIFICATION DISABLED FOR OUTBOUND REQUESTS")
+		tlsConfig(t).InsecureSkipVerify = true
+	}
+	if files := goStrings(cfg.extra_root_certs, cfg.extra_root_certs_len); len(files) > 0 {
+		roots, err := loadRootCerts(files)
+		if err != nil {
+			return err
+		}
+		tlsConfig(t).RootCAs = roots
+	}
+	var next http.RoundTripper = t
+	if cfg.http_provider_timeout > 0 {
+		timeout := time.Duration(cfg.http_provider_timeout)
+		t.ResponseHeaderTimeout = timeout
+		next = &idleTimeoutTransport{next: t, timeout: timeout}
+	}
+	debug(fmt.Sprintf("Outbound HTTP: max_conns_per_host=%d max_idle_conns_per_host=%d provider_timeout=%s",
+		t.MaxConnsPerHost, t.MaxIdleConnsPerHost, time.Duration(cfg.http_provider_timeout)))
+	http.DefaultTransport = &requestIdTransport{next: next}
+	return nil
+}
+
+// loadRootCerts returns the system trust store extended with the certificates in files.
+func loadRootCerts(files []string) (*x509.CertPool, error) {
+	roots, err := x509.SystemCertPool()
+	if err != nil {
+		// e.g. on platforms without a system trust store accessible to Go
+		debug(fmt.Sprintf("Cannot load the system root certificates, trusting extra_root_certs only: %v", err))
+		roots = x509.NewCertPool()
+	}
+	for _, file := range files {
+		pem, err := os.ReadFile(file)
+		if err != nil {
+			return nil, fmt.Errorf("cannot read root certificates: %w", err)
+		}
+		if !roots.AppendCertsFromPEM(pem) {
+			return nil, fmt.Errorf("no PEM certificate found in %s", file)
+		}
+		debug(fmt.Sprintf("Trusting root certificates from %s", file))
+	}
+	return roots, nil
+}
+
+// tlsConfig returns the TLS configuration of t, creating it when t uses the Go defaults.
+func tlsConfig(t *http.Transport) *tls.Config {
+	if t.TLSClientConfig == nil {
+		t.TLSClientConfig = &tls.Config{}
+	}
+	return t.TLSClientConfig
+}
+
+// requestIdTransport forwards the X-Request-Id of the request that triggered an outbound request,
+// so that provider logs can be correlated with ours. Lassie derives the contexts of provider
+// requests from the context of the incoming request, see trackRetrievals.
+type requestIdTransport struct {
+	next http.RoundTripper
+}
+
+func (t *requestIdTransport) RoundTrip(req *http.Request) (*http.Response, error) {
+	id := requestIdFromContext(req.Context())
+	if id == "" || req.Header.Get(requestIdHeader) != "" {
+		return t.next.RoundTrip(req)
+	}
+	// RoundTrip must not modify the request
+	req = req.Clone(req.Context())
+	req.Header.Set(requestIdHeader, id)
+	return t.next.RoundTrip(req)
+}
+
+// idleTimeoutTransport aborts responses whose body does not deliver any data for timeout, the
+// body counterpart of ResponseHeaderTimeout. Only the time spent waiting in Read counts, a slow
+// consumer does not trigger the timeout.
+type idleTimeoutTransport struct {
+	next    http.RoundTripper
+	timeout time.Duration
+}
+
+func (t *idleTimeoutTransport) RoundTrip(req *http.Request) (*http.Response, error) {
+	ctx, cancel := context.WithCancel(req.Context())
+	res, err := t.next.RoundTrip(req.WithContext(ctx))
+	if err != nil {
+		cancel()
+		return nil, err
+	}
+	res.Body = &idleTimeoutBody{body: res.Body, timeout: t.timeout, cancel: cancel}
+	return res, nil
+}
+
+type idleTimeoutBody struct {
+	body    io.ReadCloser
+	timeout time.Duration
+	// Cancels the request, which aborts the pending Read
+	cancel  context.CancelFunc
+	timer   *time.Timer
+	expired atomic.Bool
+}
+
+func (b *idleTimeoutBody) Read(p []byte) (int, error) {
+	if b.timer == nil {
+		b.timer = time.AfterFunc(b.timeout, b.expire)
+	} else {
+		b.timer.Reset(b.timeout)
+	}
+	n, err := b.body.Read(p)
+	b.timer.Stop()
+	if err != nil && b.expired.Load() {
+		return n, fmt.Errorf("no data received from the provider for %s: %w", b.timeout, os.ErrDeadlineExceeded)
+	}
+	return n, err
+}
+
+func (b *idleTimeoutBody) expire() {
+	b.expired.Store(true)
+	b.cancel()
+}
+
+func (b *idleTimeoutBody) Close() error {
+	if b.timer != nil {
+		b.timer.Stop()
+	}
+	b.cancel()
+	return b.body.Close()
+}
@@ -0,0 +1,50 @@
+package main
+
+import (
+	"context"
+	"errors"
+	"net/http"
+
+	"github.com/filecoin-project/lassie/pkg/retriever"
+)
+
+// errorCodeHeader carries a machine-readable category of error responses, so that clients don't
+// have to match the message. See ERROR_CODE_HEADER and RetrievalError::from_error_response in
+// src/retrieval.rs and src/retrieval_error.rs
+const errorCodeHeader = "X-Lassie-Error"
+
+// The values of the X-Lassie-Error header
+const (
+	errorCodeUnauthorized      = "unauthorized"
+	errorCodeForbidden         = "forbidden"
+	errorCodeTooManyRequests   = "too-many-requests"
+	errorCodeShuttingDown      = "shutting-down"
+	errorCodeBadRequest        = "bad-request"
+	errorCodeProvidersRequired = "providers-required"
+	errorCodeNoCandidates      = "no-candidates"
+	errorCodeTimeout           = "timeout"
+	errorCodeProviderFailure   = "provider-failure"
+)
+
+// httpError works like http.Error and sets the error code header.
+func httpError(res http.ResponseWriter, code string, msg string, status int) {
+	res.Header().Set(errorCodeHeader, code)
+	http.Error(res, msg, status)
+}
+
+// fetchErrorCode classifies the error returned by the Lassie fetcher, the Lassie handler responds
+// with the message only. Errors without a category return an empty string.
+func fetchErrorCode(err error) string {
+	switch {
+	case err == nil:
+		return ""
+	case errors.Is(err, retriever.ErrNoCandidates):
+		return errorCodeNoCandidates
+	case errors.Is(err, context.DeadlineExceeded), errors.Is(err, retriever.ErrRetrievalTimedOut):
+		return errorCodeTimeout
+	case errors.Is(err, retriever.ErrAllRetrievalsFailed):
+		return errorCodeProviderFailure
+	default:
+		return ""
+	}
+}
@@ -0,0 +1,61 @@
+package main
+
+import (
+	"net/http"
+	"sync/atomic"
+	"time"
+)
+
+// lastActivity is the time (in Unix nanoseconds) when the last request started or finished, it's
+// reset by InitDaemon
+var lastActivity atomic.Int64
+
+// requestsInFlight counts the HTTP and in-process requests being served
+var requestsInFlight atomic.Int64
+
+// trackActivity records the requests for idle_shutdown.
+func trackActivity(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		requestsInFlight.Add(1)
+		lastActivity.Store(time.Now().UnixNano())
+		defer func() {
+			lastActivity.Store(time.Now().UnixNano())
+			requestsInFlight.Add(-1)
+		}()
+		next.ServeHTTP(res, req)
+	})
+}
+
+// watchIdle stops the daemon once it served no requests for `idle`, it exits when the daemon
+// stops for another reason.
+func watchIdle(d *lassieDaemon, idle time.Duration) {
+	ticker := time.NewTicker(min(max(idle/4, time.Millisecond), time.Second))
+	defer ticker.Stop()
+	for {
+		select {
+		case <-d.done:
+			return
+		case <-ticker.C:
+		}
+		if requestsInFlight.Load() > 0 || time.Since(time.Unix(0, lastActivity.Load())) < idle {
+			continue
+		}
+		stopIdleDaemon(d, idle)
+		return
+	}
+}
+
+// stopIdleDaemon stops d unless it was already stopped. Like shutdownFromAdmin, the Rust side
+// still owns the daemon handle and calls StopDaemon later.
+func stopIdleDaemon(d *lassieDaemon, idle time.Duration) {
+	mtx.Lock()
+	defer mtx.Unlock()
+	if daemon != d {
+		return
+	}
+	debugw("stopping Lassie daemon after idle_shutdown", "idle", idle)
+	if err := stopDaemon(); err != nil {
+		debug("CANNOT STOP IDLE LASSIE DAEMON", err)
+	}
+	stoppedInternally = true
+}
@@ -0,0 +1,590 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"bufio"
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"net"
+	"net/http"
+	"os"
+	"strings"
+	"sync"
+	"unsafe"
+)
+
+var errResponseAborted = errors.New("the response stream was aborted by Lassie")
+var errResponseClosed = errors.New("the response was closed by the client")
+
+// inProcessResponse is a response produced by serving a request without going through the TCP
+// listener. The body is streamed through a pipe.
+type inProcessResponse struct {
+	body   *io.PipeReader
+	cancel context.CancelFunc
+
+	// copyDone is closed when the body was copied to the OS pipe provided by the caller of
+	// ServeRequestToPipe. It's nil for responses read via ReadResponse.
+	copyDone chan struct{}
+	copyErr  error
+}
+
+var responsesMtx sync.Mutex
+var responses = map[uint64]*inProcessResponse{}
+var lastResponseHandle uint64
+
+// pipeResponseWriter implements http.ResponseWriter on top of an io.Pipe.
+type pipeResponseWriter struct {
+	header      http.Header
+	status      int
+	committed   http.Header
+	wroteHeader chan struct{}
+	once        sync.Once
+	body        *io.PipeWriter
+	aborted     bool
+}
+
+func newPipeResponseWriter(body *io.PipeWriter) *pipeResponseWriter {
+	return &pipeResponseWriter{
+		header:      http.Header{},
+		wroteHeader: make(chan struct{}),
+		body:        body,
+	}
+}
+
+func (w *pipeResponseWriter) Header() http.Header {
+	return w.header
+}
+
+func (w *pipeResponseWriter) WriteHeader(status int) {
+	w.once.Do(func() {
+		w.status = status
+		w.committed = w.header.Clone()
+		close(w.wroteHeader)
+	})
+}
+
+func (w *pipeResponseWriter) Write(p []byte) (int, error) {
+	w.WriteHeader(http.StatusOK)
+	return w.body.Write(p)
+}
+
+func (w *pipeResponseWriter) Flush() {}
+
+// Hijack is called by Lassie to abort the response in a way that triggers a client error when
+// the retrieval fails after the response headers were sent. We mark the response as aborted and
+// hand over a connection that discards everything written to it.
+func (w *pipeResponseWriter) Hijack() (net.Conn, *bufio.ReadWriter, error) {
+	w.aborted = true
+	conn, sink := net.Pipe()
+	go func() {
+		_, _ = io.Copy(io.Discard, sink)
+		sink.Close()
+	}()
+	return conn, bufio.NewReadWriter(bufio.NewReader(conn), bufio.NewWriter(conn)), nil
+}
+
+// ServeRequest serves a single trustless gateway request in-process, without going through the
+// HTTP listener. The function returns as soon as the response headers are available, the body
+// must be read via ReadResponse and the response must be released via CloseResponse.
+//
+//export ServeRequest
+func ServeRequest(req *C.serve_request_t) (result C.serve_result_t) {
+	defer recoverError("ServeRequest", &result.error)
+	result, _ = serveRequest(req, &inProcessResponse{})
+	return result
+}
+
+// ServeRequestToPipe serves a single trustless gateway request in-process and writes the response
+// body to the OS pipe (a file descriptor on Unix, a HANDLE on Windows) provided by the caller.
+// The function takes over the ownership of the pipe and closes it when the body was written.
+// Use WaitResponse to find out whether the body was streamed completely and CloseResponse to
+// release the response.
+//
+//export ServeRequestToPipe
+func ServeRequestToPipe(req *C.serve_request_t, pipe C.uintptr_t) (result C.serve_result_t) {
+	defer recoverError("ServeRequestToPipe", &result.error)
+	file := os.NewFile(uintptr(pipe), "lassie-response-pipe")
+	r := &inProcessResponse{copyDone: make(chan struct{})}
+	result, ok := serveRequest(req, r)
+	if !ok {
+		file.Close()
+		return result
+	}
+
+	go func() {
+		_, err := io.Copy(file, r.body)
+		closeErr := file.Close()
+		if err == nil {
+			err = closeErr
+		}
+		r.copyErr = err
+		close(r.copyDone)
+	}()
+
+	return result
+}
+
+// WaitResponse waits until the response body was written to the pipe provided to
+// ServeRequestToPipe and reports an error if the body was not streamed completely.
+//
+//export WaitResponse
+func WaitResponse(handle C.uint64_t) (result C.result_t) {
+	defer recoverError("WaitResponse", &result.error)
+	r := getResponse(uint64(handle))
+	if r == nil || r.copyDone == nil {
+		return newError("unknown response handle", nil)
+	}
+
+	<-r.copyDone
+	if r.copyErr != nil {
+		return newError("cannot stream the response body", r.copyErr)
+	}
+	return OK
+}
+
+// serveRequest starts serving the request in a background goroutine and waits until the response
+// headers are available. On success, the response `r` is registered under the returned handle.
+func serveRequest(req *C.serve_request_t, r *inProcessResponse) (C.serve_result_t, bool) {
+	d := getDaemon()
+	if d == nil {
+		return newServeError("Lassie daemon is not running", nil), false
+	}
+
+	httpReq, cancel, err := newInProcessRequest(d, req)
+	if err != nil {
+		return newServeError("invalid request path", err), false
+	}
+
+	pr, pw := io.Pipe()
+	w := newPipeResponseWriter(pw)
+	go func() {
+		defer func() {
+			if r := recover(); r != nil {
+				msg := panicMessage("the in-process request handler", r)
+				debug(msg)
+				w.WriteHeader(http.StatusInternalServerError)
+				pw.CloseWithError(errors.New(msg))
+			}
+		}()
+
+		d.ipfsHandler.ServeHTTP(w, httpReq)
+		// Handle the case when the handler did not write anything
+		w.WriteHeader(http.StatusOK)
+		if w.aborted {
+			pw.CloseWithError(errResponseAborted)
+		} else {
+			pw.Close()
+		}
+	}()
+
+	<-w.wroteHeader
+
+	r.body = pr
+	r.cancel = cancel
+
+	responsesMtx.Lock()
+	lastResponseHandle++
+	handle := lastResponseHandle
+	responses[handle] = r
+	responsesMtx.Unlock()
+
+	return C.serve_result_t{
+		handle:  C.uint64_t(handle),
+		status:  C.uint16_t(w.status),
+		headers: C.CString(encodeHeaders(w.committed)),
+		error:   nil,
+	}, true
+}
+
+// newInProcessRequest converts serve_request_t to http.Request. The request context is cancelled
+// when the daemon stops or when the returned CancelFunc is called.
+func newInProcessRequest(d *lassieDaemon, req *C.serve_request_t) (*http.Request, context.CancelFunc, error) {
+	ctx, cancel := context.WithCancel(d.ctx)
+	httpReq, err := http.NewRequestWithContext(ctx, http.MethodGet, C.GoString(req.path), nil)
+	if err != nil {
+		cancel()
+		return nil, nil, err
+	}
+	httpReq.RequestURI = httpReq.URL.RequestURI()
+	for name, value := range decodeHeaders(C.GoString(req.headers)) {
+		httpReq.Header[name] = value
+	}
+	return httpReq, cancel, nil
+}
+
+func newServeError(msg string, cause error) C.serve_result_t {
+	if cause != nil {
+		msg = fmt.Sprintf("%s: %+v", msg, cause)
+	}
+
+	return C.serve_result_t{
+		error: C.CString(msg),
+	}
+}
+
+// DropServeResult cleans up any resources allocated for and owned by the serve_result_t value.
+//
+//export DropServeResult
+func DropServeResult(result *C.serve_result_t) {
+	if result.headers != nil {
+		C.free(unsafe.Pointer(result.headers))
+		result.headers = nil
+	}
+	if result.error != nil {
+		C.free(unsafe.Pointer(result.error))
+		result.error = nil
+	}
+}
+
+// ReadResponse reads the next chunk of the response body into the provided buffer. A result with
+// size 0 and no error signals the end of the body.
+//
+//export ReadResponse
+func ReadResponse(handle C.uint64_t, buf *C.uint8_t, size C.size_t) (result C.read_result_t) {
+	defer recoverError("ReadResponse", &result.error)
+	r := getResponse(uint64(handle))
+	if r == nil {
+		return newReadError("unknown response handle", nil)
+	}
+
+	dst := unsafe.Slice((*byte)(unsafe.Pointer(buf)), int(size))
+	n, err := r.body.Read(dst)
+	if err != nil && !errors.Is(err, io.EOF) {
+		return newReadError("cannot read the response body", err)
+	}
+
+	return C.read_result_t{
+		size:  C.size_t(n),
+		error: nil,
+	}
+}
+
+func newReadError(msg string, cause error) C.read_result_t {
+	if cause != nil {
+		msg = fmt.Sprintf("%s: %+v", msg, cause)
+	}
+
+	return C.read_result_t{
+		size:  0,
+		error: C.CString(msg),
+	}
+}
+
+// DropReadResult cleans up any resources allocated for and owned by the read_result_t value.
+//
+//export DropReadResult
+func DropReadResult(result *C.read_result_t) {
+	if result.error != nil {
+		C.free(unsafe.Pointer(result.error))
+		result.error = nil
+	}
+}
+
+// CloseResponse releases the response. When the body was not fully read yet, the retrieval is
+// cancelled.
+//
+//export CloseResponse
+func CloseResponse(handle C.uint64_t) {
+	responsesMtx.Lock()
+	r := responses[uint64(handle)]
+	delete(responses, uint64(handle))
+	responsesMtx.Unlock()
+
+	if r != nil {
+		r.cancel()
+		r.body.CloseWithError(errResponseClosed)
+	}
+}
+
+var errSinkFailed = errors.New("the response sink rejected the data")
+
+// sinkResponseWriter implements http.ResponseWriter by calling the callbacks provided by the
+// caller of ServeRequestWithSink.
+type sinkResponseWriter struct {
+	header      http.Header
+	sink        *C.response_sink_t
+	wroteHeader bool
+	failed      bool
+	aborted     bool
+}
+
+func (w *sinkResponseWriter) Header() http.Header {
+	return w.header
+}
+
+func (w *sinkResponseWriter) WriteHeader(status int) {
+	if w.wroteHeader {
+		return
+	}
+	w.wroteHeader = true
+
+	headers := C.CString(encodeHeaders(w.header))
+	defer C.free(unsafe.Pointer(headers))
+	if !C.call_head_callback(w.sink, C.uint16_t(status), headers) {
+		w.failed = true
+	}
+}
+
+func (w *sinkResponseWriter) Write(p []byte) (int, error) {
+	w.WriteHeader(http.StatusOK)
+	if w.failed {
+		return 0, errSinkFailed
+	}
+	if len(p) == 0 {
+		return 0, nil
+	}
+
+	if !C.call_chunk_callback(w.sink, (*C.uint8_t)(unsafe.Pointer(&p[0])), C.size_t(len(p))) {
+		w.failed = true
+		return 0, errSinkFailed
+	}
+	return len(p), nil
+}
+
+func (w *sinkResponseWriter) Flush() {}
+
+// Hijack is called by Lassie to abort the response, see pipeResponseWriter.Hijack for details.
+func (w *sinkResponseWriter) Hijack() (net.Conn, *bufio.ReadWriter, error) {
+	w.aborted = true
+	conn, sink := net.Pipe()
+	go func() {
+		_, _ = io.Copy(io.Discard, sink)
+		sink.Close()
+	}()
+	return conn, bufio.NewReadWriter(bufio.NewReader(conn), bufio.NewWriter(conn)), nil
+}
+
+// ServeRequestWithSink serves a single trustless gateway request in-process and delivers the
+// response to the callbacks provided in `sink`. The callbacks are invoked synchronously from the
+// request handler as soon as Lassie produces the data, there is no intermediate buffering.
+//
+// The function returns when the response was fully delivered.
+//
+//export ServeRequestWithSink
+func ServeRequestWithSink(req *C.serve_request_t, sink *C.response_sink_t) (result C.result_t) {
+	defer recoverError("ServeRequestWithSink", &result.error)
+	d := getDaemon()
+	if d == nil {
+		return newError("Lassie daemon is not running", nil)
+	}
+
+	httpReq, cancel, err := newInProcessRequest(d, req)
+	if err != nil {
+		return newError("invalid request path", err)
+	}
+	defer cancel()
+
+	w := &sinkResponseWriter{header: http.Header{}, sink: sink}
+	d.ipfsHandler.ServeHTTP(w, httpReq)
+	// Handle the case when the handler did not write anything
+	w.WriteHeader(http.StatusOK)
+
+	if w.failed {
+		return newError(errSinkFailed.Error(), nil)
+	}
+	if w.aborted {
+		return newError(errResponseAborted.Error(), nil)
+	}
+	return OK
+}
+
+// maxErrorBodySize limits how much of a non-200 response body ServeRequestToFile keeps in memory.
+const maxErrorBodySize = 64 * 1024
+
+// fileResponseWriter implements http.ResponseWriter by writing the body of a 200 response to a
+// file. The file is created when the handler writes the response header.
+type fileResponseWriter struct {
+	header      http.Header
+	path        string
+	status      int
+	wroteHeader bool
+	file        *os.File
+	out         *bufio.Writer
+	fileErr     error
+	body        bytes.Buffer
+	bytes       int64
+	aborted     bool
+}
+
+func (w *fileResponseWriter) Header() http.Header {
+	return w.header
+}
+
+func (w *fileResponseWriter) WriteHeader(status int) {
+	if w.wroteHeader {
+		return
+	}
+	w.wroteHeader = true
+	w.status = status
+	if status != http.StatusOK {
+		return
+	}
+
+	w.file, w.fileErr = os.OpenFile(w.path, os.O_WRONLY|os.O_CREATE|os.O_TRUNC, 0o644)
+	if w.fileErr == nil {
+		w.out = bufio.NewWriterSize(w.file, 256*1024)
+	}
+}
+
+func (w *fileResponseWriter) Write(p []byte) (int, error) {
+	w.WriteHeader(http.StatusOK)
+	if w.fileErr != nil {
+		return 0, w.fileErr
+	}
+	if w.out == nil {
+		// Keep the error message, drop the rest
+		if room := maxErrorBodySize - w.body.Len(); room > 0 {
+			w.body.Write(p[:min(len(p), room)])
+		}
+		return len(p), nil
+	}
+
+	n, err := w.out.Write(p)
+	w.bytes += int64(n)
+	if err != nil {
+		w.fileErr = err
+	}
+	return n, err
+}
+
+// Flush is a no-op, the body is written to the file in large chunks.
+func (w *fileResponseWriter) Flush() {}
+
+// Hijack is called by Lassie to abort the response, see pipeResponseWriter.Hijack for details.
+func (w *fileResponseWriter) Hijack() (net.Conn, *bufio.ReadWriter, error) {
+	w.aborted = true
+	conn, sink := net.Pipe()
+	go func() {
+		_, _ = io.Copy(io.Discard, sink)
+		sink.Close()
+	}()
+	return conn, bufio.NewReadWriter(bufio.NewReader(conn), bufio.NewWriter(conn)), nil
+}
+
+// finish flushes and closes the file. The file is removed when the response is not complete.
+func (w *fileResponseWriter) finish() {
+	if w.file == nil {
+		return
+	}
+	if w.fileErr == nil {
+		w.fileErr = w.out.Flush()
+	}
+	if err := w.file.Close(); err != nil && w.fileErr == nil {
+		w.fileErr = err
+	}
+	if w.fileErr != nil || w.aborted {
+		if err := os.Remove(w.path); err != nil {
+			debug(fmt.Sprintf("cannot remove incomplete response file %s: %v", w.path, err))
+		}
+	}
+}
+
+// ServeRequestToFile handles a single trustless gateway request in-process and writes the body
+// of a 200 response directly to the file at `path`, creating or truncating it. The body of other
+// responses is returned in the result and the file is not created. An incomplete file is removed
+// when Lassie aborts the response or when writing fails.
+//
+// The function returns when the response was fully written.
+//
+//export ServeRequestToFile
+func ServeRequestToFile(req *C.serve_request_t, path *C.char) (result C.serve_to_file_result_t) {
+	defer recoverError("ServeRequestToFile", &result.error)
+	d := getDaemon()
+	if d == nil {
+		return newServeToFileError("Lassie daemon is not running", nil)
+	}
+
+	httpReq, cancel, err := newInProcessRequest(d, req)
+	if err != nil {
+		return newServeToFileError("invalid request path", err)
+	}
+	defer cancel()
+
+	w := &fileResponseWriter{header: http.Header{}, path: C.GoString(path)}
+	d.ipfsHandler.ServeHTTP(w, httpReq)
+	// Handle the case when the handler did not write anything
+	w.WriteHeader(http.StatusOK)
+	w.finish()
+
+	if w.fileErr != nil {
+		return newServeToFileError("cannot write the response to "+w.path, w.fileErr)
+	}
+	if w.aborted {
+		return newServeToFileError(errResponseAborted.Error(), nil)
+	}
+
+	result = C.serve_to_file_result_t{
+		status:  C.uint16_t(w.status),
+		bytes:   C.uint64_t(w.bytes),
+		headers: C.CString(encodeHeaders(w.header)),
+	}
+	if w.status != http.StatusOK {
+		result.body = C.CString(w.body.String())
+	}
+	return result
+}
+
+func newServeToFileError(msg string, cause error) C.serve_to_file_result_t {
+	if cause != nil {
+		msg = fmt.Sprintf("%s: %+v", msg, cause)
+	}
+
+	return C.serve_to_file_result_t{
+		error: C.CString(msg),
+	}
+}
+
+// DropServeToFileResult cleans up any resources allocated for and owned by the
+// serve_to_file_result_t value.
+//
+//export DropServeToFileResult
+func DropServeToFileResult(result *C.serve_to_file_result_t) {
+	if result.headers != nil {
+		C.free(unsafe.Pointer(result.headers))
+		result.headers = nil
+	}
+	if result.body != nil {
+		C.free(unsafe.Pointer(result.body))
+		result.body = nil
+	}
+	if result.error != nil {
+		C.free(unsafe.Pointer(result.error))
+		result.error = nil
+	}
+}
+
+func getResponse(handle uint64) *inProcessResponse {
+	responsesMtx.Lock()
+	defer responsesMtx.Unlock()
+	return responses[handle]
+}
+
+func decodeHeaders(text string) http.Header {
+	header := http.Header{}
+	for _, line := range strings.Split(text, "\n") {
+		name, value, found := strings.Cut(line, ":")
+		if !found {
+			continue
+		}
+		header.Add(strings.TrimSpace(name), strings.TrimSpace(value))
+	}
+	return header
+}
+
+func encodeHeaders(header http.Header) string {
+	var b strings.Builder
+	for name, values := range header {
+		for _, value := range values {
+			fmt.Fprintf(&b, "%s: %s\n", name, value)
+		}
+	}
+	return b.String()
+}
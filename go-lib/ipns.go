@@ -0,0 +1,141 @@
+package main
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"net/http"
+	"strings"
+	"time"
+
+	"github.com/ipfs/boxo/ipns"
+)
+
+// defaultIpnsEndpoint is the delegated routing server we fetch IPNS records from when no server
+// is configured, see https://specs.ipfs.tech/routing/http-routing-v1/#ipns-api
+const defaultIpnsEndpoint = "https://delegated-ipfs.dev"
+
+// maxIpnsDepth limits how many IPNS names pointing to other IPNS names we follow.
+const maxIpnsDepth = 32
+
+// errIpnsNotFound is returned when the routing server does not know the IPNS name.
+var errIpnsNotFound = errors.New("IPNS record not found")
+
+// errInvalidIpnsName is returned for names that are not libp2p keys, e.g. DNSLink domain names.
+var errInvalidIpnsName = errors.New("invalid IPNS name")
+
+// withIpns serves `/ipns/{name}/...` requests by resolving the name to an `/ipfs/` path and
+// passing the rewritten request to next. Other requests are passed through unchanged. The records
+// are fetched from the delegated routing server at endpoint, or from defaultIpnsEndpoint.
+//
+// Lassie itself serves `/ipfs/` paths only. The records are signed, we verify the signature and
+// the validity before trusting the routing server.
+func withIpns(next http.Handler, endpoint string) http.Handler {
+	if endpoint == "" {
+		endpoint = defaultIpnsEndpoint
+	}
+	client := &http.Client{Timeout: 30 * time.Second}
+	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		rest, ok := strings.CutPrefix(r.URL.Path, "/ipns/")
+		if !ok {
+			next.ServeHTTP(w, r)
+			return
+		}
+
+		name, subPath, _ := strings.Cut(rest, "/")
+		target, err := resolveIpns(r.Context(), client, endpoint, name)
+		if err != nil {
+			status := http.StatusBadGateway
+			switch {
+			case errors.Is(err, errIpnsNotFound):
+				status = http.StatusNotFound
+			case errors.Is(err, errInvalidIpnsName):
+				status = http.StatusBadRequest
+				w.Header().Set(errorCodeHeader, errorCodeBadRequest)
+			case errors.Is(err, context.DeadlineExceeded):
+				status = http.StatusGatewayTimeout
+				w.Header().Set(errorCodeHeader, errorCodeTimeout)
+			}
+			http.Error(w, fmt.Sprintf("cannot resolve /ipns/%s: %v", name, err), status)
+			return
+		}
+		if subPath != "" {
+			target = strings.TrimSuffix(target, "/") + "/" + subPath
+		}
+		debug(fmt.Sprintf("Resolved /ipns/%s to %s", name, target))
+
+		rewritten := r.Clone(r.Context())
+		rewritten.URL.Path = target
+		rewritten.URL.RawPath = ""
+		rewritten.RequestURI = rewritten.URL.RequestURI()
+		next.ServeHTTP(w, rewritten)
+	})
+}
+
+// resolveIpns follows the chain of IPNS records starting at name and returns the `/ipfs/` path
+// at its end.
+func resolveIpns(ctx context.Context, client *http.Client, endpoint string, name string) (string, error) {
+	suffix := ""
+	for depth := 0; depth < maxIpnsDepth; depth++ {
+		n, err := ipns.NameFromString(name)
+		if err != nil {
+			return "", fmt.Errorf("%w: %v", errInvalidIpnsName, err)
+		}
+		rec, err := fetchIpnsRecord(ctx, client, endpoint, n)
+		if err != nil {
+			return "", err
+		}
+		value, err := rec.Value()
+		if err != nil {
+			return "", err
+		}
+
+		p := strings.TrimSuffix(value.String(), "/") + suffix
+		rest, ok := strings.CutPrefix(p, "/ipns/")
+		if !ok {
+			return p, nil
+		}
+		var sub string
+		name, sub, _ = strings.Cut(rest, "/")
+		suffix = ""
+		if sub != "" {
+			suffix = "/" + sub
+		}
+	}
+	return "", fmt.Errorf("more than %d IPNS names in the chain", maxIpnsDepth)
+}
+
+func fetchIpnsRecord(ctx context.Context, client *http.Client, endpoint string, name ipns.Name) (*ipns.Record, error) {
+	url := fmt.Sprintf("%s/routing/v1/ipns/%s", strings.TrimSuffix(endpoint, "/"), name.String())
+	req, err := http.NewRequestWithContext(ctx, http.MethodGet, url, nil)
+	if err != nil {
+		return nil, err
+	}
+	req.Header.Set("Accept", "application/vnd.ipfs.ipns-record")
+
+	resp, err := client.Do(req)
+	if err != nil {
+		return nil, err
+	}
+	defer resp.Body.Close()
+	if resp.StatusCode == http.StatusNotFound {
+		return nil, errIpnsNotFound
+	}
+	if resp.StatusCode != http.StatusOK {
+		return nil, fmt.Errorf("routing server responded with %s", resp.Status)
+	}
+
+	data, err := io.ReadAll(io.LimitReader(resp.Body, ipns.MaxRecordSize))
+	if err != nil {
+		return nil, err
+	}
+	rec, err := ipns.UnmarshalRecord(data)
+	if err != nil {
+		return nil, err
+	}
+	if err := ipns.ValidateWithName(rec, name); err != nil {
+		return nil, err
+	}
+	return rec, nil
+}
@@ -0,0 +1,81 @@
+package main
+
+import (
+	"context"
+	"fmt"
+	"sync"
+	"time"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/libp2p/go-libp2p/core/host"
+	"github.com/libp2p/go-libp2p/core/peer"
+	"github.com/multiformats/go-multicodec"
+)
+
+// keepAliveTag protects the connections to recently used Bitswap providers from the connection
+// manager.
+const keepAliveTag = "lassie-keep-alive"
+
+// keepBitswapConnections returns a subscriber keeping the connections to the providers that
+// served Bitswap retrievals open for keepAlive after their last use, so that subsequent
+// retrievals from the same provider reuse the connection instead of dialing again. The
+// connections are released when ctx is done.
+//
+// Lassie creates a Bitswap session per retrieval, the sessions themselves are not reused.
+func keepBitswapConnections(ctx context.Context, h host.Host, keepAlive time.Duration) func(types.RetrievalEvent) {
+	debug(fmt.Sprintf("Keeping connections to Bitswap providers open for %v", keepAlive))
+
+	var mtx sync.Mutex
+	lastUsed := map[peer.ID]time.Time{}
+	go func() {
+		ticker := time.NewTicker(min(keepAlive, time.Minute))
+		defer ticker.Stop()
+		for {
+			select {
+			case <-ctx.Done():
+				mtx.Lock()
+				for p := range lastUsed {
+					h.ConnManager().Unprotect(p, keepAliveTag)
+				}
+				mtx.Unlock()
+				return
+			case now := <-ticker.C:
+				mtx.Lock()
+				for p, used := range lastUsed {
+					if now.Sub(used) > keepAlive {
+						h.ConnManager().Unprotect(p, keepAliveTag)
+						delete(lastUsed, p)
+					}
+				}
+				mtx.Unlock()
+			}
+		}
+	}()
+
+	return func(event types.RetrievalEvent) {
+		if event.Code() != types.FirstByteCode && event.Code() != types.SuccessCode {
+			return
+		}
+		if e, ok := event.(eventWithProtocol); !ok || e.Protocol() != multicodec.TransportBitswap {
+			return
+		}
+		e, ok := event.(eventWithProviderId)
+		if !ok || e.ProviderId() == "" {
+			return
+		}
+		p := e.ProviderId()
+
+		mtx.Lock()
+		defer mtx.Unlock()
+		if ctx.Err() != nil {
+			return
+		}
+		if _, known := lastUsed[p]; !known {
+			debugw("keeping the connection to a Bitswap provider open", "provider", p)
+			h.ConnManager().Protect(p, keepAliveTag)
+		}
+		lastUsed[p] = time.Now()
+		// Keep the addresses too, in case the provider closes the connection
+		h.Peerstore().AddAddrs(p, h.Peerstore().Addrs(p), keepAlive)
+	}
+}
@@ -8,51 +8,74 @@ package main
 //  5 trace
 
 /*
-#include <stdlib.h>
-#include <stdint.h>
-
-typedef struct {
-	const char* temp_dir;
-	uint16_t port;
-	size_t log_level;
-	uint64_t max_blocks;
-	int64_t provider_timeout;
-	int64_t global_timeout;
-	const char* access_token;
-	const char* lassie_user_agent;
-} daemon_config_t;
-
-typedef struct {
-	uint16_t port;
-	const char* error;
-} daemon_init_result_t;
-
-typedef struct {
-	const char * error;
-} result_t;
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
 */
 import "C"
 
 import (
 	"context"
+	"crypto/subtle"
+	"errors"
 	"fmt"
+	"math"
 	"net"
+	"net/http"
 	"os"
 	"strconv"
+	"strings"
 	"sync"
+	"sync/atomic"
 	"time"
 	"unsafe"
 
+	"github.com/filecoin-project/lassie/pkg/aggregateeventrecorder"
 	lassieBuild "github.com/filecoin-project/lassie/pkg/build"
 	"github.com/filecoin-project/lassie/pkg/lassie"
 	httpserver "github.com/filecoin-project/lassie/pkg/server/http"
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/google/uuid"
+	"github.com/libp2p/go-libp2p/core/host"
+	servertiming "github.com/mitchellh/go-server-timing"
 )
 
+// lassieDaemon holds the state of the running daemon.
+type lassieDaemon struct {
+	ctx    context.Context
+	cancel context.CancelFunc
+
+	// host is the libp2p host used by Lassie, we own it and must close it
+	host host.Host
+
+	// ipfsHandler serves trustless gateway requests, including `/ipns/` paths (see withIpns), and
+	// reports them to the access log callback.
+	// It's shared by the HTTP server and in-process requests (see ServeRequest).
+	ipfsHandler http.Handler
+
+	// server and listener are nil when the HTTP listener is disabled
+	server   *http.Server
+	listener net.Listener
+
+	// extraListeners are served alongside the HTTP listener, see extra_listeners
+	extraListeners []extraListener
+
+	// adminServer and adminListener are nil when the admin listener is not configured
+	adminServer   *http.Server
+	adminListener net.Listener
+
+	// done is closed by StopDaemon
+	done chan struct{}
+}
+
 var mtx sync.Mutex
-var daemon *httpserver.HttpServer
+var daemon *lassieDaemon
 var debug_log_enabled bool
 
-var OK C.result_t = C.result_t{error: nil}
+// stoppedInternally is set when the daemon was stopped via the admin listener or after
+// idle_shutdown, see shutdownFromAdmin and stopIdleDaemon
+var stoppedInternally bool
+
+var OK C.result_t = C.result_t{error: nil, error_code: C.LASSIE_OK}
 
 // InitDaemon initializes Lassie HTTP daemon listening on localhost and returns the port number.
 // The daemon is a singleton - there can be only one instance running in the host process.
@@ -60,7 +83,8 @@ var OK C.result_t = C.result_t{error: nil}
 // **Important:** This function does not run the request handler, you must call RunDaemon().
 //
 //export InitDaemon
-func InitDaemon(cfg *C.daemon_config_t) C.daemon_init_result_t {
+func InitDaemon(cfg *C.daemon_config_t) (result C.daemon_init_result_t) {
+	defer recoverErrorCode("InitDaemon", &result.error, &result.error_code)
 	// We cannot set the global debug_log_variable here, because we need to obtain the lock first.
 	// We create a local variable with a different name instead.
 	wants_debug_log := cfg.log_level >= 4
@@ -73,12 +97,31 @@ func InitDaemon(cfg *C.daemon_config_t) C.daemon_init_result_t {
 	defer mtx.Unlock()
 	defer debug("InitDaemon lock released")
 
-	debug_log_enabled = wants_debug_log
-
+	// Before touching the logging of the running daemon
 	if daemon != nil {
-		return newInitError("cannot create more than one Lassie daemon", nil)
+		return newInitErrorCode(C.LASSIE_ERR_ALREADY_RUNNING, "cannot create more than one Lassie daemon", nil)
 	}
 
+	debug_log_enabled = wants_debug_log
+	if err := setupLogging(cfg); err != nil {
+		return newInitError("cannot configure logging", err)
+	}
+	if err := setupEventLog(cfg); err != nil {
+		return newInitError("cannot open event_log", err)
+	}
+	setupTracing(cfg)
+	// The error returns below don't close the event log and the tracer provider, stopDaemon
+	// closes them again when the daemon was already created
+	defer func() {
+		if result.error != nil {
+			closeEventLog()
+			shutdownTracing()
+		}
+	}()
+	stoppedInternally = false
+	draining.Store(false)
+	lastActivity.Store(time.Now().UnixNano())
+
 	var tempDir string = C.GoString(cfg.temp_dir)
 	accessToken := C.GoString(cfg.access_token)
 	lassieBuild.UserAgent = C.GoString(cfg.lassie_user_agent)
@@ -95,64 +138,273 @@ func InitDaemon(cfg *C.daemon_config_t) C.daemon_init_result_t {
 		debug(fmt.Sprintf("Lassie configuration:\n  log_level=%d\n  port=%d\n  temp_dir=%v\n  accessToken=%v", cfg.log_level, cfg.port, tempDirStr, accessTokenStr))
 	}
 
+	// Lassie writes to the temp dir only when serving the first request, fail early instead
+	if tempDir != "" {
+		if err := checkWritableDir(tempDir); err != nil {
+			return newInitErrorCode(C.LASSIE_ERR_TEMP_DIR_NOT_WRITABLE, "temp_dir is not writable", err)
+		}
+	}
+
+	configureRuntime(cfg)
+	// Before newCandidateSource, the IPNI client captures the default transport
+	if err := configureHttpTransport(cfg); err != nil {
+		return newInitError("cannot configure outbound HTTP", err)
+	}
+
 	lassieOpts := []lassie.LassieOption{
 		lassie.WithProviderTimeout(time.Duration(cfg.provider_timeout)),
 		lassie.WithGlobalTimeout(time.Duration(cfg.global_timeout)),
 	}
 
-	// TODO: configure Libp2p connection manager (LowWater, HighWater)
 	// TODO: configure max concurrent SP retrievals
-	// connManager, err := connmgr.NewConnManager(libp2pLowWater, libp2pHighWater)
-	// if err != nil {
-	// 	return err
-	// }
-	// lassieOpts = append(
-	// 	lassieOpts,
-	// 	lassie.WithLibp2pOpts(libp2p.ConnectionManager(connManager)),
-	// 	lassie.WithConcurrentSPRetrievals(concurrentSPRetrievals),
-	// )
+	// lassieOpts = append(lassieOpts, lassie.WithConcurrentSPRetrievals(concurrentSPRetrievals))
+
+	if cfg.bitswap_concurrency > 0 {
+		lassieOpts = append(lassieOpts, lassie.WithBitswapConcurrency(int(cfg.bitswap_concurrency)))
+	}
+	if cfg.bitswap_concurrency_per_retrieval > 0 {
+		lassieOpts = append(lassieOpts, lassie.WithBitswapConcurrencyPerRetrieval(int(cfg.bitswap_concurrency_per_retrieval)))
+	}
 
-	// TODO: configure bitswap concurrency
-	// lassieOpts = append(lassieOpts, lassie.WithBitswapConcurrency(bitswapConcurrency))
+	candidateSource, err := newCandidateSource(cfg)
+	if err != nil {
+		return newInitError("cannot create candidate source", err)
+	}
+	candidateSource, err = withCircuitBreaker(cfg, candidateSource)
+	if err != nil {
+		return newInitError("cannot create candidate source", err)
+	}
+	// Outside of the circuit breaker, skipped providers are not scored
+	candidateSource, err = withProviderScores(candidateSource)
+	if err != nil {
+		return newInitError("cannot create candidate source", err)
+	}
+	lassieOpts = append(lassieOpts, lassie.WithCandidateSource(candidateSource))
+
+	if cfg.mmap_car_store_size > 0 && !mmapSupported {
+		return newInitError("cannot create the memory-mapped CAR store", errMmapNotSupported)
+	}
+
+	var cache *blockCache
+	if blockCacheDir := C.GoString(cfg.block_cache_dir); blockCacheDir != "" {
+		cache, err = newBlockCache(blockCacheDir, uint64(cfg.block_cache_max_size))
+		if err != nil {
+			return newInitError("cannot open block cache", err)
+		}
+	}
+
+	host, startupPeers, err := newHost(cfg)
+	if err != nil {
+		return newInitError("cannot configure libp2p", err)
+	}
+	lassieOpts = append(lassieOpts, lassie.WithHost(host))
 
-	ctx := context.Background()
+	ctx, cancel := context.WithCancel(context.Background())
 
 	lassie, err := lassie.NewLassie(ctx, lassieOpts...)
 	if err != nil {
+		cancel()
+		host.Close()
 		return newInitError("cannot create Lassie instance", err)
 	}
 
-	daemon, err = httpserver.NewHttpServer(ctx, lassie, httpserver.HttpServerConfig{
-		Address:             "127.0.0.1",
-		Port:                uint(cfg.port),
+	bootstrap(ctx, host, startupPeers.bootstrap)
+	preconnect(ctx, host, startupPeers.preconnect)
+	// Record the providers of our requests, see retrievals.go
+	lassie.RegisterSubscriber(onRetrievalEvent)
+	lassie.RegisterSubscriber(recordMetrics)
+	lassie.RegisterSubscriber(recordProviderStats)
+	// Must run after onRetrievalEvent, see events.go
+	lassie.RegisterSubscriber(forwardRetrievalEvent)
+	lassie.RegisterSubscriber(writeEventLog)
+	if cfg.bitswap_keep_alive > 0 {
+		lassie.RegisterSubscriber(keepBitswapConnections(ctx, host, time.Duration(cfg.bitswap_keep_alive)))
+	}
+
+	if eventRecorderURL := C.GoString(cfg.event_recorder_url); eventRecorderURL != "" {
+		instanceID := C.GoString(cfg.event_recorder_instance_id)
+		if instanceID == "" {
+			instanceID = uuid.NewString()
+		}
+		debug(fmt.Sprintf("Pushing retrieval events to %s as instance %s", eventRecorderURL, instanceID))
+		eventRecorder := aggregateeventrecorder.NewAggregateEventRecorder(ctx, aggregateeventrecorder.EventRecorderConfig{
+			InstanceID:            instanceID,
+			EndpointURL:           eventRecorderURL,
+			EndpointAuthorization: C.GoString(cfg.event_recorder_auth),
+		})
+		lassie.RegisterSubscriber(eventRecorder.RetrievalEventSubscriber())
+	}
+
+	// Correlate Lassie retrieval events with our requests, see retrievals.go
+	var fetcher types.Fetcher = correlatingFetcher{fetcher: lassie}
+	if cfg.max_download_rate > 0 || cfg.max_download_rate_per_retrieval > 0 {
+		// Inside of the block cache, cached blocks are served without waiting
+		throttled := throttlingFetcher{fetcher: fetcher, perRetrieval: uint64(cfg.max_download_rate_per_retrieval)}
+		if cfg.max_download_rate > 0 {
+			throttled.global = newRateLimiter(uint64(cfg.max_download_rate))
+		}
+		fetcher = throttled
+	}
+	if cache != nil {
+		fetcher = cachingFetcher{fetcher: fetcher, cache: cache}
+	}
+	if cfg.mmap_car_store_size > 0 {
+		// Outside of the block cache, blocks found in the mmap store don't need a cache lookup
+		fetcher = mmapFetcher{fetcher: fetcher, dir: tempDir, size: int(min(uint64(cfg.mmap_car_store_size), math.MaxInt))}
+	}
+
+	// The access token is checked by our own middleware, see requireAccessToken(). In-process
+	// requests are trusted and don't need to provide the token.
+	retrievalHandler := trackRetrievals(servertiming.Middleware(http.HandlerFunc(httpserver.IpfsHandler(fetcher, httpserver.HttpServerConfig{
 		TempDir:             tempDir,
 		MaxBlocksPerRequest: uint64(cfg.max_blocks),
-		AccessToken:         accessToken,
-	})
+	})), nil))
+	if cfg.max_concurrent_retrievals > 0 {
+		// Inside of withIpns, the IPNS names are resolved before the retrieval is queued
+		queue := newRetrievalQueue(int(cfg.max_concurrent_retrievals), int(cfg.max_queued_retrievals))
+		retrievalHandler = limitConcurrentRetrievals(queue, retrievalHandler)
+	}
+	ipfsHandler := withRequestTimeout(withIpns(retrievalHandler, C.GoString(cfg.delegated_routing_url)))
+	if cfg.disable_candidate_discovery {
+		ipfsHandler = requireProviders(ipfsHandler)
+	}
+	if cfg.max_concurrent_requests > 0 {
+		ipfsHandler = limitConcurrentRequests(int(cfg.max_concurrent_requests), ipfsHandler)
+	}
+	ipfsHandler = trackActivity(rejectWhileDraining(ipfsHandler))
+
+	d := &lassieDaemon{
+		ctx:         ctx,
+		cancel:      cancel,
+		host:        host,
+		ipfsHandler: withAccessLog(ipfsHandler),
+		done:        make(chan struct{}),
+	}
+
+	if !cfg.disable_listener {
+		allowedClientIps, err := parseAllowedClientIps(cfg)
+		if err != nil {
+			cancel()
+			host.Close()
+			return newInitError("cannot configure the HTTP server", err)
+		}
+
+		listener, err := openListener(cfg)
+		if err != nil {
+			cancel()
+			host.Close()
+			code := listenErrorCode(err, C.LASSIE_ERR_ADDR_IN_USE, C.LASSIE_ERR_PERMISSION_DENIED)
+			return newInitErrorCode(code, "cannot start the HTTP server", err)
+		}
 
+		extras, err := openExtraListeners(ctx, cfg, ipfsHandler, allowedClientIps)
+		if err != nil {
+			listener.Close()
+			cancel()
+			host.Close()
+			return newInitError("cannot start the extra listeners", err)
+		}
+
+		d.listener = listener
+		d.server = &http.Server{
+			BaseContext: func(listener net.Listener) context.Context { return ctx },
+			Handler:     newServerHandler(ipfsHandler, accessToken, allowedClientIps, bool(cfg.restrict_client_ips)),
+		}
+		d.extraListeners = extras
+	}
+
+	adminServer, adminListener, err := newAdminServer(ctx, cfg, host)
 	if err != nil {
-		return newInitError("cannot start the HTTP server", err)
+		if d.listener != nil {
+			d.listener.Close()
+		}
+		closeExtraListeners(d.extraListeners)
+		cancel()
+		host.Close()
+		code := listenErrorCode(err, C.LASSIE_ERR_ADMIN_ADDR_IN_USE, C.LASSIE_ERR_ADMIN_PERMISSION_DENIED)
+		return newInitErrorCode(code, "cannot start the admin listener", err)
 	}
+	d.adminServer = adminServer
+	d.adminListener = adminListener
+
+	daemon = d
 
-	port, err := getPort()
+	port, err := listenerPort(d.listener)
 	if err != nil {
+		stopDaemon()
 		return newInitError("cannot parse HTTP server port", err)
 	}
+	adminPort, err := listenerPort(d.adminListener)
+	if err != nil {
+		stopDaemon()
+		return newInitError("cannot parse admin listener port", err)
+	}
+
+	if cfg.idle_shutdown > 0 {
+		go watchIdle(d, time.Duration(cfg.idle_shutdown))
+	}
+	// At the info level, so that a configured log file tells when the daemon started
+	wrapperLog.Infow("Lassie daemon started", "port", port, "admin_port", adminPort)
+
+	var listenAddr, extraListenAddrs *C.char
+	if d.listener != nil {
+		listenAddr = C.CString(d.listener.Addr().String())
+	}
+	if len(d.extraListeners) > 0 {
+		addrs := make([]string, 0, len(d.extraListeners))
+		for _, extra := range d.extraListeners {
+			addrs = append(addrs, extra.listener.Addr().String())
+		}
+		extraListenAddrs = C.CString(strings.Join(addrs, "\n"))
+	}
 
 	return C.daemon_init_result_t{
-		port:  C.ushort(port),
-		error: nil,
+		port:               C.ushort(port),
+		admin_port:         C.ushort(adminPort),
+		listen_addr:        listenAddr,
+		extra_listen_addrs: extraListenAddrs,
+		error:              nil,
+	}
+}
+
+// checkWritableDir verifies that we can create files in dir.
+func checkWritableDir(dir string) error {
+	f, err := os.CreateTemp(dir, ".lassie-probe-*")
+	if err != nil {
+		return err
 	}
+	f.Close()
+	return os.Remove(f.Name())
 }
 
 func newInitError(msg string, cause error) C.daemon_init_result_t {
+	return newInitErrorCode(C.LASSIE_ERR_UNKNOWN, msg, cause)
+}
+
+// newInitErrorCode reports an error the Rust side maps to a StartError variant, see
+// StartError::from_init_error in src/start_error.rs
+func newInitErrorCode(code C.uint32_t, msg string, cause error) C.daemon_init_result_t {
 	if cause != nil {
 		msg = fmt.Sprintf("%s: %+v", msg, cause)
 	}
 
 	return C.daemon_init_result_t{
-		port:  0,
-		error: C.CString(msg),
+		port:       0,
+		admin_port: 0,
+		error:      C.CString(msg),
+		error_code: code,
+	}
+}
+
+// listenErrorCode classifies the error of opening a listener.
+func listenErrorCode(err error, addrInUse, permissionDenied C.uint32_t) C.uint32_t {
+	switch {
+	case isAddrInUse(err):
+		return addrInUse
+	case isPermissionDenied(err):
+		return permissionDenied
+	default:
+		return C.LASSIE_ERR_UNKNOWN
 	}
 }
 
@@ -161,6 +413,14 @@ func newInitError(msg string, cause error) C.daemon_init_result_t {
 //
 //export DropDaemonInitResult
 func DropDaemonInitResult(result *C.daemon_init_result_t) {
+	if result.listen_addr != nil {
+		C.free(unsafe.Pointer(result.listen_addr))
+		result.listen_addr = nil
+	}
+	if result.extra_listen_addrs != nil {
+		C.free(unsafe.Pointer(result.extra_listen_addrs))
+		result.extra_listen_addrs = nil
+	}
 	if result.error != nil {
 		C.free(unsafe.Pointer(result.error))
 		result.error = nil
@@ -168,11 +428,16 @@ func DropDaemonInitResult(result *C.daemon_init_result_t) {
 }
 
 func newError(msg string, cause error) C.result_t {
+	return newErrorCode(C.LASSIE_ERR_UNKNOWN, msg, cause)
+}
+
+func newErrorCode(code C.uint32_t, msg string, cause error) C.result_t {
 	if cause != nil {
 		msg = fmt.Sprintf("%s: %+v", msg, cause)
 	}
 	return C.result_t{
-		error: C.CString(msg),
+		error:      C.CString(msg),
+		error_code: code,
 	}
 }
 
@@ -192,25 +457,43 @@ func DropResult(result *C.result_t) {
 // **Important:** This function does not exit until you call StopDaemon from a different thread.
 //
 //export RunDaemon
-func RunDaemon() C.result_t {
-	server := getDaemon()
+func RunDaemon() (result C.result_t) {
+	defer recoverErrorCode("RunDaemon", &result.error, &result.error_code)
+	d := getDaemon()
 
-	if server == nil {
+	if d == nil {
 		// The server may have been cleaned by now if StopDaemon was called quickly after StartDaemon
 		return OK
 	}
 
+	if d.adminServer != nil {
+		go func() {
+			err := d.adminServer.Serve(d.adminListener)
+			if err != nil && !errors.Is(err, http.ErrServerClosed) {
+				debug("ADMIN LISTENER FAILED:", err)
+			}
+		}()
+	}
+
+	serveExtraListeners(d.extraListeners)
+
+	if d.server == nil {
+		debug("HTTP LISTENER DISABLED, WAITING FOR STOP")
+		<-d.done
+		return OK
+	}
+
 	debug("RUNNING LASSIE HANDLER")
-	err := server.Start()
+	err := d.server.Serve(d.listener)
 	debug("LASSIE HANDLER EXITED:", err)
-	if err != nil {
+	if err != nil && !errors.Is(err, http.ErrServerClosed) {
 		return newError("Lassie HTTP server error", err)
 	}
 
 	return OK
 }
 
-func getDaemon() *httpserver.HttpServer {
+func getDaemon() *lassieDaemon {
 	debug("RunDaemon locking the mutex")
 	mtx.Lock()
 	defer mtx.Unlock()
@@ -219,34 +502,230 @@ func getDaemon() *httpserver.HttpServer {
 	return daemon
 }
 
+// DrainDaemon stops accepting new requests and waits up to `timeout` nanoseconds for the running
+// retrievals to finish. It returns the number of retrievals still running when the timeout
+// expired. The caller must stop the daemon via StopDaemon afterwards.
+//
+//export DrainDaemon
+func DrainDaemon(timeout C.int64_t) C.uint64_t {
+	defer recoverPanic("DrainDaemon")
+	d := getDaemon()
+	if d == nil {
+		return 0
+	}
+	draining.Store(true)
+
+	ctx, cancel := context.WithTimeout(context.Background(), time.Duration(timeout))
+	defer cancel()
+	if d.server != nil {
+		// Shutdown closes the listener and waits for the HTTP requests in flight, RunDaemon
+		// returns right away. StopDaemon shuts the server down again, which is a no-op then.
+		if err := d.server.Shutdown(ctx); err != nil && !errors.Is(err, context.DeadlineExceeded) {
+			debug("CANNOT DRAIN THE HTTP SERVER", err)
+		}
+		if err := shutdownExtraListeners(ctx, d.extraListeners); err != nil && !errors.Is(err, context.DeadlineExceeded) {
+			debug("CANNOT DRAIN THE EXTRA LISTENERS", err)
+		}
+	}
+
+	// In-process requests don't go through the server
+	ticker := time.NewTicker(50 * time.Millisecond)
+	defer ticker.Stop()
+	for {
+		remaining := runningRetrievals()
+		if remaining == 0 {
+			return 0
+		}
+		select {
+		case <-ctx.Done():
+			return C.uint64_t(remaining)
+		case <-ticker.C:
+		}
+	}
+}
+
 // CloseDaemon stops the Lassie HTTP daemon.
 //
 //export StopDaemon
-func StopDaemon() C.result_t {
+func StopDaemon() (result C.result_t) {
+	defer recoverErrorCode("StopDaemon", &result.error, &result.error_code)
 	debug("StopDaemon locking the mutex")
 	mtx.Lock()
 	defer mtx.Unlock()
 	defer debug("StopDaemon lock released")
 
 	if daemon == nil {
-		return newError("Lassie daemon not running, cannot stop it", nil)
+		if stoppedInternally {
+			// The daemon was already stopped via the admin listener or after idle_shutdown
+			stoppedInternally = false
+			return OK
+		}
+		return newErrorCode(C.LASSIE_ERR_NOT_RUNNING, "Lassie daemon not running, cannot stop it", nil)
 	}
 
 	debug("STOPPING LASSIE HANDLER")
-	err := daemon.Close()
+	err := stopDaemon()
 	debug("STOP ERROR?", err)
 	if err != nil {
 		return newError("Cannot stop Lassie HTTP server", err)
 	}
 
-	daemon = nil
 	return OK
 }
 
-func getPort() (uint16, error) {
-	_, portStr, err := net.SplitHostPort(daemon.Addr())
+// stopDaemon shuts down the running daemon. The caller must hold the mutex.
+func stopDaemon() error {
+	d := daemon
+	daemon = nil
+
+	d.cancel()
+	close(d.done)
+	var err error
+	if d.server != nil {
+		err = d.server.Shutdown(context.Background())
+	}
+	if extraErr := shutdownExtraListeners(context.Background(), d.extraListeners); err == nil {
+		err = extraErr
+	}
+	if d.adminServer != nil {
+		if adminErr := d.adminServer.Shutdown(context.Background()); err == nil {
+			err = adminErr
+		}
+	}
+	if closeErr := d.host.Close(); closeErr != nil {
+		debug("CANNOT CLOSE LIBP2P HOST", closeErr)
+	}
+	closeEventLog()
+	shutdownTracing()
+	return err
+}
+
+// requireAccessToken rejects requests that don't provide the configured access token in the
+// `Authorization: Bearer {token}` header.
+func requireAccessToken(accessToken string, next http.Handler) http.Handler {
+	if accessToken == "" {
+		return next
+	}
+
+	expected := []byte("Bearer " + accessToken)
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		actual := []byte(req.Header.Get("Authorization"))
+		if subtle.ConstantTimeCompare(actual, expected) != 1 {
+			httpError(res, errorCodeUnauthorized, "Unauthorized", http.StatusUnauthorized)
+			return
+		}
+		next.ServeHTTP(res, req)
+	})
+}
+
+// errTooManyRequests is the body of 429 responses, see RetrievalError::TooManyRequests in
+// src/retrieval_error.rs
+const errTooManyRequests = "too many concurrent requests, try again later"
+
+// errShuttingDown is the body of 503 responses to requests arriving while the daemon drains, see
+// RetrievalError::ShuttingDown in src/retrieval_error.rs
+const errShuttingDown = "Lassie daemon is shutting down"
+
+// draining is set by DrainDaemon and cleared by InitDaemon
+var draining atomic.Bool
+
+// rejectWhileDraining rejects new requests once DrainDaemon was called, both over HTTP and
+// in-process, so that the daemon stops after the running retrievals finish.
+func rejectWhileDraining(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		if draining.Load() {
+			debugw("rejected request while draining", "path", req.URL.Path)
+			res.Header().Set("Connection", "close")
+			httpError(res, errorCodeShuttingDown, errShuttingDown, http.StatusServiceUnavailable)
+			return
+		}
+		next.ServeHTTP(res, req)
+	})
+}
+
+// limitConcurrentRequests rejects requests beyond the limit instead of queueing them, so that load
+// spikes push back on clients instead of exhausting the memory.
+func limitConcurrentRequests(limit int, next http.Handler) http.Handler {
+	slots := make(chan struct{}, limit)
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		select {
+		case slots <- struct{}{}:
+			defer func() { <-slots }()
+			next.ServeHTTP(res, req)
+		default:
+			debugw("rejected request over max_concurrent_requests", "path", req.URL.Path)
+			res.Header().Set("Retry-After", "1")
+			httpError(res, errorCodeTooManyRequests, errTooManyRequests, http.StatusTooManyRequests)
+		}
+	})
+}
+
+func parseAllowedClientIps(cfg *C.daemon_config_t) ([]*net.IPNet, error) {
+	ranges := goStrings(cfg.allowed_client_ips, cfg.allowed_client_ips_len)
+	nets := make([]*net.IPNet, 0, len(ranges))
+	for _, r := range ranges {
+		_, ipNet, err := net.ParseCIDR(r)
+		if err != nil {
+			return nil, fmt.Errorf("invalid client IP range `%s`: %w", r, err)
+		}
+		nets = append(nets, ipNet)
+	}
+	debug(fmt.Sprintf("Accepting HTTP requests from %v only", ranges))
+	return nets, nil
+}
+
+// restrictClientIps rejects requests from clients outside of the allowed subnets. It must wrap
+// requireAccessToken, so that unknown clients cannot probe the access token.
+func restrictClientIps(allowed []*net.IPNet, next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		host, _, err := net.SplitHostPort(req.RemoteAddr)
+		ip := net.ParseIP(host)
+		if err != nil || ip == nil {
+			httpError(res, errorCodeForbidden, "Forbidden", http.StatusForbidden)
+			return
+		}
+		for _, ipNet := range allowed {
+			// Contains handles IPv4-mapped IPv6 addresses too
+			if ipNet.Contains(ip) {
+				next.ServeHTTP(res, req)
+				return
+			}
+		}
+		debugw("rejected request from a client outside of allowed_client_ips", "remote_addr", req.RemoteAddr)
+		httpError(res, errorCodeForbidden, "Forbidden", http.StatusForbidden)
+	})
+}
+
+// openListener uses the socket passed in listener_fd, e.g. by systemd, or binds a new one on
+// localhost.
+func openListener(cfg *C.daemon_config_t) (net.Listener, error) {
+	if cfg.listener_fd >= 0 {
+		return fileListener(int(cfg.listener_fd))
+	}
+	address := fmt.Sprintf("127.0.0.1:%d", cfg.port)
+	if cfg.reuse_port {
+		if !reusePortSupported {
+			return nil, errors.New("reuse_port is not supported on this platform")
+		}
+		listenConfig := net.ListenConfig{Control: setReusePort}
+		return listenConfig.Listen(context.Background(), "tcp", address)
+	}
+	return net.Listen("tcp", address)
+}
+
+// listenerPort returns the TCP port of the listener, or 0 for nil and non-TCP listeners.
+func listenerPort(listener net.Listener) (uint16, error) {
+	if listener == nil {
+		return 0, nil
+	}
+	if _, isTcp := listener.Addr().(*net.TCPAddr); !isTcp {
+		return 0, nil
+	}
+
+	addr := listener.Addr().String()
+	_, portStr, err := net.SplitHostPort(addr)
 	if err != nil {
-		return 0, fmt.Errorf("malformed server address `%s`: %+v", daemon.Addr(), err)
+		return 0, fmt.Errorf("malformed server address `%s`: %+v", addr, err)
 	}
 	port, err := strconv.ParseUint(portStr, 10, 16)
 	if err != nil {
@@ -256,15 +735,32 @@ func getPort() (uint16, error) {
 	return uint16(port), nil
 }
 
+// goStrings converts a C array of C strings to Go strings.
+func goStrings(ptrs **C.char, n C.size_t) []string {
+	if n == 0 {
+		return nil
+	}
+	strs := make([]string, 0, n)
+	for _, ptr := range unsafe.Slice(ptrs, n) {
+		strs = append(strs, C.GoString(ptr))
+	}
+	return strs
+}
+
 func debug(a ...any) {
-	if debug_log_enabled {
-		print_debug(a...)
+	if !debug_log_enabled {
+		return
+	}
+	if json_logs_enabled {
+		wrapperLog.Debugln(a...)
+		return
 	}
+	print_debug(a...)
 }
 
 func print_debug(a ...any) {
-	fmt.Fprint(os.Stderr, "[LASSIE GO WRAPPER] ")
-	fmt.Fprintln(os.Stderr, a...)
+	fmt.Fprint(logOutput, "[LASSIE GO WRAPPER] ")
+	fmt.Fprintln(logOutput, a...)
 }
 
 func main() {}
@@ -0,0 +1,369 @@
+// C types shared by the Go library and the Rust wrapper.
+// These definitions must be kept in sync with the `#[repr(C)]` structs in `src/`.
+
+#ifndef LASSIE_FFI_H
+#define LASSIE_FFI_H
+
+#include <stdbool.h>
+#include <stdlib.h>
+#include <stdint.h>
+
+typedef struct {
+	const char* temp_dir;
+	uint16_t port;
+	size_t log_level;
+	uint64_t max_blocks;
+	// 0 means no limit
+	uint32_t max_concurrent_requests;
+	// 0 means no limit, the retrievals beyond the limit wait in a queue of max_queued_retrievals
+	// items, -1 means an unbounded queue
+	uint32_t max_concurrent_retrievals;
+	int64_t max_queued_retrievals;
+	// The Bitswap provider timeout
+	int64_t provider_timeout;
+	// Response header and idle body read timeout of outbound HTTP requests, 0 disables
+	int64_t http_provider_timeout;
+	int64_t global_timeout;
+	// Skip providers after this many consecutive failures, 0 disables the circuit breaker
+	uint32_t circuit_breaker_failures;
+	// Nanoseconds
+	int64_t circuit_breaker_cool_down;
+	const char* access_token;
+	// When set, the HTTP listener accepts requests only from allowed_client_ips (CIDR notation)
+	bool restrict_client_ips;
+	const char** allowed_client_ips;
+	size_t allowed_client_ips_len;
+	const char* lassie_user_agent;
+	bool disable_listener;
+	// A listening socket to serve HTTP requests on instead of opening one, -1 when not set
+	int32_t listener_fd;
+	// Set SO_REUSEPORT on the HTTP listener
+	bool reuse_port;
+	// Stop the daemon after no requests for this many nanoseconds, 0 disables
+	int64_t idle_shutdown;
+	// Empty string disables the event recorder
+	const char* event_recorder_url;
+	const char* event_recorder_auth;
+	const char* event_recorder_instance_id;
+	// OTLP/HTTP collector to export the traces to, empty string disables the export
+	const char* otlp_endpoint;
+	// The service.name of the exported traces, empty string means rusty-lassie
+	const char* otlp_service_name;
+	// Multiaddrs (including the /p2p/ component) of the peers to connect to at startup
+	const char** bootstrap_peers;
+	size_t bootstrap_peers_len;
+	// Multiaddrs (including the /p2p/ component) of the providers to keep connected to
+	const char** preconnect_providers;
+	size_t preconnect_providers_len;
+	bool disable_ipni;
+	bool disable_dht;
+	// Reject requests without `providers=`, no candidate source is consulted
+	bool disable_candidate_discovery;
+	// Routing V1 server for provider lookups and IPNS records, empty string keeps the defaults
+	const char* delegated_routing_url;
+	// Connection manager limits, high_water=0 keeps the libp2p defaults
+	uint32_t conn_mgr_low_water;
+	uint32_t conn_mgr_high_water;
+	int64_t conn_mgr_grace_period;
+	// 0 keeps the Lassie defaults
+	uint32_t bitswap_concurrency;
+	uint32_t bitswap_concurrency_per_retrieval;
+	// Nanoseconds to keep the connections to Bitswap providers open after their last use, 0 disables
+	int64_t bitswap_keep_alive;
+	// Empty string disables the persistent block cache
+	const char* block_cache_dir;
+	uint64_t block_cache_max_size;
+	// Bytes of the memory-mapped store kept for each retrieval, 0 disables the store
+	uint64_t mmap_car_store_size;
+	// Bytes per second accepted from providers by all retrievals and by each retrieval, 0 means no limit
+	uint64_t max_download_rate;
+	uint64_t max_download_rate_per_retrieval;
+	// Admin listener: network is "tcp" or "unix", empty string disables the listener
+	const char* admin_network;
+	const char* admin_address;
+	const char* admin_access_token;
+	// Serve /debug/pprof/ on the admin listener
+	bool admin_pprof;
+	// Additional HTTP listeners, three arrays of extra_listeners_len items: the network ("tcp" or
+	// "unix"), the address and the access token (empty string accepts all requests)
+	const char** extra_listener_networks;
+	const char** extra_listener_addresses;
+	const char** extra_listener_access_tokens;
+	size_t extra_listeners_len;
+	// Emit go-log output as single-line JSON records
+	bool json_logs;
+	// Empty string keeps the log output on stderr
+	const char* log_file;
+	// Append retrieval events as JSON lines to this file, empty string disables the event log
+	const char* event_log;
+	// Outbound HTTP requests: disable HTTP/2, 0 keeps the Go default pool limits
+	bool http1_only;
+	uint32_t http_max_conns_per_host;
+	uint32_t http_max_idle_conns_per_host;
+	// PEM files of the TLS client certificate, empty strings when not configured
+	const char* http_client_cert_file;
+	const char* http_client_key_file;
+	// Skip the verification of server certificates, see danger_accept_invalid_certs in src/lib.rs
+	bool http_insecure_skip_verify;
+	// PEM files with CA certificates trusted in addition to the system roots
+	const char** extra_root_certs;
+	size_t extra_root_certs_len;
+	// Bitmask of LASSIE_TRANSPORT_* values, 0 keeps the libp2p default transports
+	uint32_t libp2p_transports;
+	// Soft limit of the Go heap in bytes (GOMEMLIMIT), 0 keeps the limit the runtime started with
+	uint64_t go_memory_limit;
+	// GOMAXPROCS, 0 keeps the value the runtime started with
+	uint32_t go_max_procs;
+} daemon_config_t;
+
+// Keep in sync with Libp2pTransport in src/lib.rs
+#define LASSIE_TRANSPORT_TCP 1
+#define LASSIE_TRANSPORT_QUIC 2
+#define LASSIE_TRANSPORT_WEBSOCKET 4
+#define LASSIE_TRANSPORT_WEBTRANSPORT 8
+#define LASSIE_TRANSPORT_WEBRTC_DIRECT 16
+
+// Stable error codes reported next to the error messages of daemon_init_result_t and result_t,
+// keep in sync with src/error_code.rs
+#define LASSIE_OK 0
+#define LASSIE_ERR_UNKNOWN 1
+// The function panicked, the message includes the stack trace
+#define LASSIE_ERR_PANIC 2
+#define LASSIE_ERR_ALREADY_RUNNING 3
+#define LASSIE_ERR_NOT_RUNNING 4
+#define LASSIE_ERR_ADDR_IN_USE 5
+#define LASSIE_ERR_PERMISSION_DENIED 6
+#define LASSIE_ERR_ADMIN_ADDR_IN_USE 7
+#define LASSIE_ERR_ADMIN_PERMISSION_DENIED 8
+#define LASSIE_ERR_TEMP_DIR_NOT_WRITABLE 9
+
+typedef struct {
+	uint16_t port;
+	// 0 when the admin listener is disabled or listens on a unix socket
+	uint16_t admin_port;
+	// The address of the HTTP listener, `ip:port` or the path of a unix socket. NULL when the
+	// listener is disabled.
+	const char* listen_addr;
+	// The addresses of the extra listeners separated by `\n`, NULL when there are none
+	const char* extra_listen_addrs;
+	const char* error;
+	// LASSIE_OK when error is NULL
+	uint32_t error_code;
+} daemon_init_result_t;
+
+typedef struct {
+	const char * error;
+	// LASSIE_OK when error is NULL
+	uint32_t error_code;
+} result_t;
+
+typedef struct {
+	// Request path including the query string, e.g. `/ipfs/{cid}?dag-scope=entity`
+	const char* path;
+	// Request headers encoded as `Name: value` lines separated by `\n`
+	const char* headers;
+} serve_request_t;
+
+typedef struct {
+	uint64_t handle;
+	uint16_t status;
+	// Response headers encoded as `Name: value` lines separated by `\n`
+	const char* headers;
+	const char* error;
+} serve_result_t;
+
+typedef struct {
+	size_t size;
+	const char* error;
+} read_result_t;
+
+typedef struct {
+	uint16_t status;
+	// The number of body bytes written to the file
+	uint64_t bytes;
+	// Response headers encoded as `Name: value` lines separated by `\n`
+	const char* headers;
+	// The response body when the status is not 200, the file is not created in that case
+	const char* body;
+	const char* error;
+} serve_to_file_result_t;
+
+typedef struct {
+	const char* id;
+	const char* cid;
+	// Peer ID of the provider currently serving the retrieval, NULL when not known yet
+	const char* provider;
+	// Time since the retrieval started, in nanoseconds
+	uint64_t elapsed;
+	uint64_t bytes_sent;
+	uint64_t blocks_received;
+} retrieval_info_t;
+
+typedef struct {
+	retrieval_info_t* items;
+	size_t len;
+} retrieval_list_t;
+
+typedef struct {
+	// Multicodec name of the protocol, e.g. `transport-bitswap`
+	const char* protocol;
+	// Attempts to retrieve from a single provider
+	uint64_t attempts;
+	uint64_t successes;
+	uint64_t failures;
+	// Bytes received in successful attempts
+	uint64_t bytes_received;
+} protocol_metrics_t;
+
+typedef struct {
+	uint64_t total_retrievals;
+	uint64_t active_retrievals;
+	uint64_t bytes_sent;
+	protocol_metrics_t* protocols;
+	size_t protocols_len;
+} metrics_t;
+
+typedef struct {
+	// Peer ID
+	const char* provider;
+	// Multicodec name of the protocol used in the last attempt, empty when unknown
+	const char* protocol;
+	// Outcomes of the most recent attempts
+	uint64_t successes;
+	uint64_t failures;
+	// Bytes received in the successful attempts counted above
+	uint64_t bytes_received;
+	// Nanoseconds, median of the most recent time-to-first-byte samples, -1 when there are none
+	int64_t median_ttfb;
+	// Nanoseconds since the Unix epoch
+	int64_t last_seen;
+	// Nanoseconds since the Unix epoch, 0 unless the circuit breaker skips the provider
+	int64_t circuit_open_until;
+} provider_stats_t;
+
+typedef struct {
+	provider_stats_t* items;
+	size_t len;
+} provider_stats_list_t;
+
+typedef struct {
+	// Bytes in in-use heap spans
+	uint64_t heap_in_use;
+	// Cumulative bytes allocated for heap objects
+	uint64_t total_alloc;
+	// Bytes obtained from the OS
+	uint64_t sys;
+	uint64_t goroutines;
+	uint32_t gc_cycles;
+} memory_stats_t;
+
+// Callbacks receiving the response produced by ServeRequestWithSink. A callback returns false to
+// abort the response.
+typedef bool (*head_callback_t)(void* ctx, uint16_t status, const char* headers);
+typedef bool (*chunk_callback_t)(void* ctx, const uint8_t* data, size_t len);
+
+typedef struct {
+	head_callback_t on_head;
+	chunk_callback_t on_chunk;
+	void* ctx;
+} response_sink_t;
+
+// Values of access_log_record_t.outcome, keep in sync with RequestOutcome in src/access_log.rs
+#define ACCESS_LOG_SUCCEEDED 0
+#define ACCESS_LOG_FAILED 1
+#define ACCESS_LOG_ABORTED 2
+#define ACCESS_LOG_CANCELLED 3
+
+typedef struct {
+	// Empty for requests rejected before the retrieval started
+	const char* retrieval_id;
+	// Empty for in-process requests
+	const char* remote_addr;
+	const char* method;
+	const char* path;
+	// Empty for requests that are not `/ipfs/` paths
+	const char* cid;
+	uint16_t status;
+	// Response body bytes
+	uint64_t bytes;
+	// Nanoseconds
+	int64_t duration;
+	uint8_t outcome;
+} access_log_record_t;
+
+// Called after each handled request. The record is valid only during the call.
+typedef void (*access_log_callback_t)(void* ctx, const access_log_record_t* record);
+
+// Values of retrieval_event_t.kind, keep in sync with RetrievalEventKind in src/events.rs
+#define RETRIEVAL_EVENT_STARTED 0
+#define RETRIEVAL_EVENT_CANDIDATES_FOUND 1
+#define RETRIEVAL_EVENT_CANDIDATES_FILTERED 2
+#define RETRIEVAL_EVENT_CONNECTED 3
+#define RETRIEVAL_EVENT_FIRST_BYTE 4
+#define RETRIEVAL_EVENT_SUCCESS 5
+#define RETRIEVAL_EVENT_FAILURE 6
+
+typedef struct {
+	// Empty when the event cannot be correlated with a request, e.g. after the response ended
+	const char* retrieval_id;
+	const char* lassie_id;
+	// Empty when unknown
+	const char* root_cid;
+	// Peer ID, empty for events not related to a single provider
+	const char* provider;
+	// Multicodec name, e.g. transport-bitswap, empty when unknown
+	const char* protocol;
+	// Empty unless kind is RETRIEVAL_EVENT_FAILURE
+	const char* error;
+	// Peer IDs of the candidates of RETRIEVAL_EVENT_CANDIDATES_* events
+	const char** candidates;
+	size_t candidates_len;
+	// Nanoseconds since the Unix epoch
+	int64_t time;
+	// Nanoseconds, time to first byte or the retrieval duration
+	int64_t duration;
+	uint64_t bytes;
+	uint64_t blocks;
+	uint8_t kind;
+} retrieval_event_t;
+
+// Called for each retrieval event. The event is valid only during the call.
+typedef void (*retrieval_event_callback_t)(void* ctx, const retrieval_event_t* event);
+
+typedef struct {
+	const char* retrieval_id;
+	const char* cid;
+	// Peer ID and multicodec name of the provider that delivered the content, empty when unknown
+	const char* provider;
+	const char* protocol;
+	// Hex-encoded sha2-256 multihash of the CAR response body, empty for other responses
+	const char* car_checksum;
+	// Nanoseconds since the Unix epoch
+	int64_t start;
+	// Nanoseconds from start to the first body byte, -1 when no byte was sent
+	int64_t ttfb;
+	// Nanoseconds
+	int64_t duration;
+	// Response body bytes
+	uint64_t bytes;
+	uint16_t status;
+	// The response was not complete, e.g. Lassie aborted the stream or the client went away
+	bool aborted;
+} measurement_t;
+
+// Called after each retrieval. The measurement is valid only during the call.
+typedef void (*measurement_callback_t)(void* ctx, const measurement_t* measurement);
+
+// Called with the peer ID of each provider found by the candidate source and the root CID.
+// Returns false to skip the provider, otherwise stores the score, higher scores are tried first.
+typedef bool (*provider_score_callback_t)(void* ctx, const char* provider, const char* cid, double* score);
+
+// Go cannot call C function pointers directly, these trampolines are implemented in callbacks.c
+bool call_head_callback(response_sink_t* sink, uint16_t status, const char* headers);
+bool call_chunk_callback(response_sink_t* sink, const uint8_t* data, size_t len);
+void call_access_log_callback(access_log_callback_t callback, void* ctx, const access_log_record_t* record);
+void call_retrieval_event_callback(retrieval_event_callback_t callback, void* ctx, const retrieval_event_t* event);
+void call_measurement_callback(measurement_callback_t callback, void* ctx, const measurement_t* measurement);
+bool call_provider_score_callback(provider_score_callback_t callback, void* ctx, const char* provider, const char* cid, double* score);
+
+#endif
@@ -0,0 +1,195 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"fmt"
+	"time"
+
+	"github.com/libp2p/go-libp2p"
+	"github.com/libp2p/go-libp2p/core/host"
+	"github.com/libp2p/go-libp2p/core/network"
+	"github.com/libp2p/go-libp2p/core/peer"
+	"github.com/libp2p/go-libp2p/core/peerstore"
+	"github.com/libp2p/go-libp2p/p2p/net/connmgr"
+	libp2pquic "github.com/libp2p/go-libp2p/p2p/transport/quic"
+	"github.com/libp2p/go-libp2p/p2p/transport/tcp"
+	libp2pwebrtc "github.com/libp2p/go-libp2p/p2p/transport/webrtc"
+	"github.com/libp2p/go-libp2p/p2p/transport/websocket"
+	libp2pwebtransport "github.com/libp2p/go-libp2p/p2p/transport/webtransport"
+	"github.com/multiformats/go-multiaddr"
+)
+
+// preconnectTag protects the connections to preconnect providers from the connection manager.
+const preconnectTag = "lassie-preconnect"
+
+// startupPeers are the peers the daemon connects to when it starts.
+type startupPeers struct {
+	bootstrap  []peer.AddrInfo
+	preconnect []peer.AddrInfo
+}
+
+// newHost creates the libp2p host used by Lassie. We create the host ourselves instead of letting
+// Lassie do it, so that we can configure it and dial the bootstrap peers.
+func newHost(cfg *C.daemon_config_t) (host.Host, startupPeers, error) {
+	var peers startupPeers
+	var err error
+	peers.bootstrap, err = parseBootstrapPeers(cfg)
+	if err != nil {
+		return nil, peers, err
+	}
+	peers.preconnect, err = parsePreconnectProviders(cfg)
+	if err != nil {
+		return nil, peers, err
+	}
+
+	libp2pOpts := []libp2p.Option{}
+	if cfg.libp2p_transports != 0 {
+		libp2pOpts = append(libp2pOpts, transportOptions(uint32(cfg.libp2p_transports))...)
+	}
+
+	if cfg.conn_mgr_high_water > 0 {
+		debug(fmt.Sprintf("Connection manager: low_water=%d high_water=%d grace_period=%v",
+			cfg.conn_mgr_low_water, cfg.conn_mgr_high_water, time.Duration(cfg.conn_mgr_grace_period)))
+		connManager, err := connmgr.NewConnManager(
+			int(cfg.conn_mgr_low_water),
+			int(cfg.conn_mgr_high_water),
+			connmgr.WithGracePeriod(time.Duration(cfg.conn_mgr_grace_period)),
+		)
+		if err != nil {
+			return nil, peers, fmt.Errorf("cannot create connection manager: %w", err)
+		}
+		libp2pOpts = append(libp2pOpts, libp2p.ConnectionManager(connManager))
+	}
+
+	h, err := libp2p.New(libp2pOpts...)
+	if err != nil {
+		return nil, peers, fmt.Errorf("cannot create libp2p host: %w", err)
+	}
+	return h, peers, nil
+}
+
+// transportOptions replaces the libp2p default transports with the ones enabled in the bitmask.
+// The listen addresses are restricted to the enabled transports too, libp2p would try to listen
+// on QUIC addresses otherwise.
+func transportOptions(enabled uint32) []libp2p.Option {
+	opts := []libp2p.Option{libp2p.NoTransports}
+	var listenAddrs []string
+	var names []string
+	if enabled&C.LASSIE_TRANSPORT_TCP != 0 {
+		opts = append(opts, libp2p.Transport(tcp.NewTCPTransport))
+		listenAddrs = append(listenAddrs, "/ip4/0.0.0.0/tcp/0", "/ip6/::/tcp/0")
+		names = append(names, "tcp")
+	}
+	if enabled&C.LASSIE_TRANSPORT_QUIC != 0 {
+		opts = append(opts, libp2p.Transport(libp2pquic.NewTransport))
+		listenAddrs = append(listenAddrs, "/ip4/0.0.0.0/udp/0/quic-v1", "/ip6/::/udp/0/quic-v1")
+		names = append(names, "quic")
+	}
+	if enabled&C.LASSIE_TRANSPORT_WEBSOCKET != 0 {
+		opts = append(opts, libp2p.Transport(websocket.New))
+		listenAddrs = append(listenAddrs, "/ip4/0.0.0.0/tcp/0/ws", "/ip6/::/tcp/0/ws")
+		names = append(names, "websocket")
+	}
+	if enabled&C.LASSIE_TRANSPORT_WEBTRANSPORT != 0 {
+		opts = append(opts, libp2p.Transport(libp2pwebtransport.New))
+		listenAddrs = append(listenAddrs, "/ip4/0.0.0.0/udp/0/quic-v1/webtransport", "/ip6/::/udp/0/quic-v1/webtransport")
+		names = append(names, "webtransport")
+	}
+	if enabled&C.LASSIE_TRANSPORT_WEBRTC_DIRECT != 0 {
+		opts = append(opts, libp2p.Transport(libp2pwebrtc.New))
+		listenAddrs = append(listenAddrs, "/ip4/0.0.0.0/udp/0/webrtc-direct", "/ip6/::/udp/0/webrtc-direct")
+		names = append(names, "webrtc-direct")
+	}
+	debug(fmt.Sprintf("libp2p transports: %v", names))
+	return append(opts, libp2p.ListenAddrStrings(listenAddrs...))
+}
+
+func parseBootstrapPeers(cfg *C.daemon_config_t) ([]peer.AddrInfo, error) {
+	addrs := goStrings(cfg.bootstrap_peers, cfg.bootstrap_peers_len)
+	peers := make([]peer.AddrInfo, 0, len(addrs))
+	for _, addrStr := range addrs {
+		info, err := peer.AddrInfoFromString(addrStr)
+		if err != nil {
+			return nil, fmt.Errorf("invalid bootstrap peer `%s`: %w", addrStr, err)
+		}
+		peers = append(peers, *info)
+	}
+	return peers, nil
+}
+
+// parsePreconnectProviders groups the configured multiaddrs by peer ID, a provider may be listed
+// with several addresses.
+func parsePreconnectProviders(cfg *C.daemon_config_t) ([]peer.AddrInfo, error) {
+	addrs := goStrings(cfg.preconnect_providers, cfg.preconnect_providers_len)
+	maddrs := make([]multiaddr.Multiaddr, 0, len(addrs))
+	for _, addrStr := range addrs {
+		maddr, err := multiaddr.NewMultiaddr(addrStr)
+		if err == nil {
+			_, err = peer.AddrInfoFromP2pAddr(maddr)
+		}
+		if err != nil {
+			return nil, fmt.Errorf("invalid preconnect provider `%s`: %w", addrStr, err)
+		}
+		maddrs = append(maddrs, maddr)
+	}
+	return peer.AddrInfosFromP2pAddrs(maddrs...)
+}
+
+// bootstrap connects to the given peers in the background. Failures are not fatal, Lassie can
+// still retrieve content from the providers it discovers.
+func bootstrap(ctx context.Context, h host.Host, peers []peer.AddrInfo) {
+	for _, p := range peers {
+		go func(p peer.AddrInfo) {
+			if err := h.Connect(ctx, p); err != nil {
+				debug("CANNOT CONNECT TO BOOTSTRAP PEER", p.ID, err)
+				return
+			}
+			debug("CONNECTED TO BOOTSTRAP PEER", p.ID)
+		}(p)
+	}
+}
+
+// preconnect opens connections to the given providers and keeps them open until ctx is done.
+// The connections are protected from the connection manager and re-established when they drop,
+// with an exponential backoff while the provider is unreachable.
+func preconnect(ctx context.Context, h host.Host, providers []peer.AddrInfo) {
+	for _, p := range providers {
+		h.Peerstore().AddAddrs(p.ID, p.Addrs, peerstore.PermanentAddrTTL)
+		h.ConnManager().Protect(p.ID, preconnectTag)
+		go keepConnected(ctx, h, p)
+	}
+}
+
+func keepConnected(ctx context.Context, h host.Host, p peer.AddrInfo) {
+	const checkInterval = 30 * time.Second
+	const maxBackoff = 5 * time.Minute
+	backoff := time.Second
+	for {
+		wait := checkInterval
+		if h.Network().Connectedness(p.ID) != network.Connected {
+			dialCtx, cancel := context.WithTimeout(ctx, time.Minute)
+			err := h.Connect(dialCtx, p)
+			cancel()
+			if err != nil {
+				debug("CANNOT CONNECT TO PRECONNECT PROVIDER", p.ID, err)
+				wait = backoff
+				backoff = min(2*backoff, maxBackoff)
+			} else {
+				debug("CONNECTED TO PRECONNECT PROVIDER", p.ID)
+				backoff = time.Second
+			}
+		}
+
+		select {
+		case <-ctx.Done():
+			return
+		case <-time.After(wait):
+		}
+	}
+}
@@ -0,0 +1,52 @@
+//go:build unix
+
+package main
+
+import (
+	"errors"
+	"fmt"
+	"net"
+	"os"
+	"syscall"
+
+	"golang.org/x/sys/unix"
+)
+
+const reusePortSupported = true
+
+// setReusePort is a net.ListenConfig.Control function setting SO_REUSEPORT before binding.
+func setReusePort(network, address string, conn syscall.RawConn) error {
+	var sockErr error
+	err := conn.Control(func(fd uintptr) {
+		sockErr = syscall.SetsockoptInt(int(fd), syscall.SOL_SOCKET, unix.SO_REUSEPORT, 1)
+	})
+	if err != nil {
+		return err
+	}
+	return sockErr
+}
+
+func isAddrInUse(err error) bool {
+	return errors.Is(err, syscall.EADDRINUSE)
+}
+
+func isPermissionDenied(err error) bool {
+	return errors.Is(err, syscall.EACCES) || errors.Is(err, syscall.EPERM)
+}
+
+// fileListener wraps a duplicate of fd, the caller keeps ownership of the original descriptor.
+func fileListener(fd int) (net.Listener, error) {
+	dup, err := syscall.Dup(fd)
+	if err != nil {
+		return nil, fmt.Errorf("cannot duplicate listener_fd %d: %w", fd, err)
+	}
+	syscall.CloseOnExec(dup)
+	f := os.NewFile(uintptr(dup), fmt.Sprintf("listener-fd-%d", fd))
+	// net.FileListener makes its own duplicate
+	defer f.Close()
+	listener, err := net.FileListener(f)
+	if err != nil {
+		return nil, fmt.Errorf("listener_fd %d is not a listening socket: %w", fd, err)
+	}
+	return listener, nil
+}
@@ -0,0 +1,33 @@
+//go:build windows
+
+package main
+
+import (
+	"errors"
+	"net"
+	"syscall"
+)
+
+const reusePortSupported = false
+
+func setReusePort(network, address string, conn syscall.RawConn) error {
+	return errors.New("SO_REUSEPORT is not supported on Windows")
+}
+
+// Unlike the Unix errno values, the syscall package does not map these Winsock errors
+const (
+	wsaeacces     = syscall.Errno(10013)
+	wsaeaddrinuse = syscall.Errno(10048)
+)
+
+func isAddrInUse(err error) bool {
+	return errors.Is(err, wsaeaddrinuse)
+}
+
+func isPermissionDenied(err error) bool {
+	return errors.Is(err, wsaeacces)
+}
+
+func fileListener(fd int) (net.Listener, error) {
+	return nil, errors.New("listener_fd is not supported on Windows")
+}
@@ -0,0 +1,92 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"net"
+	"net/http"
+)
+
+// extraListener is one of the extra_listeners serving the same requests as the HTTP listener.
+type extraListener struct {
+	server   *http.Server
+	listener net.Listener
+}
+
+// newServerHandler builds the handler of an HTTP listener. An empty accessToken accepts all
+// requests, the client IP restriction applies only to TCP listeners.
+func newServerHandler(ipfsHandler http.Handler, accessToken string, allowedClientIps []*net.IPNet, restrictIps bool) http.Handler {
+	mux := http.NewServeMux()
+	mux.Handle("/ipfs/", requireAccessToken(accessToken, ipfsHandler))
+	mux.Handle("/ipns/", requireAccessToken(accessToken, ipfsHandler))
+	var handler http.Handler = mux
+	if restrictIps {
+		handler = restrictClientIps(allowedClientIps, handler)
+	}
+	return withHealthCheck(withAccessLog(handler))
+}
+
+// openExtraListeners binds all extra_listeners, it closes the ones already bound when one fails.
+func openExtraListeners(ctx context.Context, cfg *C.daemon_config_t, ipfsHandler http.Handler, allowedClientIps []*net.IPNet) ([]extraListener, error) {
+	n := cfg.extra_listeners_len
+	networks := goStrings(cfg.extra_listener_networks, n)
+	addresses := goStrings(cfg.extra_listener_addresses, n)
+	accessTokens := goStrings(cfg.extra_listener_access_tokens, n)
+
+	extras := make([]extraListener, 0, n)
+	for i := range networks {
+		listener, err := net.Listen(networks[i], addresses[i])
+		if err != nil {
+			closeExtraListeners(extras)
+			return nil, fmt.Errorf("cannot listen on %s %s: %w", networks[i], addresses[i], err)
+		}
+		debug(fmt.Sprintf("Extra listener: %s %s", networks[i], listener.Addr()))
+		restrictIps := bool(cfg.restrict_client_ips) && networks[i] == "tcp"
+		extras = append(extras, extraListener{
+			listener: listener,
+			server: &http.Server{
+				BaseContext: func(listener net.Listener) context.Context { return ctx },
+				Handler:     newServerHandler(ipfsHandler, accessTokens[i], allowedClientIps, restrictIps),
+			},
+		})
+	}
+	return extras, nil
+}
+
+// closeExtraListeners closes the listeners that are not being served yet.
+func closeExtraListeners(extras []extraListener) {
+	for _, extra := range extras {
+		extra.listener.Close()
+	}
+}
+
+// serveExtraListeners is called by RunDaemon, the servers are stopped by stopDaemon.
+func serveExtraListeners(extras []extraListener) {
+	for _, extra := range extras {
+		go func() {
+			err := extra.server.Serve(extra.listener)
+			if err != nil && !errors.Is(err, http.ErrServerClosed) {
+				debug("EXTRA LISTENER FAILED:", extra.listener.Addr(), err)
+			}
+		}()
+	}
+}
+
+// shutdownExtraListeners waits for the requests in flight until ctx expires and returns the
+// first error.
+func shutdownExtraListeners(ctx context.Context, extras []extraListener) error {
+	var firstErr error
+	for _, extra := range extras {
+		if err := extra.server.Shutdown(ctx); err != nil && firstErr == nil {
+			firstErr = err
+		}
+	}
+	return firstErr
+}
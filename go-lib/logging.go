@@ -0,0 +1,188 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"fmt"
+	"io"
+	"os"
+	"sync"
+
+	logging "github.com/ipfs/go-log/v2"
+	"go.uber.org/zap/zapcore"
+)
+
+// Log files are rotated when they grow over logFileMaxSize, we keep logFileBackups old files
+// named `{log_file}.1` (the newest) to `{log_file}.{logFileBackups}` (the oldest).
+const logFileMaxSize = 10 << 20
+const logFileBackups = 5
+
+// wrapperLog is used for the messages of this wrapper when JSON logging is enabled.
+var wrapperLog = logging.Logger("rusty-lassie")
+
+var json_logs_enabled bool
+
+// logOutput receives the messages of this wrapper in the text format.
+var logOutput io.Writer = os.Stderr
+
+// logFile is the file configured by the last InitDaemon call, if any.
+var logFile *rotatingFile
+
+// loggingConfigured is set once we replace the go-log defaults.
+var loggingConfigured bool
+
+// setupLogging configures the format and the destination of all go-log output (Lassie, libp2p,
+// boxo and this wrapper). When neither JSON logs nor a log file are requested, go-log keeps its
+// defaults, unless a previous daemon instance changed them.
+//
+// In the JSON format, each record is a single line with the keys `level`, `ts`, `subsystem` and
+// `msg`. Records related to a retrieval handled by this wrapper carry the `retrieval_id` key too.
+//
+// The caller must hold the mutex.
+func setupLogging(cfg *C.daemon_config_t) error {
+	jsonLogs := bool(cfg.json_logs)
+	path := C.GoString(cfg.log_file)
+	if !jsonLogs && path == "" && !loggingConfigured {
+		return nil
+	}
+
+	var out io.Writer = os.Stderr
+	var file *rotatingFile
+	if path != "" {
+		var err error
+		file, err = openRotatingFile(path, logFileMaxSize, logFileBackups)
+		if err != nil {
+			return err
+		}
+		out = file
+	}
+
+	encoderConfig := zapcore.EncoderConfig{
+		TimeKey:        "ts",
+		LevelKey:       "level",
+		NameKey:        "subsystem",
+		MessageKey:     "msg",
+		StacktraceKey:  "stacktrace",
+		LineEnding:     zapcore.DefaultLineEnding,
+		EncodeLevel:    zapcore.LowercaseLevelEncoder,
+		EncodeTime:     zapcore.ISO8601TimeEncoder,
+		EncodeDuration: zapcore.StringDurationEncoder,
+		EncodeName:     zapcore.FullNameEncoder,
+	}
+	var encoder zapcore.Encoder
+	if jsonLogs {
+		encoder = zapcore.NewJSONEncoder(encoderConfig)
+	} else {
+		encoderConfig.EncodeLevel = zapcore.CapitalLevelEncoder
+		encoder = zapcore.NewConsoleEncoder(encoderConfig)
+	}
+	// Levels are still controlled per subsystem by go-log (e.g. via GOLOG_LOG_LEVEL)
+	logging.SetPrimaryCore(zapcore.NewCore(encoder, zapcore.AddSync(out), zapcore.DebugLevel))
+	if debug_log_enabled {
+		logging.SetLogLevel("rusty-lassie", "debug")
+	} else {
+		// Let the start of the daemon through, see InitDaemon
+		logging.SetLogLevel("rusty-lassie", "info")
+	}
+
+	if logFile != nil {
+		logFile.Close()
+	}
+	logFile = file
+	logOutput = out
+	json_logs_enabled = jsonLogs
+	loggingConfigured = true
+	return nil
+}
+
+// debugw logs a debug message with structured key-value context.
+func debugw(msg string, keysAndValues ...any) {
+	if !debug_log_enabled {
+		return
+	}
+	if json_logs_enabled {
+		wrapperLog.Debugw(msg, keysAndValues...)
+		return
+	}
+	print_debug(append([]any{msg}, keysAndValues...)...)
+}
+
+// rotatingFile is an append-only log file rotated when it grows over maxSize bytes.
+type rotatingFile struct {
+	mtx     sync.Mutex
+	path    string
+	maxSize int64
+	backups int
+	file    *os.File
+	size    int64
+}
+
+func openRotatingFile(path string, maxSize int64, backups int) (*rotatingFile, error) {
+	f := &rotatingFile{path: path, maxSize: maxSize, backups: backups}
+	if err := f.open(); err != nil {
+		return nil, err
+	}
+	return f, nil
+}
+
+func (f *rotatingFile) open() error {
+	file, err := os.OpenFile(f.path, os.O_CREATE|os.O_WRONLY|os.O_APPEND, 0o644)
+	if err != nil {
+		return fmt.Errorf("cannot open log file: %w", err)
+	}
+	info, err := file.Stat()
+	if err != nil {
+		file.Close()
+		return fmt.Errorf("cannot open log file: %w", err)
+	}
+	f.file = file
+	f.size = info.Size()
+	return nil
+}
+
+func (f *rotatingFile) Write(p []byte) (int, error) {
+	f.mtx.Lock()
+	defer f.mtx.Unlock()
+
+	if f.file == nil {
+		return 0, os.ErrClosed
+	}
+	if f.size > 0 && f.size+int64(len(p)) > f.maxSize {
+		if err := f.rotate(); err != nil {
+			return 0, err
+		}
+	}
+	n, err := f.file.Write(p)
+	f.size += int64(n)
+	return n, err
+}
+
+// rotate must be called with the mutex held.
+func (f *rotatingFile) rotate() error {
+	f.file.Close()
+	f.file = nil
+	for i := f.backups - 1; i >= 1; i-- {
+		os.Rename(fmt.Sprintf("%s.%d", f.path, i), fmt.Sprintf("%s.%d", f.path, i+1))
+	}
+	if f.backups > 0 {
+		os.Rename(f.path, f.path+".1")
+	} else {
+		os.Remove(f.path)
+	}
+	return f.open()
+}
+
+func (f *rotatingFile) Close() error {
+	f.mtx.Lock()
+	defer f.mtx.Unlock()
+	if f.file == nil {
+		return nil
+	}
+	err := f.file.Close()
+	f.file = nil
+	return err
+}
@@ -0,0 +1,109 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"encoding/hex"
+	"sync"
+	"time"
+	"unsafe"
+)
+
+// sha256Multihash is the multihash prefix of sha2-256 digests: the code 0x12 followed by the
+// digest length 0x20. Spark checkers report CAR checksums as hex-encoded multihashes.
+const sha256Multihash = "1220"
+
+// measurementMtx protects the registered callback, see accessLogMtx for the locking scheme.
+var measurementMtx sync.RWMutex
+var measurementCallback C.measurement_callback_t
+var measurementCtx unsafe.Pointer
+
+// SetMeasurementCallback registers the function called after every retrieval handled by the
+// daemon, replacing the previous callback.
+//
+//export SetMeasurementCallback
+func SetMeasurementCallback(callback C.measurement_callback_t, ctx unsafe.Pointer) {
+	measurementMtx.Lock()
+	defer measurementMtx.Unlock()
+	measurementCallback = callback
+	measurementCtx = ctx
+}
+
+// ClearMeasurementCallback unregisters the callback registered with ctx, see
+// ClearAccessLogCallback.
+//
+//export ClearMeasurementCallback
+func ClearMeasurementCallback(ctx unsafe.Pointer) {
+	measurementMtx.Lock()
+	defer measurementMtx.Unlock()
+	if measurementCtx == ctx {
+		measurementCallback = nil
+		measurementCtx = nil
+	}
+}
+
+// measurementsEnabled tells trackRetrievals whether to compute the checksum of the response.
+func measurementsEnabled() bool {
+	measurementMtx.RLock()
+	defer measurementMtx.RUnlock()
+	return measurementCallback != nil
+}
+
+// reportMeasurement passes the measurement of a finished retrieval to the registered callback.
+// The caller must not hold retrievalsMtx.
+func reportMeasurement(r *activeRetrieval, aborted bool) {
+	if r.checksum == nil {
+		// The callback was not registered when the retrieval started
+		return
+	}
+	elapsed := time.Since(r.started)
+
+	retrievalsMtx.Lock()
+	provider, protocol := r.provider, r.protocol
+	retrievalsMtx.Unlock()
+
+	var checksum string
+	if r.isCar {
+		checksum = sha256Multihash + hex.EncodeToString(r.checksum.Sum(nil))
+	}
+	cStrings := []*C.char{
+		C.CString(r.id),
+		C.CString(r.cid),
+		C.CString(provider),
+		C.CString(protocol),
+		C.CString(checksum),
+	}
+	defer func() {
+		for _, s := range cStrings {
+			C.free(unsafe.Pointer(s))
+		}
+	}()
+
+	ttfb := time.Duration(-1)
+	if !r.firstByte.IsZero() {
+		ttfb = r.firstByte.Sub(r.started)
+	}
+	record := C.measurement_t{
+		retrieval_id: cStrings[0],
+		cid:          cStrings[1],
+		provider:     cStrings[2],
+		protocol:     cStrings[3],
+		car_checksum: cStrings[4],
+		start:        C.int64_t(r.started.UnixNano()),
+		ttfb:         C.int64_t(ttfb),
+		duration:     C.int64_t(elapsed),
+		bytes:        C.uint64_t(r.bytesSent.Load()),
+		status:       C.uint16_t(r.status),
+		aborted:      C.bool(aborted),
+	}
+
+	measurementMtx.RLock()
+	defer measurementMtx.RUnlock()
+	if measurementCallback != nil {
+		C.call_measurement_callback(measurementCallback, measurementCtx, &record)
+	}
+}
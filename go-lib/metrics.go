@@ -0,0 +1,125 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"sort"
+	"sync"
+	"unsafe"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/multiformats/go-multicodec"
+)
+
+// protocolMetrics counts the attempts to retrieve content from a provider via one protocol.
+type protocolMetrics struct {
+	attempts      uint64
+	successes     uint64
+	failures      uint64
+	bytesReceived uint64
+}
+
+var metricsMtx sync.Mutex
+var metricsByProtocol = map[multicodec.Code]*protocolMetrics{}
+
+type eventWithProtocol interface {
+	Protocol() multicodec.Code
+}
+
+type eventWithReceivedBytes interface {
+	ReceivedBytesSize() uint64
+}
+
+// recordMetrics is subscribed to all Lassie retrieval events and updates the per-protocol
+// counters reported by GetMetrics.
+func recordMetrics(event types.RetrievalEvent) {
+	e, ok := event.(eventWithProtocol)
+	if !ok {
+		return
+	}
+
+	metricsMtx.Lock()
+	defer metricsMtx.Unlock()
+	m := metricsByProtocol[e.Protocol()]
+	if m == nil {
+		m = &protocolMetrics{}
+		metricsByProtocol[e.Protocol()] = m
+	}
+
+	switch event.Code() {
+	case types.StartedRetrievalCode:
+		m.attempts++
+	case types.FailedRetrievalCode:
+		m.failures++
+	case types.SuccessCode:
+		m.successes++
+		if e, ok := event.(eventWithReceivedBytes); ok {
+			m.bytesReceived += e.ReceivedBytesSize()
+		}
+	}
+}
+
+// GetMetrics returns a snapshot of the counters collected since the process started. The caller
+// must call DropMetrics to release the memory.
+//
+//export GetMetrics
+func GetMetrics() C.metrics_t {
+	defer recoverPanic("GetMetrics")
+	retrievalsMtx.Lock()
+	active := len(retrievals)
+	retrievalsMtx.Unlock()
+
+	result := C.metrics_t{
+		total_retrievals:  C.uint64_t(totalRetrievals.Load()),
+		active_retrievals: C.uint64_t(active),
+		bytes_sent:        C.uint64_t(totalBytesSent.Load()),
+	}
+
+	metricsMtx.Lock()
+	defer metricsMtx.Unlock()
+	if len(metricsByProtocol) == 0 {
+		return result
+	}
+
+	// Report the protocols in a stable order
+	codes := make([]multicodec.Code, 0, len(metricsByProtocol))
+	for code := range metricsByProtocol {
+		codes = append(codes, code)
+	}
+	sort.Slice(codes, func(i, j int) bool { return codes[i] < codes[j] })
+
+	items := (*C.protocol_metrics_t)(C.malloc(C.size_t(len(codes)) * C.size_t(unsafe.Sizeof(C.protocol_metrics_t{}))))
+	list := unsafe.Slice(items, len(codes))
+	for i, code := range codes {
+		m := metricsByProtocol[code]
+		list[i] = C.protocol_metrics_t{
+			protocol:       C.CString(code.String()),
+			attempts:       C.uint64_t(m.attempts),
+			successes:      C.uint64_t(m.successes),
+			failures:       C.uint64_t(m.failures),
+			bytes_received: C.uint64_t(m.bytesReceived),
+		}
+	}
+	result.protocols = items
+	result.protocols_len = C.size_t(len(codes))
+	return result
+}
+
+// DropMetrics cleans up any resources allocated for and owned by the metrics_t value.
+//
+//export DropMetrics
+func DropMetrics(metrics *C.metrics_t) {
+	if metrics.protocols == nil {
+		return
+	}
+	for _, m := range unsafe.Slice(metrics.protocols, metrics.protocols_len) {
+		C.free(unsafe.Pointer(m.protocol))
+	}
+	C.free(unsafe.Pointer(metrics.protocols))
+	metrics.protocols = nil
+	metrics.protocols_len = 0
+}
@@ -0,0 +1,16 @@
+//go:build windows
+
+package main
+
+import "os"
+
+// Windows does not allow removing a file while it's mapped, the store would leak files on crashes
+const mmapSupported = false
+
+func mapFile(f *os.File, size int) ([]byte, error) {
+	return nil, errMmapNotSupported
+}
+
+func unmapFile(data []byte) error {
+	return errMmapNotSupported
+}
@@ -0,0 +1,136 @@
+package main
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"os"
+	"sync"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/ipld/go-ipld-prime/datamodel"
+	"github.com/ipld/go-ipld-prime/linking"
+)
+
+var errMmapNotSupported = errors.New("the memory-mapped CAR store is not supported on this platform")
+
+// mmapStore keeps the blocks of a single retrieval in a memory-mapped sparse file. The file is
+// unlinked right after it's mapped, the kernel writes the dirty pages back only under memory
+// pressure and discards them when the mapping is released.
+type mmapStore struct {
+	mtx    sync.RWMutex
+	data   []byte
+	used   int
+	blocks map[string]mmapSpan
+}
+
+type mmapSpan struct {
+	offset int
+	size   int
+}
+
+func newMmapStore(dir string, size int) (*mmapStore, error) {
+	f, err := os.CreateTemp(dir, "lassie-mmap-*")
+	if err != nil {
+		return nil, err
+	}
+	// The mapping stays valid after the descriptor is closed
+	defer f.Close()
+
+	if err := f.Truncate(int64(size)); err != nil {
+		os.Remove(f.Name())
+		return nil, err
+	}
+	data, err := mapFile(f, size)
+	if err != nil {
+		os.Remove(f.Name())
+		return nil, err
+	}
+	if err := os.Remove(f.Name()); err != nil {
+		debug(fmt.Sprintf("cannot unlink the memory-mapped CAR store %s: %v", f.Name(), err))
+	}
+	return &mmapStore{data: data, blocks: make(map[string]mmapSpan)}, nil
+}
+
+// get returns a copy of the block, nil when the block is not stored.
+func (s *mmapStore) get(key string) []byte {
+	s.mtx.RLock()
+	defer s.mtx.RUnlock()
+	span, ok := s.blocks[key]
+	if !ok {
+		return nil
+	}
+	// Copy the data, the mapping is released when the retrieval ends
+	return bytes.Clone(s.data[span.offset : span.offset+span.size])
+}
+
+// put stores the block unless the store is full. Lassie reads such blocks from its temp store.
+func (s *mmapStore) put(key string, block []byte) {
+	s.mtx.Lock()
+	defer s.mtx.Unlock()
+	if _, ok := s.blocks[key]; ok || s.used+len(block) > len(s.data) {
+		return
+	}
+	copy(s.data[s.used:], block)
+	s.blocks[key] = mmapSpan{offset: s.used, size: len(block)}
+	s.used += len(block)
+}
+
+func (s *mmapStore) close() {
+	s.mtx.Lock()
+	defer s.mtx.Unlock()
+	if err := unmapFile(s.data); err != nil {
+		debug(fmt.Sprintf("cannot unmap the memory-mapped CAR store: %v", err))
+	}
+	s.data = nil
+	s.blocks = nil
+}
+
+// mmapFetcher wraps the request link system so that the blocks received by a retrieval are kept
+// in a mmapStore and Lassie reads them from there instead of from the temp CAR file. All blocks
+// are still written to the request storage, the response is built from it.
+type mmapFetcher struct {
+	fetcher types.Fetcher
+	dir     string
+	size    int
+}
+
+func (f mmapFetcher) Fetch(ctx context.Context, request types.RetrievalRequest, opts ...types.FetchOption) (*types.RetrievalStats, error) {
+	store, err := newMmapStore(f.dir, f.size)
+	if err != nil {
+		debug(fmt.Sprintf("cannot create the memory-mapped CAR store, using the temp store only: %v", err))
+		return f.fetcher.Fetch(ctx, request, opts...)
+	}
+	defer store.close()
+
+	lsys := request.LinkSystem
+	readOpener := lsys.StorageReadOpener
+	writeOpener := lsys.StorageWriteOpener
+
+	lsys.StorageReadOpener = func(lctx linking.LinkContext, lnk datamodel.Link) (io.Reader, error) {
+		if data := store.get(lnk.Binary()); data != nil {
+			return bytes.NewReader(data), nil
+		}
+		return readOpener(lctx, lnk)
+	}
+
+	lsys.StorageWriteOpener = func(lctx linking.LinkContext) (io.Writer, linking.BlockWriteCommitter, error) {
+		w, commit, err := writeOpener(lctx)
+		if err != nil {
+			return nil, nil, err
+		}
+		var buf bytes.Buffer
+		return io.MultiWriter(w, &buf), func(lnk datamodel.Link) error {
+			if err := commit(lnk); err != nil {
+				return err
+			}
+			store.put(lnk.Binary(), buf.Bytes())
+			return nil
+		}, nil
+	}
+
+	request.LinkSystem = lsys
+	return f.fetcher.Fetch(ctx, request, opts...)
+}
@@ -0,0 +1,65 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"fmt"
+	rtdebug "runtime/debug"
+	"unsafe"
+)
+
+// The exported functions recover from panics raised by Lassie or by our own code, so that a bug
+// triggered by a single request fails that call instead of crashing the host process. Panics in
+// goroutines started by Lassie cannot be recovered here, net/http recovers the panics of the HTTP
+// request handlers on its own.
+
+// panicMessage describes the recovered panic, including the stack trace of the panicking
+// goroutine.
+func panicMessage(fn string, r any) string {
+	return fmt.Sprintf("Lassie panicked in %s: %v\n%s", fn, r, rtdebug.Stack())
+}
+
+// recoverError must be deferred by exported functions returning an error string. It replaces the
+// error with the description of the recovered panic.
+func recoverError(fn string, errField **C.char) {
+	if r := recover(); r != nil {
+		setPanicError(fn, r, errField)
+	}
+}
+
+// recoverErrorCode is recoverError for the results with an error code, it sets the code to
+// LASSIE_ERR_PANIC.
+func recoverErrorCode(fn string, errField **C.char, codeField *C.uint32_t) {
+	if r := recover(); r != nil {
+		setPanicError(fn, r, errField)
+		*codeField = C.LASSIE_ERR_PANIC
+	}
+}
+
+func setPanicError(fn string, r any, errField **C.char) {
+	msg := panicMessage(fn, r)
+	debug(msg)
+	if *errField != nil {
+		C.free(unsafe.Pointer(*errField))
+	}
+	*errField = C.CString(msg)
+}
+
+// recoverPanic must be deferred by exported functions that cannot report errors, the function
+// returns the zero value when it panics. The panic is always logged, the caller cannot see it.
+func recoverPanic(fn string) {
+	r := recover()
+	if r == nil {
+		return
+	}
+	msg := panicMessage(fn, r)
+	if json_logs_enabled {
+		wrapperLog.Errorln(msg)
+		return
+	}
+	print_debug(msg)
+}
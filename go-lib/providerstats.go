@@ -0,0 +1,196 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"sort"
+	"sync"
+	"time"
+	"unsafe"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+)
+
+// providerStatsWindow is the number of recent attempts and TTFB samples kept per provider.
+const providerStatsWindow = 100
+
+// maxTrackedProviders bounds the memory used by the statistics, the provider seen least
+// recently is forgotten first.
+const maxTrackedProviders = 10_000
+
+// attemptOutcome is a finished attempt to retrieve content from a provider.
+type attemptOutcome struct {
+	success bool
+	bytes   uint64
+}
+
+// providerStats holds the most recent attempts of a single provider.
+type providerStats struct {
+	protocol string
+	lastSeen time.Time
+	// outcomes and ttfbs keep at most providerStatsWindow items, the oldest first
+	outcomes []attemptOutcome
+	ttfbs    []time.Duration
+
+	// The circuit breaker state, see circuitbreaker.go
+	consecutiveFailures int
+	openUntil           time.Time
+}
+
+var providerStatsMtx sync.Mutex
+var statsByProvider = map[string]*providerStats{}
+
+// recordProviderStats is subscribed to all Lassie retrieval events and updates the per-provider
+// statistics reported by GetProviderStats.
+func recordProviderStats(event types.RetrievalEvent) {
+	e, ok := event.(eventWithProviderId)
+	if !ok || e.ProviderId() == "" {
+		return
+	}
+	provider := e.ProviderId().String()
+
+	var outcome *attemptOutcome
+	var ttfb time.Duration
+	switch event.Code() {
+	case types.SuccessCode:
+		outcome = &attemptOutcome{success: true}
+		if e, ok := event.(eventWithReceivedBytes); ok {
+			outcome.bytes = e.ReceivedBytesSize()
+		}
+	case types.FailedRetrievalCode:
+		outcome = &attemptOutcome{success: false}
+	case types.FirstByteCode:
+		e, ok := event.(eventWithDuration)
+		if !ok {
+			return
+		}
+		ttfb = e.Duration()
+	default:
+		return
+	}
+
+	providerStatsMtx.Lock()
+	defer providerStatsMtx.Unlock()
+	s := statsByProvider[provider]
+	if s == nil {
+		if len(statsByProvider) >= maxTrackedProviders {
+			forgetLeastRecentProvider()
+		}
+		s = &providerStats{}
+		statsByProvider[provider] = s
+	}
+	s.lastSeen = event.Time()
+	if e, ok := event.(eventWithProtocol); ok {
+		s.protocol = e.Protocol().String()
+	}
+	if outcome != nil {
+		s.outcomes = appendWindow(s.outcomes, *outcome)
+		s.updateCircuit(event)
+	} else {
+		s.ttfbs = appendWindow(s.ttfbs, ttfb)
+	}
+}
+
+// appendWindow appends item and drops the oldest items beyond providerStatsWindow.
+func appendWindow[T any](items []T, item T) []T {
+	items = append(items, item)
+	if len(items) > providerStatsWindow {
+		items = append(items[:0], items[len(items)-providerStatsWindow:]...)
+	}
+	return items
+}
+
+// forgetLeastRecentProvider must be called with providerStatsMtx held.
+func forgetLeastRecentProvider() {
+	var oldest string
+	for provider, s := range statsByProvider {
+		if oldest == "" || s.lastSeen.Before(statsByProvider[oldest].lastSeen) {
+			oldest = provider
+		}
+	}
+	delete(statsByProvider, oldest)
+}
+
+// medianDuration returns the median of the samples, or -1 when there are none.
+func medianDuration(samples []time.Duration) time.Duration {
+	if len(samples) == 0 {
+		return -1
+	}
+	sorted := append([]time.Duration(nil), samples...)
+	sort.Slice(sorted, func(i, j int) bool { return sorted[i] < sorted[j] })
+	mid := len(sorted) / 2
+	if len(sorted)%2 == 0 {
+		return (sorted[mid-1] + sorted[mid]) / 2
+	}
+	return sorted[mid]
+}
+
+// GetProviderStats returns a snapshot of the per-provider statistics, sorted by peer ID. The
+// caller must call DropProviderStats to release the memory.
+//
+//export GetProviderStats
+func GetProviderStats() C.provider_stats_list_t {
+	defer recoverPanic("GetProviderStats")
+	providerStatsMtx.Lock()
+	defer providerStatsMtx.Unlock()
+
+	if len(statsByProvider) == 0 {
+		return C.provider_stats_list_t{items: nil, len: 0}
+	}
+
+	providers := make([]string, 0, len(statsByProvider))
+	for provider := range statsByProvider {
+		providers = append(providers, provider)
+	}
+	sort.Strings(providers)
+
+	now := time.Now()
+	items := (*C.provider_stats_t)(C.malloc(C.size_t(len(providers)) * C.size_t(unsafe.Sizeof(C.provider_stats_t{}))))
+	list := unsafe.Slice(items, len(providers))
+	for i, provider := range providers {
+		s := statsByProvider[provider]
+		var successes, failures, bytes uint64
+		for _, o := range s.outcomes {
+			if o.success {
+				successes++
+				bytes += o.bytes
+			} else {
+				failures++
+			}
+		}
+		list[i] = C.provider_stats_t{
+			provider:       C.CString(provider),
+			protocol:       C.CString(s.protocol),
+			successes:      C.uint64_t(successes),
+			failures:       C.uint64_t(failures),
+			bytes_received: C.uint64_t(bytes),
+			median_ttfb:    C.int64_t(medianDuration(s.ttfbs)),
+			last_seen:      C.int64_t(s.lastSeen.UnixNano()),
+		}
+		if breakerFailures > 0 && now.Before(s.openUntil) {
+			list[i].circuit_open_until = C.int64_t(s.openUntil.UnixNano())
+		}
+	}
+	return C.provider_stats_list_t{items: items, len: C.size_t(len(list))}
+}
+
+// DropProviderStats cleans up any resources allocated for and owned by the provider_stats_list_t
+// value.
+//
+//export DropProviderStats
+func DropProviderStats(list *C.provider_stats_list_t) {
+	if list.items == nil {
+		return
+	}
+	for _, s := range unsafe.Slice(list.items, list.len) {
+		C.free(unsafe.Pointer(s.provider))
+		C.free(unsafe.Pointer(s.protocol))
+	}
+	C.free(unsafe.Pointer(list.items))
+	list.items = nil
+	list.len = 0
+}
@@ -0,0 +1,136 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"sort"
+	"sync"
+	"unsafe"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/ipfs/go-cid"
+)
+
+// scoreMtx protects the registered callback, see accessLogMtx for the locking scheme.
+var scoreMtx sync.RWMutex
+var scoreCallback C.provider_score_callback_t
+var scoreCtx unsafe.Pointer
+
+// SetProviderScoreCallback registers the function scoring the retrieval candidates, replacing the
+// previous callback.
+//
+//export SetProviderScoreCallback
+func SetProviderScoreCallback(callback C.provider_score_callback_t, ctx unsafe.Pointer) {
+	scoreMtx.Lock()
+	defer scoreMtx.Unlock()
+	scoreCallback = callback
+	scoreCtx = ctx
+}
+
+// ClearProviderScoreCallback unregisters the callback registered with ctx, see
+// ClearAccessLogCallback.
+//
+//export ClearProviderScoreCallback
+func ClearProviderScoreCallback(ctx unsafe.Pointer) {
+	scoreMtx.Lock()
+	defer scoreMtx.Unlock()
+	if scoreCtx == ctx {
+		scoreCallback = nil
+		scoreCtx = nil
+	}
+}
+
+// withProviderScores wraps the candidate source so that the registered callback can reorder and
+// drop the candidates. The callback can be registered at any time, the wrapper is installed
+// unconditionally.
+func withProviderScores(source types.CandidateSource) (types.CandidateSource, error) {
+	source, err := orDefaultCandidateSource(source)
+	if err != nil {
+		return nil, err
+	}
+	return scoredCandidateSource{next: source}, nil
+}
+
+// scoredCandidateSource passes the candidates to Lassie in the order of their scores, the best
+// first. Without a registered callback, the candidates are passed through as they are found.
+type scoredCandidateSource struct {
+	next types.CandidateSource
+}
+
+type scoredCandidate struct {
+	candidate types.RetrievalCandidate
+	score     float64
+}
+
+func (s scoredCandidateSource) FindCandidates(ctx context.Context, c cid.Cid, cb func(types.RetrievalCandidate)) error {
+	scoreMtx.RLock()
+	enabled := scoreCallback != nil
+	scoreMtx.RUnlock()
+	if !enabled {
+		return s.next.FindCandidates(ctx, c, cb)
+	}
+
+	// Scores can be compared only when all candidates are known
+	var mtx sync.Mutex
+	var candidates []types.RetrievalCandidate
+	err := s.next.FindCandidates(ctx, c, func(candidate types.RetrievalCandidate) {
+		mtx.Lock()
+		defer mtx.Unlock()
+		candidates = append(candidates, candidate)
+	})
+	for _, candidate := range scoreCandidates(c, candidates) {
+		cb(candidate)
+	}
+	return err
+}
+
+// scoreCandidates calls the registered callback once per provider. It drops the candidates the
+// callback rejects and sorts the rest by score, keeping the discovery order for equal scores.
+func scoreCandidates(c cid.Cid, candidates []types.RetrievalCandidate) []types.RetrievalCandidate {
+	cCid := C.CString(c.String())
+	defer C.free(unsafe.Pointer(cCid))
+
+	scoreMtx.RLock()
+	defer scoreMtx.RUnlock()
+	if scoreCallback == nil {
+		// Unregistered in the meantime
+		return candidates
+	}
+
+	type verdict struct {
+		keep  bool
+		score float64
+	}
+	verdicts := map[string]verdict{}
+	scored := make([]scoredCandidate, 0, len(candidates))
+	for _, candidate := range candidates {
+		provider := candidate.MinerPeer.ID.String()
+		v, ok := verdicts[provider]
+		if !ok {
+			cProvider := C.CString(provider)
+			var score C.double
+			v.keep = bool(C.call_provider_score_callback(scoreCallback, scoreCtx, cProvider, cCid, &score))
+			v.score = float64(score)
+			C.free(unsafe.Pointer(cProvider))
+			verdicts[provider] = v
+			if !v.keep {
+				debugw("skipping provider rejected by the score callback", "provider", provider, "cid", c)
+			}
+		}
+		if v.keep {
+			scored = append(scored, scoredCandidate{candidate: candidate, score: v.score})
+		}
+	}
+
+	sort.SliceStable(scored, func(i, j int) bool { return scored[i].score > scored[j].score })
+	result := make([]types.RetrievalCandidate, len(scored))
+	for i, s := range scored {
+		result[i] = s.candidate
+	}
+	return result
+}
@@ -0,0 +1,140 @@
+package main
+
+import (
+	"context"
+	"errors"
+	"net/http"
+	"strings"
+	"sync"
+)
+
+// errTooManyRetrievals is the body of 429 responses when the retrieval queue is full, see
+// RetrievalError::TooManyRequests in src/retrieval_error.rs
+const errTooManyRetrievals = "too many concurrent retrievals, try again later"
+
+var errRetrievalQueueFull = errors.New("the retrieval queue is full")
+
+// priorityHeader lets clients move their retrievals ahead in the queue, see PRIORITY_HEADER in
+// src/retrieval.rs
+const priorityHeader = "X-Priority"
+
+type priority int
+
+const (
+	priorityLow priority = iota
+	priorityNormal
+	priorityHigh
+	priorityLevels
+)
+
+// parsePriority maps the values of the X-Priority header, anything unknown is normal priority.
+func parsePriority(value string) priority {
+	switch strings.ToLower(strings.TrimSpace(value)) {
+	case "low":
+		return priorityLow
+	case "high":
+		return priorityHigh
+	default:
+		return priorityNormal
+	}
+}
+
+// retrievalQueue runs up to limit retrievals at the same time. The others wait until a running
+// retrieval finishes, at most maxQueued of them when maxQueued is not negative. Retrievals with a
+// higher priority go first, retrievals with the same priority in FIFO order.
+type retrievalQueue struct {
+	mtx       sync.Mutex
+	limit     int
+	maxQueued int
+	active    int
+	queued    int
+	waiting   [priorityLevels][]*queuedRetrieval
+}
+
+type queuedRetrieval struct {
+	// Closed when the retrieval takes over the slot of a finished one
+	ready chan struct{}
+}
+
+func newRetrievalQueue(limit int, maxQueued int) *retrievalQueue {
+	return &retrievalQueue{limit: limit, maxQueued: maxQueued}
+}
+
+// acquire waits for a free slot. The caller must call release when acquire returned nil.
+func (q *retrievalQueue) acquire(ctx context.Context, prio priority) error {
+	q.mtx.Lock()
+	if q.active < q.limit && q.queued == 0 {
+		q.active++
+		q.mtx.Unlock()
+		return nil
+	}
+	if q.maxQueued >= 0 && q.queued >= q.maxQueued {
+		q.mtx.Unlock()
+		return errRetrievalQueueFull
+	}
+	r := &queuedRetrieval{ready: make(chan struct{})}
+	q.waiting[prio] = append(q.waiting[prio], r)
+	q.queued++
+	q.mtx.Unlock()
+
+	select {
+	case <-r.ready:
+		return nil
+	case <-ctx.Done():
+		q.mtx.Lock()
+		defer q.mtx.Unlock()
+		for i, w := range q.waiting[prio] {
+			if w == r {
+				q.waiting[prio] = append(q.waiting[prio][:i], q.waiting[prio][i+1:]...)
+				q.queued--
+				return ctx.Err()
+			}
+		}
+		// release handed us the slot in the meantime, pass it on
+		q.releaseLocked()
+		return ctx.Err()
+	}
+}
+
+func (q *retrievalQueue) release() {
+	q.mtx.Lock()
+	defer q.mtx.Unlock()
+	q.releaseLocked()
+}
+
+func (q *retrievalQueue) releaseLocked() {
+	for prio := priorityHigh; prio >= priorityLow; prio-- {
+		if len(q.waiting[prio]) > 0 {
+			next := q.waiting[prio][0]
+			q.waiting[prio] = q.waiting[prio][1:]
+			q.queued--
+			close(next.ready)
+			return
+		}
+	}
+	q.active--
+}
+
+// limitConcurrentRetrievals queues the retrievals beyond the limit of q, ordered by the X-Priority
+// header. Requests are rejected with 429 when the queue is full, and fail when the client goes
+// away or the request times out while waiting.
+func limitConcurrentRetrievals(q *retrievalQueue, next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		if err := q.acquire(req.Context(), parsePriority(req.Header.Get(priorityHeader))); err != nil {
+			if errors.Is(err, errRetrievalQueueFull) {
+				debugw("rejected retrieval over max_queued_retrievals", "path", req.URL.Path)
+				res.Header().Set("Retry-After", "1")
+				httpError(res, errorCodeTooManyRequests, errTooManyRetrievals, http.StatusTooManyRequests)
+			} else if errors.Is(err, context.DeadlineExceeded) {
+				debugw("retrieval timed out while queued", "path", req.URL.Path)
+				httpError(res, errorCodeTimeout, "retrieval timed out while queued", http.StatusGatewayTimeout)
+			} else {
+				debugw("retrieval cancelled while queued", "path", req.URL.Path, "err", err)
+				http.Error(res, "retrieval cancelled while queued", http.StatusServiceUnavailable)
+			}
+			return
+		}
+		defer q.release()
+		next.ServeHTTP(res, req)
+	})
+}
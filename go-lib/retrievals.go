@@ -0,0 +1,426 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"bufio"
+	"context"
+	"crypto/sha256"
+	"errors"
+	"hash"
+	"net"
+	"net/http"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+	"unsafe"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/google/uuid"
+	"github.com/ipfs/go-cid"
+	"github.com/libp2p/go-libp2p/core/peer"
+)
+
+// retrievalIdHeader is the response header carrying the ID assigned to each retrieval. The Rust
+// side can pass the ID to CancelRetrieval.
+const retrievalIdHeader = "X-Retrieval-Id"
+
+// requestIdHeader carries the correlation ID chosen by the client. We echo it in the response,
+// use it as the retrieval ID and forward it to HTTP providers, see withRequestId.
+const requestIdHeader = "X-Request-Id"
+
+// maxRequestIdLen limits the length of client-provided request IDs, longer IDs are ignored.
+const maxRequestIdLen = 128
+
+type requestIdKey struct{}
+
+// activeRetrievalKey stores the *activeRetrieval of the request in its context, see
+// correlatingFetcher.
+type activeRetrievalKey struct{}
+
+// requestIdFromHeader returns the request ID sent by the client, or an empty string when the
+// header is missing or malformed. We accept visible ASCII characters only, the ID ends up in
+// response headers and log lines.
+func requestIdFromHeader(req *http.Request) string {
+	id := req.Header.Get(requestIdHeader)
+	if len(id) > maxRequestIdLen {
+		return ""
+	}
+	for i := 0; i < len(id); i++ {
+		if id[i] < 0x21 || id[i] > 0x7e {
+			return ""
+		}
+	}
+	return id
+}
+
+// requestIdFromContext returns the request ID of the request that created ctx, if any.
+func requestIdFromContext(ctx context.Context) string {
+	id, _ := ctx.Value(requestIdKey{}).(string)
+	return id
+}
+
+// activeRetrieval describes a retrieval handled by the IPFS handler right now.
+type activeRetrieval struct {
+	id      string
+	cid     string
+	started time.Time
+	cancel  context.CancelFunc
+
+	bytesSent      atomic.Uint64
+	blocksReceived atomic.Uint64
+
+	// The fields below are used by the handler goroutine only, see reportMeasurement
+
+	status    int
+	firstByte time.Time
+	isCar     bool
+	hijacked  bool
+	// checksum hashes the CAR response body, nil when no measurement callback is registered
+	checksum hash.Hash
+	// fetchErr is the error returned by the Lassie fetcher, see correlatingFetcher
+	fetchErr error
+
+	// The fields below are protected by retrievalsMtx
+
+	// lassieId is the ID assigned to the retrieval by Lassie, see correlatingFetcher
+	lassieId string
+	provider string
+	protocol string
+	// succeeded is set by the success event, the provider that delivered the content is kept
+	succeeded bool
+	spans     retrievalSpans
+}
+
+var retrievalsMtx sync.Mutex
+var retrievals = map[string]*activeRetrieval{}
+var retrievalsByLassieId = map[string]*activeRetrieval{}
+
+// Counters reported by the admin listener, see admin.go
+var totalRetrievals atomic.Uint64
+var totalBytesSent atomic.Uint64
+
+// trackRetrievals assigns an ID to each request, announces it in the response headers and keeps
+// the request registered until the handler returns, so that it can be inspected via
+// ListRetrievals and cancelled via CancelRetrieval.
+//
+// The client can choose the ID via the X-Request-Id header. When another running retrieval uses
+// the same ID, we generate a new retrieval ID and keep the request ID for log lines and providers.
+func trackRetrievals(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		ctx, cancel := context.WithCancel(req.Context())
+		defer cancel()
+
+		r := &activeRetrieval{
+			cid:     cidFromPath(req.URL.Path),
+			started: time.Now(),
+			cancel:  cancel,
+			status:  http.StatusOK,
+		}
+		if measurementsEnabled() {
+			r.checksum = sha256.New()
+		}
+		requestId := requestIdFromHeader(req)
+		retrievalsMtx.Lock()
+		r.id = requestId
+		if _, taken := retrievals[r.id]; taken || r.id == "" {
+			r.id = uuid.NewString()
+		}
+		retrievals[r.id] = r
+		retrievalsMtx.Unlock()
+		if requestId == "" {
+			requestId = r.id
+		}
+		ctx = context.WithValue(ctx, requestIdKey{}, requestId)
+		ctx = context.WithValue(ctx, activeRetrievalKey{}, r)
+		ctx = startRetrievalSpans(ctx, req, r)
+		totalRetrievals.Add(1)
+		debugw("retrieval started", "retrieval_id", r.id, "request_id", requestId, "cid", r.cid)
+
+		defer func() {
+			debugw("retrieval finished", "retrieval_id", r.id, "request_id", requestId, "cid", r.cid,
+				"bytes", r.bytesSent.Load(), "blocks", r.blocksReceived.Load(), "elapsed", time.Since(r.started))
+			totalBytesSent.Add(r.bytesSent.Load())
+			reportMeasurement(r, r.hijacked || req.Context().Err() != nil)
+			retrievalsMtx.Lock()
+			endRetrievalSpans(r)
+			delete(retrievals, r.id)
+			if r.lassieId != "" {
+				delete(retrievalsByLassieId, r.lassieId)
+			}
+			retrievalsMtx.Unlock()
+		}()
+
+		res.Header().Set(retrievalIdHeader, r.id)
+		res.Header().Set(requestIdHeader, requestId)
+		next.ServeHTTP(&countingResponseWriter{ResponseWriter: res, retrieval: r}, req.WithContext(ctx))
+	})
+}
+
+// runningRetrievals returns the number of requests currently tracked by trackRetrievals.
+func runningRetrievals() int {
+	retrievalsMtx.Lock()
+	defer retrievalsMtx.Unlock()
+	return len(retrievals)
+}
+
+// cidFromPath extracts the root CID from a request path like `/ipfs/{cid}/sub/path`.
+func cidFromPath(path string) string {
+	c, _, _ := strings.Cut(strings.TrimPrefix(path, "/ipfs/"), "/")
+	return c
+}
+
+// countingResponseWriter counts the bytes and the CAR blocks of the response body written by
+// Lassie.
+type countingResponseWriter struct {
+	http.ResponseWriter
+	retrieval   *activeRetrieval
+	wroteHeader bool
+	// car is nil when the response is not a CAR stream, e.g. for error responses
+	car *carBlockCounter
+}
+
+func (w *countingResponseWriter) WriteHeader(status int) {
+	if !w.wroteHeader {
+		w.wroteHeader = true
+		w.retrieval.status = status
+		contentType := w.Header().Get("Content-Type")
+		if status == http.StatusOK && strings.HasPrefix(contentType, "application/vnd.ipld.car") {
+			w.car = &carBlockCounter{}
+			w.retrieval.isCar = true
+		}
+		// Lassie reports the failed retrieval with a message only
+		if code := fetchErrorCode(w.retrieval.fetchErr); status >= 400 && code != "" && w.Header().Get(errorCodeHeader) == "" {
+			w.Header().Set(errorCodeHeader, code)
+		}
+	}
+	w.ResponseWriter.WriteHeader(status)
+}
+
+func (w *countingResponseWriter) Write(p []byte) (int, error) {
+	if !w.wroteHeader {
+		w.WriteHeader(http.StatusOK)
+	}
+	n, err := w.ResponseWriter.Write(p)
+	if n > 0 && w.retrieval.firstByte.IsZero() {
+		w.retrieval.firstByte = time.Now()
+	}
+	w.retrieval.bytesSent.Add(uint64(n))
+	if w.car != nil {
+		w.retrieval.blocksReceived.Add(w.car.feed(p[:n]))
+		if w.retrieval.checksum != nil {
+			w.retrieval.checksum.Write(p[:n])
+		}
+	}
+	return n, err
+}
+
+func (w *countingResponseWriter) Flush() {
+	if f, ok := w.ResponseWriter.(http.Flusher); ok {
+		f.Flush()
+	}
+}
+
+// Hijack must be forwarded, Lassie hijacks the connection to abort the response stream.
+func (w *countingResponseWriter) Hijack() (net.Conn, *bufio.ReadWriter, error) {
+	if h, ok := w.ResponseWriter.(http.Hijacker); ok {
+		w.retrieval.hijacked = true
+		return h.Hijack()
+	}
+	return nil, nil, errors.New("the response writer does not support hijacking")
+}
+
+func (w *countingResponseWriter) Unwrap() http.ResponseWriter {
+	return w.ResponseWriter
+}
+
+// carBlockCounter parses a CARv1 stream just enough to count the blocks. Each section of the
+// stream (the header first, then the blocks) is prefixed with its length encoded as an unsigned
+// varint.
+type carBlockCounter struct {
+	headerSeen bool
+	invalid    bool
+	// remaining is the number of bytes left in the current section
+	remaining uint64
+	// varint and shift hold the partially decoded length of the next section
+	varint uint64
+	shift  uint
+}
+
+// feed processes the next chunk of the stream and returns the number of blocks started in it.
+func (c *carBlockCounter) feed(p []byte) uint64 {
+	var blocks uint64
+	for len(p) > 0 && !c.invalid {
+		if c.remaining > 0 {
+			n := min(c.remaining, uint64(len(p)))
+			c.remaining -= n
+			p = p[n:]
+			continue
+		}
+
+		b := p[0]
+		p = p[1:]
+		c.varint |= uint64(b&0x7f) << c.shift
+		if b&0x80 != 0 {
+			c.shift += 7
+			if c.shift > 63 {
+				c.invalid = true
+			}
+			continue
+		}
+
+		c.remaining = c.varint
+		c.varint = 0
+		c.shift = 0
+		if c.headerSeen {
+			blocks++
+		} else {
+			c.headerSeen = true
+		}
+	}
+	return blocks
+}
+
+// Lassie events carrying the information we are interested in. We use local interfaces to avoid
+// depending on concrete event types.
+type eventWithRootCid interface {
+	RootCid() cid.Cid
+}
+
+type eventWithProviderId interface {
+	ProviderId() peer.ID
+}
+
+// correlatingFetcher records the Lassie retrieval ID of each request tracked by trackRetrievals
+// before the retrieval starts, so that onRetrievalEvent attributes the Lassie events to the
+// request that caused them, also when several requests retrieve the same CID.
+type correlatingFetcher struct {
+	fetcher types.Fetcher
+}
+
+func (f correlatingFetcher) Fetch(ctx context.Context, request types.RetrievalRequest, opts ...types.FetchOption) (*types.RetrievalStats, error) {
+	r, ok := ctx.Value(activeRetrievalKey{}).(*activeRetrieval)
+	if !ok {
+		return f.fetcher.Fetch(ctx, request, opts...)
+	}
+
+	lassieId := request.RetrievalID.String()
+	retrievalsMtx.Lock()
+	if r.lassieId != "" {
+		delete(retrievalsByLassieId, r.lassieId)
+	}
+	r.lassieId = lassieId
+	retrievalsByLassieId[lassieId] = r
+	retrievalsMtx.Unlock()
+
+	stats, err := f.fetcher.Fetch(ctx, request, opts...)
+	// Fetch runs on the handler goroutine, the error response is written after it returns
+	r.fetchErr = err
+	return stats, err
+}
+
+// onRetrievalEvent is subscribed to all Lassie retrieval events. It records the provider and the
+// protocol serving the retrieval of the request correlated by correlatingFetcher.
+func onRetrievalEvent(event types.RetrievalEvent) {
+	lassieId := event.RetrievalId().String()
+
+	retrievalsMtx.Lock()
+	defer retrievalsMtx.Unlock()
+
+	r, ok := retrievalsByLassieId[lassieId]
+	if !ok {
+		// The request has already finished
+		return
+	}
+
+	traceRetrievalEvent(r, event)
+	if r.succeeded {
+		// Keep the provider that delivered the content, other attempts may still report events
+		return
+	}
+	if e, ok := event.(eventWithProviderId); ok && e.ProviderId() != "" {
+		r.provider = e.ProviderId().String()
+		r.protocol = ""
+		if e, ok := event.(eventWithProtocol); ok {
+			r.protocol = e.Protocol().String()
+		}
+	}
+	r.succeeded = event.Code() == types.SuccessCode
+}
+
+// CancelRetrieval aborts the retrieval with the given ID. It returns false when there is no such
+// retrieval running, e.g. because it has already finished.
+//
+//export CancelRetrieval
+func CancelRetrieval(id *C.char) C.bool {
+	defer recoverPanic("CancelRetrieval")
+	retrievalsMtx.Lock()
+	r, ok := retrievals[C.GoString(id)]
+	retrievalsMtx.Unlock()
+
+	if !ok {
+		return false
+	}
+	debugw("cancelling retrieval", "retrieval_id", r.id)
+	r.cancel()
+	return true
+}
+
+// ListRetrievals returns a snapshot of the currently running retrievals. The caller must call
+// DropRetrievalList to release the memory.
+//
+//export ListRetrievals
+func ListRetrievals() C.retrieval_list_t {
+	defer recoverPanic("ListRetrievals")
+	retrievalsMtx.Lock()
+	defer retrievalsMtx.Unlock()
+
+	if len(retrievals) == 0 {
+		return C.retrieval_list_t{items: nil, len: 0}
+	}
+
+	items := (*C.retrieval_info_t)(C.malloc(C.size_t(len(retrievals)) * C.size_t(unsafe.Sizeof(C.retrieval_info_t{}))))
+	list := unsafe.Slice(items, len(retrievals))
+	i := 0
+	for _, r := range retrievals {
+		info := C.retrieval_info_t{
+			id:              C.CString(r.id),
+			cid:             C.CString(r.cid),
+			provider:        nil,
+			elapsed:         C.uint64_t(time.Since(r.started)),
+			bytes_sent:      C.uint64_t(r.bytesSent.Load()),
+			blocks_received: C.uint64_t(r.blocksReceived.Load()),
+		}
+		if r.provider != "" {
+			info.provider = C.CString(r.provider)
+		}
+		list[i] = info
+		i++
+	}
+
+	return C.retrieval_list_t{items: items, len: C.size_t(len(list))}
+}
+
+// DropRetrievalList cleans up any resources allocated for and owned by the retrieval_list_t value.
+//
+//export DropRetrievalList
+func DropRetrievalList(list *C.retrieval_list_t) {
+	if list.items == nil {
+		return
+	}
+	for _, info := range unsafe.Slice(list.items, list.len) {
+		C.free(unsafe.Pointer(info.id))
+		C.free(unsafe.Pointer(info.cid))
+		if info.provider != nil {
+			C.free(unsafe.Pointer(info.provider))
+		}
+	}
+	C.free(unsafe.Pointer(list.items))
+	list.items = nil
+	list.len = 0
+}
@@ -0,0 +1,197 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"fmt"
+	"net/http"
+	"net/url"
+	"time"
+
+	lassieBuild "github.com/filecoin-project/lassie/pkg/build"
+	"github.com/filecoin-project/lassie/pkg/indexerlookup"
+	"github.com/filecoin-project/lassie/pkg/types"
+	drclient "github.com/ipfs/boxo/routing/http/client"
+	drtypes "github.com/ipfs/boxo/routing/http/types"
+	"github.com/ipfs/go-cid"
+	"github.com/ipni/go-libipni/metadata"
+	"github.com/multiformats/go-multiaddr"
+)
+
+// newCandidateSource creates the source Lassie uses to discover providers for requests that
+// don't specify `providers=`. It returns nil when Lassie's default source should be used.
+//
+// Lassie discovers providers via the IPNI indexer. DHT results are included only when the indexer
+// is asked to cascade the lookup to the IPFS DHT. A configured delegated routing server replaces
+// the indexer, the server decides which sources to consult.
+func newCandidateSource(cfg *C.daemon_config_t) (types.CandidateSource, error) {
+	if cfg.disable_candidate_discovery {
+		// requireProviders rejects the requests that would need a candidate source
+		debug("CANDIDATE DISCOVERY DISABLED, PROVIDERS ARE REQUIRED")
+		return noCandidateSource{}, nil
+	}
+
+	if endpoint := C.GoString(cfg.delegated_routing_url); endpoint != "" {
+		debug(fmt.Sprintf("Discovering providers via delegated routing at %s", endpoint))
+		return newDelegatedRoutingSource(endpoint)
+	}
+
+	if cfg.disable_ipni {
+		debug("CANDIDATE DISCOVERY DISABLED")
+		return noCandidateSource{}, nil
+	}
+
+	if cfg.disable_dht {
+		debug("DHT CASCADE DISABLED")
+		source, err := indexerlookup.NewCandidateSource(indexerlookup.WithHttpClient(&http.Client{
+			Transport: noCascadeTransport{next: http.DefaultTransport},
+		}))
+		if err != nil {
+			return nil, err
+		}
+		return source, nil
+	}
+
+	return nil, nil
+}
+
+// orDefaultCandidateSource returns the source created by newCandidateSource, or the source
+// Lassie creates by default when it's nil. Wrappers like circuitBreakerSource need a source to
+// wrap.
+func orDefaultCandidateSource(source types.CandidateSource) (types.CandidateSource, error) {
+	if source != nil {
+		return source, nil
+	}
+	return indexerlookup.NewCandidateSource()
+}
+
+// errProvidersRequired is the response to requests without `providers=` when candidate discovery
+// is disabled. Keep the message in sync with RetrievalError::from_response in
+// src/retrieval_error.rs
+const errProvidersRequired = "candidate discovery is disabled, the request must specify providers="
+
+// requireProviders rejects requests that don't specify the providers to retrieve from, before
+// they reach Lassie (or the IPNS resolver).
+func requireProviders(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.URL.Query().Get("providers") == "" {
+			httpError(w, errorCodeProvidersRequired, errProvidersRequired, http.StatusBadRequest)
+			return
+		}
+		next.ServeHTTP(w, r)
+	})
+}
+
+// noCandidateSource does not discover any providers. Lassie retrieves content only from the
+// providers specified in the request.
+type noCandidateSource struct{}
+
+func (noCandidateSource) FindCandidates(ctx context.Context, c cid.Cid, cb func(types.RetrievalCandidate)) error {
+	return nil
+}
+
+// noCascadeTransport removes the `cascade` query parameter from IPNI requests to prevent the
+// indexer from querying the IPFS DHT on our behalf.
+type noCascadeTransport struct {
+	next http.RoundTripper
+}
+
+func (t noCascadeTransport) RoundTrip(req *http.Request) (*http.Response, error) {
+	query := req.URL.Query()
+	if query.Has("cascade") {
+		req = req.Clone(req.Context())
+		query.Del("cascade")
+		req.URL.RawQuery = query.Encode()
+	}
+	return t.next.RoundTrip(req)
+}
+
+// delegatedRoutingSource discovers providers via the `/routing/v1/providers/{cid}` endpoint of an
+// HTTP delegated routing server, see https://specs.ipfs.tech/routing/http-routing-v1/
+type delegatedRoutingSource struct {
+	client *drclient.Client
+}
+
+func newDelegatedRoutingSource(endpoint string) (*delegatedRoutingSource, error) {
+	if err := checkRoutingEndpoint(endpoint); err != nil {
+		return nil, err
+	}
+	client, err := drclient.New(
+		endpoint,
+		drclient.WithHTTPClient(&http.Client{Timeout: 30 * time.Second}),
+		drclient.WithUserAgent(lassieBuild.UserAgent),
+	)
+	if err != nil {
+		return nil, err
+	}
+	return &delegatedRoutingSource{client: client}, nil
+}
+
+// checkRoutingEndpoint rejects endpoints that are not absolute HTTP(S) URLs, the routing client
+// would fail on the first lookup otherwise.
+func checkRoutingEndpoint(endpoint string) error {
+	u, err := url.Parse(endpoint)
+	if err != nil {
+		return err
+	}
+	if (u.Scheme != "http" && u.Scheme != "https") || u.Host == "" {
+		return fmt.Errorf("%q is not an http(s) URL", endpoint)
+	}
+	return nil
+}
+
+func (s *delegatedRoutingSource) FindCandidates(ctx context.Context, c cid.Cid, cb func(types.RetrievalCandidate)) error {
+	results, err := s.client.FindProviders(ctx, c)
+	if err != nil {
+		return err
+	}
+	defer results.Close()
+
+	for results.Next() {
+		result := results.Val()
+		if result.Err != nil {
+			return result.Err
+		}
+		record, ok := result.Val.(*drtypes.PeerRecord)
+		if !ok || record.ID == nil || len(record.Addrs) == 0 {
+			// Lassie has no peer routing, it cannot dial providers without addresses
+			continue
+		}
+		addrs := make([]multiaddr.Multiaddr, 0, len(record.Addrs))
+		for _, addr := range record.Addrs {
+			addrs = append(addrs, addr.Multiaddr)
+		}
+		protocols := retrievalProtocols(record.Protocols)
+		if len(protocols) == 0 {
+			continue
+		}
+		cb(types.NewRetrievalCandidate(*record.ID, addrs, c, protocols...))
+	}
+	return nil
+}
+
+// retrievalProtocols maps the transfer protocols announced in a peer record to the protocols
+// Lassie supports. Peers without any announced protocols are assumed to speak Bitswap, that's the
+// case for most results coming from the IPFS DHT.
+func retrievalProtocols(names []string) []metadata.Protocol {
+	if len(names) == 0 {
+		return []metadata.Protocol{metadata.Bitswap{}}
+	}
+	var protocols []metadata.Protocol
+	for _, name := range names {
+		switch name {
+		case "transport-bitswap":
+			protocols = append(protocols, metadata.Bitswap{})
+		case "transport-ipfs-gateway-http":
+			protocols = append(protocols, metadata.IpfsGatewayHttp{})
+		case "transport-graphsync-filecoinv1":
+			protocols = append(protocols, &metadata.GraphsyncFilecoinV1{})
+		}
+	}
+	return protocols
+}
@@ -0,0 +1,58 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"fmt"
+	"math"
+	"runtime"
+	rtdebug "runtime/debug"
+)
+
+// initialMemoryLimit is the limit the Go runtime started with, i.e. the value of the GOMEMLIMIT
+// environment variable or no limit. A negative input does not change the limit, it only reports
+// the current value.
+var initialMemoryLimit = rtdebug.SetMemoryLimit(-1)
+
+// initialMaxProcs is the GOMAXPROCS value the runtime started with, derived from the environment
+// variable or the number of CPUs.
+var initialMaxProcs = runtime.GOMAXPROCS(0)
+
+// configureRuntime applies the runtime limits configured for the daemon. Daemons without a limit
+// restore the initial value, the previous daemon may have changed it.
+func configureRuntime(cfg *C.daemon_config_t) {
+	limit := initialMemoryLimit
+	if cfg.go_memory_limit > 0 {
+		limit = int64(min(uint64(cfg.go_memory_limit), math.MaxInt64))
+		debug(fmt.Sprintf("Go memory limit: %d bytes", limit))
+	}
+	rtdebug.SetMemoryLimit(limit)
+
+	procs := initialMaxProcs
+	if cfg.go_max_procs > 0 {
+		procs = int(cfg.go_max_procs)
+		debug(fmt.Sprintf("GOMAXPROCS: %d", procs))
+	}
+	runtime.GOMAXPROCS(procs)
+}
+
+// GetMemoryStats reports the memory used by the Go runtime. Reading the statistics briefly stops
+// all goroutines, don't call it in a tight loop.
+//
+//export GetMemoryStats
+func GetMemoryStats() C.memory_stats_t {
+	defer recoverPanic("GetMemoryStats")
+	var m runtime.MemStats
+	runtime.ReadMemStats(&m)
+	return C.memory_stats_t{
+		heap_in_use: C.uint64_t(m.HeapInuse),
+		total_alloc: C.uint64_t(m.TotalAlloc),
+		sys:         C.uint64_t(m.Sys),
+		goroutines:  C.uint64_t(runtime.NumGoroutine()),
+		gc_cycles:   C.uint32_t(m.NumGC),
+	}
+}
@@ -0,0 +1,101 @@
+package main
+
+import (
+	"context"
+	"io"
+	"sync"
+	"time"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"github.com/ipld/go-ipld-prime/linking"
+)
+
+// rateLimiter is a token bucket holding up to one second worth of bytes. A caller asking for more
+// bytes than available goes into debt and waits until the bucket is refilled, so that large
+// blocks are not rejected.
+type rateLimiter struct {
+	mtx    sync.Mutex
+	rate   float64
+	tokens float64
+	last   time.Time
+}
+
+func newRateLimiter(bytesPerSecond uint64) *rateLimiter {
+	return &rateLimiter{
+		rate:   float64(bytesPerSecond),
+		tokens: float64(bytesPerSecond),
+		last:   time.Now(),
+	}
+}
+
+// wait blocks until n bytes may pass or ctx is done.
+func (l *rateLimiter) wait(ctx context.Context, n int) error {
+	l.mtx.Lock()
+	now := time.Now()
+	l.tokens = min(l.rate, l.tokens+now.Sub(l.last).Seconds()*l.rate)
+	l.last = now
+	l.tokens -= float64(n)
+	deficit := -l.tokens
+	l.mtx.Unlock()
+
+	if deficit <= 0 {
+		return nil
+	}
+	timer := time.NewTimer(time.Duration(deficit / l.rate * float64(time.Second)))
+	defer timer.Stop()
+	select {
+	case <-timer.C:
+		return nil
+	case <-ctx.Done():
+		return ctx.Err()
+	}
+}
+
+// throttlingFetcher wraps the request link system so that received blocks are accepted no faster
+// than the configured rates. Waiting before the block is stored holds back the retrieval: HTTP
+// providers are slowed down by TCP back-pressure and Bitswap asks for the next blocks later.
+type throttlingFetcher struct {
+	fetcher types.Fetcher
+	// Shared by all retrievals, nil when there is no global limit
+	global *rateLimiter
+	// Bytes per second for each retrieval, 0 disables the limit
+	perRetrieval uint64
+}
+
+func (f throttlingFetcher) Fetch(ctx context.Context, request types.RetrievalRequest, opts ...types.FetchOption) (*types.RetrievalStats, error) {
+	limiters := make([]*rateLimiter, 0, 2)
+	if f.global != nil {
+		limiters = append(limiters, f.global)
+	}
+	if f.perRetrieval > 0 {
+		limiters = append(limiters, newRateLimiter(f.perRetrieval))
+	}
+
+	lsys := request.LinkSystem
+	writeOpener := lsys.StorageWriteOpener
+	lsys.StorageWriteOpener = func(lctx linking.LinkContext) (io.Writer, linking.BlockWriteCommitter, error) {
+		w, commit, err := writeOpener(lctx)
+		if err != nil {
+			return nil, nil, err
+		}
+		return throttledWriter{ctx: ctx, w: w, limiters: limiters}, commit, nil
+	}
+
+	request.LinkSystem = lsys
+	return f.fetcher.Fetch(ctx, request, opts...)
+}
+
+type throttledWriter struct {
+	ctx      context.Context
+	w        io.Writer
+	limiters []*rateLimiter
+}
+
+func (t throttledWriter) Write(p []byte) (int, error) {
+	for _, l := range t.limiters {
+		if err := l.wait(t.ctx, len(p)); err != nil {
+			return 0, err
+		}
+	}
+	return t.w.Write(p)
+}
@@ -0,0 +1,34 @@
+package main
+
+import (
+	"context"
+	"fmt"
+	"net/http"
+	"time"
+)
+
+// timeoutHeader carries the deadline of a single request as a Go duration, e.g. `30s` or
+// `1500ms`, see TIMEOUT_HEADER in src/retrieval.rs
+const timeoutHeader = "X-Timeout"
+
+// withRequestTimeout applies the deadline requested in the X-Timeout header to the request
+// context. It covers the time spent in the retrieval queue and the IPNS resolution too. Lassie
+// responds with 504 when the deadline passes before the response started and aborts the stream
+// otherwise, like for the global timeout.
+func withRequestTimeout(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		value := req.Header.Get(timeoutHeader)
+		if value == "" {
+			next.ServeHTTP(res, req)
+			return
+		}
+		timeout, err := time.ParseDuration(value)
+		if err != nil || timeout <= 0 {
+			httpError(res, errorCodeBadRequest, fmt.Sprintf("invalid %s header %q, expected a positive duration like 30s", timeoutHeader, value), http.StatusBadRequest)
+			return
+		}
+		ctx, cancel := context.WithTimeout(req.Context(), timeout)
+		defer cancel()
+		next.ServeHTTP(res, req.WithContext(ctx))
+	})
+}
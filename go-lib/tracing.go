@@ -0,0 +1,182 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"context"
+	"fmt"
+	"net/http"
+	"strings"
+	"time"
+
+	"github.com/filecoin-project/lassie/pkg/types"
+	"go.opentelemetry.io/otel"
+	"go.opentelemetry.io/otel/attribute"
+	"go.opentelemetry.io/otel/codes"
+	"go.opentelemetry.io/otel/propagation"
+	sdktrace "go.opentelemetry.io/otel/sdk/trace"
+	"go.opentelemetry.io/otel/trace"
+	"go.opentelemetry.io/otel/trace/noop"
+)
+
+// tracerName is the instrumentation scope of the spans created by the wrapper. Lassie's libraries,
+// e.g. Bitswap, record their spans under their own names.
+const tracerName = "rusty-lassie"
+
+// tracerProvider is the provider installed by the last InitDaemon call, nil when otlp_endpoint is
+// not configured.
+var tracerProvider *sdktrace.TracerProvider
+
+// setupTracing installs the OTLP exporter configured in cfg as the global tracer provider, which
+// Lassie's libraries use too. The caller must hold the mutex.
+func setupTracing(cfg *C.daemon_config_t) {
+	endpoint := C.GoString(cfg.otlp_endpoint)
+	if endpoint == "" {
+		return
+	}
+	exporter := &otlpExporter{
+		url:         strings.TrimSuffix(endpoint, "/") + "/v1/traces",
+		serviceName: C.GoString(cfg.otlp_service_name),
+		// Not the default transport, the exports are not retrievals
+		client: &http.Client{Transport: defaultTransport.Clone(), Timeout: otlpExportTimeout},
+	}
+	otel.SetErrorHandler(otel.ErrorHandlerFunc(func(err error) {
+		debug(fmt.Sprintf("OpenTelemetry error: %v", err))
+	}))
+	tracerProvider = sdktrace.NewTracerProvider(sdktrace.WithBatcher(exporter))
+	otel.SetTracerProvider(tracerProvider)
+	debug(fmt.Sprintf("Exporting traces to %s", exporter.url))
+}
+
+// shutdownTracing exports the remaining spans and uninstalls the tracer provider, it's called when
+// the daemon stops. The caller must hold the mutex.
+func shutdownTracing() {
+	if tracerProvider == nil {
+		return
+	}
+	ctx, cancel := context.WithTimeout(context.Background(), otlpExportTimeout)
+	defer cancel()
+	if err := tracerProvider.Shutdown(ctx); err != nil {
+		debug(fmt.Sprintf("Cannot export the remaining spans: %v", err))
+	}
+	tracerProvider = nil
+	otel.SetTracerProvider(noop.NewTracerProvider())
+}
+
+// retrievalSpans are the spans of a retrieval tracked by trackRetrievals. Without a configured
+// tracer provider, they are no-op spans.
+//
+// The retrieval span is the child of the span in the traceparent header of the request, if any,
+// and the parent of the candidate discovery span and of the dial and transfer spans of each
+// provider. The phases are derived from the Lassie events, see traceRetrievalEvent.
+type retrievalSpans struct {
+	ctx       context.Context
+	retrieval trace.Span
+	discovery trace.Span
+	// The dial span and then the transfer span of each provider
+	providers map[string]trace.Span
+}
+
+// startRetrievalSpans starts the retrieval and candidate discovery spans of r and returns the
+// context of the retrieval span.
+func startRetrievalSpans(ctx context.Context, req *http.Request, r *activeRetrieval) context.Context {
+	ctx = propagation.TraceContext{}.Extract(ctx, propagation.HeaderCarrier(req.Header))
+	tracer := otel.Tracer(tracerName)
+	ctx, span := tracer.Start(ctx, "retrieval",
+		trace.WithSpanKind(trace.SpanKindServer),
+		trace.WithAttributes(
+			attribute.String("lassie.cid", r.cid),
+			attribute.String("lassie.retrieval_id", r.id),
+		))
+	_, discovery := tracer.Start(ctx, "candidate discovery")
+	r.spans = retrievalSpans{ctx: ctx, retrieval: span, discovery: discovery, providers: map[string]trace.Span{}}
+	return ctx
+}
+
+// traceRetrievalEvent moves the spans of r to the phase reported by event. The caller must hold
+// retrievalsMtx.
+func traceRetrievalEvent(r *activeRetrieval, event types.RetrievalEvent) {
+	s := &r.spans
+	if s.retrieval == nil || !s.retrieval.IsRecording() {
+		return
+	}
+	at := trace.WithTimestamp(event.Time())
+	switch event.Code() {
+	case types.CandidatesFoundCode, types.CandidatesFilteredCode, types.StartedRetrievalCode, types.FailedCode:
+		// Ending a span again does nothing
+		s.discovery.End(at)
+	}
+
+	e, ok := event.(eventWithProviderId)
+	if !ok || e.ProviderId() == "" {
+		return
+	}
+	provider := e.ProviderId().String()
+	attributes := []attribute.KeyValue{attribute.String("lassie.provider", provider)}
+	if e, ok := event.(eventWithProtocol); ok {
+		attributes = append(attributes, attribute.String("lassie.protocol", e.Protocol().String()))
+	}
+	tracer := otel.Tracer(tracerName)
+	switch event.Code() {
+	case types.StartedRetrievalCode:
+		_, s.providers[provider] = tracer.Start(s.ctx, "dial", at, trace.WithAttributes(attributes...))
+	case types.ConnectedToProviderCode:
+		if span, ok := s.providers[provider]; ok {
+			span.End(at)
+		}
+		_, s.providers[provider] = tracer.Start(s.ctx, "transfer", at, trace.WithAttributes(attributes...))
+	case types.FirstByteCode:
+		if span, ok := s.providers[provider]; ok {
+			span.AddEvent("first byte", at)
+		}
+	case types.SuccessCode:
+		if span, ok := s.providers[provider]; ok {
+			if e, ok := event.(eventWithReceivedBytes); ok {
+				span.SetAttributes(attribute.Int64("lassie.bytes", int64(e.ReceivedBytesSize())))
+			}
+			span.SetStatus(codes.Ok, "")
+			span.End(at)
+			delete(s.providers, provider)
+		}
+	case types.FailedRetrievalCode:
+		if span, ok := s.providers[provider]; ok {
+			if e, ok := event.(eventWithErrorMessage); ok {
+				span.SetStatus(codes.Error, e.ErrorMessage())
+			}
+			span.End(at)
+			delete(s.providers, provider)
+		}
+	}
+}
+
+// endRetrievalSpans ends the spans of r still open when the handler returns. The caller must hold
+// retrievalsMtx.
+func endRetrievalSpans(r *activeRetrieval) {
+	s := &r.spans
+	if s.retrieval == nil {
+		return
+	}
+	at := trace.WithTimestamp(time.Now())
+	s.discovery.End(at)
+	for _, span := range s.providers {
+		span.End(at)
+	}
+	s.retrieval.SetAttributes(
+		attribute.Int("http.response.status_code", r.status),
+		attribute.Int64("lassie.bytes", int64(r.bytesSent.Load())),
+		attribute.Int64("lassie.blocks", int64(r.blocksReceived.Load())),
+	)
+	switch {
+	case r.fetchErr != nil:
+		s.retrieval.SetStatus(codes.Error, r.fetchErr.Error())
+	case r.hijacked:
+		s.retrieval.SetStatus(codes.Error, "the response stream was aborted")
+	case r.status >= 400:
+		s.retrieval.SetStatus(codes.Error, http.StatusText(r.status))
+	}
+	s.retrieval.End(at)
+}
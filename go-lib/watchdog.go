@@ -0,0 +1,49 @@
+package main
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#include "lassie-ffi.h"
+*/
+import "C"
+
+import (
+	"net/http"
+)
+
+// healthPath answers the probes of the Rust watchdog, see DaemonConfig::watchdog
+const healthPath = "/healthz"
+
+// withHealthCheck answers the watchdog probes before the access log and the client IP
+// restrictions, the probes are not retrievals.
+func withHealthCheck(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(res http.ResponseWriter, req *http.Request) {
+		if req.URL.Path != healthPath {
+			next.ServeHTTP(res, req)
+			return
+		}
+		if req.Method != http.MethodGet && req.Method != http.MethodHead {
+			res.WriteHeader(http.StatusMethodNotAllowed)
+			return
+		}
+		res.Header().Set("Content-Type", "text/plain")
+		res.WriteHeader(http.StatusOK)
+		if req.Method == http.MethodGet {
+			res.Write([]byte("OK\n"))
+		}
+	})
+}
+
+// PingDaemon checks that the daemon is initialized and its mutex is not held by a wedged call.
+// The watchdog uses it when there is no HTTP listener to probe.
+//
+//export PingDaemon
+func PingDaemon() (result C.result_t) {
+	defer recoverErrorCode("PingDaemon", &result.error, &result.error_code)
+	mtx.Lock()
+	defer mtx.Unlock()
+
+	if daemon == nil {
+		return newErrorCode(C.LASSIE_ERR_NOT_RUNNING, "Lassie daemon not running", nil)
+	}
+	return OK
+}
@@ -0,0 +1,157 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use crate::{from_c_string, Daemon};
+
+go_lassie! {
+    fn SetAccessLogCallback(callback: AccessLogCallback, ctx: *mut c_void);
+    fn ClearAccessLogCallback(ctx: *mut c_void);
+}
+
+type AccessLogCallback = extern "C" fn(ctx: *mut c_void, record: *const GoAccessLogRecord);
+
+#[repr(C)]
+struct GoAccessLogRecord {
+    // this must be kept in sync with the definition of access_log_record_t in go-lib/lassie-ffi.h
+    retrieval_id: *const c_char,
+    remote_addr: *const c_char,
+    method: *const c_char,
+    path: *const c_char,
+    cid: *const c_char,
+    status: u16,
+    bytes: u64,
+    duration: i64,
+    outcome: u8,
+}
+
+/// A request handled by the daemon, see [`Daemon::access_log`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AccessLogRecord {
+    /// The ID assigned by the daemon (see [`RETRIEVAL_ID_HEADER`](crate::RETRIEVAL_ID_HEADER)),
+    /// `None` when the request was rejected before the retrieval started, e.g. because of a
+    /// missing access token.
+    pub retrieval_id: Option<String>,
+    /// The address of the HTTP client, `None` for in-process requests.
+    pub remote_addr: Option<String>,
+    pub method: String,
+    /// The request path without the query string.
+    pub path: String,
+    /// The root CID of `/ipfs/` requests.
+    pub cid: Option<String>,
+    pub status: u16,
+    /// The number of response body bytes sent.
+    pub bytes: u64,
+    /// Time from receiving the request until the response was complete.
+    pub duration: Duration,
+    pub outcome: RequestOutcome,
+}
+
+/// How a request ended, see [`AccessLogRecord::outcome`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RequestOutcome {
+    /// The full response was sent.
+    Succeeded,
+    /// The daemon responded with an error status.
+    Failed,
+    /// The response started, but the daemon aborted the body stream, e.g. on timeout.
+    Aborted,
+    /// The client went away or the daemon stopped before the response was complete.
+    Cancelled,
+}
+
+impl RequestOutcome {
+    fn from_go(outcome: u8) -> Self {
+        // keep in sync with ACCESS_LOG_* in go-lib/lassie-ffi.h
+        match outcome {
+            0 => RequestOutcome::Succeeded,
+            2 => RequestOutcome::Aborted,
+            3 => RequestOutcome::Cancelled,
+            _ => RequestOutcome::Failed,
+        }
+    }
+}
+
+type Callback = dyn Fn(&AccessLogRecord) + Send + Sync;
+
+/// Receives a record for every request handled by the daemon, see [`Daemon::access_log`].
+///
+/// Dropping the value unregisters the callback. The value borrows the daemon, therefore it cannot
+/// outlive it.
+pub struct AccessLog<'a> {
+    // Double boxed to pass a thin pointer to Go
+    callback: *mut Box<Callback>,
+    _daemon: PhantomData<&'a Daemon>,
+}
+
+// SAFETY:
+// The callback is `Send + Sync`, the pointer is only dereferenced by Go calling the trampoline
+// and freed in `drop`.
+unsafe impl Send for AccessLog<'_> {}
+// SAFETY:
+// See above, `AccessLog` has no methods accessing the callback.
+unsafe impl Sync for AccessLog<'_> {}
+
+impl std::fmt::Debug for AccessLog<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessLog").finish_non_exhaustive()
+    }
+}
+
+impl AccessLog<'_> {
+    pub(crate) fn register<F>(callback: F) -> Self
+    where
+        F: Fn(&AccessLogRecord) + Send + Sync + 'static,
+    {
+        let boxed: Box<Callback> = Box::new(callback);
+        let callback = Box::into_raw(Box::new(boxed));
+        // SAFETY:
+        // The pointer stays valid until `drop` unregisters it, Go stops calling the callback
+        // before `ClearAccessLogCallback` returns.
+        unsafe { SetAccessLogCallback(access_log_trampoline, callback.cast()) };
+        AccessLog {
+            callback,
+            _daemon: PhantomData,
+        }
+    }
+}
+
+impl Drop for AccessLog<'_> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // Clearing waits for running callbacks, no other reference to the callback exists after
+        // the call returns. The pointer was created by `Box::into_raw` in `register`.
+        unsafe {
+            ClearAccessLogCallback(self.callback.cast());
+            drop(Box::from_raw(self.callback));
+        }
+    }
+}
+
+extern "C" fn access_log_trampoline(ctx: *mut c_void, record: *const GoAccessLogRecord) {
+    // SAFETY:
+    // `ctx` is the pointer registered by `AccessLog::register`, it stays valid while Go may call
+    // this function. Go passes a record that is valid until this function returns.
+    let (callback, record) = unsafe { (&*ctx.cast::<Box<Callback>>(), &*record) };
+    let non_empty = |s: *const c_char| from_c_string(s).filter(|s| !s.is_empty());
+    let record = AccessLogRecord {
+        retrieval_id: non_empty(record.retrieval_id),
+        remote_addr: non_empty(record.remote_addr),
+        method: from_c_string(record.method).unwrap_or_default(),
+        path: from_c_string(record.path).unwrap_or_default(),
+        cid: non_empty(record.cid),
+        status: record.status,
+        bytes: record.bytes,
+        duration: Duration::from_nanos(u64::try_from(record.duration).unwrap_or_default()),
+        outcome: RequestOutcome::from_go(record.outcome),
+    };
+
+    // Unwinding into Go is undefined behaviour
+    if panic::catch_unwind(AssertUnwindSafe(|| callback(&record))).is_err() {
+        log::error!("Lassie access log callback panicked");
+    }
+}
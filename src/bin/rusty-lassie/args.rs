@@ -0,0 +1,1052 @@
+//! Command-line flags and `LASSIE_*` environment variables of the daemon.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use lassie::multiaddr::Multiaddr;
+use lassie::{
+    AdminAddress, AdminListenerConfig, BlockCacheConfig, DaemonConfig, DagScope, Format, IpnsName,
+    LogFormat, OtlpConfig, RetrievalRequest,
+};
+
+/// What the user asked for.
+#[derive(Debug)]
+pub enum Command {
+    Daemon(Box<DaemonArgs>),
+    Fetch(Box<FetchArgs>),
+    /// Print the help, of the `fetch` command when set.
+    Help(bool),
+    Version,
+}
+
+/// The arguments of `rusty-lassie` running the daemon.
+#[derive(Debug)]
+pub struct DaemonArgs {
+    pub config: DaemonConfig,
+    /// How long the running retrievals can take to finish once the daemon received SIGTERM or
+    /// SIGINT.
+    pub shutdown_grace_period: Duration,
+}
+
+/// The arguments of `rusty-lassie fetch`.
+#[derive(Debug)]
+pub struct FetchArgs {
+    pub request: RetrievalRequest,
+    pub output: PathBuf,
+    /// The URL of a running daemon, `None` to start one configured by `config`.
+    pub daemon_url: Option<String>,
+    pub daemon_access_token: Option<String>,
+    pub config: DaemonConfig,
+}
+
+struct Opt {
+    name: &'static str,
+    /// The placeholder shown in the help, `None` for boolean flags.
+    value: Option<&'static str>,
+    help: &'static str,
+}
+
+const OPTIONS: &[Opt] = &[
+    Opt {
+        name: "config",
+        value: Some("PATH"),
+        help: "Read the options from a TOML file, env vars and flags take precedence",
+    },
+    Opt {
+        name: "port",
+        value: Some("PORT"),
+        help: "HTTP port on 127.0.0.1, 0 picks a free port (default: 0)",
+    },
+    Opt {
+        name: "reuse-port",
+        value: None,
+        help: "Set SO_REUSEPORT so a new instance can bind the port while this one drains",
+    },
+    Opt {
+        name: "auto-restart",
+        value: None,
+        help: "Restart Lassie on the same port when its HTTP handler fails",
+    },
+    Opt {
+        name: "watchdog-interval",
+        value: Some("DURATION"),
+        help: "Probe the HTTP listener this often and log when it stops responding",
+    },
+    Opt {
+        name: "temp-dir",
+        value: Some("DIR"),
+        help: "Directory for temporary CAR files",
+    },
+    Opt {
+        name: "create-temp-dir",
+        value: None,
+        help: "Create the temp dir when it does not exist",
+    },
+    Opt {
+        name: "private-temp-dir",
+        value: None,
+        help: "Create the missing temp dir accessible by the current user only",
+    },
+    Opt {
+        name: "max-blocks",
+        value: Some("N"),
+        help: "Maximum number of blocks per retrieval",
+    },
+    Opt {
+        name: "max-concurrent-requests",
+        value: Some("N"),
+        help: "Reject requests over this limit with 429",
+    },
+    Opt {
+        name: "max-concurrent-retrievals",
+        value: Some("N"),
+        help: "Queue retrievals over this limit",
+    },
+    Opt {
+        name: "provider-timeout",
+        value: Some("DURATION"),
+        help: "Per-provider timeout, e.g. 20s or 2m",
+    },
+    Opt {
+        name: "http-provider-timeout",
+        value: Some("DURATION"),
+        help: "Timeout for the response headers and each body read of HTTP providers",
+    },
+    Opt {
+        name: "bitswap-provider-timeout",
+        value: Some("DURATION"),
+        help: "Timeout for the next block of Bitswap providers, overrides --provider-timeout",
+    },
+    Opt {
+        name: "global-timeout",
+        value: Some("DURATION"),
+        help: "Timeout of the whole retrieval",
+    },
+    Opt {
+        name: "startup-timeout",
+        value: Some("DURATION"),
+        help: "Fail when Lassie does not start within this time",
+    },
+    Opt {
+        name: "access-token",
+        value: Some("TOKEN"),
+        help: "Require `Authorization: Bearer TOKEN`",
+    },
+    Opt {
+        name: "allowed-client-ips",
+        value: Some("NETS"),
+        help: "Comma-separated IP networks allowed to connect",
+    },
+    Opt {
+        name: "user-agent",
+        value: Some("AGENT"),
+        help: "User agent of outbound requests",
+    },
+    Opt {
+        name: "bootstrap-peers",
+        value: Some("ADDRS"),
+        help: "Comma-separated libp2p bootstrap multiaddrs",
+    },
+    Opt {
+        name: "preconnect-providers",
+        value: Some("ADDRS"),
+        help: "Comma-separated provider multiaddrs to connect at startup",
+    },
+    Opt {
+        name: "disable-ipni",
+        value: None,
+        help: "Don't look up providers in IPNI",
+    },
+    Opt {
+        name: "disable-dht",
+        value: None,
+        help: "Don't look up providers in the DHT",
+    },
+    Opt {
+        name: "disable-candidate-discovery",
+        value: None,
+        help: "Require `providers=` in every request",
+    },
+    Opt {
+        name: "delegated-routing-url",
+        value: Some("URL"),
+        help: "Routing V1 server for provider and IPNS lookups",
+    },
+    Opt {
+        name: "bitswap-concurrency",
+        value: Some("N"),
+        help: "Maximum number of concurrent Bitswap requests",
+    },
+    Opt {
+        name: "max-download-rate",
+        value: Some("SIZE"),
+        help: "Bytes per second accepted from providers by all retrievals, e.g. 10MiB",
+    },
+    Opt {
+        name: "block-cache-dir",
+        value: Some("DIR"),
+        help: "Persistent block cache directory",
+    },
+    Opt {
+        name: "block-cache-max-size",
+        value: Some("SIZE"),
+        help: "Block cache size, e.g. 10GiB (default: 1GiB)",
+    },
+    Opt {
+        name: "admin-port",
+        value: Some("PORT"),
+        help: "Admin listener port on 127.0.0.1",
+    },
+    Opt {
+        name: "admin-socket",
+        value: Some("PATH"),
+        help: "Admin listener Unix socket",
+    },
+    Opt {
+        name: "admin-access-token",
+        value: Some("TOKEN"),
+        help: "Access token of the admin listener",
+    },
+    Opt {
+        name: "log-format",
+        value: Some("FORMAT"),
+        help: "Log format of the Go side: text or json",
+    },
+    Opt {
+        name: "log-file",
+        value: Some("PATH"),
+        help: "Write the Go log output to this file",
+    },
+    Opt {
+        name: "event-log",
+        value: Some("PATH"),
+        help: "Append retrieval events as JSON lines",
+    },
+    Opt {
+        name: "event-recorder-url",
+        value: Some("URL"),
+        help: "Push retrieval events to this URL",
+    },
+    Opt {
+        name: "event-recorder-auth",
+        value: Some("AUTH"),
+        help: "Authorization header of the event recorder",
+    },
+    Opt {
+        name: "event-recorder-instance-id",
+        value: Some("ID"),
+        help: "Instance ID reported to the event recorder",
+    },
+    Opt {
+        name: "otlp-endpoint",
+        value: Some("URL"),
+        help: "Export traces to this OTLP/HTTP collector, e.g. http://localhost:4318",
+    },
+    Opt {
+        name: "go-memory-limit",
+        value: Some("SIZE"),
+        help: "Soft memory limit of the Go runtime, e.g. 2GiB",
+    },
+    Opt {
+        name: "shutdown-grace-period",
+        value: Some("DURATION"),
+        help: "Time for running retrievals to finish after SIGTERM or SIGINT (default: 20s)",
+    },
+];
+
+const DEFAULT_BLOCK_CACHE_SIZE: u64 = 1 << 30;
+// Below the default grace periods of systemd (90s) and Kubernetes (30s)
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(20);
+
+/// The options of `rusty-lassie fetch` on top of the daemon options in [`OPTIONS`].
+const FETCH_OPTIONS: &[Opt] = &[
+    Opt {
+        name: "output",
+        value: Some("PATH"),
+        help: "Write the content to this file (short: -o)",
+    },
+    Opt {
+        name: "providers",
+        value: Some("MULTIADDRS"),
+        help: "Comma-separated providers to retrieve from, skipping the candidate discovery",
+    },
+    Opt {
+        name: "format",
+        value: Some("FORMAT"),
+        help: "car (default) for the whole DAG, raw for the bytes of the root block",
+    },
+    Opt {
+        name: "daemon",
+        value: Some("URL"),
+        help: "Use the daemon running at this URL instead of starting one",
+    },
+    Opt {
+        name: "daemon-access-token",
+        value: Some("TOKEN"),
+        help: "Access token of the daemon given by --daemon",
+    },
+];
+
+/// Parse the command-line `args` (without the program name) and the environment variables
+/// returned by `env`. Flags take precedence over environment variables, which take precedence
+/// over the configuration file given by `--config`.
+pub fn parse<I, E>(args: I, env: E) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+    E: Fn(&str) -> Option<String>,
+{
+    let mut args = args.into_iter().peekable();
+    let mut fetch = args.next_if(|arg| arg == "fetch").map(|_| Fetch::default());
+    let mut daemon_flags = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(fetch.is_some())),
+            "-V" | "--version" => return Ok(Command::Version),
+            _ => {}
+        }
+        let flag = match (arg.strip_prefix("--"), &mut fetch) {
+            (Some(flag), _) => flag,
+            (None, Some(fetch)) if arg == "-o" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| "-o requires a value <PATH>".to_string())?;
+                fetch.output = Some(path.into());
+                continue;
+            }
+            (None, Some(fetch)) if fetch.root.is_none() && !arg.starts_with('-') => {
+                fetch.root = Some(arg);
+                continue;
+            }
+            (None, _) => return Err(format!("unexpected argument {arg:?}")),
+        };
+        let (name, inline_value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+        let fetch_opt = fetch
+            .as_ref()
+            .and_then(|_| FETCH_OPTIONS.iter().find(|opt| opt.name == name));
+        let opt = fetch_opt
+            .or_else(|| OPTIONS.iter().find(|opt| opt.name == name))
+            .ok_or_else(|| format!("unknown option --{name}"))?;
+        let value = match (opt.value, inline_value) {
+            (Some(_), Some(value)) => value,
+            (Some(placeholder), None) => args
+                .next()
+                .ok_or_else(|| format!("--{name} requires a value <{placeholder}>"))?,
+            (None, Some(_)) => return Err(format!("--{name} does not take a value")),
+            (None, None) => "true".to_string(),
+        };
+        if let (Some(_), Some(fetch)) = (fetch_opt, &mut fetch) {
+            fetch
+                .apply(name, value)
+                .map_err(|err| format!("--{name}: {err}"))?;
+        } else {
+            daemon_flags.push((opt.name, value));
+        }
+    }
+
+    // The configuration file is the base, environment variables override it and flags override
+    // both
+    let mut settings = Settings::default();
+    let config_file = daemon_flags
+        .iter()
+        .rev()
+        .find(|(name, _)| *name == "config")
+        .map(|(_, path)| path.clone())
+        .or_else(|| env(&env_name("config")));
+    if let Some(path) = config_file {
+        settings.config = DaemonConfig::from_file(path).map_err(|err| err.to_string())?;
+    }
+    for opt in OPTIONS {
+        if let Some(value) = env(&env_name(opt.name)) {
+            let value = match opt.value {
+                Some(_) => value,
+                None => parse_bool(&value)
+                    .map_err(|err| format!("{}: {err}", env_name(opt.name)))?
+                    .to_string(),
+            };
+            settings
+                .apply(opt.name, &value)
+                .map_err(|err| format!("{}: {err}", env_name(opt.name)))?;
+        }
+    }
+    for (name, value) in &daemon_flags {
+        settings
+            .apply(name, value)
+            .map_err(|err| format!("--{name}: {err}"))?;
+    }
+
+    let shutdown_grace_period = settings
+        .shutdown_grace_period
+        .unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD);
+    let config = settings.finish()?;
+    match fetch {
+        None => Ok(Command::Daemon(Box::new(DaemonArgs {
+            config,
+            shutdown_grace_period,
+        }))),
+        Some(fetch) => {
+            let daemon_flag = daemon_flags.first().map(|(name, _)| *name);
+            fetch.finish(config, daemon_flag).map(Command::Fetch)
+        }
+    }
+}
+
+/// The help text printed by `--help`, or by `fetch --help` when `fetch` is set.
+pub fn help(fetch: bool) -> String {
+    let mut text = if fetch {
+        String::from(
+            "Usage: rusty-lassie fetch [OPTIONS] -o <PATH> <CID>[/PATH]\n\n\
+             Retrieve a CID, /ipfs/ or /ipns/ path and write the response to a file. The command\n\
+             starts a daemon for the retrieval unless --daemon is given, the daemon options and\n\
+             LASSIE_<NAME> environment variables of `rusty-lassie --help` apply to it.\n\n\
+             Options:\n",
+        )
+    } else {
+        String::from(
+            "Usage: rusty-lassie [OPTIONS]\n       rusty-lassie fetch [OPTIONS] -o <PATH> <CID>[/PATH]\n\n\
+             Run the Lassie HTTP daemon until interrupted. Every option can be also set via the\n\
+             environment variable LASSIE_<NAME>, e.g. LASSIE_PORT=8080, flags take precedence.\n\
+             The configuration file uses the field names of lassie::DaemonConfig as keys.\n\
+             Under systemd socket activation, the daemon serves on the socket passed in LISTEN_FDS.\n\
+             Run `rusty-lassie fetch --help` for the options of the fetch command.\n\n\
+             Options:\n",
+        )
+    };
+    let options = if fetch { FETCH_OPTIONS } else { OPTIONS };
+    let column = OPTIONS
+        .iter()
+        .chain(FETCH_OPTIONS)
+        .map(|opt| usage(opt).len())
+        .max()
+        .unwrap_or_default();
+    for opt in options {
+        let _ = writeln!(text, "  {:column$}  {}", usage(opt), opt.help);
+    }
+    let _ = writeln!(text, "  {:column$}  Print this help", "-h, --help");
+    let _ = writeln!(text, "  {:column$}  Print the version", "-V, --version");
+    text
+}
+
+fn usage(opt: &Opt) -> String {
+    match opt.value {
+        Some(placeholder) => format!("--{} <{placeholder}>", opt.name),
+        None => format!("--{}", opt.name),
+    }
+}
+
+fn env_name(name: &str) -> String {
+    format!("LASSIE_{}", name.replace('-', "_").to_uppercase())
+}
+
+/// Options that need more than one flag to build a `DaemonConfig` value.
+#[derive(Default)]
+struct Settings {
+    config: DaemonConfig,
+    block_cache_dir: Option<PathBuf>,
+    block_cache_max_size: Option<u64>,
+    admin_address: Option<AdminAddress>,
+    admin_access_token: Option<String>,
+    shutdown_grace_period: Option<Duration>,
+}
+
+impl Settings {
+    fn apply(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let config = &mut self.config;
+        match name {
+            // Loaded before all other options, see `parse`
+            "config" => {}
+            "port" => config.port = parse_number(value)?,
+            "reuse-port" => config.reuse_port = parse_bool(value)?,
+            "auto-restart" => config.auto_restart = parse_bool(value)?,
+            "watchdog-interval" => {
+                config
+                    .watchdog
+                    .get_or_insert_with(lassie::WatchdogConfig::default)
+                    .interval = parse_duration(value)?;
+            }
+            "temp-dir" => config.temp_dir = Some(value.into()),
+            "create-temp-dir" => config.create_temp_dir = parse_bool(value)?,
+            "private-temp-dir" => config.private_temp_dir = parse_bool(value)?,
+            "max-blocks" => config.max_blocks = Some(parse_number(value)?),
+            "max-concurrent-requests" => {
+                config.max_concurrent_requests = Some(parse_number(value)?);
+            }
+            "max-concurrent-retrievals" => {
+                config.max_concurrent_retrievals = Some(parse_number(value)?);
+            }
+            "provider-timeout" => config.provider_timeout = Some(parse_duration(value)?),
+            "http-provider-timeout" => {
+                config.http_provider_timeout = Some(parse_duration(value)?);
+            }
+            "bitswap-provider-timeout" => {
+                config.bitswap_provider_timeout = Some(parse_duration(value)?);
+            }
+            "global-timeout" => config.global_timeout = Some(parse_duration(value)?),
+            "startup-timeout" => config.startup_timeout = Some(parse_duration(value)?),
+            "access-token" => config.access_token = Some(value.to_string()),
+            "allowed-client-ips" => {
+                config.allowed_client_ips = Some(
+                    split_list(value)
+                        .map(|net| net.parse().map_err(|err| format!("{net:?}: {err}")))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+            "user-agent" => config.user_agent = Some(value.to_string()),
+            "bootstrap-peers" => {
+                config.bootstrap_peers = Some(parse_multiaddrs(value)?);
+            }
+            "preconnect-providers" => {
+                config.preconnect_providers = parse_multiaddrs(value)?;
+            }
+            "disable-ipni" => config.disable_ipni = parse_bool(value)?,
+            "disable-dht" => config.disable_dht = parse_bool(value)?,
+            "disable-candidate-discovery" => {
+                config.disable_candidate_discovery = parse_bool(value)?;
+            }
+            "delegated-routing-url" => config.delegated_routing_url = Some(value.to_string()),
+            "bitswap-concurrency" => config.bitswap_concurrency = Some(parse_number(value)?),
+            "max-download-rate" => config.max_download_rate = Some(parse_size(value)?),
+            "block-cache-dir" => self.block_cache_dir = Some(value.into()),
+            "block-cache-max-size" => self.block_cache_max_size = Some(parse_size(value)?),
+            "admin-port" => self.admin_address = Some(AdminAddress::Port(parse_number(value)?)),
+            "admin-socket" => self.admin_address = Some(AdminAddress::UnixSocket(value.into())),
+            "admin-access-token" => self.admin_access_token = Some(value.to_string()),
+            "shutdown-grace-period" => self.shutdown_grace_period = Some(parse_duration(value)?),
+            "log-format" => {
+                config.log_format = match value {
+                    "text" => LogFormat::Text,
+                    "json" => LogFormat::Json,
+                    _ => return Err(format!("expected text or json, found {value:?}")),
+                };
+            }
+            "log-file" => config.log_file = Some(value.into()),
+            "event-log" => config.event_log = Some(value.into()),
+            "event-recorder-url" => config.event_recorder_url = Some(value.to_string()),
+            "event-recorder-auth" => config.event_recorder_auth = Some(value.to_string()),
+            "event-recorder-instance-id" => {
+                config.event_recorder_instance_id = Some(value.to_string());
+            }
+            "otlp-endpoint" => {
+                config.otlp = Some(OtlpConfig {
+                    endpoint: value.to_string(),
+                    service_name: config.otlp.take().and_then(|otlp| otlp.service_name),
+                });
+            }
+            "go-memory-limit" => config.go_memory_limit = Some(parse_size(value)?),
+            _ => unreachable!("option --{name} is listed in OPTIONS but not handled"),
+        }
+        Ok(())
+    }
+
+    /// Build the nested configs, keeping the values of the configuration file that no option
+    /// overrides.
+    fn finish(self) -> Result<DaemonConfig, String> {
+        let mut config = self.config;
+        if let Some(dir) = self.block_cache_dir {
+            let max_size = config
+                .block_cache
+                .as_ref()
+                .map_or(DEFAULT_BLOCK_CACHE_SIZE, |cache| cache.max_size);
+            config.block_cache = Some(BlockCacheConfig { dir, max_size });
+        }
+        if let Some(max_size) = self.block_cache_max_size {
+            config
+                .block_cache
+                .as_mut()
+                .ok_or("--block-cache-max-size requires --block-cache-dir")?
+                .max_size = max_size;
+        }
+        if let Some(address) = self.admin_address {
+            match &mut config.admin_listener {
+                Some(admin) => admin.address = address,
+                None => {
+                    config.admin_listener = Some(AdminListenerConfig {
+                        address,
+                        access_token: None,
+                        pprof: false,
+                    });
+                }
+            }
+        }
+        if let Some(access_token) = self.admin_access_token {
+            config
+                .admin_listener
+                .as_mut()
+                .ok_or("--admin-access-token requires --admin-port or --admin-socket")?
+                .access_token = Some(access_token);
+        }
+        Ok(config)
+    }
+}
+
+/// The `fetch` arguments collected before building the request.
+#[derive(Default)]
+struct Fetch {
+    root: Option<String>,
+    output: Option<PathBuf>,
+    providers: Vec<Multiaddr>,
+    raw: bool,
+    daemon_url: Option<String>,
+    daemon_access_token: Option<String>,
+}
+
+impl Fetch {
+    fn apply(&mut self, name: &str, value: String) -> Result<(), String> {
+        match name {
+            "output" => self.output = Some(value.into()),
+            "providers" => {
+                self.providers = parse_multiaddrs(&value)?;
+            }
+            "format" => {
+                self.raw = match value.as_str() {
+                    "car" => false,
+                    "raw" => true,
+                    _ => return Err(format!("expected car or raw, found {value:?}")),
+                };
+            }
+            "daemon" => self.daemon_url = Some(value),
+            "daemon-access-token" => self.daemon_access_token = Some(value),
+            _ => unreachable!("option --{name} is listed in FETCH_OPTIONS but not handled"),
+        }
+        Ok(())
+    }
+
+    /// Build the arguments, `daemon_flag` is the first daemon option given on the command line.
+    fn finish(
+        self,
+        config: DaemonConfig,
+        daemon_flag: Option<&str>,
+    ) -> Result<Box<FetchArgs>, String> {
+        let root = self
+            .root
+            .ok_or_else(|| "missing the CID to fetch".to_string())?;
+        let output = self
+            .output
+            .ok_or_else(|| "missing the output file, use -o <PATH>".to_string())?;
+        if let (Some(_), Some(name)) = (&self.daemon_url, daemon_flag) {
+            return Err(format!(
+                "--{name} configures the daemon started by fetch and cannot be used with --daemon"
+            ));
+        }
+        if self.daemon_access_token.is_some() && self.daemon_url.is_none() {
+            return Err("--daemon-access-token requires --daemon".to_string());
+        }
+
+        let mut request = parse_root(&root)?.providers(self.providers);
+        if self.raw {
+            request = request.format(Format::RawBlock).dag_scope(DagScope::Block);
+        }
+        Ok(Box::new(FetchArgs {
+            request,
+            output,
+            daemon_url: self.daemon_url,
+            daemon_access_token: self.daemon_access_token,
+            config,
+        }))
+    }
+}
+
+/// Parse `<cid>[/path]`, `/ipfs/<cid>[/path]` or `/ipns/<name>[/path]`.
+fn parse_root(root: &str) -> Result<RetrievalRequest, String> {
+    let (ipns, root) = match (root.strip_prefix("/ipns/"), root.strip_prefix("/ipfs/")) {
+        (Some(name), _) => (true, name),
+        (None, Some(cid)) => (false, cid),
+        (None, None) => (false, root),
+    };
+    let (name, path) = root.split_once('/').unwrap_or((root, ""));
+    if name.is_empty() {
+        return Err("missing the CID to fetch".to_string());
+    }
+    let request = if ipns {
+        RetrievalRequest::ipns(name.parse::<IpnsName>().map_err(|err| err.to_string())?)
+    } else {
+        RetrievalRequest::new(name)
+    };
+    Ok(request.sub_path(path))
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn parse_multiaddrs(value: &str) -> Result<Vec<Multiaddr>, String> {
+    split_list(value)
+        .map(|addr| addr.parse::<Multiaddr>().map_err(|err| err.to_string()))
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a non-negative integer, found {value:?}"))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err(format!("expected true or false, found {value:?}")),
+    }
+}
+
+/// Parse `500ms`, `30s`, `5m` or `2h`, plain numbers are seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("expected a duration like 30s or 5m, found {value:?}"))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 60 * 60)),
+        _ => Err(format!(
+            "unknown duration unit {unit:?} in {value:?}, use ms, s, m or h"
+        )),
+    }
+}
+
+/// Parse a number of bytes with an optional `KiB`, `MiB`, `GiB` or `TiB` suffix.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("expected a size like 512MiB, found {value:?}"))?;
+    let shift = match unit {
+        "" | "B" => 0,
+        "KiB" => 10,
+        "MiB" => 20,
+        "GiB" => 30,
+        "TiB" => 40,
+        _ => {
+            return Err(format!(
+                "unknown size unit {unit:?} in {value:?}, use KiB, MiB, GiB or TiB"
+            ))
+        }
+    };
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size {value:?} is too large"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const PEER_A: &str = "12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz";
+    const PEER_B: &str = "QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN";
+
+    fn parse_config(args: &[&str], env: &[(&str, &str)]) -> Result<DaemonConfig, String> {
+        let args = args.iter().map(ToString::to_string);
+        let env = |name: &str| {
+            env.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        };
+        match parse(args, env)? {
+            Command::Daemon(args) => Ok(args.config),
+            other => panic!("unexpected command {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_flags_and_env() {
+        let bootstrap_peers =
+            format!("/ip4/1.2.3.4/tcp/4001/p2p/{PEER_A}, /ip4/5.6.7.8/tcp/4001/p2p/{PEER_B}");
+        let config = parse_config(
+            &[
+                "--port",
+                "8080",
+                "--provider-timeout=20s",
+                "--http-provider-timeout=10s",
+                "--disable-dht",
+                "--reuse-port",
+                "--auto-restart",
+                "--watchdog-interval=1m",
+                "--max-download-rate=10MiB",
+                "--max-concurrent-retrievals",
+                "8",
+                "--bootstrap-peers",
+                &bootstrap_peers,
+                "--block-cache-dir",
+                "/var/cache/lassie",
+            ],
+            &[
+                ("LASSIE_PORT", "9090"),
+                ("LASSIE_GLOBAL_TIMEOUT", "5m"),
+                ("LASSIE_BITSWAP_PROVIDER_TIMEOUT", "15s"),
+                ("LASSIE_STARTUP_TIMEOUT", "30s"),
+                ("LASSIE_DISABLE_IPNI", "true"),
+                ("LASSIE_CREATE_TEMP_DIR", "1"),
+                ("LASSIE_LOG_FORMAT", "json"),
+                ("LASSIE_OTLP_ENDPOINT", "http://localhost:4318"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 8080, "flags take precedence");
+        assert_eq!(config.provider_timeout, Some(Duration::from_secs(20)));
+        assert_eq!(config.http_provider_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(
+            config.bitswap_provider_timeout,
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(config.global_timeout, Some(Duration::from_secs(300)));
+        assert_eq!(config.startup_timeout, Some(Duration::from_secs(30)));
+        assert!(config.disable_ipni);
+        assert!(config.disable_dht);
+        assert!(config.reuse_port);
+        assert!(config.create_temp_dir);
+        assert!(config.auto_restart);
+        assert_eq!(
+            config.otlp.map(|otlp| otlp.endpoint),
+            Some("http://localhost:4318".to_string())
+        );
+        assert_eq!(
+            config.watchdog.map(|watchdog| watchdog.interval),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(config.max_download_rate, Some(10 << 20));
+        assert_eq!(config.max_concurrent_retrievals, Some(8));
+        assert_eq!(
+            config.bootstrap_peers,
+            Some(vec![
+                format!("/ip4/1.2.3.4/tcp/4001/p2p/{PEER_A}")
+                    .parse()
+                    .unwrap(),
+                format!("/ip4/5.6.7.8/tcp/4001/p2p/{PEER_B}")
+                    .parse()
+                    .unwrap(),
+            ])
+        );
+        assert_eq!(
+            config.block_cache,
+            Some(BlockCacheConfig {
+                dir: "/var/cache/lassie".into(),
+                max_size: DEFAULT_BLOCK_CACHE_SIZE,
+            })
+        );
+        assert_eq!(config.log_format, LogFormat::Json);
+    }
+
+    #[test]
+    fn layers_config_file_env_and_flags() {
+        let path = std::env::temp_dir().join(format!("rusty-lassie-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+            port = 9090
+            provider_timeout = "20s"
+            global_timeout = "1m"
+
+            [block_cache]
+            dir = "/var/cache/lassie"
+            max_size = "2GiB"
+
+            [admin_listener]
+            address = { port = 9191 }
+            pprof = true
+
+            [otlp]
+            endpoint = "http://collector.internal:4318"
+            service_name = "indexer"
+            "#,
+        )
+        .unwrap();
+        let config = parse_config(
+            &[
+                "--port",
+                "8080",
+                "--block-cache-dir",
+                "/tmp/lassie-cache",
+                "--admin-access-token",
+                "secret",
+                "--otlp-endpoint",
+                "http://localhost:4318",
+            ],
+            &[
+                ("LASSIE_CONFIG", path.to_str().unwrap()),
+                ("LASSIE_GLOBAL_TIMEOUT", "5m"),
+            ],
+        );
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.provider_timeout, Some(Duration::from_secs(20)));
+        assert_eq!(config.global_timeout, Some(Duration::from_secs(300)));
+        assert_eq!(
+            config.block_cache,
+            Some(BlockCacheConfig {
+                dir: "/tmp/lassie-cache".into(),
+                max_size: 2 << 30,
+            })
+        );
+        assert_eq!(
+            config.admin_listener,
+            Some(AdminListenerConfig {
+                address: AdminAddress::Port(9191),
+                access_token: Some("secret".to_string()),
+                pprof: true,
+            })
+        );
+        assert_eq!(
+            config.otlp,
+            Some(OtlpConfig {
+                endpoint: "http://localhost:4318".to_string(),
+                service_name: Some("indexer".to_string()),
+            })
+        );
+
+        let err = parse_config(&["--config", "/nonexistent/lassie.toml"], &[]).unwrap_err();
+        assert!(
+            err.starts_with("cannot read Lassie configuration file"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let err = |args: &[&str]| parse_config(args, &[]).unwrap_err();
+        assert_eq!(err(&["--nope"]), "unknown option --nope");
+        assert_eq!(err(&["--port"]), "--port requires a value <PORT>");
+        assert_eq!(
+            err(&["--disable-dht=yes"]),
+            "--disable-dht does not take a value"
+        );
+        assert_eq!(err(&["serve"]), "unexpected argument \"serve\"");
+        assert_eq!(
+            err(&["--global-timeout", "5d"]),
+            "--global-timeout: unknown duration unit \"d\" in \"5d\", use ms, s, m or h"
+        );
+        assert_eq!(
+            err(&["--block-cache-max-size", "1GiB"]),
+            "--block-cache-max-size requires --block-cache-dir"
+        );
+        assert_eq!(
+            parse_config(&[], &[("LASSIE_DISABLE_DHT", "maybe")]).unwrap_err(),
+            "LASSIE_DISABLE_DHT: expected true or false, found \"maybe\""
+        );
+    }
+
+    fn parse_fetch(args: &[&str]) -> Result<FetchArgs, String> {
+        let args = ["fetch"].iter().chain(args).map(ToString::to_string);
+        match parse(args, |name| {
+            (name == "LASSIE_DISABLE_DHT").then(|| "1".to_string())
+        })? {
+            Command::Fetch(fetch) => Ok(*fetch),
+            other => panic!("unexpected command {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_fetch_arguments() {
+        let fetch = parse_fetch(&[
+            "/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/docs/readme.md",
+            "-o",
+            "out.car",
+            "--providers=/dns4/frisbii.fly.dev/https",
+            "--provider-timeout",
+            "5s",
+        ])
+        .unwrap();
+        assert_eq!(
+            fetch.request,
+            RetrievalRequest::new("bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi")
+                .sub_path("docs/readme.md")
+                .providers(["/dns4/frisbii.fly.dev/https".parse().unwrap()])
+        );
+        assert_eq!(fetch.output, PathBuf::from("out.car"));
+        assert_eq!(fetch.daemon_url, None);
+        assert_eq!(fetch.config.provider_timeout, Some(Duration::from_secs(5)));
+        assert!(fetch.config.disable_dht, "env vars configure the daemon");
+
+        let fetch = parse_fetch(&[
+            "bafkqaaa",
+            "--output=block.raw",
+            "--format",
+            "raw",
+            "--daemon",
+            "http://127.0.0.1:8080",
+        ])
+        .unwrap();
+        assert_eq!(
+            fetch.request,
+            RetrievalRequest::new("bafkqaaa")
+                .format(Format::RawBlock)
+                .dag_scope(DagScope::Block)
+        );
+        assert_eq!(fetch.daemon_url.as_deref(), Some("http://127.0.0.1:8080"));
+
+        let err = |args: &[&str]| parse_fetch(args).unwrap_err();
+        assert_eq!(err(&["-o", "out.car"]), "missing the CID to fetch");
+        assert_eq!(err(&["bafkqaaa"]), "missing the output file, use -o <PATH>");
+        assert_eq!(
+            err(&["bafkqaaa", "bafkqaaa", "-o", "out.car"]),
+            "unexpected argument \"bafkqaaa\""
+        );
+        assert_eq!(
+            err(&["bafkqaaa", "-o", "out.car", "--format", "tar"]),
+            "--format: expected car or raw, found \"tar\""
+        );
+        assert_eq!(
+            err(&["bafkqaaa", "-o", "x", "--daemon", "http://h", "--port", "1"]),
+            "--port configures the daemon started by fetch and cannot be used with --daemon"
+        );
+        assert_eq!(
+            parse_config(&["--format", "raw"], &[]).unwrap_err(),
+            "unknown option --format"
+        );
+    }
+
+    #[test]
+    fn parses_sizes_and_durations() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn parses_shutdown_grace_period() {
+        let grace_period =
+            |args: &[&str]| match parse(args.iter().map(ToString::to_string), |_| None) {
+                Ok(Command::Daemon(args)) => args.shutdown_grace_period,
+                other => panic!("unexpected result {other:?}"),
+            };
+        assert_eq!(grace_period(&[]), DEFAULT_SHUTDOWN_GRACE_PERIOD);
+        assert_eq!(
+            grace_period(&["--shutdown-grace-period", "1m"]),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn handles_help_and_version() {
+        let parse = |arg: &str| parse([arg.to_string()], |_| None).unwrap();
+        assert!(matches!(parse("--help"), Command::Help(false)));
+        assert!(matches!(parse("-V"), Command::Version));
+        assert!(help(false).contains("--provider-timeout <DURATION>"));
+
+        let fetch = ["fetch".to_string(), "-h".to_string()];
+        assert!(matches!(
+            super::parse(fetch, |_| None),
+            Ok(Command::Help(true))
+        ));
+        assert!(help(true).contains("--format <FORMAT>"));
+    }
+}
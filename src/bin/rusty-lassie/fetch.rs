@@ -0,0 +1,129 @@
+//! `rusty-lassie fetch`, retrieving a single CID into a file.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::time::{Duration, Instant};
+
+use lassie::{Client, Daemon, RetrievalError};
+
+use crate::args::FetchArgs;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Retrieve the content and write it to the output file, removing the file on failure.
+pub fn run(args: FetchArgs) -> Result<(), String> {
+    let FetchArgs {
+        request,
+        output,
+        daemon_url,
+        daemon_access_token,
+        config,
+    } = args;
+
+    // The ephemeral daemon must outlive the response
+    let (client, _daemon) = if let Some(url) = daemon_url {
+        (Client::from_url(&url, daemon_access_token), None)
+    } else {
+        let daemon = Daemon::start(config).map_err(|err| format!("cannot start Lassie: {err}"))?;
+        (Client::new(&daemon), Some(daemon))
+    };
+
+    eprintln!("Fetching {}", request.path());
+    let started = Instant::now();
+    let response = client.fetch(&request).map_err(|err| err.to_string())?;
+    if let Some(id) = response.retrieval_id() {
+        log::info!("Retrieval ID: {id}");
+    }
+
+    let file = File::create(&output)
+        .map_err(|err| format!("cannot create {}: {err}", output.display()))?;
+    let written = copy_with_progress(response.into_reader(), BufWriter::new(file), started)
+        .map_err(|err| {
+            let _ = fs::remove_file(&output);
+            match err {
+                CopyError::Read(err) => RetrievalError::from_stream_error(&err).to_string(),
+                CopyError::Write(err) => format!("cannot write {}: {err}", output.display()),
+            }
+        })?;
+
+    eprintln!(
+        "Wrote {} to {} in {:.1}s",
+        format_bytes(written),
+        output.display(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+enum CopyError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+/// Copy `reader` into `writer`, redrawing the progress line on stderr when it's a terminal.
+fn copy_with_progress(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    started: Instant,
+) -> Result<u64, CopyError> {
+    let show_progress = io::stderr().is_terminal();
+    let mut buf = vec![0; 64 * 1024];
+    let mut written = 0u64;
+    let mut last_report = Instant::now();
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(CopyError::Read(err)),
+        };
+        writer.write_all(&buf[..n]).map_err(CopyError::Write)?;
+        written += n as u64;
+
+        if show_progress && last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let elapsed = started.elapsed().as_millis().max(1);
+            let rate = u64::try_from(u128::from(written) * 1000 / elapsed).unwrap_or(u64::MAX);
+            eprint!(
+                "\r{} received ({}/s)\x1b[K",
+                format_bytes(written),
+                format_bytes(rate)
+            );
+        }
+    }
+    writer.flush().map_err(CopyError::Write)?;
+    if show_progress {
+        // Clear the progress line
+        eprint!("\r\x1b[K");
+    }
+    Ok(written)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 << 30), "5.0 GiB");
+        assert_eq!(format_bytes(u64::MAX), "16777216.0 TiB");
+    }
+}
@@ -0,0 +1,107 @@
+//! The Lassie HTTP daemon as a standalone program and a one-shot `fetch` command, see
+//! `rusty-lassie --help`.
+
+use std::process::ExitCode;
+use std::sync::mpsc;
+
+use lassie::Daemon;
+
+mod args;
+mod fetch;
+
+use args::{Command, DaemonArgs};
+
+fn main() -> ExitCode {
+    env_logger::init();
+
+    let args = match args::parse(std::env::args().skip(1), |name| std::env::var(name).ok()) {
+        Ok(Command::Daemon(args)) => *args,
+        Ok(Command::Fetch(args)) => {
+            return match fetch::run(*args) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Ok(Command::Help(fetch)) => {
+            print!("{}", args::help(fetch));
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Version) => {
+            println!(
+                "rusty-lassie {} (Lassie {})",
+                env!("CARGO_PKG_VERSION"),
+                env!("LASSIE_VERSION")
+            );
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\nRun `rusty-lassie --help` to see the available options.");
+            return ExitCode::from(2);
+        }
+    };
+
+    let (signal_tx, signal_rx) = mpsc::channel();
+    if let Err(err) = ctrlc::set_handler(move || {
+        let _ = signal_tx.send(());
+    }) {
+        eprintln!("error: cannot install the signal handler: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    #[allow(unused_mut)]
+    let DaemonArgs {
+        mut config,
+        shutdown_grace_period,
+    } = args;
+    #[cfg(unix)]
+    let socket_activated = if let Some(fd) = lassie::systemd_listen_fd() {
+        if config.port != 0 {
+            eprintln!("error: --port cannot be combined with systemd socket activation");
+            return ExitCode::from(2);
+        }
+        config.listener_fd = Some(fd);
+        true
+    } else {
+        false
+    };
+    #[cfg(not(unix))]
+    let socket_activated = false;
+    let daemon = match Daemon::start(config) {
+        Ok(daemon) => daemon,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if daemon.port() > 0 {
+        println!("Lassie is listening on http://127.0.0.1:{}", daemon.port());
+    } else if socket_activated {
+        println!("Lassie is listening on the socket passed by systemd");
+    }
+    if let Some(port) = daemon.admin_port() {
+        println!("Lassie admin listener is listening on http://127.0.0.1:{port}");
+    }
+
+    // Wait for SIGINT or SIGTERM
+    let _ = signal_rx.recv();
+    eprintln!(
+        "Stopping Lassie, waiting up to {shutdown_grace_period:?} for {} running retrievals to finish",
+        daemon.active_retrievals().len()
+    );
+    std::thread::spawn(move || {
+        if signal_rx.recv().is_ok() {
+            eprintln!("Received another signal, exiting without waiting for the retrievals");
+            std::process::exit(1);
+        }
+    });
+    match daemon.shutdown_gracefully(shutdown_grace_period) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
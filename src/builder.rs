@@ -0,0 +1,305 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::multiaddr::Multiaddr;
+use crate::{
+    AdminListenerConfig, BlockCacheConfig, CircuitBreakerConfig, ConfigError,
+    ConnectionManagerConfig, Daemon, DaemonConfig, ExtraListenerConfig, IpNet, Libp2pTransport,
+    LogFormat, OtlpConfig, OutboundHttpConfig, StartError, TempDirEvictionConfig, WatchdogConfig,
+};
+
+/// Setters storing the value as-is.
+macro_rules! set {
+    ($( $field:ident: $ty:ty ),* $(,)?) => {
+        $(
+            #[doc = concat!("See [`DaemonConfig::", stringify!($field), "`].")]
+            #[must_use]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.config.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+/// Setters of the optional values.
+macro_rules! set_some {
+    ($( $field:ident: $ty:ty ),* $(,)?) => {
+        $(
+            #[doc = concat!("See [`DaemonConfig::", stringify!($field), "`].")]
+            #[must_use]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.config.$field = Some($field);
+                self
+            }
+        )*
+    };
+}
+
+/// A fluent way to configure and start the daemon, see [`Daemon::builder`].
+///
+/// ```no_run
+/// # fn main() -> Result<(), lassie::StartError> {
+/// let daemon = lassie::Daemon::builder()
+///     .port(3000)
+///     .max_blocks(10_000)
+///     .access_token("secret")
+///     .start()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Every setter corresponds to a [`DaemonConfig`] field and the fields not set keep their
+/// defaults. Unlike [`Daemon::start`], [`DaemonBuilder::start`] checks the whole configuration
+/// with [`DaemonConfig::validate`] first.
+#[derive(Debug, Clone, Default)]
+pub struct DaemonBuilder {
+    config: DaemonConfig,
+}
+
+impl DaemonBuilder {
+    /// Start from an existing configuration, e.g. one loaded from a file.
+    #[must_use]
+    pub fn from_config(config: DaemonConfig) -> Self {
+        DaemonBuilder { config }
+    }
+
+    set! {
+        create_temp_dir: bool,
+        private_temp_dir: bool,
+        port: u16,
+        disable_listener: bool,
+        reuse_port: bool,
+        auto_restart: bool,
+        disable_ipni: bool,
+        disable_dht: bool,
+        disable_candidate_discovery: bool,
+        log_format: LogFormat,
+        outbound_http: OutboundHttpConfig,
+    }
+
+    set_some! {
+        temp_dir_eviction: TempDirEvictionConfig,
+        max_blocks: u64,
+        max_concurrent_requests: u32,
+        max_concurrent_retrievals: u32,
+        max_queued_retrievals: u32,
+        provider_timeout: Duration,
+        http_provider_timeout: Duration,
+        bitswap_provider_timeout: Duration,
+        graphsync_provider_timeout: Duration,
+        global_timeout: Duration,
+        circuit_breaker: CircuitBreakerConfig,
+        otlp: OtlpConfig,
+        startup_timeout: Duration,
+        idle_shutdown: Duration,
+        connection_manager: ConnectionManagerConfig,
+        bitswap_concurrency: u32,
+        bitswap_concurrency_per_retrieval: u32,
+        bitswap_keep_alive: Duration,
+        block_cache: BlockCacheConfig,
+        mmap_car_store: u64,
+        max_download_rate: u64,
+        max_download_rate_per_retrieval: u64,
+        admin_listener: AdminListenerConfig,
+        go_memory_limit: u64,
+        go_max_procs: u32,
+        watchdog: WatchdogConfig,
+    }
+
+    /// See [`DaemonConfig::listener_fd`].
+    #[cfg(unix)]
+    #[must_use]
+    pub fn listener_fd(mut self, fd: std::os::fd::RawFd) -> Self {
+        self.config.listener_fd = Some(fd);
+        self
+    }
+
+    /// See [`DaemonConfig::temp_dir`].
+    #[must_use]
+    pub fn temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.temp_dir = Some(dir.into());
+        self
+    }
+
+    /// See [`DaemonConfig::log_file`].
+    #[must_use]
+    pub fn log_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.log_file = Some(path.into());
+        self
+    }
+
+    /// See [`DaemonConfig::event_log`].
+    #[must_use]
+    pub fn event_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.event_log = Some(path.into());
+        self
+    }
+
+    /// See [`DaemonConfig::access_token`].
+    #[must_use]
+    pub fn access_token(mut self, token: impl Into<String>) -> Self {
+        self.config.access_token = Some(token.into());
+        self
+    }
+
+    /// See [`DaemonConfig::user_agent`].
+    #[must_use]
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.config.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// See [`DaemonConfig::delegated_routing_url`].
+    #[must_use]
+    pub fn delegated_routing_url(mut self, url: impl Into<String>) -> Self {
+        self.config.delegated_routing_url = Some(url.into());
+        self
+    }
+
+    /// Report the retrieval events to an event recorder, see
+    /// [`DaemonConfig::event_recorder_url`], [`DaemonConfig::event_recorder_auth`] and
+    /// [`DaemonConfig::event_recorder_instance_id`].
+    #[must_use]
+    pub fn event_recorder(
+        mut self,
+        url: impl Into<String>,
+        auth: Option<String>,
+        instance_id: Option<String>,
+    ) -> Self {
+        self.config.event_recorder_url = Some(url.into());
+        self.config.event_recorder_auth = auth;
+        self.config.event_recorder_instance_id = instance_id;
+        self
+    }
+
+    /// See [`DaemonConfig::allowed_client_ips`].
+    #[must_use]
+    pub fn allowed_client_ips(mut self, ips: impl IntoIterator<Item = IpNet>) -> Self {
+        self.config.allowed_client_ips = Some(ips.into_iter().collect());
+        self
+    }
+
+    /// See [`DaemonConfig::bootstrap_peers`].
+    #[must_use]
+    pub fn bootstrap_peers(mut self, peers: impl IntoIterator<Item = Multiaddr>) -> Self {
+        self.config.bootstrap_peers = Some(peers.into_iter().collect());
+        self
+    }
+
+    /// See [`DaemonConfig::preconnect_providers`].
+    #[must_use]
+    pub fn preconnect_providers(mut self, providers: impl IntoIterator<Item = Multiaddr>) -> Self {
+        self.config.preconnect_providers = providers.into_iter().collect();
+        self
+    }
+
+    /// See [`DaemonConfig::extra_root_certs`].
+    #[must_use]
+    pub fn extra_root_certs<I, P>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config.extra_root_certs = Some(files.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// See [`DaemonConfig::extra_listeners`].
+    #[must_use]
+    pub fn extra_listener(mut self, listener: ExtraListenerConfig) -> Self {
+        self.config.extra_listeners.push(listener);
+        self
+    }
+
+    /// See [`DaemonConfig::libp2p_transports`].
+    #[must_use]
+    pub fn libp2p_transports(
+        mut self,
+        transports: impl IntoIterator<Item = Libp2pTransport>,
+    ) -> Self {
+        self.config.libp2p_transports = Some(transports.into_iter().collect());
+        self
+    }
+
+    /// The configuration assembled so far.
+    #[must_use]
+    pub fn config(&self) -> &DaemonConfig {
+        &self.config
+    }
+
+    /// Check the configuration and return it, e.g. to start a [`crate::LazyDaemon`].
+    ///
+    /// # Errors
+    ///
+    /// This function returns all problems reported by [`DaemonConfig::validate`].
+    pub fn build(self) -> Result<DaemonConfig, Vec<ConfigError>> {
+        let errors = self.config.validate();
+        if errors.is_empty() {
+            Ok(self.config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check the configuration and start the daemon.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`StartError::InvalidConfig`] when [`DaemonConfig::validate`]
+    /// reports a problem, and the errors of [`Daemon::start`] otherwise.
+    pub fn start(self) -> Result<Daemon, StartError> {
+        let config = self.build().map_err(StartError::InvalidConfig)?;
+        Daemon::start(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn builds_config() {
+        let peer: Multiaddr =
+            "/ip4/1.2.3.4/tcp/4001/p2p/12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz"
+                .parse()
+                .unwrap();
+        let config = DaemonBuilder::default()
+            .port(3000)
+            .max_blocks(10_000)
+            .access_token("t")
+            .disable_dht(true)
+            .bootstrap_peers([peer.clone()])
+            .build()
+            .unwrap();
+        assert_eq!(config.port, 3000);
+        assert_eq!(config.max_blocks, Some(10_000));
+        assert_eq!(config.access_token.as_deref(), Some("t"));
+        assert!(config.disable_dht);
+        assert_eq!(config.bootstrap_peers, Some(vec![peer]));
+        assert!(!config.disable_ipni, "fields not set keep their defaults");
+    }
+
+    #[test]
+    fn validates_config() {
+        let builder = DaemonBuilder::default()
+            .port(3000)
+            .disable_listener(true)
+            .mmap_car_store(0);
+        assert_eq!(
+            builder.clone().build().unwrap_err(),
+            vec![
+                ConfigError::PortWithDisabledListener(3000),
+                ConfigError::EmptyMmapCarStore,
+            ]
+        );
+        assert_eq!(
+            builder.start().err(),
+            Some(StartError::InvalidConfig(vec![
+                ConfigError::PortWithDisabledListener(3000),
+                ConfigError::EmptyMmapCarStore,
+            ]))
+        );
+    }
+}
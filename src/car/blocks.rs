@@ -0,0 +1,123 @@
+use std::io::Read;
+
+use super::reader::CarReader;
+use super::{CarError, Cid};
+
+/// An iterator over the blocks of a `CARv1` stream, e.g. the body of a Lassie response.
+///
+/// The reader parses the header when created and then reads one block at a time, so it never
+/// holds more than a single block in memory. It does not verify the blocks, use
+/// [`verify`](super::verify) for that.
+///
+/// ```no_run
+/// use lassie::car::BlockReader;
+///
+/// # fn run(response: impl std::io::Read) -> Result<(), lassie::car::CarError> {
+/// let mut blocks = BlockReader::new(response)?;
+/// println!("roots: {:?}", blocks.roots());
+/// for block in blocks {
+///     let (cid, data) = block?;
+///     println!("{cid}: {} bytes", data.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The iterator stops after yielding the first error.
+pub struct BlockReader<R> {
+    reader: CarReader<R>,
+    failed: bool,
+}
+
+impl<R: Read> BlockReader<R> {
+    /// Read the CAR header from `reader`.
+    ///
+    /// Wrap unbuffered readers like files in a [`BufReader`](std::io::BufReader), the block
+    /// framing is read in small chunks.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` when the header cannot be read or is not a valid `CARv1` header.
+    pub fn new(reader: R) -> Result<Self, CarError> {
+        Ok(BlockReader {
+            reader: CarReader::new(reader)?,
+            failed: false,
+        })
+    }
+
+    /// The root CIDs listed in the CAR header.
+    #[must_use]
+    pub fn roots(&self) -> &[Cid] {
+        self.reader.roots()
+    }
+}
+
+impl<R: Read> Iterator for BlockReader<R> {
+    type Item = Result<(Cid, Vec<u8>), CarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.reader.next_block() {
+            Ok(block) => block.map(|block| Ok((block.cid, block.data))),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<R> std::fmt::Debug for BlockReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockReader").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::writer;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn yields_blocks_in_order() {
+        let root: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .unwrap();
+        let other: Cid = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG"
+            .parse()
+            .unwrap();
+        let mut car = writer::header(std::slice::from_ref(&root));
+        car.extend(writer::section(&root, b"first"));
+        car.extend(writer::section(&other, b"second"));
+
+        let reader = BlockReader::new(car.as_slice()).expect("cannot read the CAR header");
+        assert_eq!(reader.roots(), std::slice::from_ref(&root));
+        let blocks = reader
+            .collect::<Result<Vec<_>, _>>()
+            .expect("cannot read blocks");
+        assert_eq!(
+            blocks,
+            vec![(root, b"first".to_vec()), (other, b"second".to_vec())]
+        );
+    }
+
+    #[test]
+    fn stops_after_truncated_block() {
+        let root: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .unwrap();
+        let mut car = writer::header(std::slice::from_ref(&root));
+        car.extend(writer::section(&root, b"content"));
+        car.truncate(car.len() - 2);
+
+        let mut reader = BlockReader::new(car.as_slice()).expect("cannot read the CAR header");
+        assert!(
+            matches!(reader.next(), Some(Err(CarError::InvalidSection { .. }))),
+            "truncated block should be reported"
+        );
+        assert!(reader.next().is_none());
+    }
+}
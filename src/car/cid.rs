@@ -0,0 +1,349 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use super::varint;
+
+/// Multicodec code of the `raw` codec.
+pub const RAW: u64 = 0x55;
+/// Multicodec code of the `dag-pb` codec.
+pub const DAG_PB: u64 = 0x70;
+/// Multicodec code of the `dag-cbor` codec.
+pub const DAG_CBOR: u64 = 0x71;
+
+/// Multihash code of the `identity` hash function.
+pub const IDENTITY: u64 = 0x00;
+/// Multihash code of the `sha2-256` hash function.
+pub const SHA2_256: u64 = 0x12;
+
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// A content identifier (CID), version 0 or 1.
+///
+/// This is a minimal implementation covering what's needed to work with CAR files returned by
+/// Lassie. CIDs are formatted as base32 (v1) or base58btc (v0) strings.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cid {
+    version: u64,
+    codec: u64,
+    hash_code: u64,
+    digest: Vec<u8>,
+}
+
+/// An error returned when a CID cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCidError(String);
+
+impl Display for ParseCidError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid CID: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseCidError {}
+
+fn invalid(msg: &str) -> ParseCidError {
+    ParseCidError(msg.to_string())
+}
+
+impl Cid {
+    /// Create a version 1 CID from its parts.
+    #[must_use]
+    pub fn new_v1(codec: u64, hash_code: u64, digest: Vec<u8>) -> Self {
+        Cid {
+            version: 1,
+            codec,
+            hash_code,
+            digest,
+        }
+    }
+
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The multicodec code of the content, e.g. [`DAG_PB`].
+    #[must_use]
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    /// The multihash code of the hash function, e.g. [`SHA2_256`].
+    #[must_use]
+    pub fn hash_code(&self) -> u64 {
+        self.hash_code
+    }
+
+    #[must_use]
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    /// Decode a binary CID from the beginning of `bytes`, returning the CID and the number of bytes
+    /// consumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` when `bytes` don't start with a valid CID.
+    pub fn read_bytes(bytes: &[u8]) -> Result<(Cid, usize), ParseCidError> {
+        if bytes.len() >= 2 && bytes[0] == 0x12 && bytes[1] == 0x20 {
+            let digest = bytes.get(2..34).ok_or_else(|| invalid("truncated CIDv0"))?;
+            let cid = Cid {
+                version: 0,
+                codec: DAG_PB,
+                hash_code: SHA2_256,
+                digest: digest.to_vec(),
+            };
+            return Ok((cid, 34));
+        }
+
+        let mut pos = 0;
+        let mut next = || -> Result<u64, ParseCidError> {
+            let (value, n) =
+                varint::decode(&bytes[pos..]).ok_or_else(|| invalid("malformed varint"))?;
+            pos += n;
+            Ok(value)
+        };
+        let version = next()?;
+        if version != 1 {
+            return Err(ParseCidError(format!("unsupported CID version {version}")));
+        }
+        let codec = next()?;
+        let hash_code = next()?;
+        let len = usize::try_from(next()?).map_err(|_| invalid("digest too long"))?;
+        let digest = bytes
+            .get(pos..pos + len)
+            .ok_or_else(|| invalid("truncated multihash"))?;
+        let cid = Cid::new_v1(codec, hash_code, digest.to_vec());
+        Ok((cid, pos + len))
+    }
+
+    /// Encode the CID in the binary format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.digest.len() + 8);
+        if self.version == 0 {
+            bytes.extend_from_slice(&[0x12, 0x20]);
+        } else {
+            varint::encode(self.version, &mut bytes);
+            varint::encode(self.codec, &mut bytes);
+            varint::encode(self.hash_code, &mut bytes);
+            varint::encode(self.digest.len() as u64, &mut bytes);
+        }
+        bytes.extend_from_slice(&self.digest);
+        bytes
+    }
+}
+
+impl FromStr for Cid {
+    type Err = ParseCidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = if s.len() == 46 && s.starts_with("Qm") {
+            base58_decode(s)?
+        } else {
+            let mut chars = s.chars();
+            let decoded = match chars.next() {
+                Some('b') => base32_decode(chars.as_str()),
+                Some('B') => base32_decode(&chars.as_str().to_ascii_lowercase()),
+                Some('z') => base58_decode(chars.as_str()),
+                Some(prefix) => Err(ParseCidError(format!(
+                    "unsupported multibase prefix {prefix:?}"
+                ))),
+                None => Err(invalid("empty string")),
+            }?;
+            if decoded.first() != Some(&1) {
+                return Err(invalid("multibase-encoded CIDs must be version 1"));
+            }
+            decoded
+        };
+
+        let (cid, len) = Cid::read_bytes(&bytes)?;
+        if len != bytes.len() {
+            return Err(invalid("unexpected trailing bytes"));
+        }
+        Ok(cid)
+    }
+}
+
+impl Display for Cid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.version == 0 {
+            f.write_str(&base58_encode(&self.to_bytes()))
+        } else {
+            write!(f, "b{}", base32_encode(&self.to_bytes()))
+        }
+    }
+}
+
+/// Convert a CID of the `cid` crate, e.g. to look up its block in a [`CarIndex`](super::CarIndex).
+#[cfg(feature = "cid")]
+impl From<&cid::Cid> for Cid {
+    fn from(cid: &cid::Cid) -> Self {
+        let bytes = cid.to_bytes();
+        let (converted, _) =
+            Cid::read_bytes(&bytes).expect("the cid crate produces valid binary CIDs");
+        converted
+    }
+}
+
+/// Convert to the `cid` crate representation, which limits the digest to 64 bytes.
+#[cfg(feature = "cid")]
+impl TryFrom<&Cid> for cid::Cid {
+    type Error = ParseCidError;
+
+    fn try_from(cid: &Cid) -> Result<Self, Self::Error> {
+        cid::Cid::try_from(cid.to_bytes()).map_err(|err| ParseCidError(err.to_string()))
+    }
+}
+
+impl Debug for Cid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cid({self})")
+    }
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(s: &str) -> Result<Vec<u8>, ParseCidError> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| ParseCidError(format!("invalid base32 character {:?}", c as char)))?;
+        // `value` is always less than 32
+        #[allow(clippy::cast_possible_truncation)]
+        let value = value as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+    Ok(out)
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    // Big-number base conversion, digits are stored in little-endian order
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = u32::from(byte);
+        for digit in &mut digits {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    "1".repeat(zeros)
+        .chars()
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|&d| BASE58_ALPHABET[d as usize] as char),
+        )
+        .collect()
+}
+
+fn base58_decode(s: &str) -> Result<Vec<u8>, ParseCidError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let value = BASE58_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| ParseCidError(format!("invalid base58 character {:?}", c as char)))?;
+        // `value` is always less than 58
+        #[allow(clippy::cast_possible_truncation)]
+        let mut carry = value as u32;
+        for byte in &mut bytes {
+            carry += u32::from(*byte) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    bytes.resize(bytes.len() + zeros, 0);
+    bytes.reverse();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_and_formats_cid_v1() {
+        let text = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq";
+        let cid: Cid = text.parse().expect("cannot parse CID");
+        assert_eq!(cid.version(), 1);
+        assert_eq!(cid.codec(), RAW);
+        assert_eq!(cid.hash_code(), SHA2_256);
+        assert_eq!(cid.digest().len(), 32);
+        assert_eq!(cid.to_string(), text);
+    }
+
+    #[test]
+    fn parses_and_formats_cid_v0() {
+        let text = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+        let cid: Cid = text.parse().expect("cannot parse CID");
+        assert_eq!(cid.version(), 0);
+        assert_eq!(cid.codec(), DAG_PB);
+        assert_eq!(cid.to_string(), text);
+
+        let (decoded, len) = Cid::read_bytes(&cid.to_bytes()).expect("cannot decode CID");
+        assert_eq!(len, 34);
+        assert_eq!(decoded, cid);
+    }
+
+    #[cfg(feature = "cid")]
+    #[test]
+    fn converts_from_and_to_cid_crate() {
+        for text in [
+            "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq",
+            "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG",
+        ] {
+            let other: cid::Cid = text.parse().expect("cannot parse CID");
+            let cid = Cid::from(&other);
+            assert_eq!(cid.to_string(), text);
+            assert_eq!(cid::Cid::try_from(&cid).expect("cannot convert CID"), other);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_cids() {
+        assert!("".parse::<Cid>().is_err());
+        assert!("not-a-cid".parse::<Cid>().is_err());
+        assert!("bafkreih25dih6ug3xtj73".parse::<Cid>().is_err());
+    }
+}
@@ -0,0 +1,451 @@
+//! Minimal decoders for the IPLD codecs used by Lassie: just enough to find the links between
+//! blocks.
+
+use super::cid::{self, Cid};
+use super::varint;
+
+/// Nesting limit protecting us from stack overflows on malicious input.
+const MAX_DEPTH: usize = 64;
+
+/// Decode the links of a block encoded with the given codec. Returns `None` for unsupported
+/// codecs.
+pub(crate) fn links(codec: u64, data: &[u8]) -> Option<Result<Vec<Cid>, String>> {
+    match codec {
+        cid::RAW => Some(Ok(Vec::new())),
+        cid::DAG_PB => Some(dag_pb_links(data)),
+        cid::DAG_CBOR => Some(decode_cbor(data).map(|value| {
+            let mut links = Vec::new();
+            value.collect_links(&mut links);
+            links
+        })),
+        _ => None,
+    }
+}
+
+/// A decoded DAG-PB link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PbLink {
+    pub(crate) cid: Cid,
+    pub(crate) name: Option<String>,
+    pub(crate) size: Option<u64>,
+}
+
+/// A decoded DAG-PB node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PbNode {
+    pub(crate) data: Option<Vec<u8>>,
+    pub(crate) links: Vec<PbLink>,
+}
+
+fn dag_pb_links(data: &[u8]) -> Result<Vec<Cid>, String> {
+    Ok(decode_dag_pb(data)?
+        .links
+        .into_iter()
+        .map(|link| link.cid)
+        .collect())
+}
+
+/// Decode a DAG-PB node, see <https://ipld.io/specs/codecs/dag-pb/spec/>
+pub(crate) fn decode_dag_pb(data: &[u8]) -> Result<PbNode, String> {
+    let mut node = PbNode {
+        data: None,
+        links: Vec::new(),
+    };
+    for field in ProtobufFields::new(data) {
+        match field? {
+            (1, Wire::Bytes(bytes)) => node.data = Some(bytes.to_vec()),
+            (2, Wire::Bytes(bytes)) => node.links.push(decode_pb_link(bytes)?),
+            (number, _) => return Err(format!("unexpected PBNode field {number}")),
+        }
+    }
+    Ok(node)
+}
+
+fn decode_pb_link(data: &[u8]) -> Result<PbLink, String> {
+    let mut cid = None;
+    let mut name = None;
+    let mut size = None;
+    for field in ProtobufFields::new(data) {
+        match field? {
+            (1, Wire::Bytes(bytes)) => {
+                let (link, len) = Cid::read_bytes(bytes).map_err(|err| err.to_string())?;
+                if len != bytes.len() {
+                    return Err("unexpected bytes after the link CID".to_string());
+                }
+                cid = Some(link);
+            }
+            (2, Wire::Bytes(bytes)) => {
+                name = Some(String::from_utf8(bytes.to_vec()).map_err(|err| err.to_string())?);
+            }
+            (3, Wire::Varint(value)) => size = Some(value),
+            (number, _) => return Err(format!("unexpected PBLink field {number}")),
+        }
+    }
+    let cid = cid.ok_or_else(|| "PBLink without Hash".to_string())?;
+    Ok(PbLink { cid, name, size })
+}
+
+/// The type of a `UnixFS` node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnixFsKind {
+    Raw,
+    Directory,
+    File,
+    Metadata,
+    Symlink,
+    HamtShard,
+}
+
+/// The `UnixFS` `Data` message stored in DAG-PB nodes, see <https://specs.ipfs.tech/unixfs/>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnixFsData {
+    pub(crate) kind: UnixFsKind,
+    pub(crate) data: Option<Vec<u8>>,
+    pub(crate) file_size: Option<u64>,
+    /// The number of buckets of a HAMT shard.
+    pub(crate) fanout: Option<u64>,
+}
+
+/// Decode the `UnixFS` message from the `Data` field of a DAG-PB node. Fields we don't need are
+/// skipped.
+pub(crate) fn decode_unixfs(data: &[u8]) -> Result<UnixFsData, String> {
+    let mut kind = None;
+    let mut content = None;
+    let mut file_size = None;
+    let mut fanout = None;
+    for field in ProtobufFields::new(data) {
+        match field? {
+            (1, Wire::Varint(value)) => {
+                kind = Some(match value {
+                    0 => UnixFsKind::Raw,
+                    1 => UnixFsKind::Directory,
+                    2 => UnixFsKind::File,
+                    3 => UnixFsKind::Metadata,
+                    4 => UnixFsKind::Symlink,
+                    5 => UnixFsKind::HamtShard,
+                    _ => return Err(format!("unknown UnixFS type {value}")),
+                });
+            }
+            (2, Wire::Bytes(bytes)) => content = Some(bytes.to_vec()),
+            (3, Wire::Varint(value)) => file_size = Some(value),
+            (6, Wire::Varint(value)) => fanout = Some(value),
+            _ => {}
+        }
+    }
+    let kind = kind.ok_or_else(|| "UnixFS Data without Type".to_string())?;
+    Ok(UnixFsData {
+        kind,
+        data: content,
+        file_size,
+        fanout,
+    })
+}
+
+enum Wire<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Iterator over the fields of a protobuf message. Only the wire types used by DAG-PB are
+/// supported.
+struct ProtobufFields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProtobufFields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ProtobufFields { data }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let (value, len) =
+            varint::decode(self.data).ok_or_else(|| "malformed protobuf varint".to_string())?;
+        self.data = &self.data[len..];
+        Ok(value)
+    }
+}
+
+impl<'a> Iterator for ProtobufFields<'a> {
+    type Item = Result<(u64, Wire<'a>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let field = (|| {
+            let key = self.varint()?;
+            let value = match key & 7 {
+                0 => Wire::Varint(self.varint()?),
+                2 => {
+                    let len = usize::try_from(self.varint()?).map_err(|err| err.to_string())?;
+                    if len > self.data.len() {
+                        return Err("truncated protobuf field".to_string());
+                    }
+                    let (bytes, rest) = self.data.split_at(len);
+                    self.data = rest;
+                    Wire::Bytes(bytes)
+                }
+                wire_type => return Err(format!("unsupported protobuf wire type {wire_type}")),
+            };
+            Ok((key >> 3, value))
+        })();
+        if field.is_err() {
+            // Stop the iteration after the first error
+            self.data = &[];
+        }
+        Some(field)
+    }
+}
+
+/// A decoded CBOR value. Floats and simple values are not interesting for us, we keep only the
+/// information needed to find links.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Cbor {
+    Uint(u64),
+    NegInt(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Link(Cid),
+    Tag(u64, Box<Cbor>),
+    Simple,
+}
+
+impl Cbor {
+    fn collect_links(&self, links: &mut Vec<Cid>) {
+        match self {
+            Cbor::Link(cid) => links.push(cid.clone()),
+            Cbor::Array(items) => items.iter().for_each(|item| item.collect_links(links)),
+            Cbor::Map(entries) => entries.iter().for_each(|(key, value)| {
+                key.collect_links(links);
+                value.collect_links(links);
+            }),
+            Cbor::Tag(_, value) => value.collect_links(links),
+            _ => {}
+        }
+    }
+
+    /// Get the value of a map entry with a text key.
+    pub(crate) fn get(&self, key: &str) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Cbor::Text(text) if text == key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// Decode a single CBOR item spanning the entire `data`.
+pub(crate) fn decode_cbor(data: &[u8]) -> Result<Cbor, String> {
+    let mut decoder = CborDecoder { data, pos: 0 };
+    let value = decoder.item(0)?;
+    if decoder.pos != data.len() {
+        return Err("unexpected bytes after the CBOR item".to_string());
+    }
+    Ok(value)
+}
+
+struct CborDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl CborDecoder<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| "truncated CBOR item".to_string())?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn argument(&mut self, info: u8) -> Result<u64, String> {
+        let value = match info {
+            0..=23 => u64::from(info),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(u16::from_be_bytes(
+                self.take(2)?.try_into().unwrap_or_default(),
+            )),
+            26 => u64::from(u32::from_be_bytes(
+                self.take(4)?.try_into().unwrap_or_default(),
+            )),
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap_or_default()),
+            _ => return Err(format!("unsupported CBOR additional info {info}")),
+        };
+        Ok(value)
+    }
+
+    fn length(&mut self, info: u8) -> Result<usize, String> {
+        let len = self.argument(info)?;
+        // Protect against huge allocations, every item takes at least one byte
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.data.len() - self.pos)
+            .ok_or_else(|| "CBOR length exceeds the remaining data".to_string())
+    }
+
+    fn item(&mut self, depth: usize) -> Result<Cbor, String> {
+        if depth > MAX_DEPTH {
+            return Err("CBOR nesting is too deep".to_string());
+        }
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let value = match major {
+            0 => Cbor::Uint(self.argument(info)?),
+            1 => Cbor::NegInt(self.argument(info)?),
+            2 => {
+                let len = self.length(info)?;
+                Cbor::Bytes(self.take(len)?.to_vec())
+            }
+            3 => {
+                let len = self.length(info)?;
+                let bytes = self.take(len)?.to_vec();
+                Cbor::Text(String::from_utf8(bytes).map_err(|err| err.to_string())?)
+            }
+            4 => {
+                let len = self.length(info)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.item(depth + 1)?);
+                }
+                Cbor::Array(items)
+            }
+            5 => {
+                let len = self.length(info)?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.item(depth + 1)?;
+                    let value = self.item(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Cbor::Map(entries)
+            }
+            6 => {
+                let tag = self.argument(info)?;
+                let value = self.item(depth + 1)?;
+                match (tag, value) {
+                    // Tag 42 is a CID link, the bytes are prefixed with the identity multibase
+                    (42, Cbor::Bytes(bytes)) => {
+                        let Some((0, cid_bytes)) = bytes.split_first() else {
+                            return Err("CID link without the 0x00 prefix".to_string());
+                        };
+                        let (cid, len) =
+                            Cid::read_bytes(cid_bytes).map_err(|err| err.to_string())?;
+                        if len != cid_bytes.len() {
+                            return Err("unexpected bytes after the linked CID".to_string());
+                        }
+                        Cbor::Link(cid)
+                    }
+                    (tag, value) => Cbor::Tag(tag, Box::new(value)),
+                }
+            }
+            7 => {
+                // Skip the payload of floats, ignore the value of simple values
+                match info {
+                    0..=24 => {
+                        if info == 24 {
+                            self.take(1)?;
+                        }
+                    }
+                    25 => {
+                        self.take(2)?;
+                    }
+                    26 => {
+                        self.take(4)?;
+                    }
+                    27 => {
+                        self.take(8)?;
+                    }
+                    _ => return Err(format!("unsupported CBOR simple value {info}")),
+                }
+                Cbor::Simple
+            }
+            _ => unreachable!("CBOR major type has only 3 bits"),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn decodes_dag_pb_links() {
+        let target: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .expect("cannot parse CID");
+        let cid_bytes = target.to_bytes();
+
+        // PBLink { Hash, Name: "file.txt", Tsize: 200 }
+        let mut link = vec![0x0a, u8::try_from(cid_bytes.len()).unwrap()];
+        link.extend_from_slice(&cid_bytes);
+        link.extend_from_slice(&[0x12, 8]);
+        link.extend_from_slice(b"file.txt");
+        link.extend_from_slice(&[0x18, 0xc8, 0x01]);
+        // PBNode { Links: [link], Data: [0x08, 0x01] }
+        let mut node = vec![0x12, u8::try_from(link.len()).unwrap()];
+        node.extend_from_slice(&link);
+        node.extend_from_slice(&[0x0a, 2, 0x08, 0x01]);
+
+        let decoded = decode_dag_pb(&node).expect("cannot decode DAG-PB");
+        assert_eq!(decoded.data, Some(vec![0x08, 0x01]));
+        assert_eq!(
+            decoded.links,
+            vec![PbLink {
+                cid: target.clone(),
+                name: Some("file.txt".to_string()),
+                size: Some(200),
+            }]
+        );
+        assert_eq!(links(cid::DAG_PB, &node), Some(Ok(vec![target])));
+    }
+
+    #[test]
+    fn decodes_dag_cbor_links() {
+        let target: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .expect("cannot parse CID");
+        let mut cid_bytes = vec![0];
+        cid_bytes.extend_from_slice(&target.to_bytes());
+
+        // {"link": 42(h'00...'), "n": 1.5}
+        let mut data = vec![0xa2, 0x64];
+        data.extend_from_slice(b"link");
+        data.extend_from_slice(&[0xd8, 0x2a, 0x58, u8::try_from(cid_bytes.len()).unwrap()]);
+        data.extend_from_slice(&cid_bytes);
+        data.extend_from_slice(&[0x61, b'n', 0xf9, 0x3e, 0x00]);
+
+        assert_eq!(links(cid::DAG_CBOR, &data), Some(Ok(vec![target])));
+    }
+
+    #[test]
+    fn decodes_unixfs_data() {
+        // Data { Type: File, Data: "hi", filesize: 2, blocksizes: [2] }
+        let data = [0x08, 0x02, 0x12, 2, b'h', b'i', 0x18, 0x02, 0x20, 0x02];
+        assert_eq!(
+            decode_unixfs(&data),
+            Ok(UnixFsData {
+                kind: UnixFsKind::File,
+                data: Some(b"hi".to_vec()),
+                file_size: Some(2),
+                fanout: None,
+            })
+        );
+        assert!(decode_unixfs(&[0x08, 0x09]).is_err());
+        assert!(decode_unixfs(&[0x12, 0]).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_cbor() {
+        assert!(decode_cbor(&[0x5a, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(decode_cbor(&[0x81]).is_err());
+    }
+}
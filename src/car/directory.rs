@@ -0,0 +1,272 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek};
+
+use super::codec::{self, UnixFsKind};
+use super::verify::check_hash;
+use super::{cid, index, CarError, CarIndex, Cid};
+
+/// An entry of a `UnixFS` directory, see [`list_directory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub cid: Cid,
+    /// The cumulative size of the entry's DAG recorded in the directory (`Tsize`). For files,
+    /// this is slightly larger than the file content because it includes the DAG-PB framing.
+    pub size: Option<u64>,
+}
+
+/// An error returned when a directory cannot be listed from a CAR stream.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DirectoryError {
+    Car(CarError),
+    /// The CAR does not contain a block of the directory.
+    MissingBlock(Cid),
+    /// The block does not match its CID.
+    HashMismatch(Cid),
+    /// The root is not a `UnixFS` directory, e.g. it's a file.
+    NotADirectory(Cid),
+    /// The block cannot be decoded as `UnixFS`.
+    InvalidBlock {
+        cid: Cid,
+        reason: String,
+    },
+}
+
+impl Display for DirectoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirectoryError::Car(err) => write!(f, "{err}"),
+            DirectoryError::MissingBlock(cid) => write!(f, "block {cid} is missing"),
+            DirectoryError::HashMismatch(cid) => write!(f, "block {cid} does not match its CID"),
+            DirectoryError::NotADirectory(cid) => {
+                write!(f, "block {cid} is not a UnixFS directory")
+            }
+            DirectoryError::InvalidBlock { cid, reason } => {
+                write!(f, "block {cid} is not valid UnixFS: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DirectoryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DirectoryError::Car(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CarError> for DirectoryError {
+    fn from(err: CarError) -> Self {
+        DirectoryError::Car(err)
+    }
+}
+
+/// List the entries of the `UnixFS` directory `root` stored in a CAR stream, sorted by name.
+///
+/// Only the directory blocks are read, so the CAR of an entity-scoped retrieval
+/// (`dag-scope=entity`) is enough, the content of the entries is not needed. Sharded (HAMT)
+/// directories are flattened into a single list.
+///
+/// # Errors
+///
+/// Returns `Err` when a directory block is missing or corrupted, when `root` is not a `UnixFS`
+/// directory, or when the stream cannot be read.
+pub fn list_directory<R: Read + Seek>(
+    reader: &mut R,
+    root: &Cid,
+) -> Result<Vec<DirectoryEntry>, DirectoryError> {
+    reader.rewind().map_err(CarError::Io)?;
+    let index = index(&mut *reader)?;
+    directory_entries(&index, reader, root)
+}
+
+/// List the entries of the directory `root` using an existing index of `reader`.
+pub(crate) fn directory_entries<R: Read + Seek>(
+    index: &CarIndex,
+    reader: &mut R,
+    root: &Cid,
+) -> Result<Vec<DirectoryEntry>, DirectoryError> {
+    let mut entries = Vec::new();
+    // The root and the nested HAMT shards
+    let mut stack = vec![root.clone()];
+    while let Some(cid) = stack.pop() {
+        if cid.codec() != cid::DAG_PB {
+            return Err(DirectoryError::NotADirectory(cid));
+        }
+        let data = index
+            .read_block(reader, &cid)?
+            .ok_or_else(|| DirectoryError::MissingBlock(cid.clone()))?;
+        if check_hash(&cid, &data).is_some() {
+            return Err(DirectoryError::HashMismatch(cid));
+        }
+
+        let invalid = |reason: String| DirectoryError::InvalidBlock {
+            cid: cid.clone(),
+            reason,
+        };
+        let node = codec::decode_dag_pb(&data).map_err(invalid)?;
+        let unixfs =
+            codec::decode_unixfs(node.data.as_deref().unwrap_or_default()).map_err(invalid)?;
+        match unixfs.kind {
+            UnixFsKind::Directory if cid == *root => {
+                for link in node.links {
+                    let name = link
+                        .name
+                        .ok_or_else(|| invalid("directory link without a name".to_string()))?;
+                    entries.push(DirectoryEntry {
+                        name,
+                        cid: link.cid,
+                        size: link.size,
+                    });
+                }
+            }
+            UnixFsKind::HamtShard => {
+                let prefix_len = match unixfs.fanout {
+                    // Link names start with the bucket index, hex encoded and zero padded
+                    Some(fanout) if fanout.is_power_of_two() && fanout > 1 => {
+                        format!("{:X}", fanout - 1).len()
+                    }
+                    _ => return Err(invalid("HAMT shard without a valid fanout".to_string())),
+                };
+                for link in node.links {
+                    let name = link
+                        .name
+                        .ok_or_else(|| invalid("HAMT link without a name".to_string()))?;
+                    match name.get(prefix_len..) {
+                        // A link with only the bucket index points to a nested shard
+                        Some("") => stack.push(link.cid),
+                        Some(name) => entries.push(DirectoryEntry {
+                            name: name.to_string(),
+                            cid: link.cid,
+                            size: link.size,
+                        }),
+                        None => {
+                            return Err(invalid(format!("HAMT link name {name:?} is too short")))
+                        }
+                    }
+                }
+            }
+            _ => return Err(DirectoryError::NotADirectory(cid)),
+        }
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::{varint, writer};
+    use pretty_assertions::assert_eq;
+    use sha2::{Digest, Sha256};
+    use std::io::Cursor;
+
+    fn sha256_cid(codec: u64, data: &[u8]) -> Cid {
+        Cid::new_v1(codec, cid::SHA2_256, Sha256::digest(data).to_vec())
+    }
+
+    fn put_bytes(field: u8, bytes: &[u8], out: &mut Vec<u8>) {
+        out.push(field);
+        varint::encode(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    /// A DAG-PB node with the given `UnixFS` type, fanout and named links with sizes.
+    fn dir_node(kind: u8, fanout: Option<u64>, links: &[(&str, &Cid, u64)]) -> Vec<u8> {
+        let mut node = Vec::new();
+        for (name, cid, size) in links {
+            let mut link = Vec::new();
+            put_bytes(0x0a, &cid.to_bytes(), &mut link);
+            put_bytes(0x12, name.as_bytes(), &mut link);
+            link.push(0x18);
+            varint::encode(*size, &mut link);
+            put_bytes(0x12, &link, &mut node);
+        }
+        let mut unixfs = vec![0x08, kind];
+        if let Some(fanout) = fanout {
+            unixfs.push(0x30);
+            varint::encode(fanout, &mut unixfs);
+        }
+        put_bytes(0x0a, &unixfs, &mut node);
+        node
+    }
+
+    fn build_car(root: &Cid, blocks: &[(&Cid, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut car = writer::header(std::slice::from_ref(root));
+        for (cid, data) in blocks {
+            car.extend(writer::section(cid, data));
+        }
+        Cursor::new(car)
+    }
+
+    fn entry(name: &str, cid: &Cid, size: u64) -> DirectoryEntry {
+        DirectoryEntry {
+            name: name.to_string(),
+            cid: cid.clone(),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn lists_entries_without_their_content() {
+        let file = sha256_cid(cid::RAW, b"hello");
+        let subdir = sha256_cid(cid::DAG_PB, &dir_node(1, None, &[]));
+        let root_node = dir_node(1, None, &[("docs", &subdir, 4), ("hello.txt", &file, 5)]);
+        let root = sha256_cid(cid::DAG_PB, &root_node);
+
+        // An entity-scoped CAR contains the directory block only
+        let mut car = build_car(&root, &[(&root, &root_node)]);
+        assert_eq!(
+            list_directory(&mut car, &root).unwrap(),
+            vec![entry("docs", &subdir, 4), entry("hello.txt", &file, 5)]
+        );
+    }
+
+    #[test]
+    fn flattens_hamt_shards() {
+        let hello = sha256_cid(cid::RAW, b"hello");
+        let world = sha256_cid(cid::RAW, b"world");
+        let inner_node = dir_node(5, Some(256), &[("03world.txt", &world, 5)]);
+        let inner = sha256_cid(cid::DAG_PB, &inner_node);
+        let root_node = dir_node(
+            5,
+            Some(256),
+            &[("1F", &inner, 60), ("A0hello.txt", &hello, 5)],
+        );
+        let root = sha256_cid(cid::DAG_PB, &root_node);
+
+        let mut car = build_car(&root, &[(&root, &root_node), (&inner, &inner_node)]);
+        assert_eq!(
+            list_directory(&mut car, &root).unwrap(),
+            vec![entry("hello.txt", &hello, 5), entry("world.txt", &world, 5)]
+        );
+
+        let mut car = build_car(&root, &[(&root, &root_node)]);
+        assert!(matches!(
+            list_directory(&mut car, &root),
+            Err(DirectoryError::MissingBlock(cid)) if cid == inner
+        ));
+    }
+
+    #[test]
+    fn rejects_files_and_corrupted_blocks() {
+        let file_node = dir_node(2, None, &[]);
+        let file = sha256_cid(cid::DAG_PB, &file_node);
+        let mut car = build_car(&file, &[(&file, &file_node)]);
+        assert!(matches!(
+            list_directory(&mut car, &file),
+            Err(DirectoryError::NotADirectory(cid)) if cid == file
+        ));
+
+        let root = sha256_cid(cid::DAG_PB, &dir_node(1, None, &[]));
+        let mut car = build_car(&root, &[(&root, &file_node)]);
+        assert!(matches!(
+            list_directory(&mut car, &root),
+            Err(DirectoryError::HashMismatch(cid)) if cid == root
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+use super::reader::CarReader;
+use super::{CarError, Cid};
+
+/// The location of a block inside a CAR stream, see [`CarIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IndexEntry {
+    pub cid: Cid,
+    /// Offset of the section holding the block, measured from the start of the `CARv1` stream.
+    /// This is the same offset `CARv2` indexes use.
+    pub offset: u64,
+    /// Offset of the block payload, i.e. the section without the length prefix and the CID.
+    pub data_offset: u64,
+    /// Length of the block payload in bytes.
+    pub data_len: u64,
+}
+
+/// An index mapping CIDs to block offsets in a `CARv1` stream, built by [`index`].
+///
+/// Use it to read individual blocks from a CAR file saved to disk without parsing the entire file
+/// again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarIndex {
+    roots: Vec<Cid>,
+    entries: Vec<IndexEntry>,
+    by_cid: HashMap<Cid, usize>,
+}
+
+impl CarIndex {
+    /// The root CIDs listed in the CAR header.
+    #[must_use]
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// All blocks in the order they appear in the CAR stream. Duplicate blocks are indexed only
+    /// once, at their first occurrence.
+    #[must_use]
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn get(&self, cid: &Cid) -> Option<&IndexEntry> {
+        self.by_cid.get(cid).map(|&i| &self.entries[i])
+    }
+
+    /// Read the payload of the block `cid` from the CAR stream this index was built for.
+    ///
+    /// Returns `Ok(None)` when the block is not in the index. The payload is not verified against
+    /// the CID, use [`super::verify`] for that.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` when the block cannot be read from `reader`.
+    pub fn read_block<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        cid: &Cid,
+    ) -> Result<Option<Vec<u8>>, CarError> {
+        let Some(entry) = self.get(cid) else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Start(entry.data_offset))?;
+        let mut data = vec![0; usize::try_from(entry.data_len).unwrap_or(usize::MAX)];
+        reader.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+}
+
+/// Build an index of all blocks in a `CARv1` stream.
+///
+/// # Errors
+///
+/// Returns `Err` when the stream cannot be read or it's not a valid `CARv1` file.
+pub fn index<R: Read>(reader: R) -> Result<CarIndex, CarError> {
+    let mut car = CarReader::new(reader)?;
+    let mut entries = Vec::new();
+    let mut by_cid = HashMap::new();
+    while let Some(block) = car.next_block()? {
+        if by_cid.contains_key(&block.cid) {
+            continue;
+        }
+        let data_len = block.data.len() as u64;
+        by_cid.insert(block.cid.clone(), entries.len());
+        entries.push(IndexEntry {
+            cid: block.cid,
+            offset: block.offset,
+            data_offset: block.offset + block.section_len - data_len,
+            data_len,
+        });
+    }
+    Ok(CarIndex {
+        roots: car.roots().to_vec(),
+        entries,
+        by_cid,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    #[test]
+    fn indexes_testdata_car() {
+        let car = std::fs::read(
+            "tests/testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car",
+        )
+        .expect("cannot read the test CAR file");
+        let root: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .unwrap();
+
+        let index = index(car.as_slice()).expect("cannot index CAR");
+        assert_eq!(index.roots(), std::slice::from_ref(&root));
+        assert_eq!(index.entries().len(), 1);
+
+        let entry = index.get(&root).expect("root block is not indexed");
+        // 59 bytes of the header (including the length varint), 2 bytes of the section length
+        assert_eq!(entry.offset, 59);
+        assert_eq!(entry.data_offset, 59 + 2 + 36);
+        assert_eq!(entry.data_offset + entry.data_len, car.len() as u64);
+
+        let data = index
+            .read_block(&mut Cursor::new(&car), &root)
+            .expect("cannot read block")
+            .expect("block not found");
+        assert_eq!(data, car[car.len() - data.len()..].to_vec());
+
+        let unknown = "bafkqaaa".parse().unwrap();
+        assert_eq!(index.get(&unknown), None);
+    }
+}
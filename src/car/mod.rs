@@ -0,0 +1,88 @@
+//! Utilities for working with the CAR files returned by Lassie.
+//!
+//! Lassie verifies the blocks it receives from providers, but the CAR stream travels through
+//! more hops before it reaches your application (HTTP proxies, disk caches, etc.). Use
+//! [`verify`] to check a CAR stream end-to-end:
+//!
+//! ```no_run
+//! use lassie::car::{self, Cid};
+//!
+//! let root: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq".parse()?;
+//! let file = std::fs::File::open("retrieval.car")?;
+//! let report = car::verify(std::io::BufReader::new(file), &root)?;
+//! assert!(report.is_complete(), "{:?}", report.issues);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Use [`BlockReader`] to process the blocks of a CAR stream one by one.
+//!
+//! Use [`index`] to build a [`CarIndex`] of a CAR file saved to disk and read individual blocks
+//! from it later.
+//!
+//! Use [`payload_digest`] to hash the content of a `UnixFS` file, e.g. to compare it with a
+//! checksum published next to the content.
+//!
+//! Use [`list_directory`] to list the entries of a `UnixFS` directory from the CAR of an
+//! entity-scoped retrieval, without fetching the content of the entries.
+
+use std::fmt::{Display, Formatter};
+use std::io;
+
+mod blocks;
+mod cid;
+pub(crate) mod codec;
+pub(crate) mod directory;
+mod index;
+pub(crate) mod payload;
+pub(crate) mod reader;
+pub(crate) mod varint;
+mod verify;
+pub(crate) mod writer;
+
+pub use blocks::BlockReader;
+pub use cid::{Cid, ParseCidError, DAG_CBOR, DAG_PB, IDENTITY, RAW, SHA2_256};
+pub use directory::{list_directory, DirectoryEntry, DirectoryError};
+pub use index::{index, CarIndex, IndexEntry};
+pub use payload::{payload_digest, write_payload, PayloadError, PayloadHash};
+pub(crate) use verify::check_hash;
+pub use verify::{verify, VerifyIssue, VerifyReport};
+
+/// An error returned when a CAR stream cannot be read.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CarError {
+    Io(io::Error),
+    InvalidHeader(String),
+    UnsupportedVersion(u64),
+    InvalidSection { offset: u64, reason: String },
+}
+
+impl Display for CarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CarError::Io(err) => write!(f, "cannot read the CAR stream: {err}"),
+            CarError::InvalidHeader(reason) => write!(f, "invalid CAR header: {reason}"),
+            CarError::UnsupportedVersion(version) => {
+                write!(f, "unsupported CAR version {version}")
+            }
+            CarError::InvalidSection { offset, reason } => {
+                write!(f, "invalid CAR section at offset {offset}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CarError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CarError {
+    fn from(err: io::Error) -> Self {
+        CarError::Io(err)
+    }
+}
@@ -0,0 +1,277 @@
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Seek, Write};
+
+use sha2::{Digest, Sha256};
+
+use super::codec::{self, UnixFsKind};
+use super::verify::check_hash;
+use super::{cid, index, CarError, CarIndex, Cid};
+
+/// A hash function supported by [`payload_digest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PayloadHash {
+    Sha256,
+    /// Requires the `blake3` feature.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+/// An error returned when the file payload cannot be reconstructed from a CAR stream.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PayloadError {
+    Car(CarError),
+    /// The CAR does not contain a block of the file.
+    MissingBlock(Cid),
+    /// The block does not match its CID.
+    HashMismatch(Cid),
+    /// The block is not part of a `UnixFS` file, e.g. it's a directory.
+    NotAFile(Cid),
+    /// The block cannot be decoded as `UnixFS`.
+    InvalidBlock {
+        cid: Cid,
+        reason: String,
+    },
+    /// The payload cannot be written to the output.
+    Io(io::Error),
+}
+
+impl Display for PayloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::Car(err) => write!(f, "{err}"),
+            PayloadError::MissingBlock(cid) => write!(f, "block {cid} is missing"),
+            PayloadError::HashMismatch(cid) => write!(f, "block {cid} does not match its CID"),
+            PayloadError::NotAFile(cid) => write!(f, "block {cid} is not a UnixFS file"),
+            PayloadError::InvalidBlock { cid, reason } => {
+                write!(f, "block {cid} is not valid UnixFS: {reason}")
+            }
+            PayloadError::Io(err) => write!(f, "cannot write the payload: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PayloadError::Car(err) => Some(err),
+            PayloadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CarError> for PayloadError {
+    fn from(err: CarError) -> Self {
+        PayloadError::Car(err)
+    }
+}
+
+/// Reconstruct the content of the `UnixFS` file `root` stored in a CAR stream and write it to
+/// `out`. Returns the number of bytes written.
+///
+/// Every block is checked against its CID, so the output is exactly the content identified by
+/// `root`. The blocks can be stored in any order, the stream is indexed first.
+///
+/// # Errors
+///
+/// Returns `Err` when a block is missing, corrupted or not part of a `UnixFS` file, or when the
+/// stream cannot be read.
+pub fn write_payload<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    root: &Cid,
+    out: &mut W,
+) -> Result<u64, PayloadError> {
+    reader.rewind().map_err(CarError::Io)?;
+    let index = index(&mut *reader)?;
+    write_file(&index, reader, root, out)
+}
+
+/// Write the content of the `UnixFS` file `root` using an existing index of `reader`.
+pub(crate) fn write_file<R: Read + Seek, W: Write>(
+    index: &CarIndex,
+    reader: &mut R,
+    root: &Cid,
+    out: &mut W,
+) -> Result<u64, PayloadError> {
+    let mut written = 0;
+    // Depth-first traversal, the content of a node precedes the content of its children
+    let mut stack = vec![root.clone()];
+    while let Some(cid) = stack.pop() {
+        let data = if cid.hash_code() == cid::IDENTITY {
+            cid.digest().to_vec()
+        } else {
+            index
+                .read_block(reader, &cid)?
+                .ok_or_else(|| PayloadError::MissingBlock(cid.clone()))?
+        };
+        if check_hash(&cid, &data).is_some() {
+            return Err(PayloadError::HashMismatch(cid));
+        }
+
+        let invalid = |reason: String| PayloadError::InvalidBlock {
+            cid: cid.clone(),
+            reason,
+        };
+        let content = match cid.codec() {
+            cid::RAW => data,
+            cid::DAG_PB => {
+                let node = codec::decode_dag_pb(&data).map_err(invalid)?;
+                let unixfs = codec::decode_unixfs(node.data.as_deref().unwrap_or_default())
+                    .map_err(invalid)?;
+                if !matches!(unixfs.kind, UnixFsKind::File | UnixFsKind::Raw) {
+                    return Err(PayloadError::NotAFile(cid));
+                }
+                stack.extend(node.links.into_iter().rev().map(|link| link.cid));
+                unixfs.data.unwrap_or_default()
+            }
+            _ => return Err(PayloadError::NotAFile(cid)),
+        };
+        out.write_all(&content).map_err(PayloadError::Io)?;
+        written += content.len() as u64;
+    }
+    Ok(written)
+}
+
+/// Hash the content of the `UnixFS` file `root` stored in a CAR stream, see [`write_payload`].
+///
+/// Use this to compare the retrieved bytes with a checksum published next to the content.
+///
+/// # Errors
+///
+/// See [`write_payload`].
+pub fn payload_digest<R: Read + Seek>(
+    reader: &mut R,
+    root: &Cid,
+    hash: PayloadHash,
+) -> Result<Vec<u8>, PayloadError> {
+    match hash {
+        PayloadHash::Sha256 => {
+            let mut hasher = HashWriter(Sha256::new(), |hasher, data| Digest::update(hasher, data));
+            write_payload(reader, root, &mut hasher)?;
+            Ok(hasher.0.finalize().to_vec())
+        }
+        #[cfg(feature = "blake3")]
+        PayloadHash::Blake3 => {
+            let mut hasher = HashWriter(blake3::Hasher::new(), |hasher, data| {
+                hasher.update(data);
+            });
+            write_payload(reader, root, &mut hasher)?;
+            Ok(hasher.0.finalize().as_bytes().to_vec())
+        }
+    }
+}
+
+/// Adapts a hasher to [`Write`].
+struct HashWriter<H>(H, fn(&mut H, &[u8]));
+
+impl<H> Write for HashWriter<H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (self.1)(&mut self.0, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::{varint, writer};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn sha256_cid(codec: u64, data: &[u8]) -> Cid {
+        Cid::new_v1(codec, cid::SHA2_256, Sha256::digest(data).to_vec())
+    }
+
+    /// A DAG-PB node of a `UnixFS` file with the given `UnixFS` type, inline data and children.
+    fn file_node(kind: u8, data: &[u8], children: &[&Cid]) -> Vec<u8> {
+        let mut node = Vec::new();
+        for child in children {
+            let cid_bytes = child.to_bytes();
+            let mut link = vec![0x0a];
+            varint::encode(cid_bytes.len() as u64, &mut link);
+            link.extend_from_slice(&cid_bytes);
+            node.push(0x12);
+            varint::encode(link.len() as u64, &mut node);
+            node.extend_from_slice(&link);
+        }
+        let mut unixfs = vec![0x08, kind, 0x12];
+        varint::encode(data.len() as u64, &mut unixfs);
+        unixfs.extend_from_slice(data);
+        node.push(0x0a);
+        varint::encode(unixfs.len() as u64, &mut node);
+        node.extend_from_slice(&unixfs);
+        node
+    }
+
+    fn build_car(root: &Cid, blocks: &[(&Cid, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut car = writer::header(std::slice::from_ref(root));
+        for (cid, data) in blocks {
+            car.extend(writer::section(cid, data));
+        }
+        Cursor::new(car)
+    }
+
+    #[test]
+    fn reconstructs_chunked_file() {
+        let first = b"hello ".as_slice();
+        let second = b"world".as_slice();
+        let first_cid = sha256_cid(cid::RAW, first);
+        let second_cid = sha256_cid(cid::RAW, second);
+        let inner = file_node(2, b"", &[&second_cid]);
+        let inner_cid = sha256_cid(cid::DAG_PB, &inner);
+        let root_node = file_node(2, b"", &[&first_cid, &inner_cid]);
+        let root = sha256_cid(cid::DAG_PB, &root_node);
+
+        // Blocks in a non-DFS order
+        let mut car = build_car(
+            &root,
+            &[
+                (&second_cid, second),
+                (&root, &root_node),
+                (&inner_cid, &inner),
+                (&first_cid, first),
+            ],
+        );
+        let mut payload = Vec::new();
+        assert_eq!(write_payload(&mut car, &root, &mut payload).unwrap(), 11);
+        assert_eq!(payload, b"hello world");
+        assert_eq!(
+            payload_digest(&mut car, &root, PayloadHash::Sha256).unwrap(),
+            Sha256::digest(b"hello world").to_vec()
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_or_corrupted_files() {
+        let leaf = b"data".as_slice();
+        let leaf_cid = sha256_cid(cid::RAW, leaf);
+        let root_node = file_node(2, b"", &[&leaf_cid]);
+        let root = sha256_cid(cid::DAG_PB, &root_node);
+
+        let mut car = build_car(&root, &[(&root, &root_node)]);
+        assert!(matches!(
+            write_payload(&mut car, &root, &mut io::sink()),
+            Err(PayloadError::MissingBlock(cid)) if cid == leaf_cid
+        ));
+
+        let mut car = build_car(&root, &[(&root, &root_node), (&leaf_cid, b"evil")]);
+        assert!(matches!(
+            write_payload(&mut car, &root, &mut io::sink()),
+            Err(PayloadError::HashMismatch(cid)) if cid == leaf_cid
+        ));
+
+        let dir_node = file_node(1, b"", &[]);
+        let dir = sha256_cid(cid::DAG_PB, &dir_node);
+        let mut car = build_car(&dir, &[(&dir, &dir_node)]);
+        assert!(matches!(
+            write_payload(&mut car, &dir, &mut io::sink()),
+            Err(PayloadError::NotAFile(cid)) if cid == dir
+        ));
+    }
+}
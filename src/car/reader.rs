@@ -0,0 +1,114 @@
+use std::io::{self, Read};
+
+use super::codec::{self, Cbor};
+use super::{varint, CarError, Cid};
+
+/// Sections larger than this are rejected. Bitswap limits blocks to 2 MiB, we allow some headroom
+/// for other transports.
+const MAX_SECTION_SIZE: u64 = 32 << 20;
+
+/// Header sections larger than this are rejected.
+const MAX_HEADER_SIZE: u64 = 1 << 20;
+
+/// A block read from a CAR stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Block {
+    pub(crate) cid: Cid,
+    pub(crate) data: Vec<u8>,
+    /// Offset of the section (starting with the section length varint) from the start of the
+    /// CAR stream.
+    pub(crate) offset: u64,
+    /// Length of the entire section, including the length varint and the CID.
+    pub(crate) section_len: u64,
+}
+
+/// Streaming reader for `CARv1` files, see <https://ipld.io/specs/transport/car/carv1/>
+pub(crate) struct CarReader<R> {
+    reader: R,
+    roots: Vec<Cid>,
+    offset: u64,
+}
+
+impl<R: Read> CarReader<R> {
+    /// Read the CAR header.
+    pub(crate) fn new(mut reader: R) -> Result<Self, CarError> {
+        let (len, varint_len) = varint::read(&mut reader)?
+            .ok_or_else(|| CarError::InvalidHeader("the CAR stream is empty".to_string()))?;
+        if len > MAX_HEADER_SIZE {
+            return Err(CarError::InvalidHeader(format!(
+                "header is too large ({len} bytes)"
+            )));
+        }
+        let mut header = vec![0; usize::try_from(len).unwrap_or(usize::MAX)];
+        reader.read_exact(&mut header)?;
+
+        let header = codec::decode_cbor(&header).map_err(CarError::InvalidHeader)?;
+        let Some(&Cbor::Uint(version)) = header.get("version") else {
+            return Err(CarError::InvalidHeader("missing `version`".to_string()));
+        };
+        if version != 1 {
+            return Err(CarError::UnsupportedVersion(version));
+        }
+        let roots = match header.get("roots") {
+            Some(Cbor::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Cbor::Link(cid) => Ok(cid.clone()),
+                    _ => Err(CarError::InvalidHeader(
+                        "`roots` must contain only CIDs".to_string(),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(CarError::InvalidHeader("missing `roots`".to_string())),
+        };
+
+        Ok(CarReader {
+            reader,
+            roots,
+            offset: varint_len as u64 + len,
+        })
+    }
+
+    /// The root CIDs listed in the CAR header.
+    pub(crate) fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// The number of bytes read so far, i.e. the offset of the next section.
+    #[cfg(feature = "client")]
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Read the next block, returns `Ok(None)` at the end of the stream.
+    pub(crate) fn next_block(&mut self) -> Result<Option<Block>, CarError> {
+        let offset = self.offset;
+        let Some((len, varint_len)) = varint::read(&mut self.reader)? else {
+            return Ok(None);
+        };
+        let invalid = |reason: String| CarError::InvalidSection { offset, reason };
+        if len > MAX_SECTION_SIZE {
+            return Err(invalid(format!("section is too large ({len} bytes)")));
+        }
+
+        let mut section = vec![0; usize::try_from(len).unwrap_or(usize::MAX)];
+        self.reader.read_exact(&mut section).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                invalid("truncated section".to_string())
+            } else {
+                CarError::Io(err)
+            }
+        })?;
+        let (cid, cid_len) = Cid::read_bytes(&section).map_err(|err| invalid(err.to_string()))?;
+        section.drain(..cid_len);
+
+        let section_len = varint_len as u64 + len;
+        self.offset += section_len;
+        Ok(Some(Block {
+            cid,
+            data: section,
+            offset,
+            section_len,
+        }))
+    }
+}
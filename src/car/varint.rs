@@ -0,0 +1,73 @@
+//! Unsigned LEB128 varints as used by multiformats and CAR files.
+
+use std::io::{self, Read};
+
+/// Decode a varint from the beginning of `bytes`, returning the value and the number of bytes
+/// consumed. Returns `None` when the varint is truncated or longer than 64 bits.
+pub(crate) fn decode(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+pub(crate) fn encode(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        // Truncation is intended, we are taking the lowest 7 bits
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Read a varint from `reader`, returning the value and the number of bytes consumed.
+///
+/// Returns `Ok(None)` when the reader is at EOF before the first byte of the varint.
+pub(crate) fn read<R: Read>(reader: &mut R) -> io::Result<Option<(u64, usize)>> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if let Err(err) = reader.read_exact(&mut byte) {
+            if err.kind() == io::ErrorKind::UnexpectedEof && i == 0 {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        value |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint is longer than 64 bits",
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn encodes_and_decodes() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            encode(value, &mut bytes);
+            assert_eq!(decode(&bytes), Some((value, bytes.len())));
+            assert_eq!(
+                read(&mut bytes.as_slice()).expect("cannot read varint"),
+                Some((value, bytes.len()))
+            );
+        }
+    }
+}
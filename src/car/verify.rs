@@ -0,0 +1,303 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+
+use sha2::{Digest, Sha256};
+
+use super::reader::CarReader;
+use super::{cid, codec, CarError, Cid};
+
+/// The result of [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VerifyReport {
+    /// The root CID the CAR was verified against.
+    pub root: Cid,
+    /// Whether the CAR header lists `root` as one of its roots.
+    pub root_in_header: bool,
+    /// The number of blocks in the CAR, including duplicates.
+    pub blocks: u64,
+    /// The total size of all block payloads in bytes.
+    pub bytes: u64,
+    /// Problems found in the CAR, in the order they were discovered.
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+    /// Returns `true` when every block in the CAR matches its CID and belongs to the DAG of the
+    /// root.
+    ///
+    /// A valid CAR may still be missing some blocks, e.g. when the retrieval was limited with
+    /// `max_blocks` or a `dag-scope`. Use [`Self::is_complete`] to check that too.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.issues
+            .iter()
+            .all(|issue| matches!(issue, VerifyIssue::MissingBlock(_)))
+    }
+
+    /// Returns `true` when the CAR is valid and contains the entire DAG of the root.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A problem found by [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifyIssue {
+    /// The CAR header does not list the requested root.
+    RootNotInHeader,
+    /// The block payload does not match the CID.
+    HashMismatch(Cid),
+    /// The CID uses a hash function we cannot verify.
+    UnsupportedHash(Cid),
+    /// The CID uses a codec we cannot decode, links from this block were not followed.
+    UnsupportedCodec(Cid),
+    /// The block payload cannot be decoded using the codec from the CID.
+    MalformedBlock { cid: Cid, reason: String },
+    /// The block is not reachable from the root via blocks that passed verification.
+    UnreachableBlock(Cid),
+    /// The block is linked from the DAG of the root but it's not included in the CAR.
+    MissingBlock(Cid),
+}
+
+/// Walk a CAR stream returned by Lassie, re-hash every block and check that the blocks form the
+/// DAG of `root`.
+///
+/// Supported codecs are `raw`, `dag-pb` and `dag-cbor`, supported hash functions are `sha2-256`
+/// and `identity`.
+///
+/// # Errors
+///
+/// Returns `Err` when the stream cannot be read or it's not a valid `CARv1` file. Problems with
+/// the content of the blocks are reported in [`VerifyReport::issues`].
+pub fn verify<R: Read>(reader: R, root: &Cid) -> Result<VerifyReport, CarError> {
+    let mut car = CarReader::new(reader)?;
+    let mut report = VerifyReport {
+        root: root.clone(),
+        root_in_header: car.roots().contains(root),
+        blocks: 0,
+        bytes: 0,
+        issues: Vec::new(),
+    };
+    if !report.root_in_header {
+        report.issues.push(VerifyIssue::RootNotInHeader);
+    }
+
+    // Links of all blocks that passed verification, in the order the blocks were received
+    let mut order = Vec::new();
+    let mut links: HashMap<Cid, Vec<Cid>> = HashMap::new();
+    let mut received = HashSet::new();
+    while let Some(block) = car.next_block()? {
+        report.blocks += 1;
+        report.bytes += block.data.len() as u64;
+        if !received.insert(block.cid.clone()) {
+            // Lassie sends duplicate blocks when requested with `dups=y`
+            continue;
+        }
+        order.push(block.cid.clone());
+
+        if let Some(issue) = check_hash(&block.cid, &block.data) {
+            report.issues.push(issue);
+            continue;
+        }
+        match codec::links(block.cid.codec(), &block.data) {
+            Some(Ok(block_links)) => {
+                links.insert(block.cid, block_links);
+            }
+            Some(Err(reason)) => report.issues.push(VerifyIssue::MalformedBlock {
+                cid: block.cid,
+                reason,
+            }),
+            None => report.issues.push(VerifyIssue::UnsupportedCodec(block.cid)),
+        }
+    }
+
+    // Walk the DAG from the root
+    let mut reachable = HashSet::new();
+    let mut queue = VecDeque::from([root.clone()]);
+    while let Some(cid) = queue.pop_front() {
+        if !reachable.insert(cid.clone()) {
+            continue;
+        }
+        if !received.contains(&cid) {
+            // Identity CIDs carry the data inline, there is no block to send
+            if cid.hash_code() != cid::IDENTITY {
+                report.issues.push(VerifyIssue::MissingBlock(cid));
+            }
+            continue;
+        }
+        if let Some(block_links) = links.get(&cid) {
+            queue.extend(block_links.iter().cloned());
+        }
+    }
+
+    report.issues.extend(
+        order
+            .into_iter()
+            .filter(|cid| !reachable.contains(cid))
+            .map(VerifyIssue::UnreachableBlock),
+    );
+
+    Ok(report)
+}
+
+pub(crate) fn check_hash(cid: &Cid, data: &[u8]) -> Option<VerifyIssue> {
+    let matches = match cid.hash_code() {
+        cid::SHA2_256 => Sha256::digest(data).as_slice() == cid.digest(),
+        cid::IDENTITY => data == cid.digest(),
+        _ => return Some(VerifyIssue::UnsupportedHash(cid.clone())),
+    };
+    (!matches).then(|| VerifyIssue::HashMismatch(cid.clone()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::varint;
+    use pretty_assertions::assert_eq;
+
+    fn sha256_cid(codec: u64, data: &[u8]) -> Cid {
+        Cid::new_v1(codec, cid::SHA2_256, Sha256::digest(data).to_vec())
+    }
+
+    fn dag_pb_node(children: &[&Cid]) -> Vec<u8> {
+        let mut node = Vec::new();
+        for child in children {
+            let cid_bytes = child.to_bytes();
+            let mut link = vec![0x0a];
+            varint::encode(cid_bytes.len() as u64, &mut link);
+            link.extend_from_slice(&cid_bytes);
+            node.push(0x12);
+            varint::encode(link.len() as u64, &mut node);
+            node.extend_from_slice(&link);
+        }
+        node
+    }
+
+    fn build_car(roots: &[&Cid], blocks: &[(&Cid, &[u8])]) -> Vec<u8> {
+        // {"roots": [...], "version": 1}
+        let mut header = vec![0xa2, 0x65];
+        header.extend_from_slice(b"roots");
+        header.push(0x80 + u8::try_from(roots.len()).unwrap());
+        for root in roots {
+            let cid_bytes = root.to_bytes();
+            header.extend_from_slice(&[
+                0xd8,
+                0x2a,
+                0x58,
+                u8::try_from(cid_bytes.len() + 1).unwrap(),
+            ]);
+            header.push(0);
+            header.extend_from_slice(&cid_bytes);
+        }
+        header.push(0x67);
+        header.extend_from_slice(b"version");
+        header.push(0x01);
+
+        let mut car = Vec::new();
+        varint::encode(header.len() as u64, &mut car);
+        car.extend_from_slice(&header);
+        for (cid, data) in blocks {
+            let cid_bytes = cid.to_bytes();
+            varint::encode((cid_bytes.len() + data.len()) as u64, &mut car);
+            car.extend_from_slice(&cid_bytes);
+            car.extend_from_slice(data);
+        }
+        car
+    }
+
+    #[test]
+    fn verifies_testdata_car() {
+        let car = std::fs::read(
+            "tests/testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car",
+        )
+        .expect("cannot read the test CAR file");
+        let root = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .unwrap();
+
+        let report = verify(car.as_slice(), &root).expect("cannot verify CAR");
+        assert_eq!(report.issues, vec![]);
+        assert!(report.root_in_header);
+        assert_eq!(report.blocks, 1);
+        assert!(report.is_complete());
+    }
+
+    #[test]
+    fn verifies_dag() {
+        let leaf_a: &[u8] = b"hello";
+        let leaf_b: &[u8] = b"world";
+        let cid_a = sha256_cid(cid::RAW, leaf_a);
+        let cid_b = sha256_cid(cid::RAW, leaf_b);
+        let node = dag_pb_node(&[&cid_a, &cid_b]);
+        let root = sha256_cid(cid::DAG_PB, &node);
+
+        let car = build_car(
+            &[&root],
+            &[(&root, &node), (&cid_a, leaf_a), (&cid_b, leaf_b)],
+        );
+        let report = verify(car.as_slice(), &root).expect("cannot verify CAR");
+        assert_eq!(report.issues, vec![]);
+        assert_eq!(report.blocks, 3);
+        assert_eq!(report.bytes, (node.len() + 10) as u64);
+    }
+
+    #[test]
+    fn reports_issues() {
+        let leaf_a: &[u8] = b"hello";
+        let leaf_b: &[u8] = b"world";
+        let cid_a = sha256_cid(cid::RAW, leaf_a);
+        let cid_b = sha256_cid(cid::RAW, leaf_b);
+        let node = dag_pb_node(&[&cid_a, &cid_b]);
+        let root = sha256_cid(cid::DAG_PB, &node);
+        let stray = sha256_cid(cid::RAW, b"stray");
+
+        let car = build_car(
+            &[&cid_a],
+            &[(&root, &node), (&cid_a, b"tampered"), (&stray, b"stray")],
+        );
+        let report = verify(car.as_slice(), &root).expect("cannot verify CAR");
+        assert_eq!(
+            report.issues,
+            vec![
+                VerifyIssue::RootNotInHeader,
+                VerifyIssue::HashMismatch(cid_a),
+                VerifyIssue::MissingBlock(cid_b),
+                VerifyIssue::UnreachableBlock(stray),
+            ]
+        );
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn accepts_partial_dag() {
+        let leaf: &[u8] = b"hello";
+        let cid_leaf = sha256_cid(cid::RAW, leaf);
+        let node = dag_pb_node(&[&cid_leaf]);
+        let root = sha256_cid(cid::DAG_PB, &node);
+
+        let car = build_car(&[&root], &[(&root, &node)]);
+        let report = verify(car.as_slice(), &root).expect("cannot verify CAR");
+        assert_eq!(report.issues, vec![VerifyIssue::MissingBlock(cid_leaf)]);
+        assert!(report.is_valid());
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn rejects_invalid_car() {
+        let root = sha256_cid(cid::RAW, b"hello");
+        assert!(matches!(
+            verify(&[][..], &root),
+            Err(CarError::InvalidHeader(_))
+        ));
+
+        let mut car = build_car(&[&root], &[(&root, b"hello")]);
+        car.truncate(car.len() - 2);
+        assert!(matches!(
+            verify(car.as_slice(), &root),
+            Err(CarError::InvalidSection { .. })
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+use super::{varint, Cid};
+
+/// Encode the `CARv1` header section listing `roots`.
+pub(crate) fn header(roots: &[Cid]) -> Vec<u8> {
+    // DAG-CBOR {"roots": [...], "version": 1}
+    let mut header = vec![0xa2, 0x65];
+    header.extend(b"roots");
+    put_cbor_head(&mut header, 4, roots.len());
+    for root in roots {
+        let mut link = vec![0x00];
+        link.extend(root.to_bytes());
+        header.extend([0xd8, 0x2a]);
+        put_cbor_head(&mut header, 2, link.len());
+        header.extend(link);
+    }
+    header.push(0x67);
+    header.extend(b"version");
+    header.push(0x01);
+
+    let mut out = Vec::new();
+    varint::encode(header.len() as u64, &mut out);
+    out.extend(header);
+    out
+}
+
+/// Encode a `CARv1` block section.
+pub(crate) fn section(cid: &Cid, data: &[u8]) -> Vec<u8> {
+    let cid = cid.to_bytes();
+    let mut out = Vec::new();
+    varint::encode((cid.len() + data.len()) as u64, &mut out);
+    out.extend(cid);
+    out.extend(data);
+    out
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn put_cbor_head(out: &mut Vec<u8>, major: u8, len: usize) {
+    // CIDs and root lists are always shorter than 64 KiB
+    let major = major << 5;
+    if len < 24 {
+        out.push(major | len as u8);
+    } else if len < 256 {
+        out.extend([major | 0x18, len as u8]);
+    } else {
+        out.push(major | 0x19);
+        out.extend((len as u16).to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::reader::CarReader;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn writes_readable_car() {
+        let root: Cid = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq"
+            .parse()
+            .unwrap();
+        let mut car = header(std::slice::from_ref(&root));
+        car.extend(section(&root, b"hello"));
+
+        let mut reader = CarReader::new(car.as_slice()).unwrap();
+        assert_eq!(reader.roots(), std::slice::from_ref(&root));
+        let block = reader.next_block().unwrap().unwrap();
+        assert_eq!((block.cid, block.data), (root, b"hello".to_vec()));
+        assert_eq!(reader.next_block().unwrap(), None);
+    }
+}
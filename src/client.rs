@@ -0,0 +1,679 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Write};
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use crate::multiaddr::Multiaddr;
+use crate::{
+    Daemon, DaemonHandle, Priority, RetrievalError, ERROR_CODE_HEADER, PRIORITY_HEADER,
+    REQUEST_ID_HEADER, RETRIEVAL_ID_HEADER, TIMEOUT_HEADER, TRACEPARENT_HEADER,
+};
+
+/// How long the client waits for the daemon to report a [`RetrievalRequest::timeout`] before it
+/// aborts the request itself.
+const TIMEOUT_GRACE: Duration = Duration::from_secs(1);
+
+/// The name of an IPNS record, e.g. `k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8`.
+///
+/// The daemon resolves the name by fetching the signed record from the delegated routing server
+/// at `delegated-ipfs.dev`, verifies it and retrieves the content the record points to. Only
+/// names derived from public keys are supported, `DNSLink` domain names are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IpnsName(String);
+
+impl IpnsName {
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for IpnsName {
+    type Err = ParseIpnsNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("/ipns/").unwrap_or(s);
+        if name.is_empty() {
+            return Err(ParseIpnsNameError("empty string".to_string()));
+        }
+        if name.contains('.') {
+            return Err(ParseIpnsNameError(format!(
+                "DNSLink names are not supported (value: {name:?})"
+            )));
+        }
+        // Keys are encoded as CIDs (base36 or base32) or as legacy base58 peer IDs
+        if !name.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(ParseIpnsNameError(format!(
+                "the name must be a multibase-encoded key (value: {name:?})"
+            )));
+        }
+        Ok(IpnsName(name.to_string()))
+    }
+}
+
+impl Display for IpnsName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An error returned when an IPNS name cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIpnsNameError(String);
+
+impl Display for ParseIpnsNameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid IPNS name: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseIpnsNameError {}
+
+/// Which part of the DAG to retrieve, see
+/// <https://specs.ipfs.tech/http-gateways/trustless-gateway/#dag-scope-request-query-parameter>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DagScope {
+    /// The entire DAG below the requested path (Lassie's default).
+    All,
+    /// The blocks needed to read the requested file or to list the requested directory.
+    Entity,
+    /// The block at the requested path only.
+    Block,
+}
+
+impl DagScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            DagScope::All => "all",
+            DagScope::Entity => "entity",
+            DagScope::Block => "block",
+        }
+    }
+}
+
+/// The response format of a retrieval, see [`RetrievalRequest::format`].
+///
+/// The format drives both the `Accept` header and the `format=` query parameter of the request.
+/// [`Client::fetch`] then checks the `Content-Type` of the response and reports a mismatch as
+/// [`RetrievalError::UnexpectedContentType`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    /// A `CARv1` stream of the requested DAG (the default).
+    #[default]
+    CarV1,
+    /// A `CARv2` file. Lassie produces `CARv1` streams only and rejects such requests.
+    CarV2,
+    /// The raw bytes of the requested block, use it together with [`DagScope::Block`].
+    RawBlock,
+}
+
+impl Format {
+    /// The value of the `Accept` header requesting this format.
+    #[must_use]
+    pub fn accept(self) -> &'static str {
+        match self {
+            Format::CarV1 => "application/vnd.ipld.car;version=1",
+            Format::CarV2 => "application/vnd.ipld.car;version=2",
+            Format::RawBlock => "application/vnd.ipld.raw",
+        }
+    }
+
+    fn query_value(self) -> &'static str {
+        match self {
+            Format::CarV1 | Format::CarV2 => "car",
+            Format::RawBlock => "raw",
+        }
+    }
+
+    /// Check the `Content-Type` of a response, e.g.
+    /// `application/vnd.ipld.car;version=1;order=dfs;dups=y`. CAR responses without a version
+    /// are `CARv1`.
+    fn matches(self, content_type: &str) -> bool {
+        let mut parts = content_type.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or_default();
+        let version = parts
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.eq_ignore_ascii_case("version"))
+            .map(|(_, value)| value.trim_matches('"'));
+        let is_car = media_type.eq_ignore_ascii_case("application/vnd.ipld.car");
+        match self {
+            Format::CarV1 => is_car && matches!(version, None | Some("1")),
+            Format::CarV2 => is_car && version == Some("2"),
+            Format::RawBlock => media_type.eq_ignore_ascii_case("application/vnd.ipld.raw"),
+        }
+    }
+}
+
+/// The content a [`RetrievalRequest`] points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Root {
+    Cid(String),
+    Ipns(IpnsName),
+}
+
+/// A description of a single retrieval to perform via the Lassie daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetrievalRequest {
+    root: Root,
+    sub_path: Vec<String>,
+    pub(crate) dag_scope: Option<DagScope>,
+    format: Format,
+    /// The first and the last byte of the file to retrieve with [`DagScope::Entity`], see
+    /// [`RetrievalRequest::entity_bytes`].
+    pub(crate) entity_bytes_from: Option<u64>,
+    pub(crate) entity_bytes_to: Option<u64>,
+    pub(crate) providers: Vec<Multiaddr>,
+    protocols: Vec<String>,
+    block_limit: Option<u64>,
+    request_id: Option<String>,
+    traceparent: Option<String>,
+    priority: Option<Priority>,
+    timeout: Option<Duration>,
+}
+
+impl RetrievalRequest {
+    #[must_use]
+    pub fn new(cid: impl Into<String>) -> Self {
+        Self::with_root(Root::Cid(cid.into()))
+    }
+
+    /// Retrieve the content identified by a CID parsed with the `cid` crate.
+    #[cfg(feature = "cid")]
+    #[must_use]
+    pub fn from_cid(cid: &cid::Cid) -> Self {
+        Self::new(cid.to_string())
+    }
+
+    /// Retrieve the content the IPNS record `name` points to.
+    #[must_use]
+    pub fn ipns(name: IpnsName) -> Self {
+        Self::with_root(Root::Ipns(name))
+    }
+
+    fn with_root(root: Root) -> Self {
+        RetrievalRequest {
+            root,
+            sub_path: Vec::new(),
+            dag_scope: None,
+            format: Format::default(),
+            entity_bytes_from: None,
+            entity_bytes_to: None,
+            providers: Vec::new(),
+            protocols: Vec::new(),
+            block_limit: None,
+            request_id: None,
+            traceparent: None,
+            priority: None,
+            timeout: None,
+        }
+    }
+
+    /// Retrieve only the DAG reachable from `path` inside the root, e.g. `docs/user guide.md`.
+    ///
+    /// The path is split at `/` and each segment is percent-encoded, so names with spaces or
+    /// non-ASCII characters don't need any escaping.
+    #[must_use]
+    pub fn sub_path(mut self, path: &str) -> Self {
+        self.sub_path = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(str::to_string)
+            .collect();
+        self
+    }
+
+    #[must_use]
+    pub fn dag_scope(mut self, scope: DagScope) -> Self {
+        self.dag_scope = Some(scope);
+        self
+    }
+
+    /// Retrieve only the blocks of the file holding the bytes `from..=to`, or `from..` when `to`
+    /// is `None`. Implies [`DagScope::Entity`].
+    #[must_use]
+    pub fn entity_bytes(mut self, from: u64, to: Option<u64>) -> Self {
+        self.dag_scope = Some(DagScope::Entity);
+        self.entity_bytes_from = Some(from);
+        self.entity_bytes_to = to;
+        self
+    }
+
+    /// Request the response in `format` instead of [`Format::CarV1`].
+    #[must_use]
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Retrieve the content from the given providers only, skipping the candidate discovery.
+    ///
+    /// Each provider is specified as a multiaddr, e.g. `/dns4/frisbii.fly.dev/https`, parse it
+    /// with [`str::parse`] to catch typos before sending the request.
+    #[must_use]
+    pub fn providers<I>(mut self, providers: I) -> Self
+    where
+        I: IntoIterator<Item = Multiaddr>,
+    {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    /// Restrict the retrieval protocols Lassie can use, e.g. `http` or `bitswap`.
+    #[must_use]
+    pub fn protocols<I, S>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Stop the retrieval after receiving `limit` blocks.
+    ///
+    /// When the daemon was started with [`DaemonConfig::max_blocks`](crate::DaemonConfig::max_blocks), the
+    /// lower of the two limits applies.
+    #[must_use]
+    pub fn block_limit(mut self, limit: u64) -> Self {
+        self.block_limit = Some(limit);
+        self
+    }
+
+    /// Send `id` in the [`REQUEST_ID_HEADER`] to correlate the retrieval with your own traces.
+    #[must_use]
+    pub fn request_id(mut self, id: impl Into<String>) -> Self {
+        self.request_id = Some(id.into());
+        self
+    }
+
+    /// Send `traceparent` in the [`TRACEPARENT_HEADER`], so that the spans exported by the daemon
+    /// (see [`DaemonConfig::otlp`](crate::DaemonConfig::otlp)) become part of your trace.
+    #[must_use]
+    pub fn traceparent(mut self, traceparent: impl Into<String>) -> Self {
+        self.traceparent = Some(traceparent.into());
+        self
+    }
+
+    /// Send `priority` in the [`PRIORITY_HEADER`] so that the retrieval starts before the queued
+    /// ones with a lower priority, see
+    /// [`DaemonConfig::max_concurrent_retrievals`](crate::DaemonConfig::max_concurrent_retrievals).
+    #[must_use]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Give up when the retrieval does not finish within `timeout`, independently of
+    /// [`DaemonConfig::global_timeout`](crate::DaemonConfig::global_timeout).
+    ///
+    /// The timeout is sent to the daemon in the [`TIMEOUT_HEADER`], so that it stops the
+    /// retrieval and frees its resources. The client also enforces it: when the daemon does not
+    /// report the timeout within a second, the request or the read of the body is aborted. Both
+    /// cases are reported as [`RetrievalError::Timeout`].
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The requested CID, or the IPNS name for requests created by [`RetrievalRequest::ipns`].
+    #[must_use]
+    pub fn cid(&self) -> &str {
+        match &self.root {
+            Root::Cid(cid) => cid,
+            Root::Ipns(name) => name.as_str(),
+        }
+    }
+
+    #[must_use]
+    pub fn ipns_name(&self) -> Option<&IpnsName> {
+        match &self.root {
+            Root::Cid(_) => None,
+            Root::Ipns(name) => Some(name),
+        }
+    }
+
+    /// The request path without the query string, e.g. `/ipfs/{cid}/sub/path` or `/ipns/{name}`.
+    #[must_use]
+    pub fn path(&self) -> String {
+        let mut path = match &self.root {
+            Root::Cid(cid) => format!("/ipfs/{cid}"),
+            Root::Ipns(name) => format!("/ipns/{name}"),
+        };
+        for segment in &self.sub_path {
+            path.push('/');
+            percent_encode(segment, &mut path);
+        }
+        path
+    }
+}
+
+/// Check whether the request was aborted by the deadline set via [`RetrievalRequest::timeout`].
+fn is_timeout(err: &ureq::Transport) -> bool {
+    std::error::Error::source(err)
+        .and_then(|source| source.downcast_ref::<std::io::Error>())
+        .is_some_and(|err| err.kind() == std::io::ErrorKind::TimedOut)
+}
+
+/// Percent-encode everything except the unreserved characters of RFC 3986.
+fn percent_encode(segment: &str, out: &mut String) {
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(char::from(byte));
+        } else {
+            // Writing to a String never fails
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+}
+
+/// A blocking HTTP client for the Lassie daemon reporting failures as [`RetrievalError`].
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    access_token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// Create a client talking to the given daemon.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[must_use]
+    pub fn new(daemon: &Daemon) -> Self {
+        Self::with_agent(daemon, ureq::Agent::new())
+    }
+
+    /// Create a client talking to the given daemon, using a custom-configured `ureq` agent.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[must_use]
+    pub fn with_agent(daemon: &Daemon, agent: ureq::Agent) -> Self {
+        Self::from_handle_with_agent(&daemon.handle(), agent)
+    }
+
+    /// Create a client talking to the daemon behind `handle`, e.g. from a worker thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[must_use]
+    pub fn from_handle(handle: &DaemonHandle) -> Self {
+        Self::from_handle_with_agent(handle, ureq::Agent::new())
+    }
+
+    /// Create a client talking to the daemon behind `handle`, using a custom-configured `ureq`
+    /// agent.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[must_use]
+    pub fn from_handle_with_agent(handle: &DaemonHandle, agent: ureq::Agent) -> Self {
+        Client {
+            base_url: handle.base_url(),
+            access_token: handle.access_token().map(str::to_string),
+            agent,
+        }
+    }
+
+    /// Create a client talking to a daemon running in another process, e.g. at
+    /// `http://127.0.0.1:8080`.
+    #[must_use]
+    pub fn from_url(base_url: &str, access_token: Option<String>) -> Self {
+        Client {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            access_token,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Start the retrieval described by `request`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the daemon cannot be reached or responds with an error
+    /// status code.
+    pub fn fetch(&self, request: &RetrievalRequest) -> Result<RetrievalResponse, RetrievalError> {
+        let url = format!("{}{}", self.base_url, request.path());
+        let mut req = self
+            .agent
+            .get(&url)
+            .set("Accept", request.format.accept())
+            .query("format", request.format.query_value());
+
+        if let Some(token) = &self.access_token {
+            req = req.set("Authorization", &format!("Bearer {token}"));
+        }
+        if let Some(id) = &request.request_id {
+            req = req.set(REQUEST_ID_HEADER, id);
+        }
+        if let Some(traceparent) = &request.traceparent {
+            req = req.set(TRACEPARENT_HEADER, traceparent);
+        }
+        if let Some(priority) = request.priority {
+            req = req.set(PRIORITY_HEADER, priority.as_str());
+        }
+        if let Some(timeout) = request.timeout {
+            // Go rejects a zero timeout, round sub-millisecond values up
+            let millis = timeout.as_millis().max(1);
+            req = req
+                .set(TIMEOUT_HEADER, &format!("{millis}ms"))
+                .timeout(timeout.saturating_add(TIMEOUT_GRACE));
+        }
+        if !request.providers.is_empty() {
+            let providers: Vec<&str> = request.providers.iter().map(Multiaddr::as_str).collect();
+            req = req.query("providers", &providers.join(","));
+        }
+        if !request.protocols.is_empty() {
+            req = req.query("protocols", &request.protocols.join(","));
+        }
+        if let Some(limit) = request.block_limit {
+            req = req.query("blockLimit", &limit.to_string());
+        }
+        if let Some(scope) = request.dag_scope {
+            req = req.query("dag-scope", scope.as_str());
+        }
+        if let Some(from) = request.entity_bytes_from {
+            let to = request
+                .entity_bytes_to
+                .map_or_else(|| "*".to_string(), |to| to.to_string());
+            req = req.query("entity-bytes", &format!("{from}:{to}"));
+        }
+
+        log::debug!("Fetching {url}");
+        match req.call() {
+            Ok(response) => {
+                let content_type = response.header("Content-Type");
+                if !content_type.is_some_and(|value| request.format.matches(value)) {
+                    return Err(RetrievalError::UnexpectedContentType {
+                        expected: request.format.accept().to_string(),
+                        actual: content_type.map(str::to_string),
+                    });
+                }
+                Ok(RetrievalResponse { response })
+            }
+            Err(ureq::Error::Status(status, response)) => {
+                let error_code = response.header(ERROR_CODE_HEADER).map(str::to_string);
+                let body = response.into_string().unwrap_or_default();
+                Err(RetrievalError::from_error_response(
+                    status,
+                    error_code.as_deref(),
+                    &body,
+                ))
+            }
+            Err(ureq::Error::Transport(err)) if is_timeout(&err) => {
+                Err(RetrievalError::Timeout(err.to_string()))
+            }
+            Err(ureq::Error::Transport(err)) => Err(RetrievalError::Transport(err.to_string())),
+        }
+    }
+
+    /// Retrieve all `requests`, running at most `concurrency` retrievals at the same time.
+    ///
+    /// The returned iterator yields the results in the order the retrievals complete, each one
+    /// paired with the index of its request in `requests`. The response bodies are read into
+    /// memory, see [`RetrievalResponse::read_to_end`].
+    ///
+    /// Dropping the iterator cancels the retrievals that have not started yet; the ones in flight
+    /// finish in the background.
+    pub fn fetch_many<I>(&self, requests: I, concurrency: usize) -> FetchMany
+    where
+        I: IntoIterator<Item = RetrievalRequest>,
+    {
+        let queue: VecDeque<_> = requests.into_iter().enumerate().collect();
+        let workers = concurrency.clamp(1, queue.len().max(1));
+        let queue = Arc::new(Mutex::new(queue));
+        let (tx, results) = mpsc::channel();
+
+        for _ in 0..workers {
+            let client = self.clone();
+            let queue = Arc::clone(&queue);
+            let tx = tx.clone();
+            std::thread::spawn(move || loop {
+                let next = queue.lock().ok().and_then(|mut queue| queue.pop_front());
+                let Some((index, request)) = next else {
+                    break;
+                };
+                let result = client
+                    .fetch(&request)
+                    .and_then(RetrievalResponse::read_to_end);
+                if tx.send((index, result)).is_err() {
+                    // The caller dropped the iterator
+                    break;
+                }
+            });
+        }
+
+        FetchMany { queue, results }
+    }
+}
+
+/// An iterator over the results of [`Client::fetch_many`].
+pub struct FetchMany {
+    queue: Arc<Mutex<VecDeque<(usize, RetrievalRequest)>>>,
+    results: mpsc::Receiver<(usize, Result<Vec<u8>, RetrievalError>)>,
+}
+
+impl Iterator for FetchMany {
+    type Item = (usize, Result<Vec<u8>, RetrievalError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The channel is closed after all workers have exited
+        self.results.recv().ok()
+    }
+}
+
+impl Drop for FetchMany {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.clear();
+        }
+    }
+}
+
+/// A successful response to a retrieval request.
+pub struct RetrievalResponse {
+    response: ureq::Response,
+}
+
+impl RetrievalResponse {
+    #[must_use]
+    pub fn content_type(&self) -> Option<&str> {
+        self.response.header("Content-Type")
+    }
+
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response.header(name)
+    }
+
+    /// The ID assigned to this retrieval by the daemon, see [`Daemon::cancel`].
+    #[must_use]
+    pub fn retrieval_id(&self) -> Option<&str> {
+        self.header(RETRIEVAL_ID_HEADER)
+    }
+
+    /// The correlation ID echoed by the daemon, see [`REQUEST_ID_HEADER`].
+    #[must_use]
+    pub fn request_id(&self) -> Option<&str> {
+        self.header(REQUEST_ID_HEADER)
+    }
+
+    /// Get a reader streaming the response body (typically a CAR file).
+    #[must_use]
+    pub fn into_reader(self) -> impl Read + Send {
+        self.response.into_reader()
+    }
+
+    /// Read the entire response body into memory.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`RetrievalError::StreamAborted`] when the daemon aborts the response
+    /// stream, e.g. because the block limit or the global timeout was reached.
+    pub fn read_to_end(self) -> Result<Vec<u8>, RetrievalError> {
+        let mut content = Vec::new();
+        self.into_reader()
+            .read_to_end(&mut content)
+            .map_err(|err| RetrievalError::from_stream_error(&err))?;
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn builds_ipns_path() {
+        let name: IpnsName = "/ipns/k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8"
+            .parse()
+            .unwrap();
+        let request = RetrievalRequest::ipns(name.clone());
+        assert_eq!(request.ipns_name(), Some(&name));
+        assert_eq!(
+            request.path(),
+            "/ipns/k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8"
+        );
+    }
+
+    #[test]
+    fn encodes_sub_path_segments() {
+        let request = RetrievalRequest::new("bafy").sub_path("/docs//user guide/Příliš.md");
+        assert_eq!(
+            request.path(),
+            "/ipfs/bafy/docs/user%20guide/P%C5%99%C3%ADli%C5%A1.md"
+        );
+    }
+
+    #[test]
+    fn checks_response_content_type() {
+        let lassie_car = "application/vnd.ipld.car;version=1;order=dfs;dups=y";
+        assert!(Format::CarV1.matches(lassie_car));
+        assert!(Format::CarV1.matches("application/vnd.ipld.car"));
+        assert!(!Format::CarV2.matches(lassie_car));
+        assert!(Format::CarV2.matches("application/vnd.ipld.car; version=\"2\""));
+        assert!(!Format::RawBlock.matches(lassie_car));
+        assert!(Format::RawBlock.matches("application/vnd.ipld.raw"));
+        assert!(!Format::CarV1.matches("text/plain; charset=utf-8"));
+    }
+
+    #[test]
+    fn rejects_invalid_ipns_names() {
+        for name in ["", "en.wikipedia-on-ipfs.org", "k51/sub/path", "k51?query"] {
+            assert!(
+                name.parse::<IpnsName>().is_err(),
+                "{name:?} should be rejected"
+            );
+        }
+    }
+}
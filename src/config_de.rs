@@ -0,0 +1,120 @@
+//! Deserializers for the human-friendly values of configuration files, e.g. `"30s"` or `"2GiB"`.
+
+use std::fmt::{self, Formatter};
+use std::time::Duration;
+
+use serde::de::{self, Visitor};
+use serde::Deserializer;
+
+/// A number of seconds or a string like `500ms`, `30s`, `5m` or `2h`.
+pub(crate) fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    deserializer.deserialize_any(UnitVisitor {
+        expecting: "a duration like \"30s\" or a number of seconds",
+        from_number: Duration::from_secs,
+        parse: parse_duration,
+    })
+}
+
+pub(crate) fn opt_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    duration(deserializer).map(Some)
+}
+
+/// A number of bytes or a string like `512MiB` or `2GiB`.
+pub(crate) fn size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    deserializer.deserialize_any(UnitVisitor {
+        expecting: "a size like \"512MiB\" or a number of bytes",
+        from_number: |bytes| bytes,
+        parse: parse_size,
+    })
+}
+
+pub(crate) fn opt_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    size(deserializer).map(Some)
+}
+
+/// Accepts both plain numbers and strings with a unit suffix.
+struct UnitVisitor<T> {
+    expecting: &'static str,
+    from_number: fn(u64) -> T,
+    parse: fn(&str) -> Option<T>,
+}
+
+impl<T> Visitor<'_> for UnitVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str(self.expecting)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        Ok((self.from_number)(v))
+    }
+
+    // TOML integers are signed
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        u64::try_from(v)
+            .map(self.from_number)
+            .map_err(|_| E::custom(format!("expected {}, found {v}", self.expecting)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        (self.parse)(v)
+            .ok_or_else(|| E::custom(format!("expected {}, found {v:?}", self.expecting)))
+    }
+}
+
+/// Split `30s` into `(30, "s")`.
+fn split_unit(value: &str) -> Option<(u64, &str)> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    Some((number.parse().ok()?, unit.trim_start()))
+}
+
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = split_unit(value)?;
+    match unit {
+        "ms" => Some(Duration::from_millis(number)),
+        "" | "s" => Some(Duration::from_secs(number)),
+        "m" => number.checked_mul(60).map(Duration::from_secs),
+        "h" => number.checked_mul(60 * 60).map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = split_unit(value)?;
+    let shift = match unit {
+        "" | "B" => 0,
+        "KiB" => 10,
+        "MiB" => 20,
+        "GiB" => 30,
+        "TiB" => 40,
+        _ => return None,
+    };
+    number.checked_mul(1 << shift)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5 m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2d"), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_size("512B"), Some(512));
+        assert_eq!(parse_size("1MB"), None);
+        assert_eq!(parse_size(&format!("{}TiB", u64::MAX)), None);
+    }
+}
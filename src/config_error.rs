@@ -0,0 +1,788 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::multiaddr::Multiaddr;
+use crate::{AdminAddress, DaemonConfig};
+
+/// A problem found by [`DaemonConfig::validate`].
+///
+/// The first value of most variants is the name of the offending [`DaemonConfig`] field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// Go limits the largest representable duration to approximately 290 years.
+    DurationIsTooLong(&'static str, Duration),
+    ContainsNullByte(&'static str, String),
+    PathContainsNullByte(&'static str, PathBuf),
+    PathIsNotValidUtf8(&'static str, PathBuf),
+    TempDirDoesNotExist(PathBuf),
+    TempDirNotWritable {
+        path: PathBuf,
+        reason: String,
+    },
+    /// [`DaemonConfig::port`] is set while [`DaemonConfig::disable_listener`] is `true`.
+    PortWithDisabledListener(u16),
+    /// Two listeners (the HTTP listener, the admin listener or the
+    /// [extra listeners](DaemonConfig::extra_listeners)) are configured to use the same port.
+    PortConflict(u16),
+    /// [`ConnectionManagerConfig::low_water`](crate::ConnectionManagerConfig::low_water) is
+    /// greater than [`ConnectionManagerConfig::high_water`](crate::ConnectionManagerConfig::high_water).
+    InvalidConnectionLimits {
+        low_water: u32,
+        high_water: u32,
+    },
+    /// The value is not an absolute `http://` or `https://` URL.
+    InvalidUrl(&'static str, String),
+    /// [`DaemonConfig::libp2p_transports`] is an empty list.
+    NoLibp2pTransports,
+    /// [`CircuitBreakerConfig::failures`](crate::CircuitBreakerConfig::failures) is zero.
+    CircuitBreakerWithoutFailures,
+    /// [`DaemonConfig::mmap_car_store`] is zero.
+    EmptyMmapCarStore,
+    /// `DaemonConfig::listener_fd` is set together with [`DaemonConfig::port`] or
+    /// [`DaemonConfig::disable_listener`]. The value is the name of the conflicting field.
+    ListenerFdConflict(&'static str),
+    /// [`DaemonConfig::reuse_port`] is enabled without a fixed [`DaemonConfig::port`].
+    ReusePortWithoutPort,
+    /// [`DaemonConfig::extra_listeners`] are configured while
+    /// [`DaemonConfig::disable_listener`] is `true`.
+    ExtraListenersWithDisabledListener,
+    /// A [`WatchdogConfig`](crate::WatchdogConfig) value is zero, the value is the name of its
+    /// field.
+    ZeroWatchdogSetting(&'static str),
+    /// [`DaemonConfig::max_download_rate`] or
+    /// [`DaemonConfig::max_download_rate_per_retrieval`] is zero, the value is the name of the
+    /// field.
+    ZeroDownloadRate(&'static str),
+    /// [`DaemonConfig::max_concurrent_retrievals`] is zero.
+    NoConcurrentRetrievals,
+    /// [`DaemonConfig::max_queued_retrievals`] is set without
+    /// [`DaemonConfig::max_concurrent_retrievals`].
+    QueueWithoutRetrievalLimit,
+    /// An address in [`DaemonConfig::bootstrap_peers`] or
+    /// [`DaemonConfig::preconnect_providers`] has no `/p2p/` component, Lassie cannot connect to
+    /// a peer without knowing its ID.
+    MissingPeerId(&'static str, Multiaddr),
+    /// A setting the Go version of Lassie has no counterpart for, the value is the name of the
+    /// field. At the moment, this is [`DaemonConfig::graphsync_provider_timeout`].
+    Unsupported(&'static str),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid Lassie daemon configuration: ")?;
+        match self {
+            ConfigError::DurationIsTooLong(field, d) => f.write_fmt(format_args!(
+                "{field} duration {d:#?} is too long, Go limits the largest representable duration to approximately 290 years",
+            )),
+            ConfigError::ContainsNullByte(field, value) => f.write_fmt(format_args!(
+                "null bytes are not allowed in {field} (value: {value:?})",
+            )),
+            ConfigError::PathContainsNullByte(field, path) => f.write_fmt(format_args!(
+                "null bytes are not allowed in {field} (value: {:?})",
+                path.display(),
+            )),
+            ConfigError::PathIsNotValidUtf8(field, path) => f.write_fmt(format_args!(
+                "paths that are not valid UTF-8 are not supported in {field} (value: {:?})",
+                path.display(),
+            )),
+            ConfigError::TempDirDoesNotExist(path) => f.write_fmt(format_args!(
+                "temp_dir {:?} does not exist or is not a directory",
+                path.display(),
+            )),
+            ConfigError::TempDirNotWritable { path, reason } => f.write_fmt(format_args!(
+                "temp_dir {:?} is not writable: {reason}",
+                path.display(),
+            )),
+            ConfigError::PortWithDisabledListener(port) => f.write_fmt(format_args!(
+                "port {port} is configured, but the HTTP listener is disabled",
+            )),
+            ConfigError::PortConflict(port) => f.write_fmt(format_args!(
+                "two listeners cannot share port {port}",
+            )),
+            ConfigError::InvalidConnectionLimits {
+                low_water,
+                high_water,
+            } => f.write_fmt(format_args!(
+                "connection_manager low_water ({low_water}) is greater than high_water ({high_water})",
+            )),
+            ConfigError::InvalidUrl(field, value) => f.write_fmt(format_args!(
+                "{field} must be an http:// or https:// URL (value: {value:?})",
+            )),
+            ConfigError::NoLibp2pTransports => {
+                f.write_str("libp2p_transports must enable at least one transport")
+            }
+            ConfigError::CircuitBreakerWithoutFailures => {
+                f.write_str("circuit_breaker failures must be at least 1")
+            }
+            ConfigError::EmptyMmapCarStore => {
+                f.write_str("mmap_car_store must be at least 1 byte")
+            }
+            ConfigError::ListenerFdConflict(field) => f.write_fmt(format_args!(
+                "listener_fd cannot be combined with {field}",
+            )),
+            ConfigError::ReusePortWithoutPort => f.write_str("reuse_port requires a non-zero port"),
+            ConfigError::ExtraListenersWithDisabledListener => {
+                f.write_str("extra_listeners require the HTTP listener, it's disabled")
+            }
+            ConfigError::ZeroWatchdogSetting(field) => {
+                f.write_fmt(format_args!("watchdog {field} must be greater than zero"))
+            }
+            ConfigError::ZeroDownloadRate(field) => {
+                f.write_fmt(format_args!("{field} must be at least 1 byte per second"))
+            }
+            ConfigError::NoConcurrentRetrievals => {
+                f.write_str("max_concurrent_retrievals must be at least 1")
+            }
+            ConfigError::QueueWithoutRetrievalLimit => {
+                f.write_str("max_queued_retrievals requires max_concurrent_retrievals")
+            }
+            ConfigError::MissingPeerId(field, addr) => f.write_fmt(format_args!(
+                "{field} address `{addr}` must include the peer ID, e.g. `{addr}/p2p/12D3KooW...`"
+            )),
+            ConfigError::Unsupported(field) => {
+                f.write_fmt(format_args!("{field} is not supported by Lassie"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub(crate) fn validate(config: &DaemonConfig) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+
+    let durations = [
+        ("provider_timeout", config.provider_timeout),
+        ("http_provider_timeout", config.http_provider_timeout),
+        ("bitswap_provider_timeout", config.bitswap_provider_timeout),
+        ("global_timeout", config.global_timeout),
+        ("idle_shutdown", config.idle_shutdown),
+        (
+            "connection_manager",
+            config.connection_manager.as_ref().map(|cm| cm.grace_period),
+        ),
+        (
+            "circuit_breaker",
+            config.circuit_breaker.as_ref().map(|cb| cb.cool_down),
+        ),
+        ("bitswap_keep_alive", config.bitswap_keep_alive),
+    ];
+    for (field, duration) in durations {
+        if let Some(d) = duration {
+            if i64::try_from(d.as_nanos()).is_err() {
+                errors.push(ConfigError::DurationIsTooLong(field, d));
+            }
+        }
+    }
+
+    let admin_access_token = config
+        .admin_listener
+        .as_ref()
+        .and_then(|admin| admin.access_token.as_deref());
+    let strings = [
+        ("access_token", config.access_token.as_deref()),
+        ("user_agent", config.user_agent.as_deref()),
+        ("event_recorder_url", config.event_recorder_url.as_deref()),
+        ("event_recorder_auth", config.event_recorder_auth.as_deref()),
+        (
+            "event_recorder_instance_id",
+            config.event_recorder_instance_id.as_deref(),
+        ),
+        (
+            "delegated_routing_url",
+            config.delegated_routing_url.as_deref(),
+        ),
+        ("admin_listener", admin_access_token),
+        (
+            "otlp",
+            config.otlp.as_ref().map(|otlp| otlp.endpoint.as_str()),
+        ),
+        (
+            "otlp",
+            config
+                .otlp
+                .as_ref()
+                .and_then(|otlp| otlp.service_name.as_deref()),
+        ),
+    ]
+    .into_iter()
+    .chain(
+        config
+            .extra_listeners
+            .iter()
+            .map(|extra| ("extra_listeners", extra.access_token.as_deref())),
+    )
+    .chain(
+        config
+            .bootstrap_peers
+            .iter()
+            .flatten()
+            .map(|peer| ("bootstrap_peers", Some(peer.as_str()))),
+    )
+    .chain(
+        config
+            .preconnect_providers
+            .iter()
+            .map(|addr| ("preconnect_providers", Some(addr.as_str()))),
+    );
+    for (field, value) in strings {
+        if let Some(value) = value.filter(|v| v.contains('\0')) {
+            errors.push(ConfigError::ContainsNullByte(field, value.to_string()));
+        }
+    }
+
+    check_paths(config, &mut errors);
+
+    // A missing directory is fine when Daemon::start is going to create it
+    let will_create = |dir: &Path| config.create_temp_dir && !dir.exists();
+    if let Some(dir) = config.temp_dir.as_deref().filter(|dir| !will_create(dir)) {
+        if let Some(err) = check_temp_dir(dir) {
+            errors.push(err);
+        }
+    }
+
+    check_settings(config, &mut errors);
+    errors.extend(check_peer_ids(config));
+    errors
+}
+
+/// Check for settings the Go side would ignore. `GoConfig::new` runs this check too.
+pub(crate) fn check_unsupported(config: &DaemonConfig) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    if config.graphsync_provider_timeout.is_some() {
+        errors.push(ConfigError::Unsupported("graphsync_provider_timeout"));
+    }
+    errors
+}
+
+/// Check that the peers Lassie dials at startup have IDs. `GoConfig::new` runs this check too,
+/// the Go side would fail with a less helpful error.
+pub(crate) fn check_peer_ids(config: &DaemonConfig) -> Vec<ConfigError> {
+    let bootstrap_peers = config.bootstrap_peers.iter().flatten();
+    bootstrap_peers
+        .map(|addr| ("bootstrap_peers", addr))
+        .chain(
+            config
+                .preconnect_providers
+                .iter()
+                .map(|addr| ("preconnect_providers", addr)),
+        )
+        .filter(|(_, addr)| addr.peer_id().is_none())
+        .map(|(field, addr)| ConfigError::MissingPeerId(field, addr.clone()))
+        .collect()
+}
+
+/// Check that the paths can be passed to Go.
+fn check_paths(config: &DaemonConfig, errors: &mut Vec<ConfigError>) {
+    let admin_socket = match config.admin_listener.as_ref().map(|admin| &admin.address) {
+        Some(AdminAddress::UnixSocket(path)) => Some(path.as_path()),
+        _ => None,
+    };
+    let client_certificate = config.outbound_http.client_certificate.as_ref();
+    let paths = [
+        ("temp_dir", config.temp_dir.as_deref()),
+        (
+            "block_cache",
+            config.block_cache.as_ref().map(|cache| cache.dir.as_path()),
+        ),
+        ("log_file", config.log_file.as_deref()),
+        ("event_log", config.event_log.as_deref()),
+        ("admin_listener", admin_socket),
+        (
+            "outbound_http",
+            client_certificate.map(|cert| cert.cert_file.as_path()),
+        ),
+        (
+            "outbound_http",
+            client_certificate.map(|cert| cert.key_file.as_path()),
+        ),
+    ]
+    .into_iter()
+    .chain(
+        config
+            .extra_root_certs
+            .iter()
+            .flatten()
+            .map(|path| ("extra_root_certs", Some(path.as_path()))),
+    )
+    .chain(
+        config
+            .extra_listeners
+            .iter()
+            .filter_map(|extra| match &extra.address {
+                AdminAddress::UnixSocket(path) => Some(("extra_listeners", Some(path.as_path()))),
+                AdminAddress::Port(_) => None,
+            }),
+    );
+    for (field, path) in paths {
+        if let Some(path) = path {
+            match path.to_str() {
+                None => errors.push(ConfigError::PathIsNotValidUtf8(field, path.to_path_buf())),
+                Some(str) if str.contains('\0') => {
+                    errors.push(ConfigError::PathContainsNullByte(field, path.to_path_buf()));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+/// Check that the listeners don't conflict with each other.
+fn check_listener_ports(config: &DaemonConfig, errors: &mut Vec<ConfigError>) {
+    if config.disable_listener && config.port != 0 {
+        errors.push(ConfigError::PortWithDisabledListener(config.port));
+    }
+    if let Some(AdminAddress::Port(admin_port)) =
+        config.admin_listener.as_ref().map(|admin| &admin.address)
+    {
+        if *admin_port != 0 && *admin_port == config.port && !config.disable_listener {
+            errors.push(ConfigError::PortConflict(config.port));
+        }
+    }
+    if !config.extra_listeners.is_empty() {
+        if config.disable_listener {
+            errors.push(ConfigError::ExtraListenersWithDisabledListener);
+        }
+        // The ports taken by the listeners checked so far
+        let mut ports: Vec<u16> = [Some(config.port)]
+            .into_iter()
+            .chain(
+                config
+                    .admin_listener
+                    .iter()
+                    .map(|admin| match admin.address {
+                        AdminAddress::Port(port) => Some(port),
+                        AdminAddress::UnixSocket(_) => None,
+                    }),
+            )
+            .flatten()
+            .filter(|port| *port != 0)
+            .collect();
+        for extra in &config.extra_listeners {
+            if let AdminAddress::Port(port) = extra.address {
+                if port != 0 && ports.contains(&port) {
+                    errors.push(ConfigError::PortConflict(port));
+                }
+                ports.push(port);
+            }
+        }
+    }
+}
+
+/// Check the values that must be consistent with each other or follow a syntax.
+fn check_settings(config: &DaemonConfig, errors: &mut Vec<ConfigError>) {
+    check_listener_ports(config, errors);
+
+    if let Some(cm) = &config.connection_manager {
+        if cm.low_water > cm.high_water {
+            errors.push(ConfigError::InvalidConnectionLimits {
+                low_water: cm.low_water,
+                high_water: cm.high_water,
+            });
+        }
+    }
+
+    if let Some(url) = &config.delegated_routing_url {
+        if !is_http_url(url) {
+            errors.push(ConfigError::InvalidUrl(
+                "delegated_routing_url",
+                url.clone(),
+            ));
+        }
+    }
+
+    if let Some(otlp) = &config.otlp {
+        if !is_http_url(&otlp.endpoint) {
+            errors.push(ConfigError::InvalidUrl("otlp", otlp.endpoint.clone()));
+        }
+    }
+
+    if config.libp2p_transports.as_ref().is_some_and(Vec::is_empty) {
+        errors.push(ConfigError::NoLibp2pTransports);
+    }
+
+    if config
+        .circuit_breaker
+        .as_ref()
+        .is_some_and(|cb| cb.failures == 0)
+    {
+        errors.push(ConfigError::CircuitBreakerWithoutFailures);
+    }
+
+    if let Some(watchdog) = &config.watchdog {
+        if watchdog.interval.is_zero() {
+            errors.push(ConfigError::ZeroWatchdogSetting("interval"));
+        }
+        if watchdog.timeout.is_zero() {
+            errors.push(ConfigError::ZeroWatchdogSetting("timeout"));
+        }
+        if watchdog.failures == 0 {
+            errors.push(ConfigError::ZeroWatchdogSetting("failures"));
+        }
+    }
+
+    errors.extend(check_unsupported(config));
+
+    if config.mmap_car_store == Some(0) {
+        errors.push(ConfigError::EmptyMmapCarStore);
+    }
+
+    match (
+        config.max_concurrent_retrievals,
+        config.max_queued_retrievals,
+    ) {
+        (Some(0), _) => errors.push(ConfigError::NoConcurrentRetrievals),
+        (None, Some(_)) => errors.push(ConfigError::QueueWithoutRetrievalLimit),
+        _ => {}
+    }
+
+    if config.max_download_rate == Some(0) {
+        errors.push(ConfigError::ZeroDownloadRate("max_download_rate"));
+    }
+    if config.max_download_rate_per_retrieval == Some(0) {
+        errors.push(ConfigError::ZeroDownloadRate(
+            "max_download_rate_per_retrieval",
+        ));
+    }
+
+    #[cfg(unix)]
+    if config.listener_fd.is_some() {
+        if config.port != 0 {
+            errors.push(ConfigError::ListenerFdConflict("port"));
+        }
+        if config.disable_listener {
+            errors.push(ConfigError::ListenerFdConflict("disable_listener"));
+        }
+        if config.reuse_port {
+            errors.push(ConfigError::ListenerFdConflict("reuse_port"));
+        }
+    }
+    if config.reuse_port && config.port == 0 {
+        errors.push(ConfigError::ReusePortWithoutPort);
+    }
+}
+
+/// A cheap check catching typos like a missing scheme, the Go side parses the URL properly.
+fn is_http_url(url: &str) -> bool {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    rest.is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+}
+
+fn check_temp_dir(dir: &Path) -> Option<ConfigError> {
+    if !dir.is_dir() {
+        return Some(ConfigError::TempDirDoesNotExist(dir.to_path_buf()));
+    }
+
+    // Create and remove a file, that's the only reliable check across platforms
+    let probe = dir.join(format!(".lassie-probe-{}", std::process::id()));
+    let result = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|file| {
+            drop(file);
+            std::fs::remove_file(&probe)
+        });
+    result.err().map(|err| ConfigError::TempDirNotWritable {
+        path: dir.to_path_buf(),
+        reason: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        AdminListenerConfig, CircuitBreakerConfig, ConnectionManagerConfig, ExtraListenerConfig,
+        OtlpConfig, WatchdogConfig,
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(validate(&DaemonConfig::default()), vec![]);
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let missing_dir = std::env::temp_dir().join("lassie-validate-missing-dir");
+        let bootstrap_peer: Multiaddr = "/unix/\0".parse().unwrap();
+        let config = DaemonConfig {
+            temp_dir: Some(missing_dir.clone()),
+            port: 3000,
+            disable_listener: true,
+            global_timeout: Some(Duration::MAX),
+            access_token: Some("secret\0".to_string()),
+            bootstrap_peers: Some(vec![bootstrap_peer.clone()]),
+            connection_manager: Some(ConnectionManagerConfig {
+                low_water: 10,
+                high_water: 5,
+                ..ConnectionManagerConfig::default()
+            }),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            validate(&config),
+            vec![
+                ConfigError::DurationIsTooLong("global_timeout", Duration::MAX),
+                ConfigError::ContainsNullByte("access_token", "secret\0".to_string()),
+                ConfigError::ContainsNullByte("bootstrap_peers", "/unix/\0".to_string()),
+                ConfigError::TempDirDoesNotExist(missing_dir),
+                ConfigError::PortWithDisabledListener(3000),
+                ConfigError::InvalidConnectionLimits {
+                    low_water: 10,
+                    high_water: 5
+                },
+                ConfigError::MissingPeerId("bootstrap_peers", bootstrap_peer),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_port_conflict() {
+        let config = DaemonConfig {
+            port: 3000,
+            admin_listener: Some(AdminListenerConfig {
+                address: AdminAddress::Port(3000),
+                access_token: None,
+                pprof: false,
+            }),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config), vec![ConfigError::PortConflict(3000)]);
+    }
+
+    #[test]
+    fn rejects_empty_transport_list() {
+        let config = DaemonConfig {
+            libp2p_transports: Some(vec![]),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config), vec![ConfigError::NoLibp2pTransports]);
+    }
+
+    #[test]
+    fn rejects_circuit_breaker_without_failures() {
+        let config = |failures| DaemonConfig {
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failures,
+                ..CircuitBreakerConfig::default()
+            }),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config(1)), vec![]);
+        assert_eq!(
+            validate(&config(0)),
+            vec![ConfigError::CircuitBreakerWithoutFailures]
+        );
+    }
+
+    #[test]
+    fn checks_extra_listeners() {
+        let extra = |address| ExtraListenerConfig {
+            address,
+            access_token: Some("proxy".to_string()),
+        };
+        let config = DaemonConfig {
+            port: 3000,
+            extra_listeners: vec![
+                extra(AdminAddress::UnixSocket(PathBuf::from("/run/lassie.sock"))),
+                extra(AdminAddress::Port(0)),
+                extra(AdminAddress::Port(3001)),
+            ],
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config), vec![]);
+
+        let config = DaemonConfig {
+            disable_listener: true,
+            extra_listeners: vec![
+                extra(AdminAddress::Port(3001)),
+                extra(AdminAddress::Port(3001)),
+            ],
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            validate(&config),
+            vec![
+                ConfigError::ExtraListenersWithDisabledListener,
+                ConfigError::PortConflict(3001),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_zero_watchdog_settings() {
+        let config = |watchdog| DaemonConfig {
+            watchdog: Some(watchdog),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config(WatchdogConfig::default())), vec![]);
+        assert_eq!(
+            validate(&config(WatchdogConfig {
+                interval: Duration::ZERO,
+                timeout: Duration::ZERO,
+                failures: 0,
+            })),
+            vec![
+                ConfigError::ZeroWatchdogSetting("interval"),
+                ConfigError::ZeroWatchdogSetting("timeout"),
+                ConfigError::ZeroWatchdogSetting("failures"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_empty_mmap_car_store() {
+        let config = |size| DaemonConfig {
+            mmap_car_store: Some(size),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config(1 << 30)), vec![]);
+        assert_eq!(validate(&config(0)), vec![ConfigError::EmptyMmapCarStore]);
+    }
+
+    #[test]
+    fn checks_retrieval_queue() {
+        let config = |limit, queued| DaemonConfig {
+            max_concurrent_retrievals: limit,
+            max_queued_retrievals: queued,
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config(Some(4), None)), vec![]);
+        assert_eq!(validate(&config(Some(4), Some(0))), vec![]);
+        assert_eq!(
+            validate(&config(Some(0), Some(16))),
+            vec![ConfigError::NoConcurrentRetrievals]
+        );
+        assert_eq!(
+            validate(&config(None, Some(16))),
+            vec![ConfigError::QueueWithoutRetrievalLimit]
+        );
+    }
+
+    #[test]
+    fn rejects_zero_download_rates() {
+        let config = |rate| DaemonConfig {
+            max_download_rate: Some(rate),
+            max_download_rate_per_retrieval: Some(rate),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config(1 << 20)), vec![]);
+        assert_eq!(
+            validate(&config(0)),
+            vec![
+                ConfigError::ZeroDownloadRate("max_download_rate"),
+                ConfigError::ZeroDownloadRate("max_download_rate_per_retrieval"),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_provider_timeouts() {
+        let config = DaemonConfig {
+            http_provider_timeout: Some(Duration::MAX),
+            bitswap_provider_timeout: Some(Duration::from_secs(20)),
+            graphsync_provider_timeout: Some(Duration::from_secs(20)),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            validate(&config),
+            vec![
+                ConfigError::DurationIsTooLong("http_provider_timeout", Duration::MAX),
+                ConfigError::Unsupported("graphsync_provider_timeout"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_reuse_port_without_port() {
+        let config = |port| DaemonConfig {
+            port,
+            reuse_port: true,
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config(8080)), vec![]);
+        assert_eq!(
+            validate(&config(0)),
+            vec![ConfigError::ReusePortWithoutPort]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_listener_fd_conflicts() {
+        let config = DaemonConfig {
+            listener_fd: Some(3),
+            port: 8080,
+            disable_listener: true,
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            validate(&config),
+            vec![
+                ConfigError::PortWithDisabledListener(8080),
+                ConfigError::ListenerFdConflict("port"),
+                ConfigError::ListenerFdConflict("disable_listener"),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_delegated_routing_url() {
+        let config = |url: &str| DaemonConfig {
+            delegated_routing_url: Some(url.to_string()),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config("https://delegated-ipfs.dev")), vec![]);
+        assert_eq!(validate(&config("http://127.0.0.1:8080/")), vec![]);
+        assert_eq!(
+            validate(&config("delegated-ipfs.dev")),
+            vec![ConfigError::InvalidUrl(
+                "delegated_routing_url",
+                "delegated-ipfs.dev".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn checks_otlp_endpoint() {
+        let config = |endpoint: &str| DaemonConfig {
+            otlp: Some(OtlpConfig {
+                endpoint: endpoint.to_string(),
+                service_name: Some("indexer\0".to_string()),
+            }),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            validate(&config("localhost:4318")),
+            vec![
+                ConfigError::ContainsNullByte("otlp", "indexer\0".to_string()),
+                ConfigError::InvalidUrl("otlp", "localhost:4318".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_missing_temp_dir_when_creating_it() {
+        let config = DaemonConfig {
+            temp_dir: Some(std::env::temp_dir().join("lassie-validate-missing-dir")),
+            create_temp_dir: true,
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config), vec![]);
+    }
+
+    #[test]
+    fn accepts_writable_temp_dir() {
+        let config = DaemonConfig {
+            temp_dir: Some(std::env::temp_dir()),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(validate(&config), vec![]);
+    }
+}
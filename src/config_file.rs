@@ -0,0 +1,172 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::DaemonConfig;
+
+/// The reason why [`DaemonConfig::from_file`] or [`DaemonConfig::from_toml`] failed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigFileError {
+    /// The file cannot be read.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The content is not valid TOML or does not describe a [`DaemonConfig`], e.g. because it
+    /// contains an unknown option or a value of the wrong type. `path` is `None` for
+    /// [`DaemonConfig::from_toml`].
+    Parse {
+        path: Option<PathBuf>,
+        message: String,
+    },
+}
+
+impl Display for ConfigFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConfigFileError::Io { path, source } => f.write_fmt(format_args!(
+                "cannot read Lassie configuration file {:?}: {source}",
+                path.display(),
+            )),
+            ConfigFileError::Parse {
+                path: Some(path),
+                message,
+            } => f.write_fmt(format_args!(
+                "invalid Lassie configuration file {:?}: {message}",
+                path.display(),
+            )),
+            ConfigFileError::Parse {
+                path: None,
+                message,
+            } => f.write_fmt(format_args!("invalid Lassie configuration: {message}")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigFileError::Io { source, .. } => Some(source),
+            ConfigFileError::Parse { .. } => None,
+        }
+    }
+}
+
+pub(crate) fn from_file(path: &Path) -> Result<DaemonConfig, ConfigFileError> {
+    let text = std::fs::read_to_string(path).map_err(|source| ConfigFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    from_toml(&text).map_err(|err| match err {
+        ConfigFileError::Parse { message, .. } => ConfigFileError::Parse {
+            path: Some(path.to_path_buf()),
+            message,
+        },
+        other => other,
+    })
+}
+
+pub(crate) fn from_toml(text: &str) -> Result<DaemonConfig, ConfigFileError> {
+    toml::from_str(text).map_err(|err| ConfigFileError::Parse {
+        path: None,
+        message: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AdminAddress, Libp2pTransport, LogFormat};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_toml() {
+        let config = from_toml(
+            r#"
+            temp_dir = "/var/lib/lassie"
+            create_temp_dir = true
+            private_temp_dir = true
+            port = 8080
+            provider_timeout = "20s"
+            global_timeout = 300
+            allowed_client_ips = ["127.0.0.1/32", "10.0.0.0/8"]
+            preconnect_providers = ["/ip4/192.0.2.1/tcp/24001/p2p/12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz"]
+            libp2p_transports = ["tcp", "quic", "webrtc-direct"]
+            mmap_car_store = "512MiB"
+            log_format = "json"
+            log_file = "/var/log/lassie.log"
+
+            [temp_dir_eviction]
+            max_size = "20GiB"
+
+            [block_cache]
+            dir = "/var/cache/lassie"
+            max_size = 1073741824
+
+            [admin_listener]
+            address = { unix_socket = "/run/lassie/admin.sock" }
+            access_token = "secret"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.temp_dir, Some("/var/lib/lassie".into()));
+        assert!(config.create_temp_dir);
+        assert!(config.private_temp_dir);
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.provider_timeout, Some(Duration::from_secs(20)));
+        assert_eq!(config.global_timeout, Some(Duration::from_secs(300)));
+        assert_eq!(
+            config.allowed_client_ips,
+            Some(vec![
+                "127.0.0.1/32".parse().unwrap(),
+                "10.0.0.0/8".parse().unwrap()
+            ])
+        );
+        assert_eq!(
+            config.libp2p_transports,
+            Some(vec![
+                Libp2pTransport::Tcp,
+                Libp2pTransport::Quic,
+                Libp2pTransport::WebRtcDirect
+            ])
+        );
+        assert_eq!(config.mmap_car_store, Some(512 << 20));
+        assert_eq!(config.log_format, LogFormat::Json);
+        let eviction = config.temp_dir_eviction.unwrap();
+        assert_eq!(eviction.max_size, 20 << 30);
+        assert_eq!(eviction.interval, Duration::from_secs(60), "default value");
+        assert_eq!(config.block_cache.unwrap().max_size, 1 << 30);
+        let admin = config.admin_listener.unwrap();
+        assert_eq!(
+            admin.address,
+            AdminAddress::UnixSocket("/run/lassie/admin.sock".into())
+        );
+        assert!(!admin.pprof);
+        assert!(!config.disable_dht, "fields not listed keep their defaults");
+    }
+
+    #[test]
+    fn rejects_invalid_toml() {
+        let err = |text: &str| match from_toml(text) {
+            Err(ConfigFileError::Parse {
+                path: None,
+                message,
+            }) => message,
+            other => panic!("unexpected result {other:?}"),
+        };
+        assert!(err("prot = 8080").contains("unknown field `prot`"));
+        assert!(err("provider_timeout = \"20 parsecs\"").contains("a duration like \"30s\""));
+        assert!(err("global_timeout = -1").contains("a duration like \"30s\""));
+    }
+
+    #[test]
+    fn reports_file_errors() {
+        let path = std::env::temp_dir().join("rusty-lassie-missing-config.toml");
+        match from_file(&path) {
+            Err(ConfigFileError::Io { path: p, .. }) => assert_eq!(p, path),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::car::reader::CarReader;
+use crate::car::{check_hash, writer, CarError, Cid, RAW};
+use crate::{Client, DagScope, RetrievalError, RetrievalRequest};
+
+/// The outcome of [`Client::download_to`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadReport {
+    /// The number of blocks in the CAR file.
+    pub blocks: u64,
+    /// How many of `blocks` were kept from a previous, interrupted download.
+    pub resumed_blocks: u64,
+    /// The size of the CAR file in bytes.
+    pub bytes: u64,
+}
+
+/// An error returned by [`Client::download_to`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DownloadError {
+    /// The retrieval failed, see [`RetrievalError`].
+    Retrieval(RetrievalError),
+    /// The daemon's response is not a valid CAR stream.
+    Car(CarError),
+    /// The CAR file cannot be read or written.
+    Io(io::Error),
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot download the CAR file: ")?;
+        match self {
+            DownloadError::Retrieval(err) => write!(f, "{err}"),
+            DownloadError::Car(err) => write!(f, "{err}"),
+            DownloadError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DownloadError::Retrieval(err) => Some(err),
+            DownloadError::Car(err) => Some(err),
+            DownloadError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(err: io::Error) -> Self {
+        DownloadError::Io(err)
+    }
+}
+
+/// The verified part of a CAR file left behind by an interrupted download.
+#[derive(Default)]
+struct Partial {
+    roots: Vec<Cid>,
+    /// The offset where the first missing or corrupted section starts.
+    len: u64,
+    blocks: HashSet<Cid>,
+    /// The total size of `raw` blocks. For `UnixFS` files with raw leaves received in DFS order,
+    /// that's the number of file bytes we already have, counted from the first requested byte.
+    raw_bytes: u64,
+}
+
+impl Partial {
+    fn read(file: &File) -> Partial {
+        let Ok(mut reader) = CarReader::new(BufReader::new(file)) else {
+            return Partial::default();
+        };
+        let mut partial = Partial {
+            roots: reader.roots().to_vec(),
+            len: reader.offset(),
+            ..Partial::default()
+        };
+        // Stop at the first truncated or corrupted block, everything after it gets overwritten
+        while let Ok(Some(block)) = reader.next_block() {
+            if check_hash(&block.cid, &block.data).is_some() {
+                break;
+            }
+            partial.len = block.offset + block.section_len;
+            if block.cid.codec() == RAW {
+                partial.raw_bytes += block.data.len() as u64;
+            }
+            partial.blocks.insert(block.cid);
+        }
+        partial
+    }
+
+    /// The first byte of the file to request when resuming `request`, `None` when the whole
+    /// range must be retrieved again.
+    fn resume_offset(&self, request: &RetrievalRequest) -> Option<u64> {
+        if request.dag_scope != Some(DagScope::Entity) || self.raw_bytes == 0 {
+            return None;
+        }
+        let same_root = match request.ipns_name() {
+            // The name may point elsewhere now, that's checked against the roots of the response
+            Some(_) => true,
+            None => request
+                .cid()
+                .parse::<Cid>()
+                .is_ok_and(|cid| self.roots == [cid]),
+        };
+        let from = request.entity_bytes_from.unwrap_or(0) + self.raw_bytes;
+        // Nothing is left to skip to, the range is retrieved again and only new blocks are written
+        let past_end = request.entity_bytes_to.is_some_and(|to| from > to);
+        (same_root && !past_end).then_some(from)
+    }
+}
+
+impl Client {
+    /// Retrieve the content as a CAR file saved to `path`.
+    ///
+    /// When `path` contains a CAR file from an interrupted download, e.g. because the connection
+    /// to the provider dropped, the blocks already on disk are verified and kept, and only the
+    /// remaining blocks are appended. For requests with [`DagScope::Entity`], the daemon is asked
+    /// to skip the part of the file that was already downloaded (via the `entity-bytes`
+    /// parameter), for other requests, or when the file on disk has a different root, the DAG is
+    /// retrieved again, but only new blocks are written.
+    /// Retry the call with the same arguments to resume.
+    ///
+    /// The blocks in a resumed CAR file are not necessarily in DFS order.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the retrieval fails or the file cannot be written. The
+    /// blocks received before the failure stay on disk for the next attempt.
+    pub fn download_to(
+        &self,
+        request: &RetrievalRequest,
+        path: &Path,
+    ) -> Result<DownloadReport, DownloadError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut partial = Partial::read(&file);
+        if !partial.blocks.is_empty() {
+            log::debug!(
+                "Resuming download to {} with {} blocks",
+                path.display(),
+                partial.blocks.len()
+            );
+        }
+
+        let resume_from = partial.resume_offset(request);
+        let mut reader = match resume_from {
+            Some(from) => {
+                let mut request = request.clone();
+                request.entity_bytes_from = Some(from);
+                self.car_stream(&request)?
+            }
+            None => self.car_stream(request)?,
+        };
+
+        // Start over when the root has changed, e.g. when the IPNS name points elsewhere now
+        if reader.roots() != partial.roots.as_slice() {
+            partial = Partial::default();
+            // The response skipped the beginning of a different file
+            if resume_from.is_some() {
+                reader = self.car_stream(request)?;
+            }
+        }
+        file.set_len(partial.len)?;
+        file.seek(SeekFrom::Start(partial.len))?;
+
+        let resumed_blocks = partial.blocks.len() as u64;
+        let mut out = BufWriter::new(file);
+        let mut bytes = partial.len;
+        if bytes == 0 {
+            let header = writer::header(reader.roots());
+            out.write_all(&header)?;
+            bytes = header.len() as u64;
+        }
+        let result = loop {
+            match reader.next_block() {
+                Ok(Some(block)) => {
+                    if partial.blocks.insert(block.cid.clone()) {
+                        let section = writer::section(&block.cid, &block.data);
+                        out.write_all(&section)?;
+                        bytes += section.len() as u64;
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(stream_error(err)),
+            }
+        };
+        out.flush()?;
+        result?;
+
+        Ok(DownloadReport {
+            blocks: partial.blocks.len() as u64,
+            resumed_blocks,
+            bytes,
+        })
+    }
+
+    fn car_stream(
+        &self,
+        request: &RetrievalRequest,
+    ) -> Result<CarReader<impl io::Read + Send>, DownloadError> {
+        let response = self.fetch(request).map_err(DownloadError::Retrieval)?;
+        CarReader::new(response.into_reader()).map_err(stream_error)
+    }
+}
+
+fn stream_error(err: CarError) -> DownloadError {
+    match err {
+        CarError::Io(err) => DownloadError::Retrieval(RetrievalError::from_stream_error(&err)),
+        err => DownloadError::Car(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const ROOT: &str = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq";
+    const OTHER_ROOT: &str = "bafkreiaqkhvbvlmzdkvcb5urwq5mol6yoke7yoahdmwqfq6dyxz7y2ezsa";
+
+    fn partial(raw_bytes: u64) -> Partial {
+        Partial {
+            roots: vec![ROOT.parse().unwrap()],
+            raw_bytes,
+            ..Partial::default()
+        }
+    }
+
+    #[test]
+    fn resumes_after_the_bytes_on_disk() {
+        let request = RetrievalRequest::new(ROOT).dag_scope(DagScope::Entity);
+        assert_eq!(partial(1024).resume_offset(&request), Some(1024));
+        assert_eq!(partial(0).resume_offset(&request), None);
+        let request = RetrievalRequest::new(ROOT).dag_scope(DagScope::All);
+        assert_eq!(partial(1024).resume_offset(&request), None);
+    }
+
+    #[test]
+    fn resumes_after_the_requested_offset() {
+        let request = RetrievalRequest::new(ROOT).entity_bytes(4096, None);
+        assert_eq!(partial(1024).resume_offset(&request), Some(5120));
+        let request = RetrievalRequest::new(ROOT).entity_bytes(4096, Some(5119));
+        assert_eq!(partial(1024).resume_offset(&request), None);
+        let request = RetrievalRequest::new(ROOT).entity_bytes(4096, Some(8191));
+        assert_eq!(partial(1024).resume_offset(&request), Some(5120));
+    }
+
+    #[test]
+    fn does_not_resume_a_different_root() {
+        let request = RetrievalRequest::new(OTHER_ROOT).dag_scope(DagScope::Entity);
+        assert_eq!(partial(1024).resume_offset(&request), None);
+    }
+}
@@ -0,0 +1,10 @@
+//! The stable error codes Go reports next to the error messages, keep in sync with the
+//! `LASSIE_ERR_*` constants in go-lib/lassie-ffi.h. Only the codes the wrapper handles are listed.
+
+pub(crate) const ALREADY_RUNNING: u32 = 3;
+pub(crate) const NOT_RUNNING: u32 = 4;
+pub(crate) const ADDR_IN_USE: u32 = 5;
+pub(crate) const PERMISSION_DENIED: u32 = 6;
+pub(crate) const ADMIN_ADDR_IN_USE: u32 = 7;
+pub(crate) const ADMIN_PERMISSION_DENIED: u32 = 8;
+pub(crate) const TEMP_DIR_NOT_WRITABLE: u32 = 9;
@@ -0,0 +1,214 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, SystemTime};
+
+use crate::{from_c_string, Daemon};
+
+go_lassie! {
+    fn SetRetrievalEventCallback(callback: RetrievalEventCallback, ctx: *mut c_void);
+    fn ClearRetrievalEventCallback(ctx: *mut c_void);
+}
+
+type RetrievalEventCallback = extern "C" fn(ctx: *mut c_void, event: *const GoRetrievalEvent);
+
+#[repr(C)]
+struct GoRetrievalEvent {
+    // this must be kept in sync with the definition of retrieval_event_t in go-lib/lassie-ffi.h
+    retrieval_id: *const c_char,
+    lassie_id: *const c_char,
+    root_cid: *const c_char,
+    provider: *const c_char,
+    protocol: *const c_char,
+    error: *const c_char,
+    candidates: *const *const c_char,
+    candidates_len: usize,
+    time: i64,
+    duration: i64,
+    bytes: u64,
+    blocks: u64,
+    kind: u8,
+}
+
+/// An event reported by Lassie while retrieving content, see [`Daemon::retrieval_events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RetrievalEvent {
+    /// The ID assigned by the daemon (see [`RETRIEVAL_ID_HEADER`](crate::RETRIEVAL_ID_HEADER)),
+    /// `None` when the event cannot be correlated with a request, e.g. when it arrives after the
+    /// response has ended.
+    pub retrieval_id: Option<String>,
+    /// The ID assigned by Lassie, shared by all events of one retrieval.
+    pub lassie_id: String,
+    pub root_cid: Option<String>,
+    pub time: SystemTime,
+    pub kind: RetrievalEventKind,
+}
+
+/// What happened, see [`RetrievalEvent::kind`].
+///
+/// Providers are identified by their peer ID, protocols by their multicodec name, e.g.
+/// `transport-bitswap` or `transport-ipfs-gateway-http`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RetrievalEventKind {
+    /// Lassie started the retrieval.
+    Started,
+    /// Candidate discovery found providers offering the content.
+    CandidatesFound { providers: Vec<String> },
+    /// The providers left after filtering the candidates by the requested protocols.
+    CandidatesFiltered { providers: Vec<String> },
+    /// Lassie connected to a provider.
+    Connected { provider: String, protocol: String },
+    /// The first byte arrived from a provider.
+    FirstByte {
+        provider: String,
+        protocol: String,
+        /// Time to first byte.
+        duration: Duration,
+    },
+    /// A provider delivered the content.
+    Success {
+        provider: String,
+        protocol: String,
+        bytes: u64,
+        blocks: u64,
+        duration: Duration,
+    },
+    /// The retrieval from a provider failed, or the whole retrieval failed when `provider` is
+    /// `None`.
+    Failure {
+        provider: Option<String>,
+        protocol: Option<String>,
+        error: String,
+    },
+}
+
+type Callback = dyn Fn(&RetrievalEvent) + Send + Sync;
+
+/// Receives the retrieval events reported by Lassie, see [`Daemon::retrieval_events`].
+///
+/// Dropping the value unregisters the callback. The value borrows the daemon, therefore it cannot
+/// outlive it.
+pub struct RetrievalEvents<'a> {
+    // Double boxed to pass a thin pointer to Go
+    callback: *mut Box<Callback>,
+    _daemon: PhantomData<&'a Daemon>,
+}
+
+// SAFETY:
+// The callback is `Send + Sync`, the pointer is only dereferenced by Go calling the trampoline
+// and freed in `drop`.
+unsafe impl Send for RetrievalEvents<'_> {}
+// SAFETY:
+// See above, `RetrievalEvents` has no methods accessing the callback.
+unsafe impl Sync for RetrievalEvents<'_> {}
+
+impl std::fmt::Debug for RetrievalEvents<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetrievalEvents").finish_non_exhaustive()
+    }
+}
+
+impl RetrievalEvents<'_> {
+    pub(crate) fn register<F>(callback: F) -> Self
+    where
+        F: Fn(&RetrievalEvent) + Send + Sync + 'static,
+    {
+        let boxed: Box<Callback> = Box::new(callback);
+        let callback = Box::into_raw(Box::new(boxed));
+        // SAFETY:
+        // The pointer stays valid until `drop` unregisters it, Go stops calling the callback
+        // before `ClearRetrievalEventCallback` returns.
+        unsafe { SetRetrievalEventCallback(retrieval_event_trampoline, callback.cast()) };
+        RetrievalEvents {
+            callback,
+            _daemon: PhantomData,
+        }
+    }
+}
+
+impl Drop for RetrievalEvents<'_> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // Clearing waits for running callbacks, no other reference to the callback exists after
+        // the call returns. The pointer was created by `Box::into_raw` in `register`.
+        unsafe {
+            ClearRetrievalEventCallback(self.callback.cast());
+            drop(Box::from_raw(self.callback));
+        }
+    }
+}
+
+extern "C" fn retrieval_event_trampoline(ctx: *mut c_void, event: *const GoRetrievalEvent) {
+    // SAFETY:
+    // `ctx` is the pointer registered by `RetrievalEvents::register`, it stays valid while Go may
+    // call this function. Go passes an event that is valid until this function returns.
+    let (callback, event) = unsafe { (&*ctx.cast::<Box<Callback>>(), &*event) };
+    let Some(event) = convert_event(event) else {
+        return;
+    };
+
+    // Unwinding into Go is undefined behaviour
+    if panic::catch_unwind(AssertUnwindSafe(|| callback(&event))).is_err() {
+        log::error!("Lassie retrieval event callback panicked");
+    }
+}
+
+fn convert_event(event: &GoRetrievalEvent) -> Option<RetrievalEvent> {
+    let non_empty = |s: *const c_char| from_c_string(s).filter(|s| !s.is_empty());
+    let text = |s: *const c_char| from_c_string(s).unwrap_or_default();
+    let duration = Duration::from_nanos(u64::try_from(event.duration).unwrap_or_default());
+    let providers = || {
+        if event.candidates.is_null() {
+            return Vec::new();
+        }
+        // SAFETY:
+        // Go allocates `candidates_len` valid C strings, they live until the callback returns.
+        let candidates =
+            unsafe { std::slice::from_raw_parts(event.candidates, event.candidates_len) };
+        candidates.iter().map(|&c| text(c)).collect()
+    };
+
+    // keep in sync with RETRIEVAL_EVENT_* in go-lib/lassie-ffi.h
+    let kind = match event.kind {
+        0 => RetrievalEventKind::Started,
+        1 => RetrievalEventKind::CandidatesFound {
+            providers: providers(),
+        },
+        2 => RetrievalEventKind::CandidatesFiltered {
+            providers: providers(),
+        },
+        3 => RetrievalEventKind::Connected {
+            provider: text(event.provider),
+            protocol: text(event.protocol),
+        },
+        4 => RetrievalEventKind::FirstByte {
+            provider: text(event.provider),
+            protocol: text(event.protocol),
+            duration,
+        },
+        5 => RetrievalEventKind::Success {
+            provider: text(event.provider),
+            protocol: text(event.protocol),
+            bytes: event.bytes,
+            blocks: event.blocks,
+            duration,
+        },
+        6 => RetrievalEventKind::Failure {
+            provider: non_empty(event.provider),
+            protocol: non_empty(event.protocol),
+            error: text(event.error),
+        },
+        _ => return None,
+    };
+    Some(RetrievalEvent {
+        retrieval_id: non_empty(event.retrieval_id),
+        lassie_id: text(event.lassie_id),
+        root_cid: non_empty(event.root_cid),
+        time: SystemTime::UNIX_EPOCH
+            + Duration::from_nanos(u64::try_from(event.time).unwrap_or_default()),
+        kind,
+    })
+}
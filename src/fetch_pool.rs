@@ -0,0 +1,296 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use crate::{retrieval, Client, DaemonHandle, RetrievalError, RetrievalRequest, RetrievalResponse};
+
+/// The ID of a job submitted to a [`FetchPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+/// The state of a job submitted to a [`FetchPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// The job is waiting for a free slot.
+    Queued,
+    /// The retrieval is in progress.
+    Running,
+    /// The retrieval finished, collect the content with [`FetchPool::wait`].
+    Succeeded,
+    /// The retrieval failed, collect the error with [`FetchPool::wait`].
+    Failed,
+    /// The job was cancelled by [`FetchPool::cancel`].
+    Cancelled,
+}
+
+/// A queue of retrievals executed by a fixed number of worker threads.
+///
+/// Jobs with a higher priority start first, jobs with the same priority start in the order they
+/// were submitted. At most `max_in_flight` retrievals run at the same time.
+///
+/// The results are kept until collected with [`FetchPool::wait`]. Dropping the pool cancels all
+/// queued and running jobs and waits for the workers to exit.
+pub struct FetchPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    queue: BinaryHeap<Queued>,
+    jobs: HashMap<JobId, Job>,
+    stopping: bool,
+}
+
+struct Job {
+    status: JobStatus,
+    retrieval_id: Option<String>,
+    result: Option<Result<Vec<u8>, RetrievalError>>,
+}
+
+struct Queued {
+    priority: i32,
+    id: JobId,
+    request: RetrievalRequest,
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, the older job (lower ID) wins among equal priorities
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Queued {}
+
+impl FetchPool {
+    /// Create a pool running at most `max_in_flight` retrievals via the daemon behind `handle`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[must_use]
+    pub fn new(handle: &DaemonHandle, max_in_flight: usize) -> Self {
+        let client = Client::from_handle(handle);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+        let workers = (0..max_in_flight.max(1))
+            .map(|_| {
+                let client = client.clone();
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || run_worker(&client, &shared))
+            })
+            .collect();
+        FetchPool { shared, workers }
+    }
+
+    /// Queue the retrieval, jobs with a higher `priority` start first.
+    #[must_use]
+    pub fn submit(&self, request: RetrievalRequest, priority: i32) -> JobId {
+        let mut state = self.shared.lock();
+        let id = JobId(state.next_id);
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            Job {
+                status: JobStatus::Queued,
+                retrieval_id: None,
+                result: None,
+            },
+        );
+        state.queue.push(Queued {
+            priority,
+            id,
+            request,
+        });
+        drop(state);
+        self.shared.changed.notify_all();
+        id
+    }
+
+    /// The current state of the job, `None` when the ID is unknown or the result was already
+    /// collected.
+    #[must_use]
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.shared.lock().jobs.get(&id).map(|job| job.status)
+    }
+
+    /// The number of jobs waiting for a free slot.
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Cancel a queued or running job. Returns `false` when the job has already finished.
+    #[must_use]
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.shared.lock();
+        let Some(job) = state.jobs.get_mut(&id) else {
+            return false;
+        };
+        let running = match job.status {
+            JobStatus::Queued => false,
+            JobStatus::Running => true,
+            _ => return false,
+        };
+        job.status = JobStatus::Cancelled;
+        let retrieval_id = job.retrieval_id.take();
+        if !running {
+            state.queue.retain(|queued| queued.id != id);
+        }
+        drop(state);
+
+        // The worker notices the cancellation when the response stream is aborted. When the
+        // daemon has not assigned the retrieval ID yet, the worker discards the result instead.
+        if let Some(retrieval_id) = retrieval_id {
+            retrieval::cancel(&retrieval_id);
+        }
+        self.shared.changed.notify_all();
+        true
+    }
+
+    /// Wait until the job finishes and collect its result.
+    ///
+    /// Returns `None` when the ID is unknown, the job was cancelled or its result was already
+    /// collected.
+    #[must_use]
+    pub fn wait(&self, id: JobId) -> Option<Result<Vec<u8>, RetrievalError>> {
+        let mut state = self.shared.lock();
+        loop {
+            match state.jobs.get(&id)?.status {
+                JobStatus::Queued | JobStatus::Running => {
+                    state = self
+                        .shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                }
+                JobStatus::Cancelled => {
+                    state.jobs.remove(&id);
+                    return None;
+                }
+                JobStatus::Succeeded | JobStatus::Failed => {
+                    return state.jobs.remove(&id).and_then(|job| job.result);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FetchPool {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.stopping = true;
+        state.queue.clear();
+        let running: Vec<_> = state
+            .jobs
+            .values_mut()
+            .filter_map(|job| job.retrieval_id.take())
+            .collect();
+        drop(state);
+        self.shared.changed.notify_all();
+
+        for retrieval_id in running {
+            retrieval::cancel(&retrieval_id);
+        }
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                log::error!("Lassie fetch pool worker panicked");
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state is consistent even when a thread panicked while holding the lock
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+fn run_worker(client: &Client, shared: &Shared) {
+    loop {
+        let mut state = shared.lock();
+        let queued = loop {
+            if state.stopping {
+                return;
+            }
+            if let Some(queued) = state.queue.pop() {
+                break queued;
+            }
+            state = shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        };
+        if let Some(job) = state.jobs.get_mut(&queued.id) {
+            job.status = JobStatus::Running;
+        }
+        drop(state);
+        shared.changed.notify_all();
+
+        let result = client.fetch(&queued.request).and_then(|response| {
+            if let Some(retrieval_id) = response.retrieval_id() {
+                let cancelled = set_retrieval_id(shared, queued.id, retrieval_id);
+                if cancelled {
+                    retrieval::cancel(retrieval_id);
+                }
+            }
+            RetrievalResponse::read_to_end(response)
+        });
+
+        let mut state = shared.lock();
+        if let Some(job) = state.jobs.get_mut(&queued.id) {
+            job.retrieval_id = None;
+            if job.status == JobStatus::Running {
+                job.status = if result.is_ok() {
+                    JobStatus::Succeeded
+                } else {
+                    JobStatus::Failed
+                };
+                job.result = Some(result);
+            }
+        }
+        drop(state);
+        shared.changed.notify_all();
+    }
+}
+
+/// Remember the retrieval ID of a running job, so that it can be cancelled. Returns `true` when
+/// the job was cancelled before the ID was known.
+fn set_retrieval_id(shared: &Shared, id: JobId, retrieval_id: &str) -> bool {
+    let mut state = shared.lock();
+    let stopping = state.stopping;
+    match state.jobs.get_mut(&id) {
+        Some(job) if job.status == JobStatus::Running && !stopping => {
+            job.retrieval_id = Some(retrieval_id.to_string());
+            false
+        }
+        _ => true,
+    }
+}
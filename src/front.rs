@@ -0,0 +1,493 @@
+//! A network-facing front listener written in Rust.
+//!
+//! [`FrontListener`] accepts HTTP connections with hyper, terminates TLS with rustls, checks the
+//! access token and the per-client rate limit, and forwards `/ipfs/*` and `/ipns/*` requests to
+//! the Go request handler via the in-process bridge (see [`LassieService`]). Start the daemon with
+//! [`DaemonConfig::disable_listener`](crate::DaemonConfig::disable_listener) so that the Go HTTP
+//! server does not listen at all and Rust handles the entire network-facing surface:
+//!
+//! ```ignore
+//! let daemon = Arc::new(Daemon::start(DaemonConfig {
+//!     disable_listener: true,
+//!     ..DaemonConfig::default()
+//! })?);
+//! let front = FrontListener::bind(FrontConfig {
+//!     tls: Some(FrontTlsConfig {
+//!         cert_file: "/etc/lassie/cert.pem".into(),
+//!         key_file: "/etc/lassie/key.pem".into(),
+//!     }),
+//!     access_token: Some("secret".to_string()),
+//!     rate_limit: Some(RateLimitConfig::default()),
+//!     ..FrontConfig::new("0.0.0.0:8443".parse()?)
+//! })
+//! .await?;
+//! tokio::spawn(front.serve(daemon));
+//! ```
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use http::{Request, StatusCode};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{crypto, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tower_service::Service;
+
+use crate::tower::{text_response, LassieService};
+use crate::Daemon;
+
+/// Forget the clients with a full bucket once the rate limiter tracks this many addresses. When
+/// all of them made requests recently, new clients are rejected until some buckets refill.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Close the connections that don't complete the TLS handshake in time.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Close the connections that don't send the request headers in time, e.g. slowloris clients.
+/// The timer starts again for each request on a keep-alive connection.
+const HEADER_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configuration of the front listener, see [`FrontListener::bind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontConfig {
+    /// The address to listen on, use port 0 to let the OS pick a free port.
+    pub address: SocketAddr,
+
+    /// Serve HTTPS with this certificate. By default, the listener serves plain HTTP, e.g. for
+    /// deployments where another proxy terminates TLS.
+    pub tls: Option<FrontTlsConfig>,
+
+    /// Require requests to provide authorization header with the configured access token, e.g.
+    /// `Authorization: Bearer {token}`. Requests without the token are rejected with 401.
+    pub access_token: Option<String>,
+
+    /// Limit the rate of requests per client IP address, excess requests are rejected with 429.
+    /// By default, there is no limit.
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl FrontConfig {
+    /// Plain HTTP on `address` without access token and rate limit.
+    #[must_use]
+    pub fn new(address: SocketAddr) -> Self {
+        FrontConfig {
+            address,
+            tls: None,
+            access_token: None,
+            rate_limit: None,
+        }
+    }
+}
+
+/// The certificate of the front listener, see [`FrontConfig::tls`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontTlsConfig {
+    /// PEM file with the certificate chain, the leaf certificate first.
+    pub cert_file: PathBuf,
+    /// PEM file with the private key (PKCS#8, PKCS#1 or SEC1).
+    pub key_file: PathBuf,
+}
+
+/// Token bucket rate limit, see [`FrontConfig::rate_limit`].
+///
+/// Each client can make `burst` requests at once, the bucket refills at `requests_per_second`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    pub requests_per_second: u32,
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            requests_per_second: 10,
+            burst: 20,
+        }
+    }
+}
+
+/// A bound front listener, call [`FrontListener::serve`] to start accepting connections.
+pub struct FrontListener {
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    guard: Arc<RequestGuard>,
+}
+
+impl std::fmt::Debug for FrontListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrontListener")
+            .field("local_addr", &self.listener.local_addr().ok())
+            .field("tls", &self.tls.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FrontListener {
+    /// Load the TLS certificate and bind the listening socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` when the certificate or the key cannot be read or parsed, when the rate limit
+    /// allows zero requests, or when the address cannot be bound.
+    pub async fn bind(config: FrontConfig) -> io::Result<Self> {
+        let tls = config.tls.as_ref().map(load_tls).transpose()?;
+        let limiter = config
+            .rate_limit
+            .map(|limit| {
+                if limit.requests_per_second == 0 || limit.burst == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the front listener rate limit must allow at least 1 request",
+                    ));
+                }
+                Ok(RateLimiter::new(limit))
+            })
+            .transpose()?;
+        let listener = TcpListener::bind(config.address).await?;
+        Ok(FrontListener {
+            listener,
+            tls,
+            guard: Arc::new(RequestGuard {
+                access_token: config.access_token,
+                limiter,
+            }),
+        })
+    }
+
+    /// The address the listener is bound to, useful when [`FrontConfig::address`] uses port 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` when the OS cannot report the address.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept connections and forward their requests to `daemon`. Each connection runs in its
+    /// own tokio task, the returned future never completes, drop it to stop accepting new
+    /// connections.
+    pub async fn serve(self, daemon: Arc<Daemon>) {
+        let service = LassieService::new(daemon);
+        loop {
+            let (stream, peer) = match self.listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    // E.g. the process is out of file descriptors, give other tasks a chance
+                    // to close theirs
+                    log::warn!("Lassie front listener cannot accept a connection: {err}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            let service = service.clone();
+            let guard = Arc::clone(&self.guard);
+            let tls = self.tls.clone();
+            tokio::spawn(async move {
+                let result = match tls {
+                    Some(acceptor) => {
+                        match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+                            .await
+                        {
+                            Ok(Ok(stream)) => {
+                                serve_connection(stream, peer.ip(), service, guard).await
+                            }
+                            Ok(Err(err)) => {
+                                log::debug!("TLS handshake with {peer} failed: {err}");
+                                return;
+                            }
+                            Err(_) => {
+                                log::debug!("TLS handshake with {peer} timed out");
+                                return;
+                            }
+                        }
+                    }
+                    None => serve_connection(stream, peer.ip(), service, guard).await,
+                };
+                if let Err(err) = result {
+                    log::debug!("Connection from {peer} failed: {err}");
+                }
+            });
+        }
+    }
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    client: IpAddr,
+    service: LassieService,
+    guard: Arc<RequestGuard>,
+) -> Result<(), hyper::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let handler = service_fn(move |req: Request<Incoming>| {
+        let mut service = service.clone();
+        let rejected = guard.check(client, &req, Instant::now());
+        async move {
+            if let Some((status, msg)) = rejected {
+                return Ok::<_, Infallible>(text_response(status, msg));
+            }
+            service.call(req).await
+        }
+    });
+    http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(HEADER_READ_TIMEOUT)
+        .serve_connection(TokioIo::new(stream), handler)
+        .await
+}
+
+fn load_tls(config: &FrontTlsConfig) -> io::Result<TlsAcceptor> {
+    let invalid = |what: &str, path: &PathBuf, err: &dyn std::fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("cannot load the TLS {what} from {}: {err}", path.display()),
+        )
+    };
+    let certs = CertificateDer::pem_file_iter(&config.cert_file)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|err| invalid("certificate", &config.cert_file, &err))?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_file)
+        .map_err(|err| invalid("private key", &config.key_file, &err))?;
+
+    let mut server_config =
+        ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|err| invalid("certificate", &config.cert_file, &err))?;
+    // hyper serves HTTP/1.1 only
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// The checks performed before a request is forwarded to the daemon.
+struct RequestGuard {
+    access_token: Option<String>,
+    limiter: Option<RateLimiter>,
+}
+
+impl RequestGuard {
+    /// Returns the status and the body of the rejection, `None` when the request may proceed.
+    fn check<B>(
+        &self,
+        client: IpAddr,
+        req: &Request<B>,
+        now: Instant,
+    ) -> Option<(StatusCode, &'static str)> {
+        if let Some(token) = &self.access_token {
+            let actual = req
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .map_or(&[][..], |value| value.as_bytes());
+            if !is_bearer_token(actual, token) {
+                return Some((StatusCode::UNAUTHORIZED, "Unauthorized"));
+            }
+        }
+        if let Some(limiter) = &self.limiter {
+            if !limiter.allow(client, now) {
+                // see RetrievalError::TooManyRequests
+                return Some((
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "too many requests, try again later",
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Compare the header value with `Bearer {token}` in constant time.
+fn is_bearer_token(actual: &[u8], token: &str) -> bool {
+    let expected = [b"Bearer ".as_slice(), token.as_bytes()].concat();
+    if actual.len() != expected.len() {
+        return false;
+    }
+    actual
+        .iter()
+        .zip(&expected)
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+    clients: HashMap<IpAddr, Bucket>,
+    /// When the idle clients were forgotten the last time.
+    swept: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    fn allow(&self, client: IpAddr, now: Instant) -> bool {
+        let burst = f64::from(self.config.burst);
+        let rate = f64::from(self.config.requests_per_second);
+        // An empty bucket is full again after this time
+        let refill_time = Duration::from_secs_f64(burst / rate);
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let Buckets { clients, swept } = &mut *buckets;
+        if clients.len() >= MAX_TRACKED_CLIENTS
+            && now.saturating_duration_since(*swept) >= refill_time
+        {
+            // A full bucket is the same as no bucket. Sweeping at most once per refill time
+            // keeps the cost per request constant when all clients are active.
+            clients.retain(|_, bucket| now.saturating_duration_since(bucket.updated) < refill_time);
+            *swept = now;
+        }
+
+        let key = client_key(client);
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&key) {
+            return false;
+        }
+        let bucket = clients.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+/// The rate limit applies to IPv6 clients per /64 network, the smallest network usually
+/// assigned to a single host.
+fn client_key(client: IpAddr) -> IpAddr {
+    match client.to_canonical() {
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & !(u128::MAX >> 64))),
+        ip @ IpAddr::V4(_) => ip,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn limits_request_rate_per_client() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_second: 2,
+            burst: 3,
+        });
+        let alice: IpAddr = "10.0.0.1".parse().unwrap();
+        let bob: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        let allowed = |client, at| limiter.allow(client, start + at);
+        assert_eq!(
+            [Duration::ZERO; 4].map(|at| allowed(alice, at)),
+            [true, true, true, false]
+        );
+        assert!(
+            allowed(bob, Duration::ZERO),
+            "clients have separate buckets"
+        );
+        assert!(allowed(alice, Duration::from_millis(500)));
+        assert!(!allowed(alice, Duration::from_millis(600)));
+    }
+
+    #[test]
+    fn limits_ipv6_clients_per_network() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_second: 1,
+            burst: 1,
+        });
+        let now = Instant::now();
+        assert!(limiter.allow("2001:db8::1".parse().unwrap(), now));
+        assert!(
+            !limiter.allow("2001:db8::ffff:1".parse().unwrap(), now),
+            "the same /64 network shares the bucket"
+        );
+        assert!(limiter.allow("2001:db8:0:1::1".parse().unwrap(), now));
+
+        assert!(limiter.allow("10.0.0.1".parse().unwrap(), now));
+        assert!(
+            !limiter.allow("::ffff:10.0.0.1".parse().unwrap(), now),
+            "IPv4-mapped addresses share the bucket of the IPv4 address"
+        );
+    }
+
+    #[test]
+    fn forgets_idle_clients() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_second: 10,
+            burst: 10,
+        });
+        let start = Instant::now();
+        let client = |i: usize| IpAddr::from(u32::try_from(i).unwrap().to_be_bytes());
+        for i in 0..MAX_TRACKED_CLIENTS {
+            assert!(limiter.allow(client(i), start));
+        }
+        assert!(
+            !limiter.allow(client(MAX_TRACKED_CLIENTS), start),
+            "new clients wait while all tracked clients are active"
+        );
+
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.allow(client(0), later));
+        assert!(limiter.allow(client(MAX_TRACKED_CLIENTS), later));
+        let tracked = limiter.buckets.lock().unwrap().clients.len();
+        assert_eq!(tracked, 2);
+    }
+
+    #[test]
+    fn checks_access_token() {
+        let guard = RequestGuard {
+            access_token: Some("secret".to_string()),
+            limiter: None,
+        };
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri("/ipfs/bafkqaaa");
+            if let Some(auth) = auth {
+                builder = builder.header("Authorization", auth);
+            }
+            builder.body(()).unwrap()
+        };
+        let check = |auth| {
+            guard
+                .check(IpAddr::from([127, 0, 0, 1]), &request(auth), Instant::now())
+                .map(|(status, _)| status)
+        };
+
+        assert_eq!(check(Some("Bearer secret")), None);
+        assert_eq!(check(Some("Bearer secreT")), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(check(Some("secret")), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(check(None), Some(StatusCode::UNAUTHORIZED));
+    }
+}
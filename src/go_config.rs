@@ -0,0 +1,420 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{config_error, AdminAddress, DaemonConfig, HttpVersion, LogFormat, StartError};
+
+#[repr(C)]
+pub(crate) struct GoDaemonConfig {
+    // this must be kept in sync with the definition of daemon_config_t in go-lib/lassie-ffi.h
+    temp_dir: *const c_char,
+    port: u16,
+    log_level: usize,
+    max_blocks: u64,
+    max_concurrent_requests: u32,
+    max_concurrent_retrievals: u32,
+    max_queued_retrievals: i64,
+    provider_timeout: i64,
+    http_provider_timeout: i64,
+    global_timeout: i64,
+    circuit_breaker_failures: u32,
+    circuit_breaker_cool_down: i64,
+    access_token: *const c_char,
+    restrict_client_ips: bool,
+    allowed_client_ips: *const *const c_char,
+    allowed_client_ips_len: usize,
+    lassie_user_agent: *const c_char,
+    disable_listener: bool,
+    listener_fd: i32,
+    reuse_port: bool,
+    idle_shutdown: i64,
+    event_recorder_url: *const c_char,
+    event_recorder_auth: *const c_char,
+    event_recorder_instance_id: *const c_char,
+    otlp_endpoint: *const c_char,
+    otlp_service_name: *const c_char,
+    bootstrap_peers: *const *const c_char,
+    bootstrap_peers_len: usize,
+    preconnect_providers: *const *const c_char,
+    preconnect_providers_len: usize,
+    disable_ipni: bool,
+    disable_dht: bool,
+    disable_candidate_discovery: bool,
+    delegated_routing_url: *const c_char,
+    conn_mgr_low_water: u32,
+    conn_mgr_high_water: u32,
+    conn_mgr_grace_period: i64,
+    bitswap_concurrency: u32,
+    bitswap_concurrency_per_retrieval: u32,
+    bitswap_keep_alive: i64,
+    block_cache_dir: *const c_char,
+    block_cache_max_size: u64,
+    mmap_car_store_size: u64,
+    max_download_rate: u64,
+    max_download_rate_per_retrieval: u64,
+    admin_network: *const c_char,
+    admin_address: *const c_char,
+    admin_access_token: *const c_char,
+    admin_pprof: bool,
+    extra_listener_networks: *const *const c_char,
+    extra_listener_addresses: *const *const c_char,
+    extra_listener_access_tokens: *const *const c_char,
+    extra_listeners_len: usize,
+    json_logs: bool,
+    log_file: *const c_char,
+    event_log: *const c_char,
+    http1_only: bool,
+    http_max_conns_per_host: u32,
+    http_max_idle_conns_per_host: u32,
+    http_client_cert_file: *const c_char,
+    http_client_key_file: *const c_char,
+    http_insecure_skip_verify: bool,
+    extra_root_certs: *const *const c_char,
+    extra_root_certs_len: usize,
+    libp2p_transports: u32,
+    go_memory_limit: u64,
+    go_max_procs: u32,
+}
+
+/// [`DaemonConfig`] converted to the C representation expected by `InitDaemon`.
+///
+/// This struct owns all strings and arrays referenced by the pointers in [`GoDaemonConfig`].
+pub(crate) struct GoConfig {
+    raw: GoDaemonConfig,
+    _strings: CStrings,
+    _bootstrap_peers: Vec<*const c_char>,
+    _preconnect_providers: Vec<*const c_char>,
+    _allowed_client_ips: Vec<*const c_char>,
+    _extra_root_certs: Vec<*const c_char>,
+    _extra_listeners: [Vec<*const c_char>; 3],
+}
+
+// SAFETY:
+// The pointers reference only the heap-allocated strings and arrays owned by this struct, they
+// stay valid when the struct moves to another thread, see `DaemonConfig::startup_timeout`.
+unsafe impl Send for GoConfig {}
+
+impl GoConfig {
+    // A field-by-field conversion, it grows with every configuration option
+    #[allow(clippy::too_many_lines)]
+    pub(crate) fn new(config: &DaemonConfig) -> Result<Self, StartError> {
+        let mut strings = CStrings::default();
+
+        let log_level = if log::log_enabled!(log::Level::Debug) {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Off
+        };
+
+        let global_timeout = match config.global_timeout {
+            Some(d) => try_convert_duration_to_go_type(d)?,
+            None => 0,
+        };
+
+        let idle_shutdown = match config.idle_shutdown {
+            Some(d) => try_convert_duration_to_go_type(d)?,
+            None => 0,
+        };
+
+        // Lassie's provider timeout is consumed by the Bitswap retriever only
+        let provider_timeout = match config.bitswap_provider_timeout.or(config.provider_timeout) {
+            Some(d) => try_convert_duration_to_go_type(d)?,
+            None => 0,
+        };
+
+        let http_provider_timeout = match config.http_provider_timeout {
+            Some(d) => try_convert_duration_to_go_type(d)?,
+            None => 0,
+        };
+
+        let (circuit_breaker_failures, circuit_breaker_cool_down) = match &config.circuit_breaker {
+            Some(cb) => (cb.failures, try_convert_duration_to_go_type(cb.cool_down)?),
+            None => (0, 0),
+        };
+
+        let access_token = config.access_token.clone().unwrap_or_default();
+        let access_token = CString::new(access_token.clone())
+            .map_err(|_| StartError::AccessTokenContainsNullByte(access_token.to_string()))?;
+
+        let lassie_user_agent = config.user_agent.clone().unwrap_or_else(|| {
+            // See https://github.com/filecoin-project/lassie/pull/240
+            let lassie_version = env!("LASSIE_VERSION");
+            format!("lassie/v{lassie_version}")
+        });
+
+        let unsupported = config_error::check_unsupported(config);
+        if !unsupported.is_empty() {
+            return Err(StartError::InvalidConfig(unsupported));
+        }
+
+        let peer_id_errors = config_error::check_peer_ids(config);
+        if !peer_id_errors.is_empty() {
+            return Err(StartError::InvalidConfig(peer_id_errors));
+        }
+        let bootstrap_peers =
+            strings.add_all("bootstrap_peers", config.bootstrap_peers.iter().flatten())?;
+        let preconnect_providers =
+            strings.add_all("preconnect_providers", &config.preconnect_providers)?;
+        let allowed_client_ips = strings.add_all(
+            "allowed_client_ips",
+            config
+                .allowed_client_ips
+                .iter()
+                .flatten()
+                .map(ToString::to_string),
+        )?;
+
+        let (conn_mgr_low_water, conn_mgr_high_water, conn_mgr_grace_period) =
+            match &config.connection_manager {
+                Some(cm) => (
+                    cm.low_water,
+                    cm.high_water,
+                    try_convert_duration_to_go_type(cm.grace_period)?,
+                ),
+                None => (0, 0, 0),
+            };
+
+        let bitswap_keep_alive = match config.bitswap_keep_alive {
+            Some(d) => try_convert_duration_to_go_type(d)?,
+            None => 0,
+        };
+
+        let (admin_network, admin_address, admin_access_token) = admin_c_strings(config)?;
+        let mut extra_listeners: [Vec<*const c_char>; 3] = Default::default();
+        for extra in &config.extra_listeners {
+            let (network, address) = listener_c_strings("extra_listeners", &extra.address)?;
+            let access_token = config_c_string("extra_listeners", extra.access_token.as_deref())?;
+            for (list, value) in extra_listeners
+                .iter_mut()
+                .zip([network, address, access_token])
+            {
+                list.push(strings.add(value));
+            }
+        }
+        let client_certificate = config.outbound_http.client_certificate.as_ref();
+        let extra_root_certs = config
+            .extra_root_certs
+            .iter()
+            .flatten()
+            .map(|path| Ok(strings.add(path_c_string(Some(path))?)))
+            .collect::<Result<Vec<_>, StartError>>()?;
+
+        // Moving a CString or a Vec does not move the heap buffer, the pointers in `raw` stay valid
+        let raw = GoDaemonConfig {
+            temp_dir: strings.add(path_c_string(config.temp_dir.as_deref())?),
+            log_level: log_level as usize,
+            port: config.port,
+            global_timeout,
+            provider_timeout,
+            http_provider_timeout,
+            circuit_breaker_failures,
+            circuit_breaker_cool_down,
+            max_blocks: config.max_blocks.unwrap_or(0),
+            max_concurrent_requests: config.max_concurrent_requests.unwrap_or(0),
+            max_concurrent_retrievals: config.max_concurrent_retrievals.unwrap_or(0),
+            max_queued_retrievals: config.max_queued_retrievals.map_or(-1, i64::from),
+            access_token: strings.add(access_token),
+            restrict_client_ips: config.allowed_client_ips.is_some(),
+            allowed_client_ips: allowed_client_ips.as_ptr(),
+            allowed_client_ips_len: allowed_client_ips.len(),
+            lassie_user_agent: strings
+                .add(config_c_string("user_agent", Some(&lassie_user_agent))?),
+            disable_listener: config.disable_listener,
+            #[cfg(unix)]
+            listener_fd: config.listener_fd.unwrap_or(-1),
+            #[cfg(not(unix))]
+            listener_fd: -1,
+            reuse_port: config.reuse_port,
+            idle_shutdown,
+            event_recorder_url: strings.add(config_c_string(
+                "event_recorder_url",
+                config.event_recorder_url.as_deref(),
+            )?),
+            event_recorder_auth: strings.add(config_c_string(
+                "event_recorder_auth",
+                config.event_recorder_auth.as_deref(),
+            )?),
+            event_recorder_instance_id: strings.add(config_c_string(
+                "event_recorder_instance_id",
+                config.event_recorder_instance_id.as_deref(),
+            )?),
+            otlp_endpoint: strings.add(config_c_string(
+                "otlp",
+                config.otlp.as_ref().map(|otlp| otlp.endpoint.as_str()),
+            )?),
+            otlp_service_name: strings.add(config_c_string(
+                "otlp",
+                config
+                    .otlp
+                    .as_ref()
+                    .and_then(|otlp| otlp.service_name.as_deref()),
+            )?),
+            bootstrap_peers: bootstrap_peers.as_ptr(),
+            bootstrap_peers_len: bootstrap_peers.len(),
+            preconnect_providers: preconnect_providers.as_ptr(),
+            preconnect_providers_len: preconnect_providers.len(),
+            disable_ipni: config.disable_ipni,
+            disable_dht: config.disable_dht,
+            disable_candidate_discovery: config.disable_candidate_discovery,
+            delegated_routing_url: strings.add(config_c_string(
+                "delegated_routing_url",
+                config.delegated_routing_url.as_deref(),
+            )?),
+            conn_mgr_low_water,
+            conn_mgr_high_water,
+            conn_mgr_grace_period,
+            bitswap_concurrency: config.bitswap_concurrency.unwrap_or(0),
+            bitswap_concurrency_per_retrieval: config
+                .bitswap_concurrency_per_retrieval
+                .unwrap_or(0),
+            bitswap_keep_alive,
+            block_cache_dir: strings.add(path_c_string(
+                config.block_cache.as_ref().map(|cache| cache.dir.as_path()),
+            )?),
+            block_cache_max_size: config
+                .block_cache
+                .as_ref()
+                .map_or(0, |cache| cache.max_size),
+            mmap_car_store_size: config.mmap_car_store.unwrap_or_default(),
+            max_download_rate: config.max_download_rate.unwrap_or_default(),
+            max_download_rate_per_retrieval: config
+                .max_download_rate_per_retrieval
+                .unwrap_or_default(),
+            admin_network: strings.add(admin_network),
+            admin_address: strings.add(admin_address),
+            admin_access_token: strings.add(admin_access_token),
+            admin_pprof: config
+                .admin_listener
+                .as_ref()
+                .is_some_and(|admin| admin.pprof),
+            extra_listener_networks: extra_listeners[0].as_ptr(),
+            extra_listener_addresses: extra_listeners[1].as_ptr(),
+            extra_listener_access_tokens: extra_listeners[2].as_ptr(),
+            extra_listeners_len: config.extra_listeners.len(),
+            json_logs: config.log_format == LogFormat::Json,
+            log_file: strings.add(path_c_string(config.log_file.as_deref())?),
+            event_log: strings.add(path_c_string(config.event_log.as_deref())?),
+            http1_only: config.outbound_http.version == HttpVersion::Http1Only,
+            http_max_conns_per_host: config.outbound_http.max_conns_per_host.unwrap_or(0),
+            http_max_idle_conns_per_host: config.outbound_http.max_idle_conns_per_host.unwrap_or(0),
+            http_client_cert_file: strings.add(path_c_string(
+                client_certificate.map(|cert| cert.cert_file.as_path()),
+            )?),
+            http_client_key_file: strings.add(path_c_string(
+                client_certificate.map(|cert| cert.key_file.as_path()),
+            )?),
+            http_insecure_skip_verify: accept_invalid_certs(config),
+            extra_root_certs: extra_root_certs.as_ptr(),
+            extra_root_certs_len: extra_root_certs.len(),
+            libp2p_transports: config
+                .libp2p_transports
+                .iter()
+                .flatten()
+                .fold(0, |mask, transport| mask | transport.bit()),
+            go_memory_limit: config.go_memory_limit.unwrap_or(0),
+            go_max_procs: config.go_max_procs.unwrap_or(0),
+        };
+
+        Ok(GoConfig {
+            raw,
+            _strings: strings,
+            _bootstrap_peers: bootstrap_peers,
+            _preconnect_providers: preconnect_providers,
+            _allowed_client_ips: allowed_client_ips,
+            _extra_root_certs: extra_root_certs,
+            _extra_listeners: extra_listeners,
+        })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const GoDaemonConfig {
+        &self.raw
+    }
+}
+
+/// Owner of the C strings referenced by [`GoDaemonConfig`].
+#[derive(Default)]
+struct CStrings(Vec<CString>);
+
+impl CStrings {
+    /// Take ownership of `value` and return a pointer valid for the lifetime of `self`.
+    fn add(&mut self, value: CString) -> *const c_char {
+        let ptr = value.as_ptr();
+        self.0.push(value);
+        ptr
+    }
+
+    /// Convert a list of configuration values to an array of C strings valid for the lifetime of
+    /// `self`.
+    fn add_all(
+        &mut self,
+        field: &'static str,
+        values: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Vec<*const c_char>, StartError> {
+        values
+            .into_iter()
+            .map(|value| Ok(self.add(config_c_string(field, Some(value.as_ref()))?)))
+            .collect()
+    }
+}
+
+fn try_convert_duration_to_go_type(from: Duration) -> Result<i64, StartError> {
+    // Go Duration type represents the elapsed time between two instants as an int64 nanosecond count.
+    i64::try_from(from.as_nanos()).map_err(|_| StartError::DurationIsTooLong(from))
+}
+
+#[cfg(feature = "danger-accept-invalid-certs")]
+fn accept_invalid_certs(config: &DaemonConfig) -> bool {
+    let accept = config.outbound_http.danger_accept_invalid_certs;
+    if accept {
+        log::warn!("TLS certificate verification is DISABLED for outbound HTTPS requests");
+    }
+    accept
+}
+
+#[cfg(not(feature = "danger-accept-invalid-certs"))]
+fn accept_invalid_certs(_config: &DaemonConfig) -> bool {
+    false
+}
+
+/// Convert the admin listener configuration to the `(network, address, access_token)` triple
+/// expected by Go's `net.Listen`.
+fn admin_c_strings(config: &DaemonConfig) -> Result<(CString, CString, CString), StartError> {
+    let Some(admin) = &config.admin_listener else {
+        return Ok(Default::default());
+    };
+    let (network, address) = listener_c_strings("admin_listener", &admin.address)?;
+    let access_token = config_c_string("admin_listener", admin.access_token.as_deref())?;
+    Ok((network, address, access_token))
+}
+
+/// Convert a listener address to the `(network, address)` pair expected by Go's `net.Listen`.
+fn listener_c_strings(
+    field: &'static str,
+    address: &AdminAddress,
+) -> Result<(CString, CString), StartError> {
+    Ok(match address {
+        AdminAddress::Port(port) => (
+            CString::from(c"tcp"),
+            config_c_string(field, Some(&format!("127.0.0.1:{port}")))?,
+        ),
+        AdminAddress::UnixSocket(path) => (CString::from(c"unix"), path_c_string(Some(path))?),
+    })
+}
+
+/// Convert an optional path to a C string, `None` is converted to an empty string.
+fn path_c_string(path: Option<&Path>) -> Result<CString, StartError> {
+    let Some(path) = path else {
+        return Ok(CString::default());
+    };
+    let str = path
+        .to_str()
+        .ok_or_else(|| StartError::PathIsNotValidUtf8(path.to_path_buf()))?;
+    CString::new(str).map_err(|_| StartError::PathContainsNullByte(str.to_string()))
+}
+
+/// Convert an optional configuration value to a C string, `None` is converted to an empty string.
+fn config_c_string(field: &'static str, value: Option<&str>) -> Result<CString, StartError> {
+    let value = value.unwrap_or_default();
+    CString::new(value).map_err(|_| StartError::ConfigContainsNullByte(field, value.to_string()))
+}
@@ -0,0 +1,199 @@
+//! Bindings to the Go library built from `go-lib`.
+//!
+//! On most platforms, the Go code is compiled to a static library linked into the Rust program.
+//!
+//! Go cannot produce a static library that MSVC can link, so on `windows-msvc` the Go code is
+//! built as a DLL. The DLL is embedded in this crate and extracted to the temp directory the first
+//! time a Go function is called, the functions are then resolved at runtime. This way, the
+//! applications don't have to ship `golassie.dll` and nothing needs to be copied into the cargo
+//! target directory. [`load`] reports DLLs that cannot be extracted or loaded, the Go functions
+//! panic when they are called anyway.
+
+/// Declare functions exported by the Go library.
+///
+/// The declarations use the same syntax as an `extern "C"` block. Each module declares the
+/// functions it needs, together with the `#[repr(C)]` types they use.
+macro_rules! go_lassie {
+    ($( fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?; )*) => {
+        #[cfg(not(all(target_os = "windows", target_env = "msvc")))]
+        #[link(name = "golassie")]
+        extern "C" {
+            $( fn $name($($arg: $ty),*) $(-> $ret)?; )*
+        }
+
+        $(
+            #[cfg(all(target_os = "windows", target_env = "msvc"))]
+            #[allow(non_snake_case)]
+            unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                type GoFn = unsafe extern "C" fn($($ty),*) $(-> $ret)?;
+                static SYMBOL: std::sync::OnceLock<Result<$crate::golassie::Symbol, String>> =
+                    std::sync::OnceLock::new();
+                let symbol = SYMBOL.get_or_init(|| {
+                    $crate::golassie::symbol(concat!(stringify!($name), "\0"))
+                });
+                let symbol = symbol.as_ref().unwrap_or_else(|err| panic!("{err}"));
+                // SAFETY:
+                // The symbol was exported by Go with the signature declared here.
+                let f: GoFn = unsafe { std::mem::transmute(symbol.0) };
+                // SAFETY:
+                // The caller upholds the safety requirements of the Go function.
+                unsafe { f($($arg),*) }
+            }
+        )*
+    };
+}
+
+#[cfg(all(target_os = "windows", target_env = "msvc"))]
+pub(crate) use windows::{load, symbol, Symbol};
+
+/// Make sure the Go functions can be called. The library is linked statically except on
+/// `windows-msvc`, so there is nothing to load.
+#[cfg(not(all(target_os = "windows", target_env = "msvc")))]
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn load() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(all(target_os = "windows", target_env = "msvc"))]
+mod windows {
+    use std::ffi::{c_char, c_void};
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::OnceLock;
+
+    static DLL: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "\\golassie.dll"));
+
+    /// Let other handles read the file, but deny writing, renaming and deleting it.
+    const FILE_SHARE_READ: u32 = 0x0000_0001;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+
+    /// The address of a function exported by the DLL.
+    pub(crate) struct Symbol(pub(crate) *mut c_void);
+
+    // SAFETY:
+    // The symbol is a code address valid for the lifetime of the process (we never unload the DLL).
+    unsafe impl Send for Symbol {}
+    // SAFETY:
+    // See above.
+    unsafe impl Sync for Symbol {}
+
+    struct Module(*mut c_void);
+
+    // SAFETY:
+    // Module handles can be used from any thread.
+    unsafe impl Send for Module {}
+    // SAFETY:
+    // See above.
+    unsafe impl Sync for Module {}
+
+    /// Extract and load the Go DLL, once per process. Later calls return the first result.
+    pub(crate) fn load() -> Result<(), String> {
+        module().map(|_| ())
+    }
+
+    fn module() -> Result<&'static Module, String> {
+        static MODULE: OnceLock<Result<Module, String>> = OnceLock::new();
+        MODULE
+            .get_or_init(|| {
+                // The file cannot be replaced while `verified` is open, so we load the content we
+                // have verified
+                let (path, verified) =
+                    extract_dll().map_err(|err| format!("cannot extract golassie.dll: {err}"))?;
+                let wide: Vec<u16> = path
+                    .as_os_str()
+                    .encode_wide()
+                    .chain(std::iter::once(0))
+                    .collect();
+                // SAFETY:
+                // `wide` is a NUL-terminated UTF-16 string.
+                let handle = unsafe { LoadLibraryW(wide.as_ptr()) };
+                drop(verified);
+                if handle.is_null() {
+                    return Err(format!(
+                        "cannot load {}: {}",
+                        path.display(),
+                        std::io::Error::last_os_error()
+                    ));
+                }
+                Ok(Module(handle))
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// Resolve a function exported by the Go DLL. `name` must be NUL-terminated.
+    pub(crate) fn symbol(name: &'static str) -> Result<Symbol, String> {
+        let module = module()?;
+        // SAFETY:
+        // The module handle is valid and `name` is a NUL-terminated string.
+        let ptr = unsafe { GetProcAddress(module.0, name.as_ptr().cast()) };
+        if ptr.is_null() {
+            return Err(format!(
+                "golassie.dll does not export {}",
+                name.trim_end_matches('\0')
+            ));
+        }
+        Ok(Symbol(ptr))
+    }
+
+    /// Write the embedded DLL to the temp directory, unless it's already there. The file name
+    /// includes a hash of the DLL content, different builds never overwrite each other.
+    ///
+    /// Returns the path together with a handle that keeps other programs from modifying the file
+    /// until it's closed, see [`open_extracted`].
+    fn extract_dll() -> std::io::Result<(PathBuf, File)> {
+        let dir = std::env::temp_dir().join("rusty-lassie");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(concat!("golassie-", env!("GOLASSIE_DLL_HASH"), ".dll"));
+        if let Some(file) = open_extracted(&path) {
+            return Ok((path, file));
+        }
+
+        let tmp = dir.join(format!("golassie-{}.tmp", std::process::id()));
+        std::fs::write(&tmp, DLL)?;
+        let renamed = std::fs::rename(&tmp, &path);
+        if renamed.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        // Another process may have extracted and loaded the DLL in the meantime, or modified our
+        // copy after the rename
+        match open_extracted(&path) {
+            Some(file) => Ok((path, file)),
+            None => Err(renamed.err().unwrap_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{} was modified after extraction", path.display()),
+                )
+            })),
+        }
+    }
+
+    /// Open the extracted DLL, denying other handles write and delete access, and compare the
+    /// whole content: the file may have been corrupted or modified by another program without
+    /// changing its size. Returns `None` when the file cannot be opened or differs from the
+    /// embedded DLL.
+    ///
+    /// `LoadLibraryW` only needs read access, it can open the file while the returned handle is
+    /// open.
+    fn open_extracted(path: &Path) -> Option<File> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(FILE_SHARE_READ)
+            .open(path)
+            .ok()?;
+        if file.metadata().ok()?.len() != DLL.len() as u64 {
+            return None;
+        }
+        let mut content = Vec::with_capacity(DLL.len());
+        file.read_to_end(&mut content).ok()?;
+        (content == DLL).then_some(file)
+    }
+}
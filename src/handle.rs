@@ -0,0 +1,89 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A cheap, cloneable reference to a running [`Daemon`](crate::Daemon), see
+/// [`Daemon::handle`](crate::Daemon::handle).
+///
+/// Handles can be moved into worker threads to build request URLs and to check whether the
+/// daemon is still running. A handle does not keep the daemon alive, the daemon is stopped when
+/// the [`Daemon`](crate::Daemon) instance is dropped, regardless of how many handles exist.
+#[derive(Debug, Clone)]
+pub struct DaemonHandle {
+    port: u16,
+    connect_addr: Option<SocketAddr>,
+    access_token: Option<Arc<str>>,
+    running: Arc<AtomicBool>,
+}
+
+impl DaemonHandle {
+    pub(crate) fn new(
+        port: u16,
+        connect_addr: Option<SocketAddr>,
+        access_token: Option<&str>,
+        running: Arc<AtomicBool>,
+    ) -> Self {
+        DaemonHandle {
+            port,
+            connect_addr,
+            access_token: access_token.map(Arc::from),
+            running,
+        }
+    }
+
+    /// The port of the HTTP listener, see [`Daemon::port`](crate::Daemon::port).
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The access token the HTTP listener requires, if any.
+    #[must_use]
+    pub fn access_token(&self) -> Option<&str> {
+        self.access_token.as_deref()
+    }
+
+    /// The base URL of the HTTP listener, e.g. `http://127.0.0.1:41234`. The host is the
+    /// [`ListenAddr::connect_addr`](crate::ListenAddr::connect_addr) of the listener.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, i.e. the listener is disabled or bound to a
+    /// Unix socket. Check [`Daemon::listen_addr`](crate::Daemon::listen_addr) first when the
+    /// configuration is not known.
+    #[must_use]
+    pub fn base_url(&self) -> String {
+        let addr = self
+            .connect_addr
+            .expect("the daemon has no TCP listener to build URLs for");
+        format!("http://{addr}")
+    }
+
+    /// Build the URL for the given path, e.g. `/ipfs/{cid}`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[must_use]
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url())
+    }
+
+    /// Build the URL retrieving the given CID, e.g. `http://127.0.0.1:41234/ipfs/bafy...`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the daemon has no TCP listener, see [`DaemonHandle::base_url`].
+    #[cfg(feature = "cid")]
+    #[must_use]
+    pub fn ipfs_url(&self, cid: &cid::Cid) -> String {
+        self.url(&format!("/ipfs/{cid}"))
+    }
+
+    /// Returns `false` once the daemon stopped serving requests, see
+    /// [`Daemon::is_running`](crate::Daemon::is_running).
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,579 @@
+use std::any::Any;
+use std::ffi::{c_void, CString};
+use std::io::{self, PipeReader, PipeWriter, Read};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use crate::{from_c_string, LassieResult, RetrievalError, ERROR_CODE_HEADER, RETRIEVAL_ID_HEADER};
+
+go_lassie! {
+    fn ServeRequest(request: *const GoServeRequest) -> ServeResult;
+    fn DropServeResult(result: *mut ServeResult);
+    fn ReadResponse(handle: u64, buf: *mut u8, size: usize) -> ReadResult;
+    fn DropReadResult(result: *mut ReadResult);
+    fn CloseResponse(handle: u64);
+    fn ServeRequestToPipe(request: *const GoServeRequest, pipe: usize) -> ServeResult;
+    fn WaitResponse(handle: u64) -> LassieResult;
+    fn ServeRequestWithSink(
+        request: *const GoServeRequest,
+        sink: *const GoResponseSink,
+    ) -> LassieResult;
+    fn ServeRequestToFile(request: *const GoServeRequest, path: *const c_char) -> ServeToFileResult;
+    fn DropServeToFileResult(result: *mut ServeToFileResult);
+}
+
+#[repr(C)]
+struct GoServeRequest {
+    // this must be kept in sync with the definition of serve_request_t in go-lib/lassie-ffi.h
+    path: *const c_char,
+    headers: *const c_char,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct ServeResult {
+    handle: u64,
+    status: u16,
+    headers: *const c_char,
+    error: *const c_char,
+}
+
+impl Drop for ServeResult {
+    fn drop(&mut self) {
+        // SAFETY:
+        // We can safely call the FFI function to free the memory used by ServeResult, because Rust
+        // guarantees that the `drop` function is called only once for each ServeResult instance.
+        // Also ServeResult is a private struct that's visible only inside this file, and we never
+        // instantiate it directly, we always obtain instances via FFI calls.
+        unsafe { DropServeResult(self) }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct ReadResult {
+    size: usize,
+    error: *const c_char,
+}
+
+impl Drop for ReadResult {
+    fn drop(&mut self) {
+        // SAFETY:
+        // See the comment in `ServeResult::drop` above, the same reasoning applies here.
+        unsafe { DropReadResult(self) }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct ServeToFileResult {
+    // this must be kept in sync with the definition of serve_to_file_result_t in go-lib/lassie-ffi.h
+    status: u16,
+    bytes: u64,
+    headers: *const c_char,
+    body: *const c_char,
+    error: *const c_char,
+}
+
+impl Drop for ServeToFileResult {
+    fn drop(&mut self) {
+        // SAFETY:
+        // See the comment in `ServeResult::drop` above, the same reasoning applies here.
+        unsafe { DropServeToFileResult(self) }
+    }
+}
+
+/// A response to a request served by [`Daemon::serve_request`](crate::Daemon::serve_request).
+///
+/// The response body is streamed from the Go side via [`Read`]. Dropping the response before
+/// reading the entire body cancels the retrieval.
+#[derive(Debug)]
+pub struct InProcessResponse {
+    handle: u64,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl InProcessResponse {
+    pub(crate) fn serve(path: &str, headers: &[(&str, &str)]) -> io::Result<Self> {
+        let ServedHead {
+            handle,
+            status,
+            headers,
+        } = serve(path, headers, |request| {
+            // SAFETY:
+            // It's safe to call this FFI function as it does not have any special safety
+            // requirements and we know that `request` is not a NULL pointer.
+            unsafe { ServeRequest(request) }
+        })?;
+        Ok(InProcessResponse {
+            handle,
+            status,
+            headers,
+        })
+    }
+
+    #[must_use]
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    #[must_use]
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Get the value of the first header with the given name (case-insensitive).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The ID assigned to this retrieval by the daemon, see [`Daemon::cancel`](crate::Daemon::cancel).
+    #[must_use]
+    pub fn retrieval_id(&self) -> Option<&str> {
+        self.header(RETRIEVAL_ID_HEADER)
+    }
+}
+
+impl Read for InProcessResponse {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // SAFETY:
+        // `buf` is a valid, writable buffer of `buf.len()` bytes. Go writes at most `buf.len()`
+        // bytes into it and does not keep the pointer after the call returns.
+        let result = unsafe { ReadResponse(self.handle, buf.as_mut_ptr(), buf.len()) };
+        if let Some(msg) = from_c_string(result.error) {
+            return Err(io::Error::other(msg));
+        }
+        Ok(result.size)
+    }
+}
+
+impl Drop for InProcessResponse {
+    fn drop(&mut self) {
+        // SAFETY:
+        // We can call this FFI function as it does not have any special safety requirements. Rust
+        // guarantees that `drop` is called only once, therefore we never close the same handle twice.
+        unsafe { CloseResponse(self.handle) }
+    }
+}
+
+/// A response to a request served by
+/// [`Daemon::serve_request_to_pipe`](crate::Daemon::serve_request_to_pipe).
+///
+/// The Go side writes the response body directly into an OS pipe, this struct reads from the other
+/// end of the pipe. Dropping the response before reading the entire body cancels the retrieval.
+#[derive(Debug)]
+pub struct PipeResponse {
+    handle: u64,
+    status: u16,
+    headers: Vec<(String, String)>,
+    reader: PipeReader,
+    finished: bool,
+}
+
+impl PipeResponse {
+    pub(crate) fn serve(path: &str, headers: &[(&str, &str)]) -> io::Result<Self> {
+        let (reader, writer) = std::io::pipe()?;
+        let mut writer = Some(writer);
+        let ServedHead {
+            handle,
+            status,
+            headers,
+        } = serve(path, headers, |request| {
+            // `serve` calls this closure at most once
+            let pipe = into_raw_pipe(writer.take().expect("the pipe writer was already consumed"));
+            // SAFETY:
+            // It's safe to call this FFI function as `request` is not a NULL pointer and `pipe` is
+            // a valid pipe descriptor we own. The Go side takes over the ownership of `pipe` and
+            // closes it in all cases, including errors.
+            unsafe { ServeRequestToPipe(request, pipe) }
+        })?;
+        Ok(PipeResponse {
+            handle,
+            status,
+            headers,
+            reader,
+            finished: false,
+        })
+    }
+
+    #[must_use]
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    #[must_use]
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Get the value of the first header with the given name (case-insensitive).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The ID assigned to this retrieval by the daemon, see [`Daemon::cancel`](crate::Daemon::cancel).
+    #[must_use]
+    pub fn retrieval_id(&self) -> Option<&str> {
+        self.header(RETRIEVAL_ID_HEADER)
+    }
+}
+
+impl Read for PipeResponse {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.finished {
+            // The Go side closed the pipe, let's check whether the entire body was written
+            self.finished = true;
+            // SAFETY:
+            // We can call this FFI function as it does not have any special safety requirements.
+            let result = unsafe { WaitResponse(self.handle) };
+            if let Some(msg) = result.error() {
+                return Err(io::Error::other(msg));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for PipeResponse {
+    fn drop(&mut self) {
+        // SAFETY:
+        // We can call this FFI function as it does not have any special safety requirements. Rust
+        // guarantees that `drop` is called only once, therefore we never close the same handle twice.
+        unsafe { CloseResponse(self.handle) }
+    }
+}
+
+/// A destination for responses served by
+/// [`Daemon::serve_request_with_sink`](crate::Daemon::serve_request_with_sink).
+///
+/// The methods are called synchronously from the Go request handler as soon as Lassie produces
+/// the data, the bytes passed to [`ResponseSink::chunk`] are not buffered anywhere in between.
+/// Returning an error aborts the retrieval.
+pub trait ResponseSink {
+    /// Called once with the response status and headers, before any call to
+    /// [`ResponseSink::chunk`].
+    ///
+    /// # Errors
+    ///
+    /// Returning an error aborts the retrieval; the error is returned from
+    /// [`Daemon::serve_request_with_sink`](crate::Daemon::serve_request_with_sink).
+    fn head(&mut self, status: u16, headers: &[(String, String)]) -> io::Result<()>;
+
+    /// Called with each chunk of the response body.
+    ///
+    /// # Errors
+    ///
+    /// Returning an error aborts the retrieval; the error is returned from
+    /// [`Daemon::serve_request_with_sink`](crate::Daemon::serve_request_with_sink).
+    fn chunk(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+#[repr(C)]
+struct GoResponseSink {
+    // this must be kept in sync with the definition of response_sink_t in go-lib/lassie-ffi.h
+    on_head: extern "C" fn(ctx: *mut c_void, status: u16, headers: *const c_char) -> bool,
+    on_chunk: extern "C" fn(ctx: *mut c_void, data: *const u8, len: usize) -> bool,
+    ctx: *mut c_void,
+}
+
+/// The state shared with the sink callbacks via `GoResponseSink::ctx`.
+struct SinkState<'a> {
+    sink: &'a mut dyn ResponseSink,
+    error: Option<io::Error>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl SinkState<'_> {
+    fn call(&mut self, f: impl FnOnce(&mut dyn ResponseSink) -> io::Result<()>) -> bool {
+        if self.error.is_some() || self.panic.is_some() {
+            return false;
+        }
+        // Unwinding across the FFI boundary is undefined behaviour. We catch the panic here and
+        // resume it after the FFI call returns.
+        match panic::catch_unwind(AssertUnwindSafe(|| f(&mut *self.sink))) {
+            Ok(Ok(())) => true,
+            Ok(Err(err)) => {
+                self.error = Some(err);
+                false
+            }
+            Err(payload) => {
+                self.panic = Some(payload);
+                false
+            }
+        }
+    }
+}
+
+extern "C" fn sink_on_head(ctx: *mut c_void, status: u16, headers: *const c_char) -> bool {
+    // SAFETY:
+    // `ctx` points to the `SinkState` owned by `serve_with_sink`, which outlives the FFI call that
+    // invokes this callback. Go invokes the callbacks sequentially from a single goroutine, so
+    // there is no other reference to the state while this one is alive.
+    let state = unsafe { &mut *ctx.cast::<SinkState>() };
+    let headers = decode_headers(&from_c_string(headers).unwrap_or_default());
+    state.call(|sink| sink.head(status, &headers))
+}
+
+extern "C" fn sink_on_chunk(ctx: *mut c_void, data: *const u8, len: usize) -> bool {
+    // SAFETY:
+    // See the comment in `sink_on_head` above, the same reasoning applies here.
+    let state = unsafe { &mut *ctx.cast::<SinkState>() };
+    let data = if len == 0 {
+        &[]
+    } else {
+        // SAFETY:
+        // Go passes a pointer to a byte slice of `len` bytes that stays valid and unmodified
+        // until this callback returns.
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+    state.call(|sink| sink.chunk(data))
+}
+
+pub(crate) fn serve_with_sink(
+    path: &str,
+    headers: &[(&str, &str)],
+    sink: &mut dyn ResponseSink,
+) -> io::Result<()> {
+    let (path, headers) = encode_request(path, headers)?;
+    let request = GoServeRequest {
+        path: path.as_ptr(),
+        headers: headers.as_ptr(),
+    };
+
+    let mut state = SinkState {
+        sink,
+        error: None,
+        panic: None,
+    };
+    let go_sink = GoResponseSink {
+        on_head: sink_on_head,
+        on_chunk: sink_on_chunk,
+        ctx: (&raw mut state).cast(),
+    };
+
+    // SAFETY:
+    // `request` and `go_sink` are valid pointers for the duration of the call. Go invokes the
+    // callbacks only until this function returns and does not keep any of the pointers afterwards.
+    let result = unsafe { ServeRequestWithSink(&request, &go_sink) };
+
+    if let Some(payload) = state.panic {
+        panic::resume_unwind(payload);
+    }
+    if let Some(err) = state.error {
+        return Err(err);
+    }
+    if let Some(msg) = result.error() {
+        return Err(io::Error::other(msg));
+    }
+    Ok(())
+}
+
+/// A response written to a file by
+/// [`Daemon::serve_request_to_file`](crate::Daemon::serve_request_to_file).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileResponse {
+    bytes: u64,
+    headers: Vec<(String, String)>,
+}
+
+impl FileResponse {
+    /// The number of body bytes written to the file.
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    #[must_use]
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Get the value of the first header with the given name (case-insensitive).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The ID assigned to this retrieval by the daemon, see [`Daemon::cancel`](crate::Daemon::cancel).
+    #[must_use]
+    pub fn retrieval_id(&self) -> Option<&str> {
+        self.header(RETRIEVAL_ID_HEADER)
+    }
+}
+
+pub(crate) fn serve_to_file(
+    path: &str,
+    headers: &[(&str, &str)],
+    dest: &Path,
+) -> io::Result<FileResponse> {
+    let (path, headers) = encode_request(path, headers)?;
+    let dest = dest
+        .to_str()
+        .and_then(|dest| CString::new(dest).ok())
+        .ok_or_else(|| {
+            invalid_input(format!(
+                "the destination path must be valid UTF-8 without null bytes (value: {})",
+                dest.display()
+            ))
+        })?;
+    let request = GoServeRequest {
+        path: path.as_ptr(),
+        headers: headers.as_ptr(),
+    };
+
+    // SAFETY:
+    // `request` and `dest` are valid pointers for the duration of the call, Go copies the strings
+    // it needs and does not keep the pointers afterwards.
+    let result = unsafe { ServeRequestToFile(&request, dest.as_ptr()) };
+    if let Some(msg) = from_c_string(result.error) {
+        return Err(io::Error::other(msg));
+    }
+    let headers = decode_headers(&from_c_string(result.headers).unwrap_or_default());
+    if result.status != 200 {
+        let body = from_c_string(result.body).unwrap_or_default();
+        let error_code = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(ERROR_CODE_HEADER))
+            .map(|(_, value)| value.as_str());
+        return Err(io::Error::other(RetrievalError::from_error_response(
+            result.status,
+            error_code,
+            &body,
+        )));
+    }
+
+    Ok(FileResponse {
+        bytes: result.bytes,
+        headers,
+    })
+}
+
+#[cfg(unix)]
+fn into_raw_pipe(writer: PipeWriter) -> usize {
+    use std::os::fd::IntoRawFd;
+    let fd = writer.into_raw_fd();
+    usize::try_from(fd).expect("file descriptors are never negative")
+}
+
+#[cfg(windows)]
+fn into_raw_pipe(writer: PipeWriter) -> usize {
+    use std::os::windows::io::IntoRawHandle;
+    writer.into_raw_handle() as usize
+}
+
+/// The handle and the head of a response returned by `ServeRequest` or `ServeRequestToPipe`.
+struct ServedHead {
+    handle: u64,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+fn serve(
+    path: &str,
+    headers: &[(&str, &str)],
+    call: impl FnOnce(*const GoServeRequest) -> ServeResult,
+) -> io::Result<ServedHead> {
+    let (path, headers) = encode_request(path, headers)?;
+    let request = GoServeRequest {
+        path: path.as_ptr(),
+        headers: headers.as_ptr(),
+    };
+
+    // The strings referenced by `request` live until the end of this function, Go copies them
+    // before returning.
+    let result = call(&request);
+    if let Some(msg) = from_c_string(result.error) {
+        return Err(io::Error::other(msg));
+    }
+
+    Ok(ServedHead {
+        handle: result.handle,
+        status: result.status,
+        headers: decode_headers(&from_c_string(result.headers).unwrap_or_default()),
+    })
+}
+
+fn encode_request(path: &str, headers: &[(&str, &str)]) -> io::Result<(CString, CString)> {
+    if !path.starts_with("/ipfs/") && !path.starts_with("/ipns/") {
+        return Err(invalid_input(format!(
+            "request path must start with /ipfs/ or /ipns/ (value: {path:?})"
+        )));
+    }
+    let path = CString::new(path).map_err(|_| {
+        invalid_input(format!(
+            "null bytes are not allowed in the request path (value: {path:?})"
+        ))
+    })?;
+    let headers = CString::new(encode_headers(headers)?)
+        .map_err(|_| invalid_input("null bytes are not allowed in request headers".to_string()))?;
+    Ok((path, headers))
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn encode_headers(headers: &[(&str, &str)]) -> io::Result<String> {
+    let mut text = String::new();
+    for (name, value) in headers {
+        if name.contains([':', '\r', '\n']) || value.contains(['\r', '\n']) {
+            return Err(invalid_input(format!(
+                "invalid request header {name:?}: {value:?}"
+            )));
+        }
+        text.push_str(name);
+        text.push_str(": ");
+        text.push_str(value);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+fn decode_headers(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn encodes_and_decodes_headers() {
+        let text = encode_headers(&[("Accept", "application/vnd.ipld.car"), ("X-Foo", "a:b")])
+            .expect("cannot encode headers");
+        assert_eq!(
+            decode_headers(&text),
+            vec![
+                ("Accept".to_string(), "application/vnd.ipld.car".to_string()),
+                ("X-Foo".to_string(), "a:b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_header_injection() {
+        let err = encode_headers(&[("Accept", "text/plain\nX-Evil: 1")])
+            .expect_err("newlines in header values should be rejected");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
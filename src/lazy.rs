@@ -0,0 +1,124 @@
+use std::ops::Deref;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+#[cfg(feature = "client")]
+use crate::Client;
+use crate::{Daemon, DaemonConfig, DaemonHandle, StartError};
+
+/// A [`Daemon`] started on first use.
+///
+/// Applications where retrieval is an optional feature can keep a `LazyDaemon` around without
+/// paying for the Go runtime startup and the listening port until the first request. The daemon
+/// is started with the stored configuration by the first call to [`LazyDaemon::get`] (or one of
+/// the methods built on it) and stopped when the `LazyDaemon` is dropped.
+///
+/// When the daemon stopped in the meantime, e.g. after [`DaemonConfig::idle_shutdown`], the next
+/// call starts a new one. Unless [`DaemonConfig::port`] is fixed, the new daemon listens on a
+/// different port, get a fresh [`DaemonHandle`] for each batch of requests.
+///
+/// When the start fails, the error is returned to the caller and the next call tries again.
+/// Concurrent callers wait for the start in progress instead of starting their own daemon.
+pub struct LazyDaemon {
+    config: DaemonConfig,
+    daemon: RwLock<Option<Daemon>>,
+}
+
+/// The running daemon borrowed from a [`LazyDaemon`].
+///
+/// The daemon cannot be restarted while the guard exists. Don't keep it around longer than
+/// necessary and don't call [`LazyDaemon::get`] again while holding it, that can deadlock.
+pub struct LazyDaemonGuard<'a>(RwLockReadGuard<'a, Option<Daemon>>);
+
+impl Deref for LazyDaemonGuard<'_> {
+    type Target = Daemon;
+
+    fn deref(&self) -> &Daemon {
+        self.0
+            .as_ref()
+            .expect("LazyDaemonGuard is created only for a started daemon")
+    }
+}
+
+impl LazyDaemon {
+    /// Store the configuration without starting the daemon.
+    #[must_use]
+    pub fn new(config: DaemonConfig) -> Self {
+        LazyDaemon {
+            config,
+            daemon: RwLock::new(None),
+        }
+    }
+
+    /// The running daemon, started now if this is the first call or the previous daemon stopped.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the error of [`Daemon::start`] when the daemon is not running and
+    /// cannot be started.
+    pub fn get(&self) -> Result<LazyDaemonGuard<'_>, StartError> {
+        loop {
+            if let Some(guard) = self.get_if_started() {
+                return Ok(guard);
+            }
+
+            let mut daemon = self.daemon.write().unwrap_or_else(PoisonError::into_inner);
+            // Another thread may have started the daemon while we were waiting for the lock
+            if daemon.as_ref().is_some_and(Daemon::is_running) {
+                continue;
+            }
+            if let Some(stopped) = daemon.take() {
+                log::debug!("Restarting the stopped Lassie daemon");
+                if let Err(err) = stopped.try_shutdown() {
+                    log::warn!("Cannot clean up the stopped Lassie daemon: {err}");
+                }
+            } else {
+                log::debug!("Starting the lazy Lassie daemon on first use");
+            }
+            *daemon = Some(Daemon::start(self.config.clone())?);
+        }
+    }
+
+    /// The daemon if it's running, without starting it.
+    #[must_use]
+    pub fn get_if_started(&self) -> Option<LazyDaemonGuard<'_>> {
+        let daemon = self.daemon.read().unwrap_or_else(PoisonError::into_inner);
+        daemon
+            .as_ref()
+            .is_some_and(Daemon::is_running)
+            .then(|| LazyDaemonGuard(daemon))
+    }
+
+    /// A handle to the running daemon, see [`LazyDaemon::get`] and [`Daemon::handle`].
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the daemon cannot be started.
+    pub fn handle(&self) -> Result<DaemonHandle, StartError> {
+        self.get().map(|daemon| daemon.handle())
+    }
+
+    /// A client talking to the running daemon, see [`LazyDaemon::get`] and [`Client::new`].
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the daemon cannot be started.
+    #[cfg(feature = "client")]
+    pub fn client(&self) -> Result<Client, StartError> {
+        self.get().map(|daemon| Client::new(&daemon))
+    }
+
+    /// The configuration the daemon is (or will be) started with.
+    #[must_use]
+    pub fn config(&self) -> &DaemonConfig {
+        &self.config
+    }
+
+    /// Take the daemon out, e.g. to stop it with [`Daemon::shutdown_gracefully`]. Returns `None`
+    /// when it was never started.
+    #[must_use]
+    pub fn into_inner(self) -> Option<Daemon> {
+        self.daemon
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -1,26 +1,117 @@
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
 use std::os::raw::c_char;
-use std::path::PathBuf;
-use std::sync::{Mutex, MutexGuard};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
+#[macro_use]
+mod golassie;
+
+mod access_log;
+mod builder;
+#[cfg(feature = "car")]
+pub mod car;
+#[cfg(feature = "client")]
+mod client;
+#[cfg(feature = "serde")]
+mod config_de;
+mod config_error;
+#[cfg(feature = "toml")]
+mod config_file;
+#[cfg(all(feature = "client", feature = "car"))]
+mod download;
+mod error_code;
+mod events;
+#[cfg(feature = "client")]
+mod fetch_pool;
+#[cfg(feature = "front")]
+pub mod front;
+mod go_config;
+mod handle;
+mod in_process;
+mod lazy;
+mod listen_addr;
+mod measurement;
+mod metrics;
+pub mod multiaddr;
+mod progress;
+mod provider_stats;
+mod reputation;
+mod retrieval;
+mod retrieval_error;
+#[cfg(feature = "client")]
+mod retry;
+mod shared;
+mod shutdown_error;
+#[cfg(unix)]
+mod socket_activation;
 mod start_error;
+mod startup;
+mod supervisor;
+mod temp_dir;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "car")]
+pub mod unixfs;
+mod watchdog;
 
-pub use start_error::StartError;
+pub use access_log::{AccessLog, AccessLogRecord, RequestOutcome};
+pub use builder::DaemonBuilder;
+/// The `cid` crate, re-exported so that the CIDs you build match the version used by this crate.
+#[cfg(feature = "cid")]
+pub use cid;
+#[cfg(feature = "client")]
+pub use client::{
+    Client, DagScope, FetchMany, Format, IpnsName, ParseIpnsNameError, RetrievalRequest,
+    RetrievalResponse,
+};
+pub use config_error::ConfigError;
+#[cfg(feature = "toml")]
+pub use config_file::ConfigFileError;
+#[cfg(all(feature = "client", feature = "car"))]
+pub use download::{DownloadError, DownloadReport};
+pub use events::{RetrievalEvent, RetrievalEventKind, RetrievalEvents};
+#[cfg(feature = "client")]
+pub use fetch_pool::{FetchPool, JobId, JobStatus};
+pub use handle::DaemonHandle;
+pub use in_process::{FileResponse, InProcessResponse, PipeResponse, ResponseSink};
+pub use ipnet::IpNet;
+pub use lazy::{LazyDaemon, LazyDaemonGuard};
+pub use listen_addr::ListenAddr;
+pub use measurement::{Measurement, Measurements};
+pub use metrics::{MemoryStats, MetricsSnapshot, ProtocolMetrics};
+pub use progress::ProgressWatcher;
+pub use provider_stats::ProviderStats;
+pub use reputation::{ProviderCandidate, ProviderScores};
+pub use retrieval::{
+    ActiveRetrieval, Priority, ERROR_CODE_HEADER, PRIORITY_HEADER, REQUEST_ID_HEADER,
+    RETRIEVAL_ID_HEADER, TIMEOUT_HEADER, TRACEPARENT_HEADER,
+};
+pub use retrieval_error::RetrievalError;
+#[cfg(feature = "client")]
+pub use retry::{RetryAttempt, RetryOutcome, RetryPolicy};
+pub use shared::SharedDaemon;
+pub use shutdown_error::ShutdownError;
+#[cfg(unix)]
+pub use socket_activation::systemd_listen_fd;
+pub use start_error::{GoError, IoError, StartError};
+pub use supervisor::FailureEvent;
+pub use temp_dir::TempDirEvictionConfig;
+pub use watchdog::{Health, WatchdogConfig};
 
-#[cfg_attr(
-    all(target_os = "windows", target_env = "msvc"),
-    link(name = "golassie.dll")
-)]
-#[cfg_attr(
-    not(all(target_os = "windows", target_env = "msvc")),
-    link(name = "golassie")
-)]
-extern "C" {
+use go_config::{GoConfig, GoDaemonConfig};
+use multiaddr::Multiaddr;
+use supervisor::Supervisor;
+
+go_lassie! {
     fn InitDaemon(config: *const GoDaemonConfig) -> InitDaemonResult;
     fn DropDaemonInitResult(result: *mut InitDaemonResult);
     fn RunDaemon() -> LassieResult;
     fn StopDaemon() -> LassieResult;
+    fn DrainDaemon(timeout: i64) -> u64;
     fn DropResult(value: *mut LassieResult);
 }
 
@@ -34,7 +125,12 @@ extern "C" {}
 #[derive(Debug)]
 struct InitDaemonResult {
     port: u16,
+    admin_port: u16,
+    listen_addr: *const c_char,
+    extra_listen_addrs: *const c_char,
     error: *const c_char,
+    /// One of the constants in [`error_code`], `0` when `error` is NULL
+    error_code: u32,
 }
 
 impl Drop for InitDaemonResult {
@@ -48,6 +144,11 @@ impl Drop for InitDaemonResult {
     }
 }
 
+// SAFETY:
+// The error string is owned by the result and Go frees it in DropDaemonInitResult, which can be
+// called from any thread.
+unsafe impl Send for InitDaemonResult {}
+
 impl InitDaemonResult {
     fn error(&self) -> Option<String> {
         from_c_string(self.error)
@@ -58,6 +159,8 @@ impl InitDaemonResult {
 #[derive(Debug)]
 struct LassieResult {
     error: *const c_char,
+    /// One of the constants in [`error_code`], `0` when `error` is NULL
+    error_code: u32,
 }
 
 impl Drop for LassieResult {
@@ -77,6 +180,30 @@ impl LassieResult {
     }
 }
 
+fn create_temp_dir(config: &DaemonConfig) -> Result<(), StartError> {
+    let Some(dir) = &config.temp_dir else {
+        return Ok(());
+    };
+    if !config.create_temp_dir || dir.is_dir() {
+        return Ok(());
+    }
+
+    log::debug!("Creating temp_dir {}", dir.display());
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    if config.private_temp_dir {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(dir)
+        .map_err(|err| StartError::CannotCreateTempDir {
+            path: dir.clone(),
+            source: IoError::new(err),
+        })
+}
+
 fn from_c_string(str: *const c_char) -> Option<String> {
     if str.is_null() {
         return None;
@@ -87,19 +214,6 @@ fn from_c_string(str: *const c_char) -> Option<String> {
     Some(unsafe { CStr::from_ptr(str) }.to_string_lossy().to_string())
 }
 
-#[repr(C)]
-struct GoDaemonConfig {
-    // this must be kept in sync with the definition of daemon_config_t in go-lib/lassie-ffi.go
-    temp_dir: *const c_char,
-    port: u16,
-    log_level: usize,
-    max_blocks: u64,
-    provider_timeout: i64,
-    global_timeout: i64,
-    access_token: *const c_char,
-    lassie_user_agent: *const c_char,
-}
-
 struct GoDaemon {
     handler_thread: std::thread::JoinHandle<()>,
 }
@@ -113,13 +227,41 @@ fn get_global_daemon() -> std::sync::LockResult<MutexGuard<'static, Option<GoDae
     unsafe { DAEMON.lock() }
 }
 
+// The struct mirrors independent daemon flags, an enum would not make the fields any clearer
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
 pub struct DaemonConfig {
     /// Directory where to store temporary files (CAR store).
     ///
     /// By default, Lassie stores temporary files in the OS-specific temp directory.
     pub temp_dir: Option<PathBuf>,
 
+    /// Create [`DaemonConfig::temp_dir`] (including missing parents) when it does not exist.
+    ///
+    /// By default, the directory must already exist, otherwise the daemon fails to start.
+    pub create_temp_dir: bool,
+
+    /// Make the directory created by [`DaemonConfig::create_temp_dir`] accessible by the current
+    /// user only (mode `0700`). Existing directories are left alone, and on Windows the option
+    /// has no effect.
+    pub private_temp_dir: bool,
+
+    /// Keep the size of Lassie's temporary CAR files in [`DaemonConfig::temp_dir`] below a
+    /// threshold by removing the oldest files, see [`Daemon::disk_usage`].
+    ///
+    /// Lassie normally removes each file when the request finishes. The eviction protects
+    /// long-running daemons from files left behind by crashed processes and from huge in-flight
+    /// retrievals. A request whose file is evicted fails. Only files created by Lassie are
+    /// removed, other files in the directory are never touched.
+    ///
+    /// There is no eviction by default.
+    pub temp_dir_eviction: Option<TempDirEvictionConfig>,
+
     /// Port where to listen.
     ///
     /// By default, we ask the operating system to choose a free ephemeral port.
@@ -131,36 +273,784 @@ pub struct DaemonConfig {
     /// aborted in a way that triggers a client error.
     pub max_blocks: Option<u64>,
 
+    /// The maximum number of requests the daemon handles at the same time, including
+    /// in-process requests.
+    ///
+    /// Requests beyond the limit are rejected immediately with `429 Too Many Requests` and a
+    /// `Retry-After` header, see [`RetrievalError::TooManyRequests`]. This protects the daemon
+    /// from running out of memory during load spikes, clients should back off and retry.
+    ///
+    /// By default, there is no limit.
+    pub max_concurrent_requests: Option<u32>,
+
+    /// The maximum number of retrievals running at the same time, including in-process requests.
+    ///
+    /// Unlike [`max_concurrent_requests`](Self::max_concurrent_requests), the retrievals beyond
+    /// the limit are not rejected but wait in a queue until a running retrieval finishes. This
+    /// bounds the memory, temporary files and provider connections used during bursts without
+    /// failing the requests. A queued request gives up when the client disconnects. Requests can
+    /// move ahead in the queue with the [`PRIORITY_HEADER`], see [`Priority`].
+    ///
+    /// By default, there is no limit.
+    pub max_concurrent_retrievals: Option<u32>,
+
+    /// The maximum number of retrievals waiting for
+    /// [`max_concurrent_retrievals`](Self::max_concurrent_retrievals). Requests beyond that are
+    /// rejected with `429 Too Many Requests`, see [`RetrievalError::TooManyRequests`]. `Some(0)`
+    /// rejects the retrievals over the limit right away.
+    ///
+    /// By default, the queue is unbounded.
+    pub max_queued_retrievals: Option<u32>,
+
     /// Specify a custom timeout for retrieving data from a provider. Beyond this limit, when no
     /// data has been received, the retrieval will fail.
     ///
     /// At the moment, this configuration applies to Bitswap retrievals only and controls how
-    /// much time we allow for the storage provider to send us the next block.
+    /// much time we allow for the storage provider to send us the next block. See
+    /// [`http_provider_timeout`](Self::http_provider_timeout) for HTTP retrievals and
+    /// [`bitswap_provider_timeout`](Self::bitswap_provider_timeout), which takes precedence.
     ///
     /// On timeout, the HTTP response will be aborted in a way that triggers a client error.
     ///
     /// The default timeout is controlled by Go version of Lassie and you should not rely on any
     /// particular value. Provide your own value if this timeout is important for you.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
     pub provider_timeout: Option<Duration>,
 
+    /// How long to wait for an HTTP provider to send the response headers, and then for the
+    /// next chunk of the response body. Slower providers fail the retrieval from them.
+    ///
+    /// The timeout applies to all outbound HTTP requests of the daemon, including IPNI and
+    /// delegated routing lookups.
+    ///
+    /// No timeout is enforced by default.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
+    pub http_provider_timeout: Option<Duration>,
+
+    /// How long to wait for a Bitswap provider to send the next block, overrides
+    /// [`provider_timeout`](Self::provider_timeout).
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
+    pub bitswap_provider_timeout: Option<Duration>,
+
+    /// Not supported, Lassie does not accept a per-provider timeout for Graphsync retrievals.
+    /// Setting it fails with [`ConfigError::Unsupported`], use
+    /// [`global_timeout`](Self::global_timeout) to bound Graphsync retrievals.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
+    pub graphsync_provider_timeout: Option<Duration>,
+
     /// Specify a custom timeout for the entire retrieval process.
     ///
     /// On timeout, the HTTP response will be aborted in a way that triggers a client error.
     ///
     /// No timeout is enforced by default.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
     pub global_timeout: Option<Duration>,
 
+    /// Temporarily skip providers that failed several retrievals in a row, so that a dead
+    /// provider does not eat into the [`global_timeout`](Self::global_timeout) of every request.
+    ///
+    /// Skipped providers are reported by [`Daemon::provider_stats`]. Providers listed in the
+    /// `providers=` parameter of a request are always tried.
+    ///
+    /// All providers are tried by default.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
     /// Require retrieval requests to provide authorization header with the configured access token.
     ///
     /// For example: `Authorization: Bearer {token}`
     pub access_token: Option<String>,
+
+    /// Accept requests via the HTTP listener only from clients in these subnets, e.g.
+    /// `10.0.0.0/8`. Requests from other addresses are rejected with `403 Forbidden`, an empty list
+    /// rejects all of them.
+    ///
+    /// The check is independent of [`DaemonConfig::access_token`], configure both to require a
+    /// known address and the token. In-process requests are not affected.
+    ///
+    /// All clients are accepted by default.
+    pub allowed_client_ips: Option<Vec<IpNet>>,
+
+    /// Do not open the HTTP listener, serve requests in-process via [`Daemon::serve_request`] only.
+    ///
+    /// Use this mode in environments where opening sockets is not allowed or to avoid port
+    /// conflicts. [`Daemon::port`] returns `0` when the listener is disabled.
+    pub disable_listener: bool,
+
+    /// Serve HTTP requests on this bound and listening socket instead of opening a listener,
+    /// e.g. on a socket passed by systemd, see [`systemd_listen_fd`].
+    ///
+    /// The owner of the socket can keep the port open while the daemon restarts. The socket can be
+    /// a TCP or a Unix domain socket, [`Daemon::port`] returns `0` for the latter. The daemon
+    /// duplicates the descriptor, the caller keeps ownership of `listener_fd` and can close it
+    /// once [`Daemon::start`] returns. [`DaemonConfig::port`] must stay `0`.
+    ///
+    /// Not set by default.
+    #[cfg(unix)]
+    pub listener_fd: Option<std::os::fd::RawFd>,
+
+    /// Set `SO_REUSEPORT` on the HTTP listener, so that a new daemon process can bind the same
+    /// [`port`](Self::port) while the old one drains with [`Daemon::shutdown_gracefully`].
+    ///
+    /// The kernel spreads the new connections across all processes listening on the port; once
+    /// the old daemon closes its listener, they all go to the new one. Both processes must run as
+    /// the same user. Requires a fixed `port`, not supported on Windows. Disabled by default.
+    pub reuse_port: bool,
+
+    /// How long [`Daemon::start`] waits for Lassie to initialize and bind its listeners before
+    /// failing with [`StartError::StartupTimedOut`].
+    ///
+    /// Go cannot abort a stuck initialization, e.g. a DNS lookup that never returns. After the
+    /// timeout, it continues in the background and the daemon is stopped as soon as it comes up;
+    /// until then, [`Daemon::start`] fails with [`StartError::OnlyOneInstanceAllowed`].
+    ///
+    /// By default, [`Daemon::start`] waits as long as it takes.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
+    pub startup_timeout: Option<Duration>,
+
+    /// Stop the daemon after it served no HTTP or in-process requests for this long, e.g. to
+    /// release the port and the libp2p connections of a desktop app that retrieves content only
+    /// occasionally.
+    ///
+    /// The stopped daemon rejects requests and [`Daemon::is_running`] returns `false`, drop it
+    /// to start another one. [`LazyDaemon`] does that transparently on the next use. The admin
+    /// listener requests don't count as activity.
+    ///
+    /// By default, the daemon runs until it's dropped.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
+    pub idle_shutdown: Option<Duration>,
+
+    /// Restart the daemon when the Go HTTP handler fails, instead of leaving it down.
+    ///
+    /// The daemon is initialized again on the same port, first after 100 ms, then with the delay
+    /// doubling up to 30 seconds. The supervisor gives up after 10 failed attempts in a row. Watch
+    /// [`Daemon::failure_events`] to learn about the failures and restarts. An admin listener
+    /// configured with port `0` gets a new port on restart. Disabled by default.
+    pub auto_restart: bool,
+
+    /// Probe the daemon periodically and report it as unhealthy when it stops responding, see
+    /// [`Daemon::health`] and [`FailureEvent::Unresponsive`].
+    ///
+    /// The watchdog sends `GET /healthz` requests to the [`Daemon::listen_addr`], they are not
+    /// recorded in the access log. Without a TCP listener, it pings the Go side via FFI instead.
+    /// There is no watchdog by default.
+    pub watchdog: Option<WatchdogConfig>,
+
+    /// Push retrieval events to an external collector (the event recorder API), like
+    /// `lassie daemon --event-recorder-url` does.
+    ///
+    /// No events are pushed by default.
+    pub event_recorder_url: Option<String>,
+
+    /// The authorization token to send to the event recorder API.
+    pub event_recorder_auth: Option<String>,
+
+    /// The instance ID to report to the event recorder API.
+    ///
+    /// By default, Lassie generates a random ID each time the daemon starts.
+    pub event_recorder_instance_id: Option<String>,
+
+    /// Export the spans of the retrievals (candidate discovery, dialing and transferring from each
+    /// provider) and the spans recorded by Lassie's libraries to an OpenTelemetry collector.
+    ///
+    /// A retrieval span is the child of the span in the [`TRACEPARENT_HEADER`] of the request, if
+    /// any, see [`RetrievalRequest::traceparent`](crate::RetrievalRequest::traceparent).
+    ///
+    /// No traces are exported by default.
+    pub otlp: Option<OtlpConfig>,
+
+    /// The user agent sent to providers.
+    ///
+    /// By default, we send `lassie/v{version}` where `{version}` is the version of the Go Lassie
+    /// library.
+    pub user_agent: Option<String>,
+
+    /// Multiaddrs of the peers to connect to when the daemon starts, including the peer ID, e.g.
+    /// `/ip4/192.0.2.1/tcp/4001/p2p/12D3KooW...`. Addresses without the peer ID are rejected with
+    /// [`ConfigError::MissingPeerId`].
+    ///
+    /// Lassie discovers providers via IPNI and does not need any bootstrap peers. By default (and
+    /// when the list is empty), the daemon does not dial any peers at startup, which makes it
+    /// suitable for private network deployments.
+    pub bootstrap_peers: Option<Vec<Multiaddr>>,
+
+    /// Multiaddrs of providers to connect to when the daemon starts and to stay connected to,
+    /// including the peer ID, e.g. `/ip4/192.0.2.1/tcp/24001/p2p/12D3KooW...`. List a provider
+    /// several times to give it more addresses. Addresses without the peer ID are rejected with
+    /// [`ConfigError::MissingPeerId`].
+    ///
+    /// Retrievals from these providers reuse the open connection and skip the dial and the
+    /// handshakes. The connections are never closed by the connection manager and are
+    /// re-established when they drop. This applies to libp2p retrievals (Bitswap, Graphsync),
+    /// Lassie opens new connections for HTTP retrievals.
+    pub preconnect_providers: Vec<Multiaddr>,
+
+    /// Do not discover providers via the IPNI indexer.
+    ///
+    /// IPNI is the only candidate source used by Lassie, DHT results are obtained via the indexer
+    /// too. With IPNI disabled, the daemon retrieves content only from the providers specified in
+    /// the request via `providers=`.
+    pub disable_ipni: bool,
+
+    /// Do not ask the IPNI indexer to cascade lookups to the IPFS DHT.
+    pub disable_dht: bool,
+
+    /// The base URL of an HTTP delegated routing server implementing the Routing V1 API, e.g.
+    /// `https://delegated-ipfs.dev`.
+    ///
+    /// When configured, the daemon discovers providers via `GET /routing/v1/providers/{cid}` on
+    /// this server instead of the IPNI indexer, and [`DaemonConfig::disable_ipni`] and
+    /// [`DaemonConfig::disable_dht`] have no effect. The server also resolves IPNS names. Only
+    /// providers with known addresses are used, the daemon cannot look up peer addresses.
+    ///
+    /// By default, providers are discovered via IPNI and IPNS records are fetched from
+    /// `https://delegated-ipfs.dev`.
+    pub delegated_routing_url: Option<String>,
+
+    /// Retrieve content only from the providers specified in the request via `providers=` and
+    /// never consult IPNI, the DHT or [`DaemonConfig::delegated_routing_url`].
+    ///
+    /// Unlike [`DaemonConfig::disable_ipni`], requests without `providers=` fail immediately with
+    /// [`RetrievalError::ProvidersRequired`] instead of searching for candidates in vain.
+    /// IPNS names are still resolved.
+    pub disable_candidate_discovery: bool,
+
+    /// Limit the number of open libp2p connections.
+    ///
+    /// By default, the limits are controlled by the Go libp2p library.
+    pub connection_manager: Option<ConnectionManagerConfig>,
+
+    /// The maximum number of concurrent Bitswap requests across all retrievals, i.e. the maximum
+    /// number of outstanding wants.
+    ///
+    /// Raise this value for high-throughput deployments, lower it on low-memory devices. By
+    /// default, the limit is controlled by the Go version of Lassie.
+    pub bitswap_concurrency: Option<u32>,
+
+    /// The maximum number of concurrent Bitswap requests per retrieval.
+    ///
+    /// By default, the limit is controlled by the Go version of Lassie.
+    pub bitswap_concurrency_per_retrieval: Option<u32>,
+
+    /// Keep the connections to the providers that served Bitswap retrievals open for this long
+    /// after their last use, so that batches of retrievals from the same provider don't pay the
+    /// connection setup every time.
+    ///
+    /// The connections are protected from the [connection manager](Self::connection_manager),
+    /// but the provider may still close them. Lassie creates a new Bitswap session for every
+    /// retrieval.
+    ///
+    /// By default, idle connections are closed by the connection manager.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_duration"))]
+    pub bitswap_keep_alive: Option<Duration>,
+
+    /// Cache the retrieved blocks on disk and reuse them for subsequent retrievals, including
+    /// retrievals made after the daemon restarts.
+    ///
+    /// Requests for DAGs the cache holds entirely are served without contacting any provider.
+    /// Otherwise, Lassie consults the cache for Bitswap retrievals only, retrievals from HTTP
+    /// providers download the entire DAG (but still populate the cache).
+    ///
+    /// By default, there is no cache and every retrieval downloads all blocks from providers.
+    pub block_cache: Option<BlockCacheConfig>,
+
+    /// Keep the blocks of each retrieval in a memory-mapped file of up to this many bytes, for
+    /// machines with plenty of RAM and slow disks.
+    ///
+    /// Lassie stores the blocks it receives in a temporary CAR file in
+    /// [`temp_dir`](Self::temp_dir) and reads them back while verifying the DAG and producing the
+    /// response. With this option, the blocks are also kept in a sparse file mapped into memory
+    /// and Lassie reads them from the mapping, so the working set stays in the page cache and
+    /// the kernel writes it back lazily, only under memory pressure. The temporary CAR file then
+    /// receives sequential writes only. Blocks that don't fit are read from the temporary CAR
+    /// file as usual.
+    ///
+    /// The mapping is created in `temp_dir` and reserves address space, not memory. Not supported
+    /// on Windows. By default, there is no memory-mapped store.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_size"))]
+    pub mmap_car_store: Option<u64>,
+
+    /// Accept at most this many bytes per second from the providers, across all retrievals, so
+    /// that the retrievals don't saturate the host's uplink and starve the application's own
+    /// traffic.
+    ///
+    /// The limit is applied to the blocks as they are received: a retrieval waits before storing
+    /// a block, which slows down HTTP providers via TCP back-pressure and delays the next Bitswap
+    /// requests. Blocks served from the [`block_cache`](Self::block_cache) are not limited.
+    ///
+    /// By default, there is no limit.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_size"))]
+    pub max_download_rate: Option<u64>,
+
+    /// Like [`max_download_rate`](Self::max_download_rate), but for each retrieval on its own.
+    /// Both limits can be combined.
+    ///
+    /// By default, there is no limit.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_size"))]
+    pub max_download_rate_per_retrieval: Option<u64>,
+
+    /// Open a second listener exposing control endpoints, keeping them off the public retrieval
+    /// port:
+    ///
+    /// - `GET /stats` - uptime, the number of active and total retrievals, bytes sent
+    /// - `GET /retrievals` - the retrievals running right now, see [`Daemon::active_retrievals`]
+    /// - `GET /config` - the daemon configuration, excluding secrets
+    /// - `GET /libp2p` - the peer ID, listen addresses, connections and connection manager limits
+    ///   of the libp2p host
+    /// - `POST /shutdown` - stop the daemon; the [`Daemon`] value must still be dropped
+    ///
+    /// All endpoints return JSON. By default, there is no admin listener.
+    pub admin_listener: Option<AdminListenerConfig>,
+
+    /// Serve the retrieval requests on more endpoints besides the HTTP listener, e.g. a Unix
+    /// socket for a co-located proxy next to the loopback port for local clients.
+    ///
+    /// Each listener has its own [`access_token`](ExtraListenerConfig::access_token),
+    /// [`allowed_client_ips`](Self::allowed_client_ips) applies to the TCP listeners only. The
+    /// HTTP listener cannot be [disabled](Self::disable_listener) when there are extra listeners.
+    /// See [`Daemon::extra_listen_addrs`] for the bound addresses.
+    pub extra_listeners: Vec<ExtraListenerConfig>,
+
+    /// The format of the log output of the Go side (Lassie, libp2p and this wrapper).
+    ///
+    /// [`LogFormat::Json`] produces single-line JSON records with the keys `level`, `ts`,
+    /// `subsystem` and `msg`, written to stderr. Records this wrapper emits for a retrieval
+    /// include the `retrieval_id` and `request_id` keys (see [`RETRIEVAL_ID_HEADER`] and
+    /// [`REQUEST_ID_HEADER`]).
+    ///
+    /// The log levels of Go subsystems are controlled by the `GOLOG_LOG_LEVEL` environment
+    /// variable. With this option or [`log_file`](Self::log_file) set, the wrapper logs the start
+    /// of the daemon at the info level, with the `port` and `admin_port` keys. The Go logger is shared by the entire process, the format stays in effect until
+    /// the next daemon starts.
+    pub log_format: LogFormat,
+
+    /// Write the Go log output to this file instead of stderr.
+    ///
+    /// The file is rotated when it grows over 10 MiB, the five most recent rotated files are kept
+    /// as `{log_file}.1` (the newest) to `{log_file}.5`. Go runtime panics are still reported on
+    /// stderr.
+    pub log_file: Option<PathBuf>,
+
+    /// Append every Lassie retrieval event to this file as a single-line JSON record, e.g.
+    /// `{"ts":"...","event":"success","retrieval_id":"...","provider":"12D3KooW...","bytes":1024}`.
+    ///
+    /// The records carry the keys `ts`, `event` (the Lassie event code), `lassie_id` and, when
+    /// known, `retrieval_id`, `root_cid`, `provider`, `protocol`, `error`, `candidates`,
+    /// `duration_ms`, `bytes` and `blocks`. The file is rotated like [`log_file`](Self::log_file).
+    /// See [`Daemon::retrieval_events`] to process the events in-process instead.
+    pub event_log: Option<PathBuf>,
+
+    /// The HTTP version and connection pool limits for outbound HTTP requests, i.e. retrievals
+    /// from HTTP providers, IPNI and delegated routing lookups and event recorder reports.
+    pub outbound_http: OutboundHttpConfig,
+
+    /// PEM files with additional CA certificates to trust when connecting to HTTPS servers, e.g.
+    /// providers using certificates issued by an internal CA.
+    ///
+    /// The certificates are added to the system trust store, they don't replace it. Each file
+    /// can contain several certificates, [`Daemon::start`] fails when a file cannot be read or
+    /// contains no certificate.
+    pub extra_root_certs: Option<Vec<PathBuf>>,
+
+    /// The transports the libp2p host uses to dial Bitswap and Graphsync providers, e.g.
+    /// `vec![Libp2pTransport::Tcp]` to avoid waiting for QUIC dials where UDP is blocked. Start
+    /// from [`Libp2pTransport::ALL`] to disable a single transport.
+    ///
+    /// Providers announcing only addresses of disabled transports cannot be retrieved from. By
+    /// default, the libp2p default transports are enabled.
+    pub libp2p_transports: Option<Vec<Libp2pTransport>>,
+
+    /// A soft limit of the memory used by the Go runtime in bytes, like the `GOMEMLIMIT`
+    /// environment variable.
+    ///
+    /// The Go garbage collector runs more often when the heap approaches the limit. The limit
+    /// covers the Go side only (Lassie, libp2p, the Go runtime itself), not the memory allocated by
+    /// Rust. It's a soft limit, Go exceeds it rather than fail when the live data does not fit.
+    ///
+    /// By default, the limit the Go runtime started with (`GOMEMLIMIT` or no limit) stays in
+    /// effect.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::opt_size"))]
+    pub go_memory_limit: Option<u64>,
+
+    /// The maximum number of OS threads executing Go code at the same time, like the `GOMAXPROCS`
+    /// environment variable.
+    ///
+    /// Go sizes its scheduler to the number of CPU cores of the host, ignoring CPU quotas of
+    /// containers. Set this to the quota to avoid throttling. Threads blocked in system calls or
+    /// calls into Rust don't count towards the limit.
+    ///
+    /// By default, the value the Go runtime started with (`GOMAXPROCS` or the number of CPUs)
+    /// stays in effect.
+    pub go_max_procs: Option<u32>,
+}
+
+impl DaemonConfig {
+    /// Check the entire configuration and report all problems found.
+    ///
+    /// [`Daemon::start`] stops at the first problem, use this method to report all of them at once,
+    /// e.g. when validating a configuration file. The check includes access to the file system:
+    /// `temp_dir` must exist and be writable. An empty list means the configuration is valid.
+    #[must_use]
+    pub fn validate(&self) -> Vec<ConfigError> {
+        config_error::validate(self)
+    }
+
+    /// Read the configuration from a TOML file, see [`DaemonConfig::from_toml`].
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the file cannot be read or when its content is not a
+    /// valid configuration.
+    #[cfg(feature = "toml")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        config_file::from_file(path.as_ref())
+    }
+
+    /// Parse the configuration from TOML.
+    ///
+    /// The keys are the names of the [`DaemonConfig`] fields, fields not listed keep their default
+    /// values. Durations are numbers of seconds or strings like `"500ms"`, `"30s"`, `"5m"` or
+    /// `"2h"`; sizes are numbers of bytes or strings like `"512MiB"` or `"2GiB"`. Enum values are
+    /// lowercase, e.g. `log_format = "json"`, and the admin address is a table like
+    /// `address = { port = 9090 }` or `address = { unix_socket = "/run/lassie/admin.sock" }`:
+    ///
+    /// ```toml
+    /// port = 8080
+    /// provider_timeout = "20s"
+    /// preconnect_providers = ["/ip4/192.0.2.1/tcp/24001/p2p/12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz"]
+    /// log_format = "json"
+    ///
+    /// [block_cache]
+    /// dir = "/var/cache/lassie"
+    /// max_size = "10GiB"
+    ///
+    /// [admin_listener]
+    /// address = { port = 9090 }
+    /// ```
+    ///
+    /// Unknown keys are rejected to catch typos. The values are not validated beyond their
+    /// types, use [`DaemonConfig::validate`] to check them.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when `text` is not valid TOML or does not describe a valid
+    /// configuration.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self, ConfigFileError> {
+        config_file::from_toml(text)
+    }
+}
+
+/// The format of the Go log output, see [`DaemonConfig::log_format`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum LogFormat {
+    /// Human-readable text, the default format of Go libraries.
+    #[default]
+    Text,
+    /// Single-line JSON records.
+    Json,
+}
+
+/// Configuration of outbound HTTP requests, see [`DaemonConfig::outbound_http`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct OutboundHttpConfig {
+    pub version: HttpVersion,
+
+    /// The maximum number of connections per host, including connections in use. Requests
+    /// wait for a free connection when the limit is reached.
+    ///
+    /// There is no limit by default.
+    pub max_conns_per_host: Option<u32>,
+
+    /// The maximum number of idle connections per host kept open for reuse.
+    ///
+    /// The default is controlled by the Go HTTP client (currently 2).
+    pub max_idle_conns_per_host: Option<u32>,
+
+    /// The certificate to present to HTTPS servers requesting a client certificate (mutual TLS),
+    /// e.g. providers of a private retrieval network.
+    ///
+    /// The daemon presents the certificate to every server asking for one, including IPNI and
+    /// delegated routing servers. No client certificate is configured by default.
+    pub client_certificate: Option<ClientCertificate>,
+
+    /// Do not verify the certificates of HTTPS servers. **Anyone on the network path can then
+    /// impersonate the providers and the routing servers.**
+    ///
+    /// This exists for tests against local providers with self-signed certificates, prefer
+    /// [`DaemonConfig::extra_root_certs`] wherever possible. The field is available only with the
+    /// `danger-accept-invalid-certs` feature, so that production builds cannot enable it by
+    /// accident. The daemon logs a warning at startup when the verification is disabled.
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    pub danger_accept_invalid_certs: bool,
+}
+
+/// A TLS client certificate, see [`OutboundHttpConfig::client_certificate`].
+///
+/// The files are read when the daemon starts, [`Daemon::start`] fails when they cannot be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ClientCertificate {
+    /// A PEM file with the certificate chain, the leaf certificate first.
+    pub cert_file: PathBuf,
+    /// A PEM file with the unencrypted private key (PKCS #1, PKCS #8 or SEC 1).
+    pub key_file: PathBuf,
+}
+
+/// The HTTP versions allowed for outbound requests, see [`OutboundHttpConfig::version`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HttpVersion {
+    /// Negotiate HTTP/2 with servers supporting it (over TLS), use HTTP/1.1 otherwise.
+    #[default]
+    Auto,
+    /// Always use HTTP/1.1. Use this for providers misbehaving over HTTP/2.
+    Http1Only,
+}
+
+/// A libp2p transport, see [`DaemonConfig::libp2p_transports`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Libp2pTransport {
+    Tcp,
+    /// QUIC over UDP.
+    Quic,
+    WebSocket,
+    /// WebTransport over QUIC.
+    WebTransport,
+    /// WebRTC without a signalling server (`/webrtc-direct` addresses).
+    #[cfg_attr(feature = "serde", serde(rename = "webrtc-direct"))]
+    WebRtcDirect,
+}
+
+impl Libp2pTransport {
+    /// All supported transports.
+    pub const ALL: [Libp2pTransport; 5] = [
+        Libp2pTransport::Tcp,
+        Libp2pTransport::Quic,
+        Libp2pTransport::WebSocket,
+        Libp2pTransport::WebTransport,
+        Libp2pTransport::WebRtcDirect,
+    ];
+
+    /// The bit in `daemon_config_t.libp2p_transports`, see `LASSIE_TRANSPORT_*` in
+    /// go-lib/lassie-ffi.h.
+    pub(crate) fn bit(self) -> u32 {
+        match self {
+            Libp2pTransport::Tcp => 1,
+            Libp2pTransport::Quic => 2,
+            Libp2pTransport::WebSocket => 4,
+            Libp2pTransport::WebTransport => 8,
+            Libp2pTransport::WebRtcDirect => 16,
+        }
+    }
+}
+
+/// Configuration of the libp2p connection manager, see [`DaemonConfig::connection_manager`].
+///
+/// When the number of open connections exceeds `high_water`, the connection manager closes
+/// connections until only `low_water` connections remain. Connections younger than
+/// `grace_period` are never closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ConnectionManagerConfig {
+    pub low_water: u32,
+    pub high_water: u32,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::duration"))]
+    pub grace_period: Duration,
+}
+
+impl Default for ConnectionManagerConfig {
+    /// The defaults used by the Go libp2p library.
+    fn default() -> Self {
+        ConnectionManagerConfig {
+            low_water: 160,
+            high_water: 192,
+            grace_period: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Configuration of the circuit breaker, see [`DaemonConfig::circuit_breaker`].
+///
+/// After `failures` consecutive failed attempts, the candidates of a provider are skipped for
+/// `cool_down`. Then the provider gets another chance: a success resets the count, a failure
+/// skips the provider again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct CircuitBreakerConfig {
+    pub failures: u32,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::duration"))]
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failures: 3,
+            cool_down: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Configuration of the trace export, see [`DaemonConfig::otlp`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct OtlpConfig {
+    /// The base URL of the OTLP/HTTP collector, e.g. `http://localhost:4318`. The spans are
+    /// posted to `{endpoint}/v1/traces` in the JSON encoding.
+    pub endpoint: String,
+
+    /// The `service.name` of the exported spans, `rusty-lassie` by default.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub service_name: Option<String>,
+}
+
+/// Configuration of the persistent block cache, see [`DaemonConfig::block_cache`].
+///
+/// When the total size of cached blocks exceeds `max_size` bytes, the least recently used blocks
+/// are removed from the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct BlockCacheConfig {
+    pub dir: PathBuf,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "config_de::size"))]
+    pub max_size: u64,
+}
+
+/// Configuration of the admin listener, see [`DaemonConfig::admin_listener`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct AdminListenerConfig {
+    pub address: AdminAddress,
+
+    /// Require admin requests to provide authorization header with the configured access token,
+    /// e.g. `Authorization: Bearer {token}`. This token is independent of
+    /// [`DaemonConfig::access_token`].
+    pub access_token: Option<String>,
+
+    /// Serve the Go profiling endpoints under `/debug/pprof/`, e.g.
+    /// `GET /debug/pprof/profile?seconds=30` for a CPU profile or `GET /debug/pprof/heap`. Open
+    /// the profiles with `go tool pprof`.
+    ///
+    /// The profiles reveal internals of the process, protect the listener with an access token.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pprof: bool,
+}
+
+/// An additional HTTP listener, see [`DaemonConfig::extra_listeners`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ExtraListenerConfig {
+    pub address: AdminAddress,
+
+    /// Require the requests on this listener to provide authorization header with the configured
+    /// access token. This token is independent of [`DaemonConfig::access_token`], `None` accepts
+    /// all requests.
+    pub access_token: Option<String>,
+}
+
+/// Where the admin listener or an [extra listener](DaemonConfig::extra_listeners) accepts
+/// connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AdminAddress {
+    /// Listen on the given TCP port on localhost. Use `0` to let the OS pick a free port, see
+    /// [`Daemon::admin_port`].
+    Port(u16),
+    /// Listen on a Unix domain socket created at the given path. The path must not exist.
+    UnixSocket(PathBuf),
 }
 
+/// The running Lassie daemon. There can be only one instance per process, the daemon is stopped
+/// when this value is dropped.
+///
+/// # Thread safety
+///
+/// `Daemon` is `Send + Sync`. It holds no pointers into Go memory, all state lives in the Go
+/// runtime and every exported Go function synchronises access to it, so the methods can be called
+/// from any thread, concurrently. Moving the `Daemon` to another thread cannot invalidate the
+/// callbacks: a [`ResponseSink`] is called only until [`Daemon::serve_request_with_sink`]
+/// returns, and the callbacks of [`Daemon::watch_progress`] and [`Daemon::access_log`] keep
+/// being called until the returned [`ProgressWatcher`] or [`AccessLog`] guard is dropped. Use
+/// [`Daemon::handle`] to share the connection details with worker threads without sharing the
+/// `Daemon` itself.
 pub struct Daemon {
     port: u16,
+    listen_addr: Option<ListenAddr>,
+    extra_listen_addrs: Vec<ListenAddr>,
+    admin_port: Option<u16>,
     access_token: Option<String>,
+    running: Arc<AtomicBool>,
+    stopped: bool,
+    supervisor: Arc<Supervisor>,
+    temp_dir: PathBuf,
+    eviction: Option<temp_dir::EvictionTask>,
+    watchdog: Option<watchdog::WatchdogTask>,
 }
 
+// Keep the guarantees documented above checked by the compiler
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    const fn assert_send<T: Send>() {}
+    assert_send_sync::<Daemon>();
+    assert_send_sync::<DaemonHandle>();
+    assert_send_sync::<LazyDaemon>();
+    assert_send_sync::<SharedDaemon>();
+    assert_send::<InProcessResponse>();
+    assert_send::<PipeResponse>();
+};
+
 impl Daemon {
     /// # Errors
     ///
@@ -175,136 +1065,544 @@ impl Daemon {
         }
 
         log::info!("Starting Lassie Daemon");
-        let temp_dir = match config.temp_dir {
-            None => String::new(),
-            Some(dir) => {
-                let str = dir.to_str();
-                match str {
-                    None => return Err(StartError::PathIsNotValidUtf8(dir)),
-                    Some(val) => val.to_string(),
-                }
-            }
-        };
-
-        let temp_dir = CString::new(temp_dir.clone())
-            .map_err(|_| StartError::PathContainsNullByte(temp_dir))?;
-
-        let log_level = if log::log_enabled!(log::Level::Debug) {
-            log::LevelFilter::Debug
-        } else {
-            log::LevelFilter::Off
-        };
-
-        let global_timeout = match config.global_timeout {
-            Some(d) => try_convert_duration_to_go_type(d)?,
-            None => 0,
-        };
-
-        let provider_timeout = match config.provider_timeout {
-            Some(d) => try_convert_duration_to_go_type(d)?,
-            None => 0,
-        };
-
-        let access_token = config.access_token.clone().unwrap_or_default();
-        let access_token = CString::new(access_token.clone())
-            .map_err(|_| StartError::AccessTokenContainsNullByte(access_token.to_string()))?;
-
-        // See https://github.com/filecoin-project/lassie/pull/240
-        let lassie_version = env!("LASSIE_VERSION");
-        let lassie_user_agent = format!("lassie/v{lassie_version}");
-        let lassie_user_agent = CString::new(lassie_user_agent.clone()).map_err(|_| {
-            StartError::Lassie("Internal error: invalid Lassie version.".to_string())
-        })?;
-
-        let go_config = GoDaemonConfig {
-            temp_dir: temp_dir.as_ptr(),
-            log_level: log_level as usize,
-            port: config.port,
-            global_timeout,
-            provider_timeout,
-            max_blocks: config.max_blocks.unwrap_or(0),
-            access_token: access_token.as_ptr(),
-            lassie_user_agent: lassie_user_agent.as_ptr(),
-        };
-
-        // SAFETY:
-        // It's safe to call this FFI function as it does not have any special safety requirements
-        // and we know that `&go_config` is not a NULL pointer.
-        let result = unsafe { InitDaemon(&go_config) };
+        golassie::load().map_err(StartError::Lassie)?;
+        create_temp_dir(&config)?;
+        let go_config = GoConfig::new(&config)?;
+        let result = startup::init_daemon(go_config, config.startup_timeout)?;
         log::debug!("Lassie.InitDaemon result: {:?}", result);
 
         if let Some(msg) = result.error() {
             log::error!("Lassie.InitDaemon failed: {msg}");
-            return Err(StartError::Lassie(msg));
+            return Err(StartError::from_init_error(msg, result.error_code, &config));
         }
         let port = result.port;
+        let listen_addr = from_c_string(result.listen_addr).map(ListenAddr::from_go);
+        let extra_listen_addrs = from_c_string(result.extra_listen_addrs)
+            .iter()
+            .flat_map(|addrs| addrs.lines())
+            .map(|addr| ListenAddr::from_go(addr.to_string()))
+            .collect();
         log::debug!("Lassie.InitDaemon returned port: {port}");
+        let admin_port = match &config.admin_listener {
+            Some(AdminListenerConfig {
+                address: AdminAddress::Port(_),
+                ..
+            }) => Some(result.admin_port),
+            _ => None,
+        };
 
-        let handler_thread = std::thread::spawn(|| {
-            log::debug!("Running Lassie HTTP handler");
-            // SAFETY:
-            // This FFI function is designed to be called from a different thread.
-            let result = unsafe { RunDaemon() };
-            if let Some(msg) = result.error() {
-                log::error!("Lassie HTTP handler failed: {msg}");
-                // TODO: should we somehow notify the main thread about the problem?
-                // Maybe we should panic? That would not kill the main thread though.
-            }
-            log::debug!("HTTP handler exited");
+        let running = Arc::new(AtomicBool::new(true));
+        let supervisor = Supervisor::new();
+        // Restarts bind the port we got, unless the socket is owned by the caller
+        #[cfg(unix)]
+        let restart_on_port = config.listener_fd.is_none();
+        #[cfg(not(unix))]
+        let restart_on_port = true;
+        let restart_config = config.auto_restart.then(|| DaemonConfig {
+            port: if restart_on_port { port } else { config.port },
+            ..config.clone()
+        });
+        let handler_running = Arc::clone(&running);
+        let handler_supervisor = Arc::clone(&supervisor);
+        let handler_thread = std::thread::spawn(move || {
+            handler_supervisor.run_handler(restart_config.as_ref(), &handler_running);
         });
         *maybe_daemon = Some(GoDaemon { handler_thread });
 
-        log::info!("Lassie Daemon is listening on port {}", port);
+        match &listen_addr {
+            Some(addr) => log::info!("Lassie Daemon is listening on {addr}"),
+            None => log::info!("Lassie Daemon is running, the HTTP listener is disabled"),
+        }
+        let watchdog = config.watchdog.as_ref().map(|watchdog| {
+            let probe = listen_addr
+                .as_ref()
+                .and_then(ListenAddr::connect_addr)
+                .map_or(watchdog::Probe::Ffi(None), watchdog::Probe::Listener);
+            watchdog::WatchdogTask::start(
+                watchdog,
+                probe,
+                Arc::clone(&running),
+                Arc::clone(&supervisor),
+            )
+        });
+        let temp_dir = config.temp_dir.unwrap_or_else(std::env::temp_dir);
+        let eviction = config
+            .temp_dir_eviction
+            .map(|eviction| temp_dir::EvictionTask::start(temp_dir.clone(), &eviction));
         Ok(Daemon {
             port,
+            listen_addr,
+            extra_listen_addrs,
+            admin_port,
             access_token: config.access_token,
+            running,
+            stopped: false,
+            supervisor,
+            temp_dir,
+            eviction,
+            watchdog,
         })
     }
 
+    /// Configure the daemon with setters instead of a [`DaemonConfig`] literal, see
+    /// [`DaemonBuilder`].
+    #[must_use]
+    pub fn builder() -> DaemonBuilder {
+        DaemonBuilder::default()
+    }
+
+    /// Get a handle to the process-wide daemon shared by all callers of this function, starting
+    /// it with `config` when it's not running yet.
+    ///
+    /// Independent libraries in one process can use this instead of [`Daemon::start`] to share
+    /// the singleton daemon. Only the first caller's `config` is used, later callers get the daemon
+    /// as it was configured. The daemon is stopped when the last [`SharedDaemon`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the error of [`Daemon::start`] when the daemon cannot be started,
+    /// e.g. [`StartError::OnlyOneInstanceAllowed`] when another daemon was started with
+    /// [`Daemon::start`].
+    pub fn get_or_start(config: DaemonConfig) -> Result<SharedDaemon, StartError> {
+        shared::get_or_start(config)
+    }
+
     #[must_use]
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// The address the HTTP listener is bound to, `None` when it's disabled.
+    ///
+    /// Unlike [`Daemon::port`], this includes the IP address of a
+    /// [`listener_fd`](DaemonConfig::listener_fd) socket, which may not be 127.0.0.1, or the path
+    /// of a Unix socket.
+    #[must_use]
+    pub fn listen_addr(&self) -> Option<&ListenAddr> {
+        self.listen_addr.as_ref()
+    }
+
+    /// The addresses of the [`DaemonConfig::extra_listeners`], in the configured order.
+    #[must_use]
+    pub fn extra_listen_addrs(&self) -> &[ListenAddr] {
+        &self.extra_listen_addrs
+    }
+
+    /// The TCP port of the admin listener, `None` when the admin listener is disabled or listens
+    /// on a Unix socket.
+    #[must_use]
+    pub fn admin_port(&self) -> Option<u16> {
+        self.admin_port
+    }
+
     #[must_use]
     pub fn access_token(&self) -> &Option<String> {
         &self.access_token
     }
-}
 
-impl Drop for Daemon {
-    fn drop(&mut self) {
-        log::debug!("[Daemon::drop] Locking global daemon mutex");
-        let mut maybe_daemon = get_global_daemon().expect("global daemon mutex was poisoned");
-        assert!(
-            maybe_daemon.is_some(),
-            "Daemon.drop() was called when no GoDaemon was running"
-        );
+    /// The number of bytes used by Lassie's temporary CAR files in the temp dir.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the temp dir cannot be read.
+    pub fn disk_usage(&self) -> std::io::Result<u64> {
+        temp_dir::disk_usage(&self.temp_dir)
+    }
+
+    /// Returns `false` once the daemon stopped serving requests, e.g. after
+    /// [`DaemonConfig::idle_shutdown`], via the admin listener or because it's shutting down.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Whether the daemon is running and responding to the [`DaemonConfig::watchdog`] probes.
+    /// Without the watchdog, a running daemon is always reported as healthy.
+    #[must_use]
+    pub fn health(&self) -> Health {
+        if !self.is_running() {
+            return Health::Stopped;
+        }
+        match self
+            .watchdog
+            .as_ref()
+            .and_then(watchdog::WatchdogTask::unhealthy)
+        {
+            Some(reason) => Health::Unhealthy(reason),
+            None => Health::Healthy,
+        }
+    }
+
+    /// Take the last error of the Go HTTP handler, leaving `None` in its place.
+    ///
+    /// The handler runs in a background thread, its failures are otherwise only logged and
+    /// reported via [`Daemon::failure_events`]. Check this when the requests start failing or
+    /// [`Daemon::is_running`] returns `false`. With [`DaemonConfig::auto_restart`], a failed
+    /// restart attempt replaces the error of the handler.
+    #[must_use]
+    pub fn take_error(&self) -> Option<GoError> {
+        self.supervisor.take_error()
+    }
+
+    /// Subscribe to the failures of the running daemon: the Go HTTP handler stopping with an
+    /// error, the restarts made with [`DaemonConfig::auto_restart`] and the
+    /// [`DaemonConfig::watchdog`] reports.
+    ///
+    /// Each call creates a new channel receiving the events from now on. The failures are logged
+    /// regardless, dropping the receiver unsubscribes.
+    #[must_use]
+    pub fn failure_events(&self) -> std::sync::mpsc::Receiver<FailureEvent> {
+        self.supervisor.subscribe()
+    }
+
+    /// Create a cloneable handle that can be moved into worker threads to build request URLs and
+    /// check whether the daemon is still running.
+    #[must_use]
+    pub fn handle(&self) -> DaemonHandle {
+        let connect_addr = self.listen_addr.as_ref().and_then(ListenAddr::connect_addr);
+        DaemonHandle::new(
+            self.port,
+            connect_addr,
+            self.access_token.as_deref(),
+            Arc::clone(&self.running),
+        )
+    }
+
+    /// Serve a single trustless gateway request in-process, without going through the HTTP
+    /// listener.
+    ///
+    /// The `path` must include the `/ipfs/` or `/ipns/` prefix and can include a query string, e.g.
+    /// `/ipfs/{cid}?dag-scope=entity`. In-process requests are trusted, they don't need to provide
+    /// the access token.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the path or headers cannot be converted to Go strings or
+    /// when the daemon is not able to handle the request. Error responses produced by Lassie (e.g.
+    /// HTTP 502 when no provider was found) are returned as `Ok`, check
+    /// [`InProcessResponse::status`].
+    pub fn serve_request(
+        &self,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> std::io::Result<InProcessResponse> {
+        InProcessResponse::serve(path, headers)
+    }
+
+    /// Serve a single trustless gateway request in-process and stream the response body through an
+    /// OS pipe.
+    ///
+    /// This works like [`Daemon::serve_request`], except the Go side writes the body directly into
+    /// an OS pipe instead of copying it chunk-by-chunk across the FFI boundary. Prefer this
+    /// transport for high-throughput workloads.
+    ///
+    /// # Errors
+    ///
+    /// See [`Daemon::serve_request`]. Additionally, this function returns `Err` when the OS pipe
+    /// cannot be created.
+    pub fn serve_request_to_pipe(
+        &self,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> std::io::Result<PipeResponse> {
+        PipeResponse::serve(path, headers)
+    }
+
+    /// Serve a single trustless gateway request in-process and deliver the response to `sink`.
+    ///
+    /// The Go request handler calls [`ResponseSink::head`] and then [`ResponseSink::chunk`] for
+    /// each piece of the body as soon as it's produced, letting you write the CAR bytes directly
+    /// into a file, a socket or an object store. This function blocks until the entire response
+    /// was delivered.
+    ///
+    /// # Errors
+    ///
+    /// See [`Daemon::serve_request`]. Additionally, this function returns `Err` when the sink
+    /// returns an error (the retrieval is aborted) or when Lassie aborts the response stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics raised by the sink are propagated to the caller after the retrieval is aborted.
+    pub fn serve_request_with_sink(
+        &self,
+        path: &str,
+        headers: &[(&str, &str)],
+        sink: &mut dyn ResponseSink,
+    ) -> std::io::Result<()> {
+        in_process::serve_with_sink(path, headers, sink)
+    }
+
+    /// Serve a single trustless gateway request in-process and write the response body directly to
+    /// the file at `dest`, creating or truncating it.
+    ///
+    /// The Go request handler writes the CAR bytes to the file as Lassie produces them, so they
+    /// are neither copied across the FFI boundary nor streamed through a socket. This function
+    /// blocks until the entire response was written. Use it for archival jobs storing whole CAR
+    /// files on disk.
+    ///
+    /// # Errors
+    ///
+    /// See [`Daemon::serve_request`]. Additionally, this function returns `Err` when `dest` cannot
+    /// be written or when Lassie aborts the response stream, the incomplete file is removed in
+    /// both cases. When the daemon responds with a status other than 200, the file is not created
+    /// and the error wraps the [`RetrievalError`] describing the response, you can access it via
+    /// [`std::io::Error::get_ref`].
+    pub fn serve_request_to_file(
+        &self,
+        path: &str,
+        headers: &[(&str, &str)],
+        dest: &Path,
+    ) -> std::io::Result<FileResponse> {
+        in_process::serve_to_file(path, headers, dest)
+    }
+
+    /// Abort the retrieval with the given ID.
+    ///
+    /// The daemon assigns an ID to each request it handles, the ID is returned in the
+    /// [`RETRIEVAL_ID_HEADER`] response header (see e.g. [`InProcessResponse::retrieval_id`]).
+    /// Returns `false` when no such retrieval is running, e.g. because it has already finished.
+    #[must_use]
+    pub fn cancel(&self, retrieval_id: &str) -> bool {
+        retrieval::cancel(retrieval_id)
+    }
+
+    /// List the retrievals currently handled by the daemon, including requests made via the HTTP
+    /// listener.
+    #[must_use]
+    pub fn active_retrievals(&self) -> Vec<ActiveRetrieval> {
+        retrieval::active_retrievals()
+    }
+
+    /// Read the retrieval counters, including the attempts, successes and received bytes per
+    /// protocol (Bitswap, Graphsync, HTTP).
+    ///
+    /// This works without the admin listener and without any metrics port, poll it to feed your
+    /// own metrics system.
+    #[must_use]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        metrics::snapshot()
+    }
+
+    /// Read the recent statistics of every provider Lassie contacted: the successes and failures
+    /// of the last attempts, the median time to first byte and the bytes received, sorted by peer
+    /// ID.
+    ///
+    /// Use it to prefer healthy providers, e.g. when building the `providers` list of a request.
+    /// Like the [`metrics`](Self::metrics), the statistics are kept for the lifetime of the
+    /// process, up to 10,000 providers.
+    #[must_use]
+    pub fn provider_stats(&self) -> Vec<ProviderStats> {
+        provider_stats::snapshot()
+    }
+
+    /// Read the memory statistics of the embedded Go runtime: the heap in use, the total bytes
+    /// allocated and the number of goroutines.
+    ///
+    /// Include the numbers in your memory telemetry, Rust allocators don't see the Go heap.
+    /// Reading the statistics stops all goroutines for a short moment, a few polls per second are
+    /// fine.
+    #[must_use]
+    pub fn memory_stats(&self) -> MemoryStats {
+        metrics::memory_stats()
+    }
+
+    /// Report the progress of running retrievals every `interval`.
+    ///
+    /// The callback is called from a background thread with a snapshot of each running retrieval
+    /// (bytes and blocks received so far, current provider). Comparing consecutive snapshots of
+    /// the same retrieval lets you show progress bars or detect stalled retrievals early.
+    ///
+    /// The reporting stops when the returned [`ProgressWatcher`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the background thread cannot be spawned.
+    pub fn watch_progress<F>(
+        &self,
+        interval: Duration,
+        on_progress: F,
+    ) -> std::io::Result<ProgressWatcher<'_>>
+    where
+        F: FnMut(&ActiveRetrieval) + Send + 'static,
+    {
+        ProgressWatcher::start(interval, on_progress)
+    }
+
+    /// Call `callback` with an [`AccessLogRecord`] for every request handled by the daemon: the
+    /// client address, the CID, the status, the number of bytes sent, the duration and the
+    /// outcome.
+    ///
+    /// The callback runs on a Go thread once the response is complete, including requests
+    /// rejected by the access token check and in-process requests. Keep it short, e.g. push the
+    /// record to a channel, the next request on the same connection waits for it.
+    ///
+    /// Only one callback can be registered, a new registration replaces the previous one. The
+    /// callback is unregistered when the returned [`AccessLog`] is dropped.
+    pub fn access_log<F>(&self, callback: F) -> AccessLog<'_>
+    where
+        F: Fn(&AccessLogRecord) + Send + Sync + 'static,
+    {
+        AccessLog::register(callback)
+    }
+
+    /// Call `callback` with every [`RetrievalEvent`] reported by Lassie: the retrieval started,
+    /// candidates were found and filtered, a provider connected, sent the first byte, delivered
+    /// the content or failed.
+    ///
+    /// The callback runs on the Go thread dispatching Lassie events, keep it short. Events are
+    /// dispatched asynchronously, some may arrive after the response has been sent.
+    ///
+    /// Only one callback can be registered, a new registration replaces the previous one. The
+    /// callback is unregistered when the returned [`RetrievalEvents`] is dropped.
+    pub fn retrieval_events<F>(&self, callback: F) -> RetrievalEvents<'_>
+    where
+        F: Fn(&RetrievalEvent) + Send + Sync + 'static,
+    {
+        RetrievalEvents::register(callback)
+    }
+
+    /// Call `callback` with a [`Measurement`] of every retrieval handled by the daemon: the time
+    /// to first byte, the duration, the byte length and checksum of the CAR, the status code and
+    /// the provider and protocol that served the content. The values are the ones Spark
+    /// checkers report, measured on the Go side where the response is written.
+    ///
+    /// The callback runs on the Go thread handling the request once the response is complete,
+    /// keep it short. To poll the measurements instead, push them to a channel:
+    ///
+    /// ```no_run
+    /// # let daemon = lassie::Daemon::start(lassie::DaemonConfig::default())?;
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// let _measurements = daemon.measurements(move |m| {
+    ///     let _ = tx.send(m.clone());
+    /// });
+    /// // later
+    /// for measurement in rx.try_iter() {
+    ///     println!("{} took {:?}", measurement.cid, measurement.duration);
+    /// }
+    /// # Ok::<(), lassie::StartError>(())
+    /// ```
+    ///
+    /// Only retrievals started after the registration are measured, the daemon computes the
+    /// checksum only while a callback is registered. Only one callback can be registered, a new
+    /// registration replaces the previous one. The callback is unregistered when the returned
+    /// [`Measurements`] is dropped.
+    pub fn measurements<F>(&self, callback: F) -> Measurements<'_>
+    where
+        F: Fn(&Measurement) + Send + Sync + 'static,
+    {
+        Measurements::register(callback)
+    }
+
+    /// Let `callback` score the providers found by the candidate discovery, e.g. using reputation
+    /// data collected by your checker network. Lassie tries the providers with higher scores
+    /// first, `None` skips the provider.
+    ///
+    /// The daemon waits until the discovery has found all candidates for a request, then calls
+    /// `callback` once per provider on the Go thread running the retrieval. Keep it short, the
+    /// retrieval waits for it. Providers listed in the `providers=` parameter of a request and
+    /// providers skipped by the [circuit breaker](DaemonConfig::circuit_breaker) are not scored.
+    ///
+    /// ```no_run
+    /// # let daemon = lassie::Daemon::start(lassie::DaemonConfig::default())?;
+    /// # let reputation = std::collections::HashMap::<String, f64>::new();
+    /// let _scores = daemon.provider_scores(move |candidate| {
+    ///     // Unknown providers rank below the known ones, but are still tried
+    ///     Some(reputation.get(candidate.provider).copied().unwrap_or(0.0))
+    /// });
+    /// # Ok::<(), lassie::StartError>(())
+    /// ```
+    ///
+    /// Only one callback can be registered, a new registration replaces the previous one. The
+    /// callback is unregistered when the returned [`ProviderScores`] is dropped.
+    pub fn provider_scores<F>(&self, callback: F) -> ProviderScores<'_>
+    where
+        F: Fn(&ProviderCandidate) -> Option<f64> + Send + Sync + 'static,
+    {
+        ProviderScores::register(callback)
+    }
+
+    /// Stop the daemon and report any problem encountered while doing so.
+    ///
+    /// Dropping the daemon stops it too, but errors are only logged.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when Lassie cannot stop the HTTP server or when the thread
+    /// running the HTTP handler panicked.
+    pub fn try_shutdown(mut self) -> Result<(), ShutdownError> {
+        self.shutdown()
+    }
+
+    /// Stop accepting new requests, wait up to `grace_period` for the running retrievals to
+    /// finish, then stop the daemon like [`Daemon::try_shutdown`].
+    ///
+    /// While the daemon drains, the HTTP listener is closed and in-process requests are rejected
+    /// with [`RetrievalError::ShuttingDown`]. Retrievals still running after the grace period are
+    /// aborted. The admin listener keeps serving until the daemon stops.
+    ///
+    /// # Errors
+    ///
+    /// See [`Daemon::try_shutdown`].
+    pub fn shutdown_gracefully(mut self, grace_period: Duration) -> Result<(), ShutdownError> {
+        if self.running.load(Ordering::Acquire) {
+            log::debug!("Draining Lassie Daemon");
+            let timeout = i64::try_from(grace_period.as_nanos()).unwrap_or(i64::MAX);
+            // SAFETY:
+            // We can call this FFI function as it does not have any special safety requirements.
+            let remaining = unsafe { DrainDaemon(timeout) };
+            if remaining > 0 {
+                log::warn!(
+                    "Aborting {remaining} Lassie retrievals still running after the grace period of {grace_period:?}"
+                );
+            }
+        }
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), ShutdownError> {
+        // This makes the shutdown run at most once
+        if std::mem::replace(&mut self.stopped, true) {
+            return Ok(());
+        }
+        self.running.store(false, Ordering::Release);
+
+        drop(self.eviction.take());
+        drop(self.watchdog.take());
+
+        log::debug!("[Daemon::shutdown] Locking global daemon mutex");
+        let mut maybe_daemon = get_global_daemon().map_err(|_| ShutdownError::MutexPoisoned)?;
 
         log::debug!("Shutting down Lassie Daemon");
-        // SAFETY:
-        // We can call this FFI function as it does not have any special safety requirements.
-        let result = unsafe { StopDaemon() };
-        if let Some(msg) = result.error() {
-            panic!("Cannot stop Lassie Daemon: {msg}");
+        if let Err(msg) = self.supervisor.stop() {
+            // Keep the GoDaemon in place, the Go side may still be running
+            return Err(ShutdownError::Lassie(msg));
         }
 
         log::debug!("Waiting for Lassie to exit");
-        // It's safe to call unwrap() here because we already handled maybe_daemon.is_none() above
-        let GoDaemon { handler_thread } = maybe_daemon.take().unwrap();
-        handler_thread.join().expect("Lassie handler panicked");
+        let Some(GoDaemon { handler_thread }) = maybe_daemon.take() else {
+            log::error!("Daemon was shut down when no GoDaemon was running");
+            return Ok(());
+        };
+        handler_thread
+            .join()
+            .map_err(|_| ShutdownError::HandlerPanicked)
     }
 }
 
-fn try_convert_duration_to_go_type(from: Duration) -> Result<i64, StartError> {
-    // Go Duration type represents the elapsed time between two instants as an int64 nanosecond count.
-    i64::try_from(from.as_nanos()).map_err(|_| StartError::DurationIsTooLong(from))
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        // Panicking here would abort the process when the daemon is dropped during unwinding
+        if let Err(err) = self.shutdown() {
+            log::error!("{err}");
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
+    use std::net::{Ipv4Addr, SocketAddr};
 
     // Rust runs tests in parallel. Since Lassie Daemon is a singleton,
     // we must synchronise the tests to ensure they run sequentially
@@ -318,6 +1616,16 @@ mod test {
         let _ = Daemon::start(DaemonConfig::default()).expect("cannot start the second time");
     }
 
+    #[test]
+    fn try_shutdown_stops_daemon() {
+        let _lock = setup_test_env();
+        let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie daemon");
+        let handle = daemon.handle();
+        daemon.try_shutdown().expect("cannot stop Lassie daemon");
+        assert!(!handle.is_running());
+        let _ = Daemon::start(DaemonConfig::default()).expect("cannot start after the shutdown");
+    }
+
     #[test]
     fn cannot_start_twice() {
         let _lock = setup_test_env();
@@ -340,18 +1648,33 @@ mod test {
         });
         match result {
             Ok(_) => panic!("starting Lassie on port 1 should have failed"),
-            Err(StartError::Lassie(msg)) => {
+            Err(StartError::PermissionDenied { port, source }) => {
+                assert_eq!(port, 1);
+                let msg = source.message();
                 assert!(
-                    msg.contains("cannot start the HTTP server")
-                        && msg.contains("listen tcp 127.0.0.1:1")
-                        && msg.contains("permission denied"),
-                    "Expected bind-socket permission error, actual: {msg}",
+                    msg.contains("listen tcp 127.0.0.1:1"),
+                    "Expected bind-socket error, actual: {msg}",
                 );
             }
             Err(err) => panic!("unexpected error while starting Lassie on port 1: {err}"),
         }
     }
 
+    #[test]
+    fn reports_temp_dir_not_writable() {
+        let _lock = setup_test_env();
+        let path = std::env::temp_dir().join("lassie-missing-temp-dir");
+        let result = Daemon::start(DaemonConfig {
+            temp_dir: Some(path.clone()),
+            ..DaemonConfig::default()
+        });
+        match result {
+            Ok(_) => panic!("starting Lassie with a missing temp_dir should have failed"),
+            Err(StartError::TempDirNotWritable { path: actual, .. }) => assert_eq!(actual, path),
+            Err(err) => panic!("unexpected error while starting Lassie: {err}"),
+        }
+    }
+
     #[test]
     fn start_returns_access_token() {
         let token = Some("super_secret".to_string());
@@ -365,6 +1688,272 @@ mod test {
         assert_eq!(*result.access_token(), token);
     }
 
+    #[test]
+    fn handle_tracks_daemon_state() {
+        let _lock = setup_test_env();
+        let daemon = Daemon::start(DaemonConfig {
+            access_token: Some("secret".to_string()),
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie daemon");
+        let handle = daemon.handle();
+
+        let worker = handle.clone();
+        let url = std::thread::spawn(move || {
+            assert!(worker.is_running());
+            worker.url("/ipfs/bafy")
+        })
+        .join()
+        .expect("worker thread panicked");
+        assert_eq!(url, format!("http://127.0.0.1:{}/ipfs/bafy", daemon.port()));
+        assert_eq!(handle.access_token(), Some("secret"));
+
+        drop(daemon);
+        assert!(!handle.is_running());
+    }
+
+    #[test]
+    fn reports_listen_addr() {
+        let _lock = setup_test_env();
+        let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie daemon");
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, daemon.port()));
+        assert_eq!(daemon.listen_addr(), Some(&ListenAddr::Tcp(addr)));
+        assert_eq!(daemon.handle().base_url(), format!("http://{addr}"));
+        drop(daemon);
+
+        let daemon = Daemon::start(DaemonConfig {
+            disable_listener: true,
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie daemon");
+        assert_eq!(daemon.listen_addr(), None);
+        let handle = daemon.handle();
+        assert!(
+            std::panic::catch_unwind(move || handle.base_url()).is_err(),
+            "building URLs without a TCP listener should panic"
+        );
+    }
+
+    #[test]
+    fn creates_missing_temp_dir() {
+        let _lock = setup_test_env();
+        let path = std::env::temp_dir().join("rusty-lassie-create-temp-dir-test");
+        let _ = std::fs::remove_dir_all(&path);
+        let daemon = Daemon::start(DaemonConfig {
+            temp_dir: Some(path.join("nested")),
+            create_temp_dir: true,
+            private_temp_dir: true,
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie daemon");
+        assert!(path.join("nested").is_dir());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path.join("nested"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        drop(daemon);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn rejects_null_bytes_in_event_recorder_url() {
+        let _lock = setup_test_env();
+        let result = Daemon::start(DaemonConfig {
+            event_recorder_url: Some("http://127.0.0.1\0/".to_string()),
+            ..DaemonConfig::default()
+        });
+        match result {
+            Ok(_) => panic!("starting Lassie with invalid event_recorder_url should have failed"),
+            Err(err) => assert_eq!(
+                err,
+                StartError::ConfigContainsNullByte(
+                    "event_recorder_url",
+                    "http://127.0.0.1\0/".to_string()
+                )
+            ),
+        }
+    }
+
+    #[test]
+    fn rejects_null_bytes_in_user_agent() {
+        let _lock = setup_test_env();
+        let result = Daemon::start(DaemonConfig {
+            user_agent: Some("checker\0".to_string()),
+            ..DaemonConfig::default()
+        });
+        match result {
+            Ok(_) => panic!("starting Lassie with invalid user_agent should have failed"),
+            Err(err) => assert_eq!(
+                err,
+                StartError::ConfigContainsNullByte("user_agent", "checker\0".to_string())
+            ),
+        }
+    }
+
+    #[test]
+    fn reports_invalid_bootstrap_peer() {
+        let _lock = setup_test_env();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        let result = Daemon::start(DaemonConfig {
+            bootstrap_peers: Some(vec![addr.clone()]),
+            ..DaemonConfig::default()
+        });
+        match result {
+            Ok(_) => panic!("starting Lassie with a bootstrap peer without ID should have failed"),
+            Err(StartError::InvalidConfig(errors)) => assert_eq!(
+                errors,
+                vec![ConfigError::MissingPeerId("bootstrap_peers", addr)]
+            ),
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn reports_invalid_preconnect_provider() {
+        let _lock = setup_test_env();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/24001".parse().unwrap();
+        let result = Daemon::start(DaemonConfig {
+            preconnect_providers: vec![addr.clone()],
+            ..DaemonConfig::default()
+        });
+        match result {
+            Ok(_) => {
+                panic!("starting Lassie with a preconnect provider without ID should have failed")
+            }
+            Err(StartError::InvalidConfig(errors)) => assert_eq!(
+                errors,
+                vec![ConfigError::MissingPeerId("preconnect_providers", addr)]
+            ),
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn reports_missing_client_certificate() {
+        let _lock = setup_test_env();
+        let result = Daemon::start(DaemonConfig {
+            outbound_http: OutboundHttpConfig {
+                client_certificate: Some(ClientCertificate {
+                    cert_file: PathBuf::from("does-not-exist/client.crt"),
+                    key_file: PathBuf::from("does-not-exist/client.key"),
+                }),
+                ..OutboundHttpConfig::default()
+            },
+            ..DaemonConfig::default()
+        });
+        match result {
+            Ok(_) => panic!("starting Lassie with a missing client certificate should have failed"),
+            Err(StartError::Lassie(msg)) => assert!(
+                msg.contains("cannot load the client certificate"),
+                "Expected client certificate error, actual: {msg}"
+            ),
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn reports_invalid_extra_root_certs() {
+        let _lock = setup_test_env();
+        let not_pem = std::env::temp_dir().join("rusty-lassie-not-a-certificate.pem");
+        std::fs::write(&not_pem, "not a certificate").unwrap();
+        let result = Daemon::start(DaemonConfig {
+            extra_root_certs: Some(vec![not_pem.clone()]),
+            ..DaemonConfig::default()
+        });
+        std::fs::remove_file(&not_pem).unwrap();
+        match result {
+            Ok(_) => {
+                panic!("starting Lassie with an invalid root certificate file should have failed")
+            }
+            Err(StartError::Lassie(msg)) => assert!(
+                msg.contains("no PEM certificate found"),
+                "Expected root certificate error, actual: {msg}"
+            ),
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    #[test]
+    fn starts_accepting_invalid_certs() {
+        let _lock = setup_test_env();
+        let _daemon = Daemon::start(DaemonConfig {
+            outbound_http: OutboundHttpConfig {
+                danger_accept_invalid_certs: true,
+                ..OutboundHttpConfig::default()
+            },
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie");
+    }
+
+    #[test]
+    fn reports_memory_stats() {
+        let _lock = setup_test_env();
+        let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+        let stats = daemon.memory_stats();
+        assert!(stats.heap_in_use > 0, "heap in use: {stats:?}");
+        assert!(stats.goroutines > 0, "goroutines: {stats:?}");
+    }
+
+    #[test]
+    fn writes_json_logs_to_file() {
+        let _lock = setup_test_env();
+        let log_file = std::env::temp_dir().join("rusty-lassie-json-log-test.log");
+        let _ = std::fs::remove_file(&log_file);
+        let daemon = Daemon::start(DaemonConfig {
+            log_format: LogFormat::Json,
+            log_file: Some(log_file.clone()),
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie with JSON logs");
+        let port = daemon.port();
+        drop(daemon);
+
+        let log = std::fs::read_to_string(&log_file).expect("cannot read the log file");
+        std::fs::remove_file(&log_file).unwrap();
+        let records: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| {
+                serde_json::from_str(line)
+                    .unwrap_or_else(|err| panic!("not a JSON record: {line}: {err}"))
+            })
+            .collect();
+        for record in &records {
+            assert!(record["ts"].is_string(), "no timestamp: {record}");
+            assert!(record["level"].is_string(), "no level: {record}");
+        }
+        assert!(
+            records
+                .iter()
+                .any(|record| record["subsystem"] == "rusty-lassie"
+                    && record["msg"] == "Lassie daemon started"
+                    && record["port"] == u64::from(port)),
+            "log: {log}"
+        );
+    }
+
+    #[test]
+    fn writes_logs_to_file() {
+        let _lock = setup_test_env();
+        let log_file = std::env::temp_dir().join("rusty-lassie-log-file-test.log");
+        let _ = std::fs::remove_file(&log_file);
+        let daemon = Daemon::start(DaemonConfig {
+            log_file: Some(log_file.clone()),
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie with a log file");
+        drop(daemon);
+        assert!(log_file.is_file(), "the log file was not created");
+    }
+
     fn setup_test_env() -> MutexGuard<'static, ()> {
         let _ = env_logger::builder().is_test(true).try_init();
         let lock = TEST_GUARD.lock().expect("cannot obtain global test lock. This typically happens when one of the test fails; the problem should go away after you fix the test failure.");
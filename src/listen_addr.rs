@@ -0,0 +1,68 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+
+/// The address the HTTP listener is bound to, see [`Daemon::listen_addr`](crate::Daemon::listen_addr).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    /// A Unix socket passed via `DaemonConfig::listener_fd`.
+    Unix(PathBuf),
+}
+
+impl ListenAddr {
+    /// Parse the address reported by Go `InitDaemon`, `ip:port` or the path of a Unix socket.
+    pub(crate) fn from_go(addr: String) -> Self {
+        match addr.parse() {
+            Ok(addr) => ListenAddr::Tcp(addr),
+            Err(_) => ListenAddr::Unix(PathBuf::from(addr)),
+        }
+    }
+
+    /// The address local clients connect to: the loopback address when the listener is bound to
+    /// all interfaces (`0.0.0.0` or `::`). `None` for Unix sockets.
+    #[must_use]
+    pub fn connect_addr(&self) -> Option<SocketAddr> {
+        let ListenAddr::Tcp(addr) = self else {
+            return None;
+        };
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ip => ip,
+        };
+        Some(SocketAddr::new(ip, addr.port()))
+    }
+}
+
+impl Display for ListenAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => Display::fmt(addr, f),
+            ListenAddr::Unix(path) => f.write_fmt(format_args!("unix:{}", path.display())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_go_addresses() {
+        let tcp = ListenAddr::from_go("10.0.0.1:8080".to_string());
+        assert_eq!(tcp, ListenAddr::Tcp("10.0.0.1:8080".parse().unwrap()));
+        assert_eq!(tcp.connect_addr(), Some("10.0.0.1:8080".parse().unwrap()));
+
+        let any = ListenAddr::from_go("[::]:8080".to_string());
+        assert_eq!(any.connect_addr(), Some("[::1]:8080".parse().unwrap()));
+        assert_eq!(any.to_string(), "[::]:8080");
+
+        let unix = ListenAddr::from_go("/run/lassie.sock".to_string());
+        assert_eq!(unix, ListenAddr::Unix(PathBuf::from("/run/lassie.sock")));
+        assert_eq!(unix.connect_addr(), None);
+        assert_eq!(unix.to_string(), "unix:/run/lassie.sock");
+    }
+}
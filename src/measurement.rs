@@ -0,0 +1,153 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, SystemTime};
+
+use crate::{from_c_string, Daemon};
+
+go_lassie! {
+    fn SetMeasurementCallback(callback: MeasurementCallback, ctx: *mut c_void);
+    fn ClearMeasurementCallback(ctx: *mut c_void);
+}
+
+type MeasurementCallback = extern "C" fn(ctx: *mut c_void, measurement: *const GoMeasurement);
+
+#[repr(C)]
+struct GoMeasurement {
+    // this must be kept in sync with the definition of measurement_t in go-lib/lassie-ffi.h
+    retrieval_id: *const c_char,
+    cid: *const c_char,
+    provider: *const c_char,
+    protocol: *const c_char,
+    car_checksum: *const c_char,
+    start: i64,
+    ttfb: i64,
+    duration: i64,
+    bytes: u64,
+    status: u16,
+    aborted: bool,
+}
+
+/// The measurement of a retrieval handled by the daemon, see [`Daemon::measurements`].
+///
+/// The fields match what Spark checkers report for each retrieval task: the start, the time to
+/// first byte and the duration, the byte length and checksum of the CAR, the status code and the
+/// provider that served the content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Measurement {
+    /// The ID assigned by the daemon, see [`RETRIEVAL_ID_HEADER`](crate::RETRIEVAL_ID_HEADER).
+    pub retrieval_id: String,
+    /// The root CID of the request.
+    pub cid: String,
+    /// The peer ID of the provider that delivered the content, or of the last provider Lassie
+    /// tried. `None` when Lassie did not contact any provider or when its events had not arrived
+    /// yet.
+    pub provider: Option<String>,
+    /// The multicodec name of the protocol used with `provider`, e.g. `transport-bitswap` or
+    /// `transport-ipfs-gateway-http`.
+    pub protocol: Option<String>,
+    /// The status code of the response.
+    pub status: u16,
+    /// When the daemon received the request.
+    pub start: SystemTime,
+    /// Time from receiving the request until the first body byte was sent, `None` when the
+    /// response has no body.
+    pub ttfb: Option<Duration>,
+    /// Time from receiving the request until the response was complete.
+    pub duration: Duration,
+    /// The number of response body bytes sent.
+    pub byte_length: u64,
+    /// The sha2-256 multihash of the CAR response body as a hex string (`1220...`), the format
+    /// Spark uses for `car_checksum`. `None` when the response is not a CAR stream.
+    pub car_checksum: Option<String>,
+    /// The response started but was not complete, e.g. Lassie aborted the stream on timeout or
+    /// the client went away.
+    pub aborted: bool,
+}
+
+type Callback = dyn Fn(&Measurement) + Send + Sync;
+
+/// Receives a measurement for every retrieval handled by the daemon, see
+/// [`Daemon::measurements`].
+///
+/// Dropping the value unregisters the callback. The value borrows the daemon, therefore it cannot
+/// outlive it.
+pub struct Measurements<'a> {
+    // Double boxed to pass a thin pointer to Go
+    callback: *mut Box<Callback>,
+    _daemon: PhantomData<&'a Daemon>,
+}
+
+// SAFETY:
+// The callback is `Send + Sync`, the pointer is only dereferenced by Go calling the trampoline
+// and freed in `drop`.
+unsafe impl Send for Measurements<'_> {}
+// SAFETY:
+// See above, `Measurements` has no methods accessing the callback.
+unsafe impl Sync for Measurements<'_> {}
+
+impl std::fmt::Debug for Measurements<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Measurements").finish_non_exhaustive()
+    }
+}
+
+impl Measurements<'_> {
+    pub(crate) fn register<F>(callback: F) -> Self
+    where
+        F: Fn(&Measurement) + Send + Sync + 'static,
+    {
+        let boxed: Box<Callback> = Box::new(callback);
+        let callback = Box::into_raw(Box::new(boxed));
+        // SAFETY:
+        // The pointer stays valid until `drop` unregisters it, Go stops calling the callback
+        // before `ClearMeasurementCallback` returns.
+        unsafe { SetMeasurementCallback(measurement_trampoline, callback.cast()) };
+        Measurements {
+            callback,
+            _daemon: PhantomData,
+        }
+    }
+}
+
+impl Drop for Measurements<'_> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // Clearing waits for running callbacks, no other reference to the callback exists after
+        // the call returns. The pointer was created by `Box::into_raw` in `register`.
+        unsafe {
+            ClearMeasurementCallback(self.callback.cast());
+            drop(Box::from_raw(self.callback));
+        }
+    }
+}
+
+extern "C" fn measurement_trampoline(ctx: *mut c_void, measurement: *const GoMeasurement) {
+    // SAFETY:
+    // `ctx` is the pointer registered by `Measurements::register`, it stays valid while Go may
+    // call this function. Go passes a measurement that is valid until this function returns.
+    let (callback, measurement) = unsafe { (&*ctx.cast::<Box<Callback>>(), &*measurement) };
+    let non_empty = |s: *const c_char| from_c_string(s).filter(|s| !s.is_empty());
+    let nanos = |n: i64| Duration::from_nanos(u64::try_from(n).unwrap_or_default());
+    let measurement = Measurement {
+        retrieval_id: from_c_string(measurement.retrieval_id).unwrap_or_default(),
+        cid: from_c_string(measurement.cid).unwrap_or_default(),
+        provider: non_empty(measurement.provider),
+        protocol: non_empty(measurement.protocol),
+        status: measurement.status,
+        start: SystemTime::UNIX_EPOCH + nanos(measurement.start),
+        // Go reports -1 when no byte was sent
+        ttfb: (measurement.ttfb >= 0).then(|| nanos(measurement.ttfb)),
+        duration: nanos(measurement.duration),
+        byte_length: measurement.bytes,
+        car_checksum: non_empty(measurement.car_checksum),
+        aborted: measurement.aborted,
+    };
+
+    // Unwinding into Go is undefined behaviour
+    if panic::catch_unwind(AssertUnwindSafe(|| callback(&measurement))).is_err() {
+        log::error!("Lassie measurement callback panicked");
+    }
+}
@@ -0,0 +1,184 @@
+use std::os::raw::c_char;
+
+use crate::from_c_string;
+
+go_lassie! {
+    fn GetMetrics() -> GoMetrics;
+    fn DropMetrics(metrics: *mut GoMetrics);
+    fn GetMemoryStats() -> GoMemoryStats;
+}
+
+#[repr(C)]
+struct GoProtocolMetrics {
+    // this must be kept in sync with the definition of protocol_metrics_t in go-lib/lassie-ffi.h
+    protocol: *const c_char,
+    attempts: u64,
+    successes: u64,
+    failures: u64,
+    bytes_received: u64,
+}
+
+#[repr(C)]
+struct GoMetrics {
+    // this must be kept in sync with the definition of metrics_t in go-lib/lassie-ffi.h
+    total_retrievals: u64,
+    active_retrievals: u64,
+    bytes_sent: u64,
+    protocols: *const GoProtocolMetrics,
+    protocols_len: usize,
+}
+
+#[repr(C)]
+struct GoMemoryStats {
+    // this must be kept in sync with the definition of memory_stats_t in go-lib/lassie-ffi.h
+    heap_in_use: u64,
+    total_alloc: u64,
+    sys: u64,
+    goroutines: u64,
+    gc_cycles: u32,
+}
+
+impl Drop for GoMetrics {
+    fn drop(&mut self) {
+        // SAFETY:
+        // We can safely call the FFI function to free the memory used by GoMetrics, because Rust
+        // guarantees that the `drop` function is called only once for each instance. We always
+        // obtain instances via FFI calls.
+        unsafe { DropMetrics(self) }
+    }
+}
+
+/// The counters collected by the daemon, see [`Daemon::metrics`](crate::Daemon::metrics).
+///
+/// The counters are never reset, they include the retrievals of daemons started earlier in the
+/// same process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MetricsSnapshot {
+    /// The number of requests handled, including the running ones.
+    pub total_retrievals: u64,
+    /// The number of requests running right now.
+    pub active_retrievals: u64,
+    /// The number of response body bytes sent for finished requests.
+    pub bytes_sent: u64,
+    /// The attempts to retrieve content from individual providers, one entry per protocol.
+    pub protocols: Vec<ProtocolMetrics>,
+}
+
+impl MetricsSnapshot {
+    /// The counters of the given protocol, e.g. `transport-bitswap`.
+    #[must_use]
+    pub fn protocol(&self, name: &str) -> Option<&ProtocolMetrics> {
+        self.protocols.iter().find(|p| p.protocol == name)
+    }
+}
+
+/// The counters of a single retrieval protocol, see [`MetricsSnapshot::protocols`].
+///
+/// A single request can make several attempts, e.g. Lassie may try several providers at once and
+/// cancel the slower attempts when the first one succeeds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProtocolMetrics {
+    /// The multicodec name of the protocol: `transport-bitswap`, `transport-graphsync-filecoinv1`
+    /// or `transport-ipfs-gateway-http`.
+    pub protocol: String,
+    pub attempts: u64,
+    pub successes: u64,
+    pub failures: u64,
+    /// The number of bytes received in successful attempts.
+    pub bytes_received: u64,
+}
+
+impl ProtocolMetrics {
+    /// The share of finished attempts that succeeded, `None` when no attempt has finished yet.
+    #[must_use]
+    pub fn success_rate(&self) -> Option<f64> {
+        let finished = self.successes + self.failures;
+        #[allow(clippy::cast_precision_loss)]
+        (finished > 0).then(|| self.successes as f64 / finished as f64)
+    }
+}
+
+/// The memory used by the Go runtime, see [`Daemon::memory_stats`](crate::Daemon::memory_stats).
+///
+/// The Go runtime is shared by the whole process, the numbers include memory retained after
+/// earlier daemons stopped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MemoryStats {
+    /// The bytes in heap spans with at least one live or not yet collected object.
+    pub heap_in_use: u64,
+    /// The cumulative bytes allocated for heap objects, it never decreases.
+    pub total_alloc: u64,
+    /// The total bytes of memory obtained from the OS by the Go runtime, comparable to the
+    /// memory limit (see [`DaemonConfig::go_memory_limit`](crate::DaemonConfig::go_memory_limit)).
+    pub sys: u64,
+    /// The number of goroutines that currently exist.
+    pub goroutines: u64,
+    /// The number of completed garbage collection cycles.
+    pub gc_cycles: u32,
+}
+
+pub(crate) fn memory_stats() -> MemoryStats {
+    // SAFETY:
+    // We can call this FFI function as it does not have any special safety requirements.
+    let stats = unsafe { GetMemoryStats() };
+    MemoryStats {
+        heap_in_use: stats.heap_in_use,
+        total_alloc: stats.total_alloc,
+        sys: stats.sys,
+        goroutines: stats.goroutines,
+        gc_cycles: stats.gc_cycles,
+    }
+}
+
+pub(crate) fn snapshot() -> MetricsSnapshot {
+    // SAFETY:
+    // We can call this FFI function as it does not have any special safety requirements.
+    let metrics = unsafe { GetMetrics() };
+    let protocols = if metrics.protocols.is_null() {
+        Vec::new()
+    } else {
+        // SAFETY:
+        // Go allocates `protocols_len` consecutive items, the memory stays valid until `metrics`
+        // is dropped.
+        let items = unsafe { std::slice::from_raw_parts(metrics.protocols, metrics.protocols_len) };
+        items
+            .iter()
+            .map(|m| ProtocolMetrics {
+                protocol: from_c_string(m.protocol).unwrap_or_default(),
+                attempts: m.attempts,
+                successes: m.successes,
+                failures: m.failures,
+                bytes_received: m.bytes_received,
+            })
+            .collect()
+    };
+
+    MetricsSnapshot {
+        total_retrievals: metrics.total_retrievals,
+        active_retrievals: metrics.active_retrievals,
+        bytes_sent: metrics.bytes_sent,
+        protocols,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn computes_success_rate() {
+        let mut metrics = ProtocolMetrics {
+            protocol: "transport-bitswap".to_string(),
+            attempts: 4,
+            ..ProtocolMetrics::default()
+        };
+        assert_eq!(metrics.success_rate(), None);
+        metrics.successes = 3;
+        metrics.failures = 1;
+        assert_eq!(metrics.success_rate(), Some(0.75));
+    }
+}
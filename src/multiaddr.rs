@@ -0,0 +1,330 @@
+//! Provider addresses in the [multiaddr](https://multiformats.io/multiaddr/) format.
+//!
+//! Lassie parses the `providers=` of a request only when the retrieval starts, a typo then fails
+//! the request with a confusing error deep inside the daemon. Parse the addresses upfront
+//! instead:
+//!
+//! ```
+//! use lassie::multiaddr::Multiaddr;
+//!
+//! let http: Multiaddr = "/dns4/frisbii.fly.dev/https".parse()?;
+//! let bitswap: Multiaddr =
+//!     "/ip4/127.0.0.1/tcp/4001/p2p/12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz".parse()?;
+//! assert_eq!(bitswap.peer_id(), Some("12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz"));
+//!
+//! let err = "https://frisbii.fly.dev".parse::<Multiaddr>().unwrap_err();
+//! assert!(err.to_string().contains("does not start with `/`"));
+//! # Ok::<(), lassie::multiaddr::ParseMultiaddrError>(())
+//! ```
+//!
+//! The validation covers the protocols used to reach IPFS and Filecoin providers. It checks the
+//! syntax only, e.g. it does not verify that a peer ID is a valid multihash.
+
+use std::fmt::{Display, Formatter};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// A validated multiaddr, e.g. `/dns4/frisbii.fly.dev/https` or
+/// `/ip4/127.0.0.1/tcp/4001/p2p/12D3KooW...`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(try_from = "String")
+)]
+pub struct Multiaddr(String);
+
+impl Multiaddr {
+    /// Wrap an address built by this crate, e.g. [`MockProvider::multiaddr`](crate::testing::MockProvider::multiaddr).
+    #[cfg(feature = "testing")]
+    pub(crate) fn from_trusted(addr: String) -> Self {
+        debug_assert!(
+            addr.parse::<Multiaddr>().is_ok(),
+            "invalid multiaddr {addr}"
+        );
+        Multiaddr(addr)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The protocols and their values, e.g. `("tcp", Some("4001"))` or `("https", None)`.
+    pub fn components(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        let mut rest = &self.0[1..];
+        std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let (protocol, tail) = rest.split_once('/').unwrap_or((rest, ""));
+            let (value, tail) = match value_kind(protocol) {
+                Some(Value::None) | None => (None, tail),
+                // The path takes the rest of the address
+                Some(Value::Path) => (Some(tail), ""),
+                Some(_) => {
+                    let (value, tail) = tail.split_once('/').unwrap_or((tail, ""));
+                    (Some(value), tail)
+                }
+            };
+            rest = tail;
+            Some((protocol, value))
+        })
+    }
+
+    /// The ID of the peer in the `/p2p/` component, if any.
+    #[must_use]
+    pub fn peer_id(&self) -> Option<&str> {
+        self.components()
+            .find(|(protocol, _)| matches!(*protocol, "p2p" | "ipfs"))
+            .and_then(|(_, value)| value)
+    }
+}
+
+impl Display for Multiaddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Multiaddr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Multiaddr {
+    type Error = ParseMultiaddrError;
+
+    fn try_from(addr: String) -> Result<Self, Self::Error> {
+        addr.parse()
+    }
+}
+
+impl From<Multiaddr> for String {
+    fn from(addr: Multiaddr) -> Self {
+        addr.0
+    }
+}
+
+/// An error returned when a multiaddr cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseMultiaddrError {
+    Empty,
+    /// The address is not a multiaddr at all, e.g. a URL or a `host:port` pair.
+    MissingLeadingSlash(String),
+    UnknownProtocol(String),
+    /// The protocol requires a value, e.g. `/tcp` without the port.
+    MissingValue(String),
+    InvalidValue {
+        protocol: String,
+        value: String,
+    },
+}
+
+impl Display for ParseMultiaddrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid multiaddr: ")?;
+        match self {
+            ParseMultiaddrError::Empty => f.write_str("the address is empty"),
+            ParseMultiaddrError::MissingLeadingSlash(addr) => write!(
+                f,
+                "`{addr}` does not start with `/`, use e.g. `/dns4/example.com/https` or `/ip4/127.0.0.1/tcp/8080/http`"
+            ),
+            ParseMultiaddrError::UnknownProtocol(protocol) if protocol.is_empty() => {
+                f.write_str("empty protocol name, remove the repeated or trailing `/`")
+            }
+            ParseMultiaddrError::UnknownProtocol(protocol) => {
+                write!(f, "unknown protocol `{protocol}`")
+            }
+            ParseMultiaddrError::MissingValue(protocol) => {
+                write!(f, "`/{protocol}` must be followed by a value")
+            }
+            ParseMultiaddrError::InvalidValue { protocol, value } => {
+                write!(f, "`{value}` is not a valid `/{protocol}` value")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseMultiaddrError {}
+
+/// The kind of value a protocol takes.
+#[derive(Clone, Copy)]
+enum Value {
+    None,
+    Ip4,
+    Ip6,
+    Host,
+    Port,
+    PeerId,
+    /// Any non-empty component, e.g. a multibase-encoded certificate hash.
+    Opaque,
+    /// The rest of the address, e.g. `/unix/run/provider.sock`.
+    Path,
+}
+
+fn value_kind(protocol: &str) -> Option<Value> {
+    let kind = match protocol {
+        "ip4" => Value::Ip4,
+        "ip6" => Value::Ip6,
+        "dns" | "dns4" | "dns6" | "dnsaddr" | "sni" => Value::Host,
+        "tcp" | "udp" => Value::Port,
+        "p2p" | "ipfs" => Value::PeerId,
+        "ip6zone" | "certhash" | "http-path" => Value::Opaque,
+        "unix" => Value::Path,
+        "http" | "https" | "tls" | "noise" | "ws" | "wss" | "quic" | "quic-v1" | "webtransport"
+        | "webrtc" | "webrtc-direct" | "p2p-circuit" => Value::None,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn is_valid(kind: Value, value: &str) -> bool {
+    match kind {
+        Value::None | Value::Path | Value::Opaque => true,
+        Value::Ip4 => value.parse::<Ipv4Addr>().is_ok(),
+        Value::Ip6 => value.parse::<Ipv6Addr>().is_ok(),
+        Value::Host => value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._".contains(&b)),
+        // Leading zeros and signs are accepted by `parse`, but not by Go
+        Value::Port => value.parse::<u16>().is_ok() && value.bytes().all(|b| b.is_ascii_digit()),
+        Value::PeerId => is_peer_id(value),
+    }
+}
+
+/// Peer IDs are base58btc-encoded multihashes (`12D3KooW...`, `Qm...`) or base32 CIDs (`bafz...`).
+fn is_peer_id(value: &str) -> bool {
+    const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    match value.as_bytes() {
+        [b'b', rest @ ..] if !rest.is_empty() => rest
+            .iter()
+            .all(|b| b.is_ascii_lowercase() || (b'2'..=b'7').contains(b)),
+        [b'1' | b'Q', ..] => value.bytes().all(|b| BASE58.contains(&b)),
+        _ => false,
+    }
+}
+
+impl FromStr for Multiaddr {
+    type Err = ParseMultiaddrError;
+
+    fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        if addr.is_empty() {
+            return Err(ParseMultiaddrError::Empty);
+        }
+        let Some(rest) = addr.strip_prefix('/') else {
+            return Err(ParseMultiaddrError::MissingLeadingSlash(addr.to_string()));
+        };
+
+        let mut parts = rest.split('/');
+        while let Some(protocol) = parts.next() {
+            let kind = value_kind(protocol)
+                .ok_or_else(|| ParseMultiaddrError::UnknownProtocol(protocol.to_string()))?;
+            let value = match kind {
+                Value::None => continue,
+                Value::Path => {
+                    let path: Vec<&str> = parts.by_ref().collect();
+                    path.join("/")
+                }
+                _ => parts.next().unwrap_or_default().to_string(),
+            };
+            if value.is_empty() {
+                return Err(ParseMultiaddrError::MissingValue(protocol.to_string()));
+            }
+            if !is_valid(kind, &value) {
+                return Err(ParseMultiaddrError::InvalidValue {
+                    protocol: protocol.to_string(),
+                    value,
+                });
+            }
+        }
+        Ok(Multiaddr(addr.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const PEER_ID: &str = "12D3KooWQ4Q2d3V6JTFDNRnZDeQGa3NfNrxHmREmHyfnhrjNFhbz";
+
+    #[test]
+    fn parses_provider_addresses() {
+        for addr in [
+            "/dns4/frisbii.fly.dev/https".to_string(),
+            "/ip4/127.0.0.1/tcp/8080/http".to_string(),
+            "/ip6/::1/udp/4001/quic-v1".to_string(),
+            format!("/ip4/1.2.3.4/tcp/4001/p2p/{PEER_ID}"),
+            format!("/dns/example.com/tcp/443/tls/http/p2p/{PEER_ID}"),
+            "/unix/run/provider.sock".to_string(),
+        ] {
+            let parsed: Multiaddr = addr.parse().unwrap_or_else(|err| panic!("{addr}: {err}"));
+            assert_eq!(parsed.as_str(), addr);
+        }
+    }
+
+    #[test]
+    fn lists_components() {
+        let addr: Multiaddr = format!("/ip4/1.2.3.4/tcp/4001/p2p/{PEER_ID}")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            addr.components().collect::<Vec<_>>(),
+            vec![
+                ("ip4", Some("1.2.3.4")),
+                ("tcp", Some("4001")),
+                ("p2p", Some(PEER_ID)),
+            ]
+        );
+        assert_eq!(addr.peer_id(), Some(PEER_ID));
+
+        let addr: Multiaddr = "/unix/run/provider.sock".parse().unwrap();
+        assert_eq!(
+            addr.components().collect::<Vec<_>>(),
+            vec![("unix", Some("run/provider.sock"))]
+        );
+        assert_eq!(addr.peer_id(), None);
+    }
+
+    #[test]
+    fn reports_helpful_errors() {
+        let err = |addr: &str| addr.parse::<Multiaddr>().unwrap_err();
+        assert_eq!(err(""), ParseMultiaddrError::Empty);
+        assert_eq!(
+            err("frisbii.fly.dev:443"),
+            ParseMultiaddrError::MissingLeadingSlash("frisbii.fly.dev:443".to_string())
+        );
+        assert_eq!(
+            err("/dns4/frisbii.fly.dev/htps"),
+            ParseMultiaddrError::UnknownProtocol("htps".to_string())
+        );
+        assert_eq!(
+            err("/ip4/127.0.0.1/tcp"),
+            ParseMultiaddrError::MissingValue("tcp".to_string())
+        );
+        assert_eq!(
+            err("/ip4/127.0.0.1/tcp/80800/http"),
+            ParseMultiaddrError::InvalidValue {
+                protocol: "tcp".to_string(),
+                value: "80800".to_string()
+            }
+        );
+        assert_eq!(
+            err("/ip4/localhost/tcp/8080"),
+            ParseMultiaddrError::InvalidValue {
+                protocol: "ip4".to_string(),
+                value: "localhost".to_string()
+            }
+        );
+        assert_eq!(
+            err("/ip4/1.2.3.4/tcp/4001/p2p/0xdeadbeef"),
+            ParseMultiaddrError::InvalidValue {
+                protocol: "p2p".to_string(),
+                value: "0xdeadbeef".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::marker::PhantomData;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{ActiveRetrieval, Daemon};
+
+/// Reports the progress of running retrievals, see [`Daemon::watch_progress`].
+///
+/// Dropping the watcher stops the reporting. The watcher borrows the daemon, therefore it cannot
+/// outlive it.
+#[derive(Debug)]
+pub struct ProgressWatcher<'a> {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    _daemon: PhantomData<&'a Daemon>,
+}
+
+impl ProgressWatcher<'_> {
+    pub(crate) fn start<F>(interval: Duration, mut on_progress: F) -> std::io::Result<Self>
+    where
+        F: FnMut(&ActiveRetrieval) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("lassie-progress".to_string())
+            .spawn(move || {
+                // The loop ends when the watcher is dropped
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    for retrieval in crate::retrieval::active_retrievals() {
+                        on_progress(&retrieval);
+                    }
+                }
+            })?;
+
+        Ok(ProgressWatcher {
+            stop: Some(stop),
+            thread: Some(thread),
+            _daemon: PhantomData,
+        })
+    }
+}
+
+impl Drop for ProgressWatcher<'_> {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the reporting thread
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Lassie progress callback panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::os::raw::c_char;
+use std::time::{Duration, SystemTime};
+
+use crate::from_c_string;
+
+go_lassie! {
+    fn GetProviderStats() -> GoProviderStatsList;
+    fn DropProviderStats(list: *mut GoProviderStatsList);
+}
+
+#[repr(C)]
+struct GoProviderStats {
+    // this must be kept in sync with the definition of provider_stats_t in go-lib/lassie-ffi.h
+    provider: *const c_char,
+    protocol: *const c_char,
+    successes: u64,
+    failures: u64,
+    bytes_received: u64,
+    median_ttfb: i64,
+    last_seen: i64,
+    circuit_open_until: i64,
+}
+
+#[repr(C)]
+struct GoProviderStatsList {
+    // this must be kept in sync with the definition of provider_stats_list_t in go-lib/lassie-ffi.h
+    items: *const GoProviderStats,
+    len: usize,
+}
+
+impl Drop for GoProviderStatsList {
+    fn drop(&mut self) {
+        // SAFETY:
+        // We can safely call the FFI function to free the memory used by GoProviderStatsList,
+        // because Rust guarantees that the `drop` function is called only once for each instance.
+        // We always obtain instances via FFI calls.
+        unsafe { DropProviderStats(self) }
+    }
+}
+
+/// The recent track record of a single provider, see
+/// [`Daemon::provider_stats`](crate::Daemon::provider_stats).
+///
+/// The counters cover the provider's last 100 finished attempts and the median covers its last
+/// 100 time-to-first-byte samples, so that the statistics follow changes in the provider's health.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProviderStats {
+    /// The peer ID of the provider.
+    pub provider: String,
+    /// The multicodec name of the protocol used in the last attempt, e.g. `transport-bitswap`.
+    pub protocol: Option<String>,
+    pub successes: u64,
+    pub failures: u64,
+    /// The number of bytes received in the successful attempts.
+    pub bytes_received: u64,
+    /// The median time to first byte, `None` when no attempt has received a byte yet.
+    pub median_ttfb: Option<Duration>,
+    /// When Lassie last reported an event for this provider.
+    pub last_seen: SystemTime,
+    /// The provider is skipped until this time because it failed too many attempts in a row, see
+    /// [`DaemonConfig::circuit_breaker`](crate::DaemonConfig::circuit_breaker).
+    pub circuit_open_until: Option<SystemTime>,
+}
+
+impl ProviderStats {
+    /// The share of the recent attempts that succeeded, `None` when no attempt has finished yet.
+    #[must_use]
+    pub fn success_rate(&self) -> Option<f64> {
+        let finished = self.successes + self.failures;
+        #[allow(clippy::cast_precision_loss)]
+        (finished > 0).then(|| self.successes as f64 / finished as f64)
+    }
+}
+
+pub(crate) fn snapshot() -> Vec<ProviderStats> {
+    // SAFETY:
+    // We can call this FFI function as it does not have any special safety requirements.
+    let list = unsafe { GetProviderStats() };
+    if list.items.is_null() {
+        return Vec::new();
+    }
+
+    let nanos = |n: i64| Duration::from_nanos(u64::try_from(n).unwrap_or_default());
+    // SAFETY:
+    // Go allocates `len` consecutive items, the memory stays valid until `list` is dropped.
+    let items = unsafe { std::slice::from_raw_parts(list.items, list.len) };
+    items
+        .iter()
+        .map(|s| ProviderStats {
+            provider: from_c_string(s.provider).unwrap_or_default(),
+            protocol: from_c_string(s.protocol).filter(|p| !p.is_empty()),
+            successes: s.successes,
+            failures: s.failures,
+            bytes_received: s.bytes_received,
+            // Go reports -1 when there are no samples
+            median_ttfb: (s.median_ttfb >= 0).then(|| nanos(s.median_ttfb)),
+            last_seen: SystemTime::UNIX_EPOCH + nanos(s.last_seen),
+            // Go reports 0 when the provider is not skipped
+            circuit_open_until: (s.circuit_open_until > 0)
+                .then(|| SystemTime::UNIX_EPOCH + nanos(s.circuit_open_until)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn computes_success_rate() {
+        let mut stats = ProviderStats {
+            provider: "12D3KooWQYhTNQdmr3ArTeUHRYzFg94BKyTkoWBDWez9kSCVe2Xo".to_string(),
+            protocol: None,
+            successes: 0,
+            failures: 0,
+            bytes_received: 0,
+            median_ttfb: None,
+            last_seen: SystemTime::UNIX_EPOCH,
+            circuit_open_until: None,
+        };
+        assert_eq!(stats.success_rate(), None);
+        stats.successes = 1;
+        stats.failures = 3;
+        assert_eq!(stats.success_rate(), Some(0.25));
+    }
+}
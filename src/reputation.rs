@@ -0,0 +1,153 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{from_c_string, Daemon};
+
+go_lassie! {
+    fn SetProviderScoreCallback(callback: ProviderScoreCallback, ctx: *mut c_void);
+    fn ClearProviderScoreCallback(ctx: *mut c_void);
+}
+
+type ProviderScoreCallback = extern "C" fn(
+    ctx: *mut c_void,
+    provider: *const c_char,
+    cid: *const c_char,
+    score: *mut f64,
+) -> bool;
+
+/// A provider found by the candidate discovery, see [`Daemon::provider_scores`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProviderCandidate<'a> {
+    /// The peer ID of the provider.
+    pub provider: &'a str,
+    /// The root CID of the retrieval.
+    pub cid: &'a str,
+}
+
+type Callback = dyn Fn(&ProviderCandidate) -> Option<f64> + Send + Sync;
+
+/// Scores the retrieval candidates, see [`Daemon::provider_scores`].
+///
+/// Dropping the value unregisters the callback. The value borrows the daemon, therefore it cannot
+/// outlive it.
+pub struct ProviderScores<'a> {
+    // Double boxed to pass a thin pointer to Go
+    callback: *mut Box<Callback>,
+    _daemon: PhantomData<&'a Daemon>,
+}
+
+// SAFETY:
+// The callback is `Send + Sync`, the pointer is only dereferenced by Go calling the trampoline
+// and freed in `drop`.
+unsafe impl Send for ProviderScores<'_> {}
+// SAFETY:
+// See above, `ProviderScores` has no methods accessing the callback.
+unsafe impl Sync for ProviderScores<'_> {}
+
+impl std::fmt::Debug for ProviderScores<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderScores").finish_non_exhaustive()
+    }
+}
+
+impl ProviderScores<'_> {
+    pub(crate) fn register<F>(callback: F) -> Self
+    where
+        F: Fn(&ProviderCandidate) -> Option<f64> + Send + Sync + 'static,
+    {
+        let boxed: Box<Callback> = Box::new(callback);
+        let callback = Box::into_raw(Box::new(boxed));
+        // SAFETY:
+        // The pointer stays valid until `drop` unregisters it, Go stops calling the callback
+        // before `ClearProviderScoreCallback` returns.
+        unsafe { SetProviderScoreCallback(provider_score_trampoline, callback.cast()) };
+        ProviderScores {
+            callback,
+            _daemon: PhantomData,
+        }
+    }
+}
+
+impl Drop for ProviderScores<'_> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // Clearing waits for running callbacks, no other reference to the callback exists after
+        // the call returns. The pointer was created by `Box::into_raw` in `register`.
+        unsafe {
+            ClearProviderScoreCallback(self.callback.cast());
+            drop(Box::from_raw(self.callback));
+        }
+    }
+}
+
+extern "C" fn provider_score_trampoline(
+    ctx: *mut c_void,
+    provider: *const c_char,
+    cid: *const c_char,
+    score: *mut f64,
+) -> bool {
+    // SAFETY:
+    // `ctx` is the pointer registered by `ProviderScores::register`, it stays valid while Go may
+    // call this function. The strings and `score` are valid until this function returns.
+    let (callback, score) = unsafe { (&*ctx.cast::<Box<Callback>>(), &mut *score) };
+    let provider = from_c_string(provider).unwrap_or_default();
+    let cid = from_c_string(cid).unwrap_or_default();
+    let candidate = ProviderCandidate {
+        provider: &provider,
+        cid: &cid,
+    };
+
+    // Unwinding into Go is undefined behaviour
+    match panic::catch_unwind(AssertUnwindSafe(|| callback(&candidate))) {
+        Ok(Some(value)) => {
+            // NaN cannot be ordered, treat it as a neutral score
+            *score = if value.is_nan() { 0.0 } else { value };
+            true
+        }
+        Ok(None) => false,
+        Err(_) => {
+            log::error!("Lassie provider score callback panicked");
+            *score = 0.0;
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::ffi::CString;
+
+    fn call(callback: Box<Callback>, provider: &str) -> Option<f64> {
+        let mut callback = callback;
+        let provider = CString::new(provider).unwrap();
+        let cid = CString::new("bafkqaaa").unwrap();
+        let mut score = f64::MIN;
+        let ctx: *mut Box<Callback> = &mut callback;
+        provider_score_trampoline(ctx.cast(), provider.as_ptr(), cid.as_ptr(), &mut score)
+            .then_some(score)
+    }
+
+    #[test]
+    fn converts_scores() {
+        let scores = || -> Box<Callback> {
+            Box::new(|candidate: &ProviderCandidate| {
+                assert_eq!(candidate.cid, "bafkqaaa");
+                match candidate.provider {
+                    "good" => Some(2.5),
+                    "odd" => Some(f64::NAN),
+                    "panic" => panic!("cannot score"),
+                    _ => None,
+                }
+            })
+        };
+        assert_eq!(call(scores(), "good"), Some(2.5));
+        assert_eq!(call(scores(), "odd"), Some(0.0));
+        assert_eq!(call(scores(), "panic"), Some(0.0));
+        assert_eq!(call(scores(), "bad"), None);
+    }
+}
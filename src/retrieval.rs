@@ -0,0 +1,162 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::time::Duration;
+
+use crate::from_c_string;
+
+/// The name of the response header carrying the ID assigned by the daemon to each retrieval.
+///
+/// Pass the ID to [`Daemon::cancel`](crate::Daemon::cancel) to abort the retrieval.
+pub const RETRIEVAL_ID_HEADER: &str = "X-Retrieval-Id";
+
+/// The name of the response header carrying a machine-readable category of an error response,
+/// e.g. `no-candidates` or `timeout`.
+///
+/// Pass the value to [`RetrievalError::from_error_response`](crate::RetrievalError::from_error_response).
+/// The header is missing when the daemon cannot categorize the error.
+pub const ERROR_CODE_HEADER: &str = "X-Lassie-Error";
+
+/// The name of the header carrying a correlation ID chosen by the client.
+///
+/// The daemon echoes the ID in the response, includes it in its log lines and forwards it to HTTP
+/// providers. Unless another running retrieval uses the same ID, the daemon also uses it as the
+/// retrieval ID (see [`RETRIEVAL_ID_HEADER`]). IDs longer than 128 characters or containing
+/// anything but visible ASCII characters are ignored; the daemon responds with the generated
+/// retrieval ID instead.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The name of the request header carrying the deadline of a single request as a Go duration,
+/// e.g. `30s` or `1500ms`.
+///
+/// The daemon applies the deadline on top of
+/// [`DaemonConfig::global_timeout`](crate::DaemonConfig::global_timeout), including the time
+/// spent in the queue of
+/// [`DaemonConfig::max_concurrent_retrievals`](crate::DaemonConfig::max_concurrent_retrievals).
+/// It responds with `504 Gateway Timeout` when the deadline passes before the response started
+/// and aborts the body stream otherwise.
+pub const TIMEOUT_HEADER: &str = "X-Timeout";
+
+/// The name of the request header carrying the [`Priority`] of a retrieval.
+pub const PRIORITY_HEADER: &str = "X-Priority";
+
+/// The name of the W3C Trace Context request header carrying the parent span of a retrieval,
+/// e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+///
+/// When [`DaemonConfig::otlp`](crate::DaemonConfig::otlp) is configured, the spans of the
+/// retrieval are exported as part of this trace. Malformed values are ignored.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// The place of a retrieval in the queue of
+/// [`DaemonConfig::max_concurrent_retrievals`](crate::DaemonConfig::max_concurrent_retrievals).
+///
+/// When the limit is reached, queued retrievals with a higher priority start first, e.g. so that
+/// interactive fetches don't wait behind background bulk jobs. Retrievals with the same priority
+/// start in the order they arrived. Send the priority in the [`PRIORITY_HEADER`] using the value
+/// of [`Priority::as_str`]; requests without the header or with an unknown value have
+/// [`Priority::Normal`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// The value of the [`PRIORITY_HEADER`].
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+go_lassie! {
+    fn CancelRetrieval(id: *const c_char) -> bool;
+    fn ListRetrievals() -> RetrievalList;
+    fn DropRetrievalList(list: *mut RetrievalList);
+}
+
+#[repr(C)]
+struct RetrievalInfo {
+    // this must be kept in sync with the definition of retrieval_info_t in go-lib/lassie-ffi.h
+    id: *const c_char,
+    cid: *const c_char,
+    provider: *const c_char,
+    elapsed: u64,
+    bytes_sent: u64,
+    blocks_received: u64,
+}
+
+#[repr(C)]
+struct RetrievalList {
+    items: *const RetrievalInfo,
+    len: usize,
+}
+
+impl Drop for RetrievalList {
+    fn drop(&mut self) {
+        // SAFETY:
+        // We can safely call the FFI function to free the memory used by RetrievalList, because
+        // Rust guarantees that the `drop` function is called only once for each instance. We always
+        // obtain instances via FFI calls.
+        unsafe { DropRetrievalList(self) }
+    }
+}
+
+/// A retrieval currently handled by the daemon, see
+/// [`Daemon::active_retrievals`](crate::Daemon::active_retrievals).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ActiveRetrieval {
+    /// The ID assigned by the daemon, see [`RETRIEVAL_ID_HEADER`].
+    pub id: String,
+    /// The root CID requested.
+    pub cid: String,
+    /// Time since the daemon started handling the request.
+    pub elapsed: Duration,
+    /// The number of bytes of the response body sent so far.
+    pub bytes_sent: u64,
+    /// The number of CAR blocks produced so far.
+    pub blocks_received: u64,
+    /// The peer ID of the provider serving the content, if known yet.
+    pub provider: Option<String>,
+}
+
+pub(crate) fn cancel(retrieval_id: &str) -> bool {
+    let Ok(id) = CString::new(retrieval_id) else {
+        // Retrieval IDs never contain null bytes, there cannot be any retrieval with this ID
+        return false;
+    };
+    // SAFETY:
+    // `id` is a valid NUL-terminated string that lives until the end of this function, Go copies
+    // it before returning.
+    unsafe { CancelRetrieval(id.as_ptr()) }
+}
+
+pub(crate) fn active_retrievals() -> Vec<ActiveRetrieval> {
+    // SAFETY:
+    // We can call this FFI function as it does not have any special safety requirements.
+    let list = unsafe { ListRetrievals() };
+    if list.items.is_null() {
+        return Vec::new();
+    }
+
+    // SAFETY:
+    // Go allocates `len` consecutive items, the memory stays valid until `list` is dropped.
+    let items = unsafe { std::slice::from_raw_parts(list.items, list.len) };
+    items
+        .iter()
+        .map(|info| ActiveRetrieval {
+            id: from_c_string(info.id).unwrap_or_default(),
+            cid: from_c_string(info.cid).unwrap_or_default(),
+            elapsed: Duration::from_nanos(info.elapsed),
+            bytes_sent: info.bytes_sent,
+            blocks_received: info.blocks_received,
+            provider: from_c_string(info.provider),
+        })
+        .collect()
+}
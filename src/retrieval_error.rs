@@ -0,0 +1,351 @@
+use std::fmt::{Display, Formatter};
+
+/// A machine-readable category of a failed retrieval.
+///
+/// Lassie reports failures as an HTTP status code with a plain-text body, and the category in the
+/// [`ERROR_CODE_HEADER`](crate::ERROR_CODE_HEADER) when the daemon knows it. Use
+/// [`RetrievalError::from_error_response`] to turn such a response into a stable category that
+/// does not change when Lassie tweaks the wording of its messages.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum RetrievalError {
+    /// The request was malformed, e.g. the CID could not be parsed (HTTP 400).
+    BadRequest(String),
+
+    /// The request did not provide the access token configured for the daemon (HTTP 401).
+    Unauthorized,
+
+    /// The client address is not in
+    /// [`DaemonConfig::allowed_client_ips`](crate::DaemonConfig::allowed_client_ips) (HTTP 403).
+    Forbidden,
+
+    /// The daemon is handling
+    /// [`DaemonConfig::max_concurrent_requests`](crate::DaemonConfig::max_concurrent_requests)
+    /// requests already, or the queue of
+    /// [`DaemonConfig::max_queued_retrievals`](crate::DaemonConfig::max_queued_retrievals) is
+    /// full (HTTP 429). Retry later.
+    TooManyRequests,
+
+    /// The daemon is finishing the running retrievals before it stops and rejects new requests
+    /// (HTTP 503), see [`Daemon::shutdown_gracefully`](crate::Daemon::shutdown_gracefully).
+    ShuttingDown,
+
+    /// Lassie cannot produce a response in the requested format (HTTP 406).
+    NotAcceptable(String),
+
+    /// Lassie did not find any provider offering the requested content.
+    NoCandidates,
+
+    /// The request did not specify `providers=`, but the daemon runs with
+    /// [`DaemonConfig::disable_candidate_discovery`](crate::DaemonConfig::disable_candidate_discovery)
+    /// (HTTP 400).
+    ProvidersRequired,
+
+    /// The retrieval did not finish within the configured timeout.
+    Timeout(String),
+
+    /// The requested DAG contains more blocks than allowed by the configured limit.
+    BlockLimitExceeded,
+
+    /// All providers failed to serve the requested content.
+    ProviderFailure { msg: String },
+
+    /// The response started successfully, but the daemon aborted the body stream.
+    ///
+    /// This is how Lassie reports errors that happen after it has sent the response headers, e.g.
+    /// when the global timeout or the block limit is reached in the middle of a transfer.
+    StreamAborted(String),
+
+    /// The daemon responded with a different `Content-Type` than requested by
+    /// `RetrievalRequest::format`.
+    UnexpectedContentType {
+        expected: String,
+        actual: Option<String>,
+    },
+
+    /// The daemon responded with a status code we don't know how to classify.
+    Http { status: u16, body: String },
+
+    /// The request did not reach the daemon or the response could not be read.
+    Transport(String),
+}
+
+impl RetrievalError {
+    /// Classify an error response returned by the Lassie HTTP server by the value of its
+    /// [`ERROR_CODE_HEADER`](crate::ERROR_CODE_HEADER). Responses without the header, or with a
+    /// code this version does not know, are classified by [`RetrievalError::from_response`].
+    #[must_use]
+    pub fn from_error_response(status: u16, error_code: Option<&str>, body: &str) -> Self {
+        let msg = || body.trim().to_string();
+        match error_code {
+            Some("unauthorized") => RetrievalError::Unauthorized,
+            Some("forbidden") => RetrievalError::Forbidden,
+            Some("too-many-requests") => RetrievalError::TooManyRequests,
+            Some("shutting-down") => RetrievalError::ShuttingDown,
+            Some("bad-request") => RetrievalError::BadRequest(msg()),
+            Some("providers-required") => RetrievalError::ProvidersRequired,
+            Some("no-candidates") => RetrievalError::NoCandidates,
+            Some("timeout") => RetrievalError::Timeout(msg()),
+            Some("provider-failure") => RetrievalError::ProviderFailure { msg: msg() },
+            _ => RetrievalError::from_response(status, body),
+        }
+    }
+
+    /// Classify an error response returned by the Lassie HTTP server by its status code and
+    /// message. Prefer [`RetrievalError::from_error_response`], the messages may change between
+    /// Lassie versions.
+    #[must_use]
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let msg = body.trim().to_string();
+        let lower = msg.to_lowercase();
+
+        if status == 401 {
+            return RetrievalError::Unauthorized;
+        }
+        if status == 403 {
+            return RetrievalError::Forbidden;
+        }
+        if status == 429 {
+            return RetrievalError::TooManyRequests;
+        }
+        if status == 503 && lower.starts_with("lassie daemon is shutting down") {
+            return RetrievalError::ShuttingDown;
+        }
+        if status == 400 && lower.starts_with("candidate discovery is disabled") {
+            return RetrievalError::ProvidersRequired;
+        }
+        if lower.contains("no candidates") {
+            return RetrievalError::NoCandidates;
+        }
+        if lower.contains("block limit") || lower.contains("max blocks") {
+            return RetrievalError::BlockLimitExceeded;
+        }
+        // The message names the header, don't mistake it for a timeout
+        if status == 400 && lower.starts_with("invalid x-timeout header") {
+            return RetrievalError::BadRequest(msg);
+        }
+        if status == 504
+            || lower.contains("timed out")
+            || lower.contains("timeout")
+            || lower.contains("deadline exceeded")
+        {
+            return RetrievalError::Timeout(msg);
+        }
+
+        match status {
+            400 => RetrievalError::BadRequest(msg),
+            406 => RetrievalError::NotAcceptable(msg),
+            500 | 502 | 503 => RetrievalError::ProviderFailure { msg },
+            _ => RetrievalError::Http { status, body: msg },
+        }
+    }
+
+    /// Classify an I/O error encountered while reading the body of a successful response. Reads
+    /// that timed out on the client side are reported as [`RetrievalError::Timeout`].
+    #[must_use]
+    pub fn from_stream_error(err: &std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::TimedOut {
+            return RetrievalError::Timeout(err.to_string());
+        }
+        RetrievalError::StreamAborted(err.to_string())
+    }
+
+    /// Whether repeating the same request may succeed: transport errors, aborted streams,
+    /// timeouts, provider failures and other `5xx` responses, and
+    /// [`RetrievalError::TooManyRequests`].
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RetrievalError::TooManyRequests
+            | RetrievalError::Timeout(_)
+            | RetrievalError::ProviderFailure { .. }
+            | RetrievalError::StreamAborted(_)
+            | RetrievalError::Transport(_) => true,
+            RetrievalError::Http { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl Display for RetrievalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "retrieval failed: ")?;
+        match self {
+            RetrievalError::BadRequest(msg) => write!(f, "bad request: {msg}"),
+            RetrievalError::Unauthorized => f.write_str("missing or invalid access token"),
+            RetrievalError::Forbidden => f.write_str("the client address is not allowed"),
+            RetrievalError::TooManyRequests => f.write_str("too many concurrent requests"),
+            RetrievalError::ShuttingDown => f.write_str("the daemon is shutting down"),
+            RetrievalError::NotAcceptable(msg) => write!(f, "not acceptable: {msg}"),
+            RetrievalError::NoCandidates => f.write_str("no candidates found"),
+            RetrievalError::ProvidersRequired => {
+                f.write_str("candidate discovery is disabled, specify the providers")
+            }
+            RetrievalError::Timeout(msg) => write!(f, "timed out: {msg}"),
+            RetrievalError::BlockLimitExceeded => f.write_str("block limit exceeded"),
+            RetrievalError::ProviderFailure { msg } => write!(f, "provider failure: {msg}"),
+            RetrievalError::StreamAborted(msg) => write!(f, "response stream aborted: {msg}"),
+            RetrievalError::UnexpectedContentType { expected, actual } => match actual {
+                Some(actual) => write!(f, "expected {expected} response, received {actual}"),
+                None => write!(f, "expected {expected} response, received no Content-Type"),
+            },
+            RetrievalError::Http { status, body } => {
+                write!(f, "unexpected status {status}: {body}")
+            }
+            RetrievalError::Transport(msg) => write!(f, "transport error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for RetrievalError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn prefers_error_code() {
+        assert_eq!(
+            RetrievalError::from_error_response(502, Some("no-candidates"), "lookup timeout\n"),
+            RetrievalError::NoCandidates
+        );
+        assert_eq!(
+            RetrievalError::from_error_response(
+                504,
+                Some("timeout"),
+                "failed to fetch CID: deadline\n"
+            ),
+            RetrievalError::Timeout("failed to fetch CID: deadline".to_string())
+        );
+        assert!(RetrievalError::from_error_response(
+            502,
+            Some("provider-failure"),
+            "no block limit"
+        )
+        .is_transient());
+    }
+
+    #[test]
+    fn falls_back_to_message_without_error_code() {
+        assert_eq!(
+            RetrievalError::from_error_response(502, None, "No candidates found\n"),
+            RetrievalError::NoCandidates
+        );
+        assert_eq!(
+            RetrievalError::from_error_response(502, Some("future-code"), "No candidates found"),
+            RetrievalError::NoCandidates
+        );
+    }
+
+    #[test]
+    fn classifies_unauthorized() {
+        assert_eq!(
+            RetrievalError::from_response(401, "Unauthorized\n"),
+            RetrievalError::Unauthorized
+        );
+    }
+
+    #[test]
+    fn classifies_forbidden() {
+        assert_eq!(
+            RetrievalError::from_response(403, "Forbidden\n"),
+            RetrievalError::Forbidden
+        );
+    }
+
+    #[test]
+    fn classifies_too_many_requests() {
+        assert_eq!(
+            RetrievalError::from_response(429, "too many concurrent requests, try again later\n"),
+            RetrievalError::TooManyRequests
+        );
+    }
+
+    #[test]
+    fn classifies_shutting_down() {
+        assert_eq!(
+            RetrievalError::from_response(503, "Lassie daemon is shutting down\n"),
+            RetrievalError::ShuttingDown
+        );
+    }
+
+    #[test]
+    fn classifies_no_candidates() {
+        assert_eq!(
+            RetrievalError::from_response(502, "No candidates found\n"),
+            RetrievalError::NoCandidates
+        );
+    }
+
+    #[test]
+    fn classifies_providers_required() {
+        assert_eq!(
+            RetrievalError::from_response(
+                400,
+                "candidate discovery is disabled, the request must specify providers=\n"
+            ),
+            RetrievalError::ProvidersRequired
+        );
+    }
+
+    #[test]
+    fn classifies_timeout() {
+        assert_eq!(
+            RetrievalError::from_response(504, "failed to fetch CID: retrieval timed out\n"),
+            RetrievalError::Timeout("failed to fetch CID: retrieval timed out".to_string())
+        );
+        let read = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out reading response");
+        assert_eq!(
+            RetrievalError::from_stream_error(&read),
+            RetrievalError::Timeout("timed out reading response".to_string())
+        );
+        assert_eq!(
+            RetrievalError::from_response(400, "invalid X-Timeout header \"soon\""),
+            RetrievalError::BadRequest("invalid X-Timeout header \"soon\"".to_string())
+        );
+    }
+
+    #[test]
+    fn classifies_provider_failure() {
+        assert_eq!(
+            RetrievalError::from_response(502, "failed to fetch CID: all retrievals failed"),
+            RetrievalError::ProviderFailure {
+                msg: "failed to fetch CID: all retrievals failed".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn classifies_bad_request() {
+        assert_eq!(
+            RetrievalError::from_response(400, "failed to parse CID path parameter"),
+            RetrievalError::BadRequest("failed to parse CID path parameter".to_string())
+        );
+    }
+
+    #[test]
+    fn recognizes_transient_errors() {
+        assert!(RetrievalError::from_response(502, "all retrievals failed").is_transient());
+        assert!(RetrievalError::Transport("connection reset".to_string()).is_transient());
+        assert!(RetrievalError::Http {
+            status: 599,
+            body: String::new()
+        }
+        .is_transient());
+        assert!(!RetrievalError::from_response(400, "failed to parse CID").is_transient());
+        assert!(!RetrievalError::NoCandidates.is_transient());
+        assert!(!RetrievalError::ShuttingDown.is_transient());
+    }
+
+    #[test]
+    fn keeps_unknown_status_codes() {
+        assert_eq!(
+            RetrievalError::from_response(418, "I'm a teapot"),
+            RetrievalError::Http {
+                status: 418,
+                body: "I'm a teapot".to_string()
+            }
+        );
+    }
+}
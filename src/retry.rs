@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use crate::multiaddr::Multiaddr;
+use crate::{Client, RetrievalError, RetrievalRequest, RetrievalResponse};
+
+/// How [`Client::fetch_with_retry`] repeats failed retrievals.
+///
+/// After a transient failure (see [`RetrievalError::is_transient`]), the client waits
+/// `initial_backoff` and tries again, doubling the delay after each failed attempt up to
+/// `max_backoff`. Other errors are returned right away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of attempts including the first one, `1` (or `0`) disables the retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// When the request has more than one [provider](RetrievalRequest::providers), ask a single
+    /// provider per attempt, starting with the first one and moving to the next one after each
+    /// failure. [`RetrievalError::NoCandidates`] is then retried too, the next provider may have
+    /// the content.
+    pub rotate_providers: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            rotate_providers: false,
+        }
+    }
+}
+
+/// A single attempt made by [`Client::fetch_with_retry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RetryAttempt {
+    /// The providers the attempt asked, empty when Lassie discovered the candidates itself.
+    pub providers: Vec<Multiaddr>,
+    /// How long the attempt took, including reading the response body.
+    pub elapsed: Duration,
+    /// Why the attempt failed, `None` for the successful one.
+    pub error: Option<RetrievalError>,
+}
+
+/// The result of [`Client::fetch_with_retry`] together with all attempts made to get it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryOutcome {
+    /// The response body of the successful attempt, or the error of the last attempt.
+    pub result: Result<Vec<u8>, RetrievalError>,
+    pub attempts: Vec<RetryAttempt>,
+}
+
+impl Client {
+    /// Retrieve `request` and read the response body, retrying transient failures as configured
+    /// by `policy`.
+    ///
+    /// The body is read as part of each attempt, so that streams aborted in the middle of the
+    /// transfer are retried too. The returned [`RetryOutcome`] lists all attempts, e.g. to log
+    /// which providers failed.
+    #[must_use]
+    pub fn fetch_with_retry(
+        &self,
+        request: &RetrievalRequest,
+        policy: &RetryPolicy,
+    ) -> RetryOutcome {
+        let mut attempts = Vec::new();
+        let mut backoff = policy.initial_backoff;
+        loop {
+            let attempt_request = rotate(request, policy, attempts.len());
+            let started = Instant::now();
+            let result = self
+                .fetch(&attempt_request)
+                .and_then(RetrievalResponse::read_to_end);
+            let error = result.as_ref().err().cloned();
+            let retry = error
+                .as_ref()
+                .is_some_and(|err| is_retryable(err, &attempt_request, request));
+            attempts.push(RetryAttempt {
+                providers: attempt_request.providers,
+                elapsed: started.elapsed(),
+                error,
+            });
+            if !retry || attempts.len() >= policy.max_attempts as usize {
+                return RetryOutcome { result, attempts };
+            }
+
+            log::debug!(
+                "Retrying the retrieval of {} in {backoff:?} after attempt {}",
+                request.cid(),
+                attempts.len()
+            );
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(policy.max_backoff);
+        }
+    }
+}
+
+/// The request of the zero-based `attempt`, asking a single provider when rotating.
+fn rotate(request: &RetrievalRequest, policy: &RetryPolicy, attempt: usize) -> RetrievalRequest {
+    let mut request = request.clone();
+    if policy.rotate_providers && request.providers.len() > 1 {
+        let index = attempt % request.providers.len();
+        request.providers = vec![request.providers.swap_remove(index)];
+    }
+    request
+}
+
+/// Transient errors are retried, and so are missing candidates while rotating the providers.
+fn is_retryable(
+    err: &RetrievalError,
+    attempt_request: &RetrievalRequest,
+    request: &RetrievalRequest,
+) -> bool {
+    let rotating = attempt_request.providers.len() < request.providers.len();
+    err.is_transient() || (rotating && *err == RetrievalError::NoCandidates)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn rotates_providers() {
+        let providers: Vec<Multiaddr> = ["/dns4/a.example/https", "/dns4/b.example/https"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let request = RetrievalRequest::new("bafkqaaa").providers(providers.clone());
+        let policy = RetryPolicy {
+            rotate_providers: true,
+            ..RetryPolicy::default()
+        };
+
+        let asked: Vec<_> = (0..3)
+            .map(|attempt| rotate(&request, &policy, attempt).providers)
+            .collect();
+        assert_eq!(
+            asked,
+            vec![
+                vec![providers[0].clone()],
+                vec![providers[1].clone()],
+                vec![providers[0].clone()],
+            ]
+        );
+        assert!(is_retryable(
+            &RetrievalError::NoCandidates,
+            &rotate(&request, &policy, 0),
+            &request,
+        ));
+
+        let policy = RetryPolicy::default();
+        assert_eq!(rotate(&request, &policy, 1).providers, providers);
+        assert!(!is_retryable(
+            &RetrievalError::NoCandidates,
+            &request,
+            &request
+        ));
+    }
+}
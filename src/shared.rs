@@ -0,0 +1,54 @@
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+use crate::{Daemon, DaemonConfig, StartError};
+
+/// The daemon started by [`Daemon::get_or_start`], kept alive by the [`SharedDaemon`] handles.
+static SHARED: Mutex<Weak<Daemon>> = Mutex::new(Weak::new());
+
+fn lock_shared() -> MutexGuard<'static, Weak<Daemon>> {
+    SHARED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A reference-counted handle to the process-wide daemon, see [`Daemon::get_or_start`].
+///
+/// The daemon is stopped when the last clone is dropped.
+#[derive(Clone)]
+pub struct SharedDaemon {
+    // `None` only while dropping
+    daemon: Option<Arc<Daemon>>,
+}
+
+impl Deref for SharedDaemon {
+    type Target = Daemon;
+
+    fn deref(&self) -> &Daemon {
+        self.daemon
+            .as_deref()
+            .expect("SharedDaemon holds the daemon until dropped")
+    }
+}
+
+impl Drop for SharedDaemon {
+    fn drop(&mut self) {
+        // Stop the daemon while holding the lock, so that a concurrent `get_or_start` waits
+        // for the shutdown instead of failing with `OnlyOneInstanceAllowed`
+        let _shared = lock_shared();
+        drop(self.daemon.take());
+    }
+}
+
+pub(crate) fn get_or_start(config: DaemonConfig) -> Result<SharedDaemon, StartError> {
+    let mut shared = lock_shared();
+    if let Some(daemon) = shared.upgrade() {
+        log::debug!("Reusing the shared Lassie daemon");
+        return Ok(SharedDaemon {
+            daemon: Some(daemon),
+        });
+    }
+    let daemon = Arc::new(Daemon::start(config)?);
+    *shared = Arc::downgrade(&daemon);
+    Ok(SharedDaemon {
+        daemon: Some(daemon),
+    })
+}
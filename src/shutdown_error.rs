@@ -0,0 +1,26 @@
+use std::fmt::{Display, Formatter};
+
+/// The reason why [`Daemon::try_shutdown`](crate::Daemon::try_shutdown) failed.
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum ShutdownError {
+    MutexPoisoned,
+    /// Lassie could not stop the daemon. The Go side may still be running, therefore starting a
+    /// new daemon will fail.
+    Lassie(String),
+    /// The thread running the Lassie HTTP handler panicked.
+    HandlerPanicked,
+}
+
+impl Display for ShutdownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "failed to stop Lassie daemon: ")?;
+        match self {
+            ShutdownError::MutexPoisoned => f.write_str("the global mutex was poisoned"),
+            ShutdownError::Lassie(msg) => f.write_str(msg),
+            ShutdownError::HandlerPanicked => f.write_str("the HTTP handler thread panicked"),
+        }
+    }
+}
+
+impl std::error::Error for ShutdownError {}
@@ -0,0 +1,50 @@
+use std::os::fd::RawFd;
+
+/// The first descriptor passed by systemd, see `sd_listen_fds(3)`.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The listening socket passed to this process by systemd socket activation, to be used as
+/// [`crate::DaemonConfig::listener_fd`].
+///
+/// Returns `None` unless `LISTEN_PID` names the current process and `LISTEN_FDS` is at least `1`.
+/// When several sockets are passed, only the first one is returned. The environment variables are
+/// left in place, the caller owns the descriptor.
+#[must_use]
+pub fn systemd_listen_fd() -> Option<RawFd> {
+    listen_fd(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn listen_fd(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<RawFd> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return None;
+    }
+    match listen_fds?.parse::<u32>().ok()? {
+        0 => None,
+        _ => Some(SD_LISTEN_FDS_START),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reads_listen_fds() {
+        assert_eq!(listen_fd(Some("42"), Some("1"), 42), Some(3));
+        assert_eq!(listen_fd(Some("42"), Some("2"), 42), Some(3));
+        assert_eq!(listen_fd(Some("42"), Some("0"), 42), None);
+        assert_eq!(
+            listen_fd(Some("41"), Some("1"), 42),
+            None,
+            "passed to another process"
+        );
+        assert_eq!(listen_fd(None, Some("1"), 42), None);
+        assert_eq!(listen_fd(Some("42"), None, 42), None);
+        assert_eq!(listen_fd(Some("42"), Some("many"), 42), None);
+    }
+}
@@ -1,7 +1,15 @@
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::{error_code, AdminAddress, AdminListenerConfig, ConfigError, DaemonConfig};
+
+/// The reason why [`Daemon::start`](crate::Daemon::start) failed.
+///
+/// [`source()`](std::error::Error::source) returns the underlying cause when there is one: the
+/// raw Go error ([`GoError`]), the I/O error or the first [`ConfigError`]. Use the `is_*` methods
+/// to handle whole categories of errors without matching all variants.
 #[derive(Debug, PartialEq, Clone)]
 #[non_exhaustive]
 pub enum StartError {
@@ -10,8 +18,38 @@ pub enum StartError {
     PathContainsNullByte(String),
     PathIsNotValidUtf8(PathBuf),
     DurationIsTooLong(Duration),
+    /// Lassie failed to start for a reason not covered by the other variants.
     Lassie(String),
+    /// Another process is already listening on the configured port (the HTTP listener or the
+    /// admin listener).
+    AddrInUse {
+        port: u16,
+        source: GoError,
+    },
+    /// The operating system does not allow us to listen on the configured port, e.g. because
+    /// ports below 1024 are reserved for privileged processes.
+    PermissionDenied {
+        port: u16,
+        source: GoError,
+    },
+    /// Lassie cannot create files in the configured [`DaemonConfig::temp_dir`].
+    TempDirNotWritable {
+        path: PathBuf,
+        source: GoError,
+    },
+    /// The configured [`DaemonConfig::temp_dir`] does not exist and cannot be created, see
+    /// [`DaemonConfig::create_temp_dir`].
+    CannotCreateTempDir {
+        path: PathBuf,
+        source: IoError,
+    },
     AccessTokenContainsNullByte(String),
+    /// The configuration field (the first value) contains a null byte.
+    ConfigContainsNullByte(&'static str, String),
+    /// Lassie did not finish initializing within [`DaemonConfig::startup_timeout`].
+    StartupTimedOut(Duration),
+    /// [`DaemonConfig::validate`] reported these problems, see [`crate::DaemonBuilder::start`].
+    InvalidConfig(Vec<ConfigError>),
 }
 
 impl Display for StartError {
@@ -30,17 +68,181 @@ impl Display for StartError {
                 path.display(),
             )),
             StartError::Lassie(msg) => f.write_str(msg),
+            StartError::AddrInUse { port, .. } => {
+                f.write_fmt(format_args!("port {port} is already in use"))
+            }
+            StartError::PermissionDenied { port, .. } => {
+                f.write_fmt(format_args!("permission denied to listen on port {port}"))
+            }
+            StartError::TempDirNotWritable { path, .. } => f.write_fmt(format_args!(
+                "temp_dir {:?} is not writable",
+                path.display(),
+            )),
+            StartError::CannotCreateTempDir { path, source } => f.write_fmt(format_args!(
+                "cannot create temp_dir {:?}: {source}",
+                path.display(),
+            )),
             StartError::DurationIsTooLong(d) => f.write_fmt(format_args!(
                 "duration {d:#?} is too long, Go limits the largest representable duration to approximately 290 years",
             )),
             StartError::AccessTokenContainsNullByte(token) => f.write_fmt(format_args!(
                 "null bytes are not allowed in the access token (value: {token:?})",
             )),
+            StartError::ConfigContainsNullByte(field, value) => f.write_fmt(format_args!(
+                "null bytes are not allowed in {field} (value: {value:?})",
+            )),
+            StartError::StartupTimedOut(timeout) => f.write_fmt(format_args!(
+                "Lassie did not start within {timeout:?}",
+            )),
+            StartError::InvalidConfig(errors) => {
+                f.write_str("invalid configuration: ")?;
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    Display::fmt(err, f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for StartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StartError::AddrInUse { source, .. }
+            | StartError::PermissionDenied { source, .. }
+            | StartError::TempDirNotWritable { source, .. } => Some(source),
+            StartError::CannotCreateTempDir { source, .. } => Some(source.get_ref()),
+            StartError::InvalidConfig(errors) => errors
+                .first()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+            _ => None,
+        }
+    }
+}
+
+impl StartError {
+    /// The configuration is invalid, starting again with the same configuration fails the same
+    /// way.
+    #[must_use]
+    pub fn is_config_error(&self) -> bool {
+        matches!(
+            self,
+            StartError::PathContainsNullByte(_)
+                | StartError::PathIsNotValidUtf8(_)
+                | StartError::DurationIsTooLong(_)
+                | StartError::AccessTokenContainsNullByte(_)
+                | StartError::ConfigContainsNullByte(..)
+                | StartError::InvalidConfig(_)
+        )
+    }
+
+    /// The HTTP listener or the admin listener cannot listen on the configured port. Retrying may
+    /// help when the port is held by a process that is about to exit.
+    #[must_use]
+    pub fn is_bind_error(&self) -> bool {
+        matches!(
+            self,
+            StartError::AddrInUse { .. } | StartError::PermissionDenied { .. }
+        )
+    }
+
+    /// Classify the error returned by Go `InitDaemon` by its [`error_code`].
+    pub(crate) fn from_init_error(msg: String, code: u32, config: &DaemonConfig) -> Self {
+        let admin_port = match &config.admin_listener {
+            Some(AdminListenerConfig {
+                address: AdminAddress::Port(port),
+                ..
+            }) => Some(*port),
+            _ => None,
+        };
+        let source = GoError(msg);
+        match (code, admin_port, &config.temp_dir) {
+            (error_code::ALREADY_RUNNING, ..) => StartError::OnlyOneInstanceAllowed,
+            (error_code::ADDR_IN_USE, ..) => StartError::AddrInUse {
+                port: config.port,
+                source,
+            },
+            (error_code::PERMISSION_DENIED, ..) => StartError::PermissionDenied {
+                port: config.port,
+                source,
+            },
+            (error_code::ADMIN_ADDR_IN_USE, Some(port), _) => {
+                StartError::AddrInUse { port, source }
+            }
+            (error_code::ADMIN_PERMISSION_DENIED, Some(port), _) => {
+                StartError::PermissionDenied { port, source }
+            }
+            (error_code::TEMP_DIR_NOT_WRITABLE, _, Some(path)) => StartError::TempDirNotWritable {
+                path: path.clone(),
+                source,
+            },
+            _ => StartError::Lassie(source.0),
         }
     }
 }
 
-impl std::error::Error for StartError {}
+/// The raw error message reported by the Go side, available as
+/// [`source()`](std::error::Error::source) of the classified [`StartError`] variants and from
+/// [`Daemon::take_error`](crate::Daemon::take_error).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GoError(String);
+
+impl GoError {
+    pub(crate) fn new(msg: String) -> Self {
+        GoError(msg)
+    }
+
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for GoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for GoError {}
+
+/// An I/O error kept as the [`source()`](std::error::Error::source) of a [`StartError`].
+///
+/// [`std::io::Error`] is neither `Clone` nor `PartialEq`, this wrapper shares it and compares the
+/// kind and the message.
+#[derive(Debug, Clone)]
+pub struct IoError(Arc<std::io::Error>);
+
+impl IoError {
+    pub(crate) fn new(err: std::io::Error) -> Self {
+        IoError(Arc::new(err))
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.0.kind()
+    }
+
+    #[must_use]
+    pub fn get_ref(&self) -> &std::io::Error {
+        &self.0
+    }
+}
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.kind() == other.0.kind() && self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Display for IoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
 
 #[cfg(test)]
 mod test {
@@ -59,4 +261,156 @@ mod test {
             format!("lassie error: {}", StartError::OnlyOneInstanceAllowed)
         );
     }
+
+    #[test]
+    fn classifies_addr_in_use() {
+        let msg =
+            "cannot start the HTTP server: listen tcp 127.0.0.1:3000: bind: address already in use";
+        let config = DaemonConfig {
+            port: 3000,
+            ..DaemonConfig::default()
+        };
+        let err = StartError::from_init_error(msg.to_string(), error_code::ADDR_IN_USE, &config);
+        assert_eq!(
+            err,
+            StartError::AddrInUse {
+                port: 3000,
+                source: GoError(msg.to_string())
+            }
+        );
+        let source = std::error::Error::source(&err).expect("the raw message is the source");
+        assert_eq!(source.to_string(), msg);
+    }
+
+    #[test]
+    fn classifies_admin_listener_errors() {
+        let msg =
+            "cannot start the admin listener: listen tcp 127.0.0.1:80: bind: permission denied";
+        let config = DaemonConfig {
+            admin_listener: Some(AdminListenerConfig {
+                address: AdminAddress::Port(80),
+                access_token: None,
+                pprof: false,
+            }),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            StartError::from_init_error(
+                msg.to_string(),
+                error_code::ADMIN_PERMISSION_DENIED,
+                &config
+            ),
+            StartError::PermissionDenied {
+                port: 80,
+                source: GoError(msg.to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn classifies_temp_dir_not_writable() {
+        let msg = "temp_dir is not writable: open /ro/.lassie-probe-1: read-only file system";
+        let config = DaemonConfig {
+            temp_dir: Some(PathBuf::from("/ro")),
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            StartError::from_init_error(
+                msg.to_string(),
+                error_code::TEMP_DIR_NOT_WRITABLE,
+                &config
+            ),
+            StartError::TempDirNotWritable {
+                path: PathBuf::from("/ro"),
+                source: GoError(msg.to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn lists_all_config_errors() {
+        let err = StartError::InvalidConfig(vec![
+            ConfigError::EmptyMmapCarStore,
+            ConfigError::NoLibp2pTransports,
+        ]);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "failed to start Lassie daemon: invalid configuration: {}; {}",
+                ConfigError::EmptyMmapCarStore,
+                ConfigError::NoLibp2pTransports
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_io_error_as_source() {
+        let err = StartError::CannotCreateTempDir {
+            path: PathBuf::from("/ro/lassie"),
+            source: IoError::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied)),
+        };
+        let source = std::error::Error::source(&err).expect("the I/O error is the source");
+        let io = source
+            .downcast_ref::<std::io::Error>()
+            .expect("the source is std::io::Error");
+        assert_eq!(io.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(!err.is_config_error());
+        assert!(!err.is_bind_error());
+    }
+
+    #[test]
+    fn categorizes_errors() {
+        let bind = StartError::AddrInUse {
+            port: 3000,
+            source: GoError("address already in use".to_string()),
+        };
+        assert!(bind.is_bind_error());
+        assert!(!bind.is_config_error());
+
+        let config = StartError::InvalidConfig(vec![ConfigError::EmptyMmapCarStore]);
+        assert!(config.is_config_error());
+        assert!(
+            StartError::ConfigContainsNullByte("user_agent", "a\0".to_string()).is_config_error()
+        );
+        assert!(!StartError::OnlyOneInstanceAllowed.is_config_error());
+        assert!(!StartError::OnlyOneInstanceAllowed.is_bind_error());
+    }
+
+    #[test]
+    fn keeps_unknown_errors_as_lassie() {
+        let msg = "cannot configure libp2p: boom";
+        assert_eq!(
+            StartError::from_init_error(msg.to_string(), 1, &DaemonConfig::default()),
+            StartError::Lassie(msg.to_string())
+        );
+    }
+
+    #[test]
+    fn classifies_by_code_not_message() {
+        let msg = "cannot start the HTTP server: reworded";
+        let config = DaemonConfig {
+            port: 3000,
+            ..DaemonConfig::default()
+        };
+        assert_eq!(
+            StartError::from_init_error(msg.to_string(), error_code::ADDR_IN_USE, &config),
+            StartError::AddrInUse {
+                port: 3000,
+                source: GoError(msg.to_string())
+            }
+        );
+        assert_eq!(
+            StartError::from_init_error(
+                "cannot create more than one Lassie daemon".to_string(),
+                error_code::ALREADY_RUNNING,
+                &config
+            ),
+            StartError::OnlyOneInstanceAllowed
+        );
+        assert_eq!(
+            StartError::from_init_error(msg.to_string(), error_code::ADMIN_ADDR_IN_USE, &config),
+            StartError::Lassie(msg.to_string()),
+            "no admin port to report"
+        );
+    }
 }
@@ -0,0 +1,80 @@
+//! Go `InitDaemon` with a deadline, see [`DaemonConfig::startup_timeout`](crate::DaemonConfig).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::go_config::GoConfig;
+use crate::{InitDaemon, InitDaemonResult, StartError, StopDaemon};
+
+/// Set while the `InitDaemon` call of a timed out [`crate::Daemon::start`] is still running.
+static ABANDONED_INIT: AtomicBool = AtomicBool::new(false);
+
+enum State {
+    Waiting,
+    Done(InitDaemonResult),
+    Abandoned,
+}
+
+/// Call Go `InitDaemon`, giving up after `timeout`.
+///
+/// Go cannot interrupt a wedged initialization, the call keeps running in a background thread.
+/// When it eventually succeeds, the thread stops the daemon nobody is waiting for. Until then,
+/// new starts fail with [`StartError::OnlyOneInstanceAllowed`].
+pub(crate) fn init_daemon(
+    go_config: GoConfig,
+    timeout: Option<Duration>,
+) -> Result<InitDaemonResult, StartError> {
+    if ABANDONED_INIT.load(Ordering::SeqCst) {
+        log::error!("The previous Lassie.InitDaemon call is still running");
+        return Err(StartError::OnlyOneInstanceAllowed);
+    }
+
+    let Some(timeout) = timeout else {
+        // SAFETY:
+        // It's safe to call this FFI function as it does not have any special safety requirements
+        // and we know that `go_config.as_ptr()` is not a NULL pointer.
+        return Ok(unsafe { InitDaemon(go_config.as_ptr()) });
+    };
+
+    let shared = Arc::new((Mutex::new(State::Waiting), Condvar::new()));
+    let thread_shared = Arc::clone(&shared);
+    std::thread::spawn(move || {
+        // SAFETY:
+        // See above, `go_config` lives until the end of this closure.
+        let result = unsafe { InitDaemon(go_config.as_ptr()) };
+        let (lock, cvar) = &*thread_shared;
+        let mut state = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        if matches!(*state, State::Abandoned) {
+            drop(state);
+            if result.error().is_none() {
+                log::warn!("Lassie.InitDaemon finished after the startup timeout, stopping Lassie");
+                // SAFETY:
+                // It's safe to call this FFI function as it does not have any special safety
+                // requirements.
+                let stopped = unsafe { StopDaemon() };
+                if let Some(msg) = stopped.error() {
+                    log::error!("Lassie.StopDaemon failed: {msg}");
+                }
+            }
+            ABANDONED_INIT.store(false, Ordering::SeqCst);
+        } else {
+            *state = State::Done(result);
+            cvar.notify_one();
+        }
+    });
+
+    let (lock, cvar) = &*shared;
+    let state = lock.lock().unwrap_or_else(PoisonError::into_inner);
+    let (mut state, _) = cvar
+        .wait_timeout_while(state, timeout, |state| matches!(state, State::Waiting))
+        .unwrap_or_else(PoisonError::into_inner);
+    if let State::Done(result) = std::mem::replace(&mut *state, State::Abandoned) {
+        Ok(result)
+    } else {
+        // Set while holding the lock, the thread clears it after seeing `State::Abandoned`
+        ABANDONED_INIT.store(true, Ordering::SeqCst);
+        log::error!("Lassie.InitDaemon did not finish within {timeout:?}");
+        Err(StartError::StartupTimedOut(timeout))
+    }
+}
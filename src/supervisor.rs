@@ -0,0 +1,265 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::go_config::GoConfig;
+use crate::{error_code, startup, DaemonConfig, GoError, RunDaemon, StopDaemon};
+
+/// The delay before the first restart, doubled after each failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// The supervisor gives up after this many restarts in a row that did not bring the daemon up.
+const MAX_RESTART_ATTEMPTS: u32 = 10;
+
+/// A problem with the running daemon, see [`Daemon::failure_events`](crate::Daemon::failure_events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FailureEvent {
+    /// The Go HTTP handler stopped with an error.
+    HandlerFailed { message: String },
+    /// [`DaemonConfig::auto_restart`] brought the daemon up again on the same port. `attempt`
+    /// counts the restarts since the last failure, starting at `1`.
+    Restarted { attempt: u32, port: u16 },
+    /// A restart attempt failed, the supervisor tries again after a back-off delay.
+    RestartFailed { attempt: u32, message: String },
+    /// The supervisor stopped trying after `attempts` failed restarts, the daemon stays down.
+    GaveUp { attempts: u32 },
+    /// The [`DaemonConfig::watchdog`](crate::DaemonConfig::watchdog) probes failed, `message`
+    /// describes the last failure.
+    Unresponsive { message: String },
+    /// The daemon answers the watchdog probes again after [`FailureEvent::Unresponsive`].
+    Recovered,
+}
+
+impl Display for FailureEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FailureEvent::HandlerFailed { message } => {
+                f.write_fmt(format_args!("Lassie HTTP handler failed: {message}"))
+            }
+            FailureEvent::Restarted { attempt, port } => f.write_fmt(format_args!(
+                "Lassie restarted on port {port} (attempt {attempt})"
+            )),
+            FailureEvent::RestartFailed { attempt, message } => f.write_fmt(format_args!(
+                "Lassie restart attempt {attempt} failed: {message}"
+            )),
+            FailureEvent::GaveUp { attempts } => f.write_fmt(format_args!(
+                "Lassie stays down after {attempts} failed restart attempts"
+            )),
+            FailureEvent::Unresponsive { message } => {
+                f.write_fmt(format_args!("Lassie is not responding: {message}"))
+            }
+            FailureEvent::Recovered => f.write_str("Lassie is responding again"),
+        }
+    }
+}
+
+/// The state shared by the handler thread and the [`crate::Daemon`].
+pub(crate) struct Supervisor {
+    /// Guards the restarts so that they don't race with the shutdown
+    state: Mutex<State>,
+    stopping: Condvar,
+    subscribers: Mutex<Vec<Sender<FailureEvent>>>,
+    /// The last error of the handler thread, see [`crate::Daemon::take_error`]
+    last_error: Mutex<Option<GoError>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Set by [`Supervisor::stop`]
+    stopping: bool,
+    /// Set while a failed restart left the Go side without a daemon
+    down: bool,
+}
+
+impl Supervisor {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Supervisor {
+            state: Mutex::new(State::default()),
+            stopping: Condvar::new(),
+            subscribers: Mutex::new(Vec::new()),
+            last_error: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<FailureEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.lock_subscribers().push(tx);
+        rx
+    }
+
+    pub(crate) fn emit(&self, event: &FailureEvent) {
+        if matches!(
+            event,
+            FailureEvent::Restarted { .. } | FailureEvent::Recovered
+        ) {
+            log::info!("{event}");
+        } else {
+            log::error!("{event}");
+        }
+        self.lock_subscribers()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    pub(crate) fn take_error(&self) -> Option<GoError> {
+        self.lock_last_error().take()
+    }
+
+    fn record_error(&self, message: &str) {
+        *self.lock_last_error() = Some(GoError::new(message.to_string()));
+    }
+
+    fn lock_last_error(&self) -> std::sync::MutexGuard<'_, Option<GoError>> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock_subscribers(&self) -> std::sync::MutexGuard<'_, Vec<Sender<FailureEvent>>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Prevent further restarts and stop the Go daemon, waiting for a restart in progress.
+    pub(crate) fn stop(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.stopping = true;
+        self.stopping.notify_all();
+        if state.down {
+            return Ok(());
+        }
+        // SAFETY:
+        // We can call this FFI function as it does not have any special safety requirements.
+        let result = unsafe { StopDaemon() };
+        match result.error() {
+            Some(msg) if result.error_code == error_code::NOT_RUNNING => {
+                // Nothing is left to stop, the Go side is not keeping the singleton
+                log::debug!("Lassie daemon was not running: {msg}");
+                Ok(())
+            }
+            Some(msg) => Err(msg),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the Go HTTP handler until the daemon stops, restarting it after failures when
+    /// `restart_config` is set.
+    pub(crate) fn run_handler(&self, restart_config: Option<&DaemonConfig>, running: &AtomicBool) {
+        loop {
+            log::debug!("Running Lassie HTTP handler");
+            // SAFETY:
+            // This FFI function is designed to be called from a different thread.
+            let result = unsafe { RunDaemon() };
+            let Some(message) = result.error() else {
+                break;
+            };
+            self.record_error(&message);
+            self.emit(&FailureEvent::HandlerFailed { message });
+            let Some(config) = restart_config else {
+                break;
+            };
+            if !self.restart(config) {
+                break;
+            }
+        }
+        // The Go side stopped serving, e.g. after `idle_shutdown`
+        running.store(false, Ordering::Release);
+        log::debug!("HTTP handler exited");
+    }
+
+    /// Re-initialize the daemon with bounded exponential back-off. Returns `false` when the
+    /// daemon is stopping or the supervisor gave up.
+    fn restart(&self, config: &DaemonConfig) -> bool {
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_RESTART_ATTEMPTS {
+            let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            let (mut state, _) = self
+                .stopping
+                .wait_timeout_while(state, backoff, |state| !state.stopping)
+                .unwrap_or_else(PoisonError::into_inner);
+            if state.stopping {
+                return false;
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+
+            // Holding the state makes `Daemon::shutdown` wait until the restart is complete
+            let result = reinit(config, &mut state);
+            drop(state);
+            match result {
+                Ok(port) => {
+                    self.emit(&FailureEvent::Restarted { attempt, port });
+                    return true;
+                }
+                Err(message) => {
+                    self.record_error(&message);
+                    self.emit(&FailureEvent::RestartFailed { attempt, message });
+                }
+            }
+        }
+        self.emit(&FailureEvent::GaveUp {
+            attempts: MAX_RESTART_ATTEMPTS,
+        });
+        false
+    }
+}
+
+/// Stop the failed Go daemon and initialize it again with `config`.
+fn reinit(config: &DaemonConfig, state: &mut State) -> Result<u16, String> {
+    if !state.down {
+        // SAFETY:
+        // We can call this FFI function as it does not have any special safety requirements.
+        let stopped = unsafe { StopDaemon() };
+        if let Some(msg) = stopped.error() {
+            log::debug!("Cannot clean up the failed Lassie daemon: {msg}");
+        }
+        state.down = true;
+    }
+    let go_config = GoConfig::new(config).map_err(|err| err.to_string())?;
+    let result =
+        startup::init_daemon(go_config, config.startup_timeout).map_err(|err| err.to_string())?;
+    if let Some(msg) = result.error() {
+        return Err(msg);
+    }
+    state.down = false;
+    Ok(result.port)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn emits_events_to_subscribers() {
+        let supervisor = Supervisor::new();
+        let first = supervisor.subscribe();
+        let second = supervisor.subscribe();
+        drop(second);
+
+        let event = FailureEvent::Restarted {
+            attempt: 2,
+            port: 8080,
+        };
+        supervisor.emit(&event);
+        assert_eq!(first.try_recv(), Ok(event));
+        assert_eq!(
+            supervisor.lock_subscribers().len(),
+            1,
+            "dropped receivers unsubscribe"
+        );
+        assert_eq!(supervisor.take_error(), None);
+        supervisor.record_error("boom");
+        assert_eq!(
+            supervisor.take_error().as_ref().map(GoError::message),
+            Some("boom")
+        );
+        assert_eq!(supervisor.take_error(), None, "the error is taken");
+        assert_eq!(
+            FailureEvent::GaveUp { attempts: 10 }.to_string(),
+            "Lassie stays down after 10 failed restart attempts"
+        );
+    }
+}
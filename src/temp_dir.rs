@@ -0,0 +1,171 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// The prefix of temporary CAR files Lassie creates when serving requests (see
+/// `DeferredCarStorage` in Lassie's `pkg/storage`). We never touch other files in the temp dir,
+/// because by default it's the temp dir shared by all programs.
+const CAR_STORE_PREFIX: &str = "lassie_carstorage";
+
+/// Configuration of the temp dir eviction, see [`DaemonConfig::temp_dir_eviction`](crate::DaemonConfig::temp_dir_eviction).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct TempDirEvictionConfig {
+    /// Start removing the oldest CAR store files when they occupy more than this many bytes.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::config_de::size"))]
+    pub max_size: u64,
+    /// How often to check the disk usage.
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::config_de::duration")
+    )]
+    pub interval: Duration,
+}
+
+impl Default for TempDirEvictionConfig {
+    fn default() -> Self {
+        TempDirEvictionConfig {
+            max_size: 10 << 30,
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+struct CarStoreFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+fn car_store_files(dir: &Path) -> std::io::Result<Vec<CarStoreFile>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(CAR_STORE_PREFIX)
+        {
+            continue;
+        }
+        // The file may have been removed by Lassie in the meantime
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_file() {
+            files.push(CarStoreFile {
+                path: entry.path(),
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// The number of bytes used by Lassie's temporary CAR files in `dir`.
+pub(crate) fn disk_usage(dir: &Path) -> std::io::Result<u64> {
+    Ok(car_store_files(dir)?.iter().map(|f| f.size).sum())
+}
+
+/// Remove the oldest CAR files until they occupy at most `max_size` bytes. Returns the number of
+/// bytes used after the eviction.
+fn evict(dir: &Path, max_size: u64) -> std::io::Result<u64> {
+    let mut files = car_store_files(dir)?;
+    let mut usage: u64 = files.iter().map(|f| f.size).sum();
+    files.sort_by_key(|f| f.modified);
+    for file in files {
+        if usage <= max_size {
+            break;
+        }
+        // Removing a file that's still being written fails on Windows, we'll retry next time.
+        // On Unix, the space is reclaimed when Lassie closes the file.
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => {
+                log::debug!(
+                    "Evicted {} ({} bytes) from the temp dir",
+                    file.path.display(),
+                    file.size
+                );
+                usage -= file.size;
+            }
+            Err(err) => log::debug!("Cannot evict {}: {err}", file.path.display()),
+        }
+    }
+    Ok(usage)
+}
+
+/// The background thread enforcing [`TempDirEvictionConfig`]. Dropping the task stops the thread.
+pub(crate) struct EvictionTask {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EvictionTask {
+    pub(crate) fn start(dir: PathBuf, config: &TempDirEvictionConfig) -> Self {
+        let TempDirEvictionConfig { max_size, interval } = *config;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            // The loop ends when the task is dropped
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if let Err(err) = evict(&dir, max_size) {
+                    log::warn!("Cannot evict files from {}: {err}", dir.display());
+                }
+            }
+        });
+        EvictionTask {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for EvictionTask {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the thread
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Lassie temp dir eviction panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn write_file(dir: &Path, name: &str, size: usize, age: Duration) {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn evicts_oldest_car_files_first() {
+        let dir = std::env::temp_dir().join("rusty-lassie-eviction-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_file(&dir, "lassie_carstorage1", 100, Duration::from_secs(30));
+        write_file(&dir, "lassie_carstorage2", 100, Duration::from_secs(20));
+        write_file(&dir, "lassie_carstorage3", 100, Duration::from_secs(10));
+        write_file(&dir, "unrelated", 1000, Duration::from_secs(40));
+
+        assert_eq!(disk_usage(&dir).unwrap(), 300);
+        assert_eq!(evict(&dir, 150).unwrap(), 100);
+        assert!(!dir.join("lassie_carstorage1").exists());
+        assert!(!dir.join("lassie_carstorage2").exists());
+        assert!(dir.join("lassie_carstorage3").exists());
+        assert!(dir.join("unrelated").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
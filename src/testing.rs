@@ -0,0 +1,416 @@
+//! Helpers for testing retrieval logic without network access.
+//!
+//! [`MockProvider`] is a tiny trustless HTTP gateway serving [`Fixture`] CARs from memory. Point
+//! the daemon to it via the `providers` query parameter:
+//!
+//! ```no_run
+//! use std::io::Read;
+//! use lassie::testing::{Fixture, MockProvider};
+//! use lassie::{Daemon, DaemonConfig};
+//!
+//! let fixture = Fixture::unixfs_file(b"hello world", 4);
+//! let provider = MockProvider::start(vec![fixture.clone()])?;
+//! let daemon = Daemon::start(DaemonConfig {
+//!     disable_ipni: true,
+//!     ..DaemonConfig::default()
+//! })?;
+//!
+//! let path = format!("/ipfs/{}?{}", fixture.root(), provider.query());
+//! let mut response = daemon.serve_request(&path, &[("Accept", "application/vnd.ipld.car")])?;
+//! let mut content = Vec::new();
+//! response.read_to_end(&mut content)?;
+//! assert_eq!(content, fixture.car());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use crate::car::reader::CarReader;
+use crate::car::{varint, writer};
+use crate::car::{CarError, Cid, DAG_PB, RAW, SHA2_256};
+use crate::multiaddr::Multiaddr;
+
+/// A DAG stored in memory, served by [`MockProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    root: Cid,
+    /// Sections of the CAR in DFS order, the root block first.
+    blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+impl Fixture {
+    /// A DAG consisting of a single raw block.
+    #[must_use]
+    pub fn raw_block(data: &[u8]) -> Self {
+        let root = raw_cid(data);
+        Fixture {
+            blocks: vec![(root.clone(), data.to_vec())],
+            root,
+        }
+    }
+
+    /// A `UnixFS` file split into raw leaves of `chunk_size` bytes, all linked from the root
+    /// DAG-PB node.
+    ///
+    /// The DAG has a single level, it's not balanced like the DAGs produced by Kubo. Keep the
+    /// number of chunks in hundreds, the root node grows by ~40 bytes per chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `chunk_size` is zero.
+    #[must_use]
+    pub fn unixfs_file(data: &[u8], chunk_size: usize) -> Self {
+        let leaves: Vec<(Cid, Vec<u8>)> = data
+            .chunks(chunk_size)
+            .map(|chunk| (raw_cid(chunk), chunk.to_vec()))
+            .collect();
+
+        // UnixFS Data message: Type = File, filesize, blocksizes
+        let mut unixfs = Vec::new();
+        put_varint_field(&mut unixfs, 1, 2);
+        put_varint_field(&mut unixfs, 3, data.len() as u64);
+        for (_, chunk) in &leaves {
+            put_varint_field(&mut unixfs, 4, chunk.len() as u64);
+        }
+
+        // PBNode: Links first, then Data (the canonical DAG-PB field order)
+        let mut node = Vec::new();
+        for (leaf, chunk) in &leaves {
+            let mut link = Vec::new();
+            put_bytes_field(&mut link, 1, &leaf.to_bytes());
+            put_bytes_field(&mut link, 2, b"");
+            put_varint_field(&mut link, 3, chunk.len() as u64);
+            put_bytes_field(&mut node, 2, &link);
+        }
+        put_bytes_field(&mut node, 1, &unixfs);
+
+        let root = cid(DAG_PB, &node);
+        let mut blocks = vec![(root.clone(), node)];
+        blocks.extend(leaves);
+        Fixture { root, blocks }
+    }
+
+    /// Build a fixture from blocks you encoded yourself. The blocks must be listed in the order
+    /// of a depth-first traversal starting at `root`, including duplicates.
+    #[must_use]
+    pub fn from_blocks(root: Cid, blocks: Vec<(Cid, Vec<u8>)>) -> Self {
+        Fixture { root, blocks }
+    }
+
+    /// Load a fixture from a CAR file with a single root, e.g. a response recorded from a real
+    /// provider. The blocks are served in the order they appear in the CAR.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the CAR cannot be parsed or does not have exactly one
+    /// root.
+    pub fn from_car(car: &[u8]) -> Result<Self, CarError> {
+        let mut reader = CarReader::new(car)?;
+        let [root] = reader.roots() else {
+            return Err(CarError::InvalidHeader(format!(
+                "expected a single root, found {}",
+                reader.roots().len()
+            )));
+        };
+        let root = root.clone();
+        let mut blocks = Vec::new();
+        while let Some(block) = reader.next_block()? {
+            blocks.push((block.cid, block.data));
+        }
+        Ok(Fixture { root, blocks })
+    }
+
+    #[must_use]
+    pub fn root(&self) -> &Cid {
+        &self.root
+    }
+
+    #[must_use]
+    pub fn blocks(&self) -> &[(Cid, Vec<u8>)] {
+        &self.blocks
+    }
+
+    /// The entire DAG encoded as `CARv1`, the same bytes Lassie returns for `dag-scope=all`.
+    #[must_use]
+    pub fn car(&self) -> Vec<u8> {
+        car(&self.root, &self.blocks)
+    }
+}
+
+/// The CID of a raw block, e.g. `bafkrei...`.
+#[must_use]
+pub fn raw_cid(data: &[u8]) -> Cid {
+    cid(RAW, data)
+}
+
+/// The `CIDv1` of a block encoded with `codec`, using a SHA2-256 multihash.
+#[must_use]
+pub fn cid(codec: u64, data: &[u8]) -> Cid {
+    Cid::new_v1(codec, SHA2_256, Sha256::digest(data).to_vec())
+}
+
+/// Encode the blocks as `CARv1` with a single root.
+#[must_use]
+pub fn car(root: &Cid, blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+    let mut out = writer::header(std::slice::from_ref(root));
+    for (cid, data) in blocks {
+        out.extend(writer::section(cid, data));
+    }
+    out
+}
+
+fn put_varint_field(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint::encode(field << 3, out);
+    varint::encode(value, out);
+}
+
+fn put_bytes_field(out: &mut Vec<u8>, field: u64, value: &[u8]) {
+    varint::encode((field << 3) | 2, out);
+    varint::encode(value.len() as u64, out);
+    out.extend(value);
+}
+
+/// A trustless HTTP gateway serving [`Fixture`]s on a random localhost port.
+///
+/// Requests for `/ipfs/{root}` return the fixture CAR, only the root block is returned for
+/// `dag-scope=block`. Unknown CIDs produce 404 responses. The provider is stopped when dropped.
+///
+/// The provider doubles as a delegated routing server listing itself as the provider of any CID,
+/// see [`MockProvider::routing_url`].
+#[derive(Debug)]
+pub struct MockProvider {
+    port: u16,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockProvider {
+    /// # Errors
+    ///
+    /// This function returns `Err` when the listening socket cannot be opened.
+    pub fn start(fixtures: Vec<Fixture>) -> io::Result<Self> {
+        Self::start_with_block_delay(fixtures, Duration::ZERO)
+    }
+
+    /// Start a provider pausing for `delay` before sending each block, e.g. to test timeouts or
+    /// to observe retrievals in progress.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Err` when the listening socket cannot be opened.
+    pub fn start_with_block_delay(fixtures: Vec<Fixture>, delay: Duration) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let fixtures = Arc::new(fixtures);
+
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            std::thread::Builder::new()
+                .name("lassie-mock-provider".to_string())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if stopped.load(Ordering::Acquire) {
+                            break;
+                        }
+                        let Ok(stream) = stream else { continue };
+                        let fixtures = Arc::clone(&fixtures);
+                        std::thread::spawn(move || {
+                            if let Err(err) = handle(stream, &fixtures, delay) {
+                                log::debug!("MockProvider cannot serve the request: {err}");
+                            }
+                        });
+                    }
+                })?
+        };
+
+        Ok(MockProvider {
+            port,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The multiaddr of the provider, e.g. `/ip4/127.0.0.1/tcp/41234/http`.
+    #[must_use]
+    pub fn multiaddr(&self) -> Multiaddr {
+        Multiaddr::from_trusted(format!("/ip4/127.0.0.1/tcp/{}/http", self.port))
+    }
+
+    /// The peer ID the provider announces in its delegated routing responses.
+    #[must_use]
+    pub fn peer_id(&self) -> &'static str {
+        MOCK_PROVIDER_PEER_ID
+    }
+
+    /// The URL to use as
+    /// [`DaemonConfig::delegated_routing_url`](crate::DaemonConfig::delegated_routing_url), so
+    /// that Lassie discovers the provider as a candidate instead of being directed to it.
+    #[must_use]
+    pub fn routing_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    /// The query string directing Lassie to this provider, append it to the request path.
+    #[must_use]
+    pub fn query(&self) -> String {
+        format!("protocols=http&providers={}", self.multiaddr())
+    }
+}
+
+impl Drop for MockProvider {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+        // Wake up the accept loop
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+const MOCK_PROVIDER_PEER_ID: &str = "12D3KooWQYhTNQdmr3ArTeUHRYzFg94BKyTkoWBDWez9kSCVe2Xo";
+
+fn handle(mut stream: TcpStream, fixtures: &[Fixture], delay: Duration) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the request headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let target = request_line.split(' ').nth(1).unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path.starts_with("/routing/v1/providers/") {
+        let port = stream.local_addr()?.port();
+        return write_routing_response(&mut stream, port);
+    }
+    let fixture = path
+        .strip_prefix("/ipfs/")
+        .and_then(|cid| fixtures.iter().find(|f| f.root.to_string() == cid));
+    let Some(fixture) = fixture else {
+        return stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+    };
+
+    let blocks = if query.split('&').any(|param| param == "dag-scope=block") {
+        &fixture.blocks[..1]
+    } else {
+        &fixture.blocks[..]
+    };
+    let mut sections = vec![writer::header(std::slice::from_ref(&fixture.root))];
+    sections.extend(blocks.iter().map(|(cid, data)| writer::section(cid, data)));
+    let body_len: usize = sections.iter().map(Vec::len).sum();
+
+    stream.write_all(
+        format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/vnd.ipld.car;version=1;order=dfs;dups=y\r\n\
+             Content-Length: {body_len}\r\n\
+             Connection: close\r\n\r\n",
+        )
+        .as_bytes(),
+    )?;
+    for (i, section) in sections.iter().enumerate() {
+        if i > 0 && !delay.is_zero() {
+            stream.flush()?;
+            std::thread::sleep(delay);
+        }
+        stream.write_all(section)?;
+    }
+    Ok(())
+}
+
+/// See <https://specs.ipfs.tech/routing/http-routing-v1/#get-routing-v1-providers-cid>
+fn write_routing_response(stream: &mut TcpStream, port: u16) -> io::Result<()> {
+    let body = format!(
+        r#"{{"Providers":[{{"Schema":"peer","ID":"{MOCK_PROVIDER_PEER_ID}","Addrs":["/ip4/127.0.0.1/tcp/{port}/http"],"Protocols":["transport-ipfs-gateway-http"]}}]}}"#
+    );
+    stream.write_all(
+        format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .as_bytes(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car;
+    use pretty_assertions::assert_eq;
+    use std::io::Read;
+
+    #[test]
+    fn fixtures_are_valid_cars() {
+        for fixture in [
+            Fixture::raw_block(b"hello"),
+            Fixture::unixfs_file(&[7u8; 1000], 300),
+        ] {
+            let report = car::verify(&fixture.car()[..], fixture.root()).unwrap();
+            assert!(report.is_complete(), "{:?}", report.issues);
+        }
+    }
+
+    #[test]
+    fn loads_fixture_from_car() {
+        let fixture = Fixture::unixfs_file(b"hello world", 4);
+        assert_eq!(Fixture::from_car(&fixture.car()).unwrap(), fixture);
+    }
+
+    #[test]
+    fn unixfs_file_has_a_leaf_per_chunk() {
+        let fixture = Fixture::unixfs_file(b"hello world", 4);
+        assert_eq!(fixture.blocks().len(), 4);
+        assert_eq!(fixture.blocks()[3].1, b"rld");
+    }
+
+    #[test]
+    fn provider_serves_fixtures() {
+        let fixture = Fixture::unixfs_file(b"hello world", 4);
+        let provider = MockProvider::start(vec![fixture.clone()]).unwrap();
+
+        let get = |target: &str| {
+            let mut stream = TcpStream::connect(("127.0.0.1", provider.port())).unwrap();
+            write!(stream, "GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            response
+        };
+
+        let response = get(&format!("/ipfs/{}?dag-scope=all", fixture.root()));
+        assert!(response.starts_with(b"HTTP/1.1 200 OK"));
+        assert!(response.ends_with(&fixture.car()));
+
+        let response = get(&format!("/ipfs/{}?dag-scope=block", fixture.root()));
+        assert!(response.ends_with(&car(fixture.root(), &fixture.blocks()[..1])));
+
+        let response = get("/ipfs/bafkqaaa");
+        assert!(response.starts_with(b"HTTP/1.1 404 Not Found"));
+
+        let response = String::from_utf8(get("/routing/v1/providers/bafkqaaa")).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        let addr = format!("/ip4/127.0.0.1/tcp/{}/http", provider.port());
+        assert!(response.contains(&addr), "{response}");
+        assert!(response.contains(provider.peer_id()), "{response}");
+    }
+}
@@ -0,0 +1,183 @@
+//! Integration with the [`tower`](https://docs.rs/tower) ecosystem.
+//!
+//! [`LassieService`] implements `tower::Service<http::Request<_>>` and serves trustless gateway
+//! requests via the in-process FFI bridge (see [`Daemon::serve_request`]), so you can mount
+//! Lassie inside an existing axum or hyper application:
+//!
+//! ```ignore
+//! let daemon = Arc::new(Daemon::start(DaemonConfig::default())?);
+//! let app = axum::Router::new()
+//!     .route("/", axum::routing::get(|| async { "hello" }))
+//!     .route_service("/ipfs/{*path}", lassie::tower::LassieService::new(daemon));
+//! ```
+//!
+//! Note that the service needs the full request path including the `/ipfs/` prefix, don't use
+//! `Router::nest_service` as it strips the prefix.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http::{Request, Response, StatusCode};
+use http_body::{Body, Frame};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::Daemon;
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A `tower::Service` forwarding `/ipfs/*` and `/ipns/*` requests to the Lassie daemon.
+#[derive(Clone)]
+pub struct LassieService {
+    daemon: Arc<Daemon>,
+}
+
+impl LassieService {
+    #[must_use]
+    pub fn new(daemon: Arc<Daemon>) -> Self {
+        LassieService { daemon }
+    }
+}
+
+impl std::fmt::Debug for LassieService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LassieService").finish_non_exhaustive()
+    }
+}
+
+type ResponseHead = (u16, Vec<(String, String)>);
+
+impl<B> tower_service::Service<Request<B>> for LassieService {
+    type Response = Response<LassieBody>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str().to_string())
+            .unwrap_or_default();
+        let headers: Vec<(String, String)> = req
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = value.to_str().ok()?;
+                Some((name.as_str().to_string(), value.to_string()))
+            })
+            .collect();
+        let daemon = Arc::clone(&self.daemon);
+
+        Box::pin(async move {
+            if !path.starts_with("/ipfs/") && !path.starts_with("/ipns/") {
+                return Ok(text_response(StatusCode::NOT_FOUND, "Not Found"));
+            }
+
+            let (head_tx, head_rx) = oneshot::channel();
+            let (body_tx, body_rx) = mpsc::channel(8);
+            tokio::task::spawn_blocking(move || {
+                serve_blocking(&daemon, &path, &headers, head_tx, &body_tx);
+            });
+
+            let (status, headers) = match head_rx.await {
+                Ok(Ok(head)) => head,
+                Ok(Err(err)) => {
+                    log::error!("Lassie cannot serve the request: {err}");
+                    return Ok(text_response(StatusCode::BAD_GATEWAY, &err.to_string()));
+                }
+                Err(_) => {
+                    return Ok(text_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Lassie request handler exited unexpectedly",
+                    ))
+                }
+            };
+
+            let mut builder = Response::builder().status(status);
+            for (name, value) in headers {
+                builder = builder.header(name, value);
+            }
+            Ok(builder
+                .body(LassieBody { rx: body_rx })
+                .unwrap_or_else(|err| {
+                    text_response(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string())
+                }))
+        })
+    }
+}
+
+fn serve_blocking(
+    daemon: &Daemon,
+    path: &str,
+    headers: &[(String, String)],
+    head_tx: oneshot::Sender<io::Result<ResponseHead>>,
+    body_tx: &mpsc::Sender<io::Result<Bytes>>,
+) {
+    let headers: Vec<(&str, &str)> = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let mut response = match daemon.serve_request(path, &headers) {
+        Ok(response) => response,
+        Err(err) => {
+            let _ = head_tx.send(Err(err));
+            return;
+        }
+    };
+
+    let head = (response.status(), response.headers().to_vec());
+    if head_tx.send(Ok(head)).is_err() {
+        // The client went away, dropping the response cancels the retrieval
+        return;
+    }
+
+    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        let chunk = match response.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => Ok(Bytes::copy_from_slice(&buf[..n])),
+            Err(err) => Err(err),
+        };
+        let failed = chunk.is_err();
+        if body_tx.blocking_send(chunk).is_err() || failed {
+            break;
+        }
+    }
+}
+
+pub(crate) fn text_response(status: StatusCode, msg: &str) -> Response<LassieBody> {
+    let (tx, rx) = mpsc::channel(1);
+    // The channel has enough capacity for one message, therefore `try_send` cannot fail
+    let _ = tx.try_send(Ok(Bytes::from(format!("{msg}\n"))));
+    let mut response = Response::new(LassieBody { rx });
+    *response.status_mut() = status;
+    response
+}
+
+/// The body of a response produced by [`LassieService`], streamed from the Lassie daemon.
+#[derive(Debug)]
+pub struct LassieBody {
+    rx: mpsc::Receiver<io::Result<Bytes>>,
+}
+
+impl Body for LassieBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.rx
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|res| res.map(Frame::data)))
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use super::{read_node, single_root, Node, UnixFsError};
+use crate::car::payload::write_file;
+use crate::car::{index, CarError};
+
+/// The progress of [`extract_to_dir_with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtractProgress<'a> {
+    /// The entry being extracted.
+    pub path: &'a Path,
+    /// The number of files, directories and symlinks created so far, including `path`.
+    pub entries: u64,
+    /// The number of file content bytes written so far.
+    pub bytes: u64,
+}
+
+/// Extract the `UnixFS` DAG stored in a CAR stream to `dest`, see
+/// [`extract_to_dir_with_progress`].
+///
+/// # Errors
+///
+/// See [`extract_to_dir_with_progress`].
+pub fn extract_to_dir<R: Read + Seek>(reader: &mut R, dest: &Path) -> Result<(), UnixFsError> {
+    extract_to_dir_with_progress(reader, dest, |_| {})
+}
+
+/// Extract the `UnixFS` DAG stored in a CAR stream to `dest` and report the progress to
+/// `progress`.
+///
+/// `dest` becomes the root of the CAR: a directory with all its entries (`dest` may already
+/// exist), a file or a symlink. Sharded directories are extracted like regular ones, the content
+/// of chunked files is streamed to disk block by block. `progress` is called after each entry is
+/// created and after each block of file content is written.
+///
+/// Existing entries are never overwritten and names are restricted to a single path component,
+/// so a malicious DAG cannot write outside of `dest`. Symlinks are created as they are, their
+/// targets are not checked.
+///
+/// # Errors
+///
+/// Returns `Err` when the CAR does not have a single root, when a block is missing, corrupted or
+/// not valid `UnixFS`, when an entry already exists or cannot be created, or when the stream
+/// cannot be read. The entries extracted before the error are left in place.
+pub fn extract_to_dir_with_progress<R, F>(
+    reader: &mut R,
+    dest: &Path,
+    mut progress: F,
+) -> Result<(), UnixFsError>
+where
+    R: Read + Seek,
+    F: FnMut(&ExtractProgress),
+{
+    reader.rewind().map_err(CarError::Io)?;
+    let index = index(&mut *reader)?;
+    let root = single_root(&index)?;
+
+    let mut entries = 0;
+    let mut bytes = 0;
+    // Depth-first traversal, a directory is created before its entries
+    let mut stack = vec![(dest.to_path_buf(), root)];
+    while let Some((path, cid)) = stack.pop() {
+        match read_node(&index, reader, &cid)? {
+            Node::File { .. } => {
+                let file = File::options()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .map_err(UnixFsError::Io)?;
+                entries += 1;
+                progress(&ExtractProgress {
+                    path: &path,
+                    entries,
+                    bytes,
+                });
+                let mut out = ProgressWriter {
+                    inner: BufWriter::new(file),
+                    path: &path,
+                    entries,
+                    bytes: &mut bytes,
+                    progress: &mut progress,
+                };
+                write_file(&index, reader, &cid, &mut out)?;
+                out.inner.flush().map_err(UnixFsError::Io)?;
+            }
+            Node::Directory(children) => {
+                // Only the destination itself may already exist
+                if path == dest {
+                    fs::create_dir_all(&path)
+                } else {
+                    fs::create_dir(&path)
+                }
+                .map_err(UnixFsError::Io)?;
+                entries += 1;
+                progress(&ExtractProgress {
+                    path: &path,
+                    entries,
+                    bytes,
+                });
+                stack.extend(
+                    children
+                        .into_iter()
+                        .rev()
+                        .map(|entry| (path.join(&entry.name), entry.cid)),
+                );
+            }
+            Node::Symlink(target) => {
+                symlink(Path::new(&target), &path).map_err(UnixFsError::Io)?;
+                entries += 1;
+                progress(&ExtractProgress {
+                    path: &path,
+                    entries,
+                    bytes,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+/// Reports the progress after each write of file content.
+struct ProgressWriter<'a, W, F> {
+    inner: W,
+    path: &'a Path,
+    entries: u64,
+    bytes: &'a mut u64,
+    progress: &'a mut F,
+}
+
+impl<W: Write, F: FnMut(&ExtractProgress)> Write for ProgressWriter<'_, W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        *self.bytes += written as u64;
+        (self.progress)(&ExtractProgress {
+            path: self.path,
+            entries: self.entries,
+            bytes: *self.bytes,
+        });
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::{DAG_PB, RAW};
+    use crate::unixfs::fixtures::{build_car, sha256_cid, unixfs_node};
+    use pretty_assertions::assert_eq;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_file(&dir);
+        dir
+    }
+
+    #[test]
+    fn extracts_directory_tree() {
+        let first = b"hello ".as_slice();
+        let second = b"world".as_slice();
+        let first_cid = sha256_cid(RAW, first);
+        let second_cid = sha256_cid(RAW, second);
+        // A file chunked into two blocks
+        let file = unixfs_node(2, b"", &[("", &first_cid), ("", &second_cid)]);
+        let file_cid = sha256_cid(DAG_PB, &file);
+        let link = unixfs_node(4, b"../hello.txt", &[]);
+        let link_cid = sha256_cid(DAG_PB, &link);
+        let shard = unixfs_node(5, b"", &[("0Alink", &link_cid)]);
+        let shard_cid = sha256_cid(DAG_PB, &shard);
+        let root_node = unixfs_node(1, b"", &[("docs", &shard_cid), ("hello.txt", &file_cid)]);
+        let root = sha256_cid(DAG_PB, &root_node);
+        let mut car = build_car(
+            &root,
+            &[
+                (&root, &root_node),
+                (&shard_cid, &shard),
+                (&link_cid, &link),
+                (&file_cid, &file),
+                (&first_cid, first),
+                (&second_cid, second),
+            ],
+        );
+
+        let dest = test_dir("rusty-lassie-extract-test");
+        let mut reported = Vec::new();
+        extract_to_dir_with_progress(&mut car, &dest, |progress| {
+            reported.push((
+                progress.path.strip_prefix(&dest).unwrap().to_path_buf(),
+                progress.entries,
+                progress.bytes,
+            ));
+        })
+        .unwrap();
+
+        assert_eq!(fs::read(dest.join("hello.txt")).unwrap(), b"hello world");
+        assert_eq!(
+            fs::read_link(dest.join("docs/link")).unwrap(),
+            Path::new("../hello.txt")
+        );
+        assert_eq!(
+            reported,
+            vec![
+                ("".into(), 1, 0),
+                ("docs".into(), 2, 0),
+                ("docs/link".into(), 3, 0),
+                ("hello.txt".into(), 4, 0),
+                ("hello.txt".into(), 4, 6),
+                ("hello.txt".into(), 4, 11),
+            ]
+        );
+
+        // Existing entries are not overwritten
+        assert!(matches!(
+            extract_to_dir(&mut car, &dest),
+            Err(UnixFsError::Io(err)) if err.kind() == io::ErrorKind::AlreadyExists
+        ));
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn rejects_drive_prefixed_entry_names() {
+        let file = sha256_cid(RAW, b"evil");
+        let root_node = unixfs_node(1, b"", &[("C:evil", &file)]);
+        let root = sha256_cid(DAG_PB, &root_node);
+        let mut car = build_car(&root, &[(&root, &root_node), (&file, b"evil")]);
+
+        let dest = test_dir("rusty-lassie-extract-unsafe-test");
+        assert!(matches!(
+            extract_to_dir(&mut car, &dest),
+            Err(UnixFsError::InvalidBlock { cid, .. }) if cid == root
+        ));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn extracts_single_file() {
+        let content = b"content".as_slice();
+        let root = sha256_cid(RAW, content);
+        let mut car = build_car(&root, &[(&root, content)]);
+
+        let dest = test_dir("rusty-lassie-extract-file-test");
+        extract_to_dir(&mut car, &dest).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), content);
+        fs::remove_file(&dest).unwrap();
+    }
+}
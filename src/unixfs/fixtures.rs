@@ -0,0 +1,44 @@
+//! Builders for the `UnixFS` DAGs used in the tests of this module.
+
+use std::io::Cursor;
+
+use sha2::{Digest, Sha256};
+
+use crate::car::{varint, writer, Cid, SHA2_256};
+
+pub(super) fn sha256_cid(codec: u64, data: &[u8]) -> Cid {
+    Cid::new_v1(codec, SHA2_256, Sha256::digest(data).to_vec())
+}
+
+fn put_bytes(field: u8, bytes: &[u8], out: &mut Vec<u8>) {
+    out.push(field);
+    varint::encode(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+/// A DAG-PB node with the given `UnixFS` type, inline data and named links.
+pub(super) fn unixfs_node(kind: u8, data: &[u8], links: &[(&str, &Cid)]) -> Vec<u8> {
+    let mut node = Vec::new();
+    for (name, cid) in links {
+        let mut link = Vec::new();
+        put_bytes(0x0a, &cid.to_bytes(), &mut link);
+        put_bytes(0x12, name.as_bytes(), &mut link);
+        put_bytes(0x12, &link, &mut node);
+    }
+    let mut unixfs = vec![0x08, kind];
+    put_bytes(0x12, data, &mut unixfs);
+    // HAMT shards use the default fanout
+    if kind == 5 {
+        unixfs.extend_from_slice(&[0x30, 0x80, 0x02]);
+    }
+    put_bytes(0x0a, &unixfs, &mut node);
+    node
+}
+
+pub(super) fn build_car(root: &Cid, blocks: &[(&Cid, &[u8])]) -> Cursor<Vec<u8>> {
+    let mut car = writer::header(std::slice::from_ref(root));
+    for (cid, data) in blocks {
+        car.extend(writer::section(cid, data));
+    }
+    Cursor::new(car)
+}
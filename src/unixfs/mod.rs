@@ -0,0 +1,222 @@
+//! Utilities for converting the `UnixFS` DAGs retrieved by Lassie into regular files.
+//!
+//! The functions read a CAR stream, e.g. the body of a Lassie response saved to disk, and check
+//! every block against its CID. Use [`to_tar`] to convert a retrieved file or directory tree into
+//! a tar archive, e.g. to let users of an HTTP frontend download a directory in one go:
+//!
+//! ```no_run
+//! let mut car = std::fs::File::open("retrieval.car")?;
+//! let mut archive = std::fs::File::create("retrieval.tar")?;
+//! lassie::unixfs::to_tar(&mut car, &mut archive)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Use [`extract_to_dir`] to write the tree to disk instead, e.g. to mirror or back up content.
+
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path};
+
+use crate::car::codec::{self, UnixFsKind};
+use crate::car::directory::{self, DirectoryError};
+use crate::car::payload::PayloadError;
+use crate::car::{check_hash, CarError, CarIndex, Cid, DirectoryEntry, DAG_PB, IDENTITY, RAW};
+
+mod extract;
+#[cfg(test)]
+mod fixtures;
+mod tar;
+
+pub use extract::{extract_to_dir, extract_to_dir_with_progress, ExtractProgress};
+pub use tar::to_tar;
+
+/// An error returned when a `UnixFS` DAG cannot be converted.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum UnixFsError {
+    Car(CarError),
+    /// The CAR header does not list exactly one root.
+    InvalidRoots(usize),
+    /// The CAR does not contain a block of the DAG.
+    MissingBlock(Cid),
+    /// The block does not match its CID.
+    HashMismatch(Cid),
+    /// The block cannot be decoded as `UnixFS` or contains an entry that cannot be converted,
+    /// e.g. a name with a `/`.
+    InvalidBlock {
+        cid: Cid,
+        reason: String,
+    },
+    /// The output cannot be written.
+    Io(io::Error),
+}
+
+impl Display for UnixFsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnixFsError::Car(err) => write!(f, "{err}"),
+            UnixFsError::InvalidRoots(count) => {
+                write!(f, "expected a CAR with a single root, found {count} roots")
+            }
+            UnixFsError::MissingBlock(cid) => write!(f, "block {cid} is missing"),
+            UnixFsError::HashMismatch(cid) => write!(f, "block {cid} does not match its CID"),
+            UnixFsError::InvalidBlock { cid, reason } => {
+                write!(f, "block {cid} is not valid UnixFS: {reason}")
+            }
+            UnixFsError::Io(err) => write!(f, "cannot write the output: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for UnixFsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnixFsError::Car(err) => Some(err),
+            UnixFsError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CarError> for UnixFsError {
+    fn from(err: CarError) -> Self {
+        UnixFsError::Car(err)
+    }
+}
+
+impl From<PayloadError> for UnixFsError {
+    fn from(err: PayloadError) -> Self {
+        match err {
+            PayloadError::Car(err) => UnixFsError::Car(err),
+            PayloadError::MissingBlock(cid) => UnixFsError::MissingBlock(cid),
+            PayloadError::HashMismatch(cid) => UnixFsError::HashMismatch(cid),
+            PayloadError::NotAFile(cid) => UnixFsError::InvalidBlock {
+                cid,
+                reason: "a file links to a block that is not part of a file".to_string(),
+            },
+            PayloadError::InvalidBlock { cid, reason } => UnixFsError::InvalidBlock { cid, reason },
+            PayloadError::Io(err) => UnixFsError::Io(err),
+        }
+    }
+}
+
+impl From<DirectoryError> for UnixFsError {
+    fn from(err: DirectoryError) -> Self {
+        match err {
+            DirectoryError::Car(err) => UnixFsError::Car(err),
+            DirectoryError::MissingBlock(cid) => UnixFsError::MissingBlock(cid),
+            DirectoryError::HashMismatch(cid) => UnixFsError::HashMismatch(cid),
+            DirectoryError::NotADirectory(cid) => UnixFsError::InvalidBlock {
+                cid,
+                reason: "a directory links to a HAMT shard that is not a shard".to_string(),
+            },
+            DirectoryError::InvalidBlock { cid, reason } => {
+                UnixFsError::InvalidBlock { cid, reason }
+            }
+        }
+    }
+}
+
+/// A node of a `UnixFS` DAG, without its content.
+enum Node {
+    File { size: u64 },
+    Directory(Vec<DirectoryEntry>),
+    Symlink(String),
+}
+
+/// The single root of the indexed CAR.
+fn single_root(index: &CarIndex) -> Result<Cid, UnixFsError> {
+    match index.roots() {
+        [root] => Ok(root.clone()),
+        roots => Err(UnixFsError::InvalidRoots(roots.len())),
+    }
+}
+
+/// Read the block `cid` and decode the `UnixFS` node it stores.
+fn read_node<R: Read + Seek>(
+    index: &CarIndex,
+    reader: &mut R,
+    cid: &Cid,
+) -> Result<Node, UnixFsError> {
+    let data = if cid.hash_code() == IDENTITY {
+        cid.digest().to_vec()
+    } else {
+        index
+            .read_block(reader, cid)?
+            .ok_or_else(|| UnixFsError::MissingBlock(cid.clone()))?
+    };
+    if check_hash(cid, &data).is_some() {
+        return Err(UnixFsError::HashMismatch(cid.clone()));
+    }
+
+    let invalid = |reason: String| UnixFsError::InvalidBlock {
+        cid: cid.clone(),
+        reason,
+    };
+    match cid.codec() {
+        RAW => Ok(Node::File {
+            size: data.len() as u64,
+        }),
+        DAG_PB => {
+            let node = codec::decode_dag_pb(&data).map_err(invalid)?;
+            let unixfs =
+                codec::decode_unixfs(node.data.as_deref().unwrap_or_default()).map_err(invalid)?;
+            match unixfs.kind {
+                UnixFsKind::File | UnixFsKind::Raw => {
+                    let size = match unixfs.file_size {
+                        Some(size) => size,
+                        // Count the content when the root does not record the size
+                        None => {
+                            crate::car::payload::write_file(index, reader, cid, &mut io::sink())?
+                        }
+                    };
+                    Ok(Node::File { size })
+                }
+                UnixFsKind::Directory | UnixFsKind::HamtShard => {
+                    let entries = directory::directory_entries(index, reader, cid)?;
+                    if let Some(entry) = entries.iter().find(|entry| !is_safe_name(&entry.name)) {
+                        return Err(invalid(format!("unsafe entry name {:?}", entry.name)));
+                    }
+                    Ok(Node::Directory(entries))
+                }
+                UnixFsKind::Symlink => {
+                    let target = String::from_utf8(unixfs.data.unwrap_or_default())
+                        .map_err(|_| invalid("symlink target is not UTF-8".to_string()))?;
+                    Ok(Node::Symlink(target))
+                }
+                UnixFsKind::Metadata => Err(invalid("metadata nodes are not supported".into())),
+            }
+        }
+        codec => Err(invalid(format!("unsupported codec 0x{codec:x}"))),
+    }
+}
+
+/// Entry names must be a single path component, otherwise a malicious directory could write
+/// outside of the destination. On Windows, a name with a drive prefix like `C:evil` replaces the
+/// base path it's joined to, and reserved device names like `NUL` don't refer to files.
+fn is_safe_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !name.contains(['/', '\\', ':', '\0'])
+        && !is_reserved_windows_name(name)
+}
+
+/// Windows opens a device for these names, with or without an extension, e.g. `CON.txt`.
+fn is_reserved_windows_name(name: &str) -> bool {
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(' ');
+    let stem = stem.to_ascii_uppercase();
+    matches!(
+        stem.as_bytes(),
+        b"CON"
+            | b"PRN"
+            | b"AUX"
+            | b"NUL"
+            | [b'C', b'O', b'M', b'1'..=b'9']
+            | [b'L', b'P', b'T', b'1'..=b'9']
+    )
+}
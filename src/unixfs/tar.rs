@@ -0,0 +1,253 @@
+use std::io::{Read, Seek, Write};
+
+use super::{read_node, single_root, Node, UnixFsError};
+use crate::car::payload::write_file;
+use crate::car::{index, CarError};
+
+const BLOCK: usize = 512;
+/// The largest size the octal `size` field of a ustar header can store.
+const MAX_USTAR_SIZE: u64 = 0o777_7777_7777;
+
+/// Convert the `UnixFS` DAG stored in a CAR stream into a tar archive written to `out`.
+///
+/// The archive contains the root of the CAR named after its CID: a single file, or a directory
+/// with all its entries, e.g. `bafy.../docs/index.html`. Symlinks are stored as symlinks. File
+/// content is streamed from the CAR to `out` without buffering, only the index of the blocks is
+/// kept in memory.
+///
+/// # Errors
+///
+/// Returns `Err` when the CAR does not have a single root, when a block is missing, corrupted or
+/// not valid `UnixFS`, or when the stream cannot be read or the archive cannot be written.
+pub fn to_tar<R: Read + Seek, W: Write>(reader: &mut R, out: &mut W) -> Result<(), UnixFsError> {
+    reader.rewind().map_err(CarError::Io)?;
+    let index = index(&mut *reader)?;
+    let root = single_root(&index)?;
+
+    // Depth-first traversal, a directory precedes its entries
+    let mut stack = vec![(root.to_string(), root)];
+    while let Some((path, cid)) = stack.pop() {
+        match read_node(&index, reader, &cid)? {
+            Node::File { size } => {
+                write_header(out, &path, b'0', 0o644, size, "")?;
+                let written = write_file(&index, reader, &cid, out)?;
+                if written != size {
+                    return Err(UnixFsError::InvalidBlock {
+                        cid,
+                        reason: format!("the file has {written} bytes, {size} expected"),
+                    });
+                }
+                write_padding(out, size)?;
+            }
+            Node::Directory(entries) => {
+                write_header(out, &format!("{path}/"), b'5', 0o755, 0, "")?;
+                stack.extend(
+                    entries
+                        .into_iter()
+                        .rev()
+                        .map(|entry| (format!("{path}/{}", entry.name), entry.cid)),
+                );
+            }
+            Node::Symlink(target) => write_header(out, &path, b'2', 0o777, 0, &target)?,
+        }
+    }
+    // The end of the archive is marked by two empty blocks
+    out.write_all(&[0; 2 * BLOCK]).map_err(UnixFsError::Io)?;
+    Ok(())
+}
+
+/// Write the ustar header of an entry, preceded by a PAX header when the path, the link target or
+/// the size do not fit into the ustar fields.
+fn write_header<W: Write>(
+    out: &mut W,
+    path: &str,
+    kind: u8,
+    mode: u32,
+    size: u64,
+    link: &str,
+) -> Result<(), UnixFsError> {
+    let mut records = Vec::new();
+    if path.len() > 100 {
+        pax_record(&mut records, "path", path);
+    }
+    if link.len() > 100 {
+        pax_record(&mut records, "linkpath", link);
+    }
+    if size > MAX_USTAR_SIZE {
+        pax_record(&mut records, "size", &size.to_string());
+    }
+    if !records.is_empty() {
+        let len = records.len() as u64;
+        out.write_all(&ustar_header("PaxHeader", b'x', 0o644, len, ""))
+            .map_err(UnixFsError::Io)?;
+        out.write_all(&records).map_err(UnixFsError::Io)?;
+        write_padding(out, len)?;
+    }
+    out.write_all(&ustar_header(path, kind, mode, size, link))
+        .map_err(UnixFsError::Io)
+}
+
+fn ustar_header(path: &str, kind: u8, mode: u32, size: u64, link: &str) -> [u8; BLOCK] {
+    let mut header = [0; BLOCK];
+    // Fields too long for the header are stored in the PAX header
+    put_str(&mut header[0..100], path);
+    put_octal(&mut header[100..108], mode.into());
+    put_octal(&mut header[108..116], 0);
+    put_octal(&mut header[116..124], 0);
+    put_octal(&mut header[124..136], size.min(MAX_USTAR_SIZE));
+    put_octal(&mut header[136..148], 0);
+    header[156] = kind;
+    put_str(&mut header[157..257], link);
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with the checksum field filled with spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    put_octal(&mut header[148..155], checksum.into());
+    header
+}
+
+fn put_str(field: &mut [u8], value: &str) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Zero-padded octal number terminated by a NUL byte.
+fn put_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let formatted = format!("{value:0digits$o}");
+    field[..digits].copy_from_slice(&formatted.as_bytes()[formatted.len() - digits..]);
+    field[digits] = 0;
+}
+
+/// A PAX record `<len> <key>=<value>\n`, where `<len>` includes its own digits.
+fn pax_record(records: &mut Vec<u8>, key: &str, value: &str) {
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + 1;
+    while len.to_string().len() + rest > len {
+        len += 1;
+    }
+    records.extend_from_slice(format!("{len} {key}={value}\n").as_bytes());
+}
+
+fn write_padding<W: Write>(out: &mut W, len: u64) -> Result<(), UnixFsError> {
+    // The remainder is smaller than a block, the cast cannot truncate
+    #[allow(clippy::cast_possible_truncation)]
+    let padding = (BLOCK - (len % BLOCK as u64) as usize) % BLOCK;
+    out.write_all(&[0; BLOCK][..padding])
+        .map_err(UnixFsError::Io)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::car::{DAG_PB, RAW};
+    use crate::unixfs::fixtures::{build_car, sha256_cid, unixfs_node};
+    use pretty_assertions::assert_eq;
+    use std::io;
+
+    /// The `(path, type, size, link target)` of the entries of an archive, the content of files
+    /// is appended to `content`.
+    fn read_archive(archive: &[u8], content: &mut Vec<u8>) -> Vec<(String, u8, usize, String)> {
+        let field = |header: &[u8]| {
+            let end = header.iter().position(|&b| b == 0).unwrap_or(header.len());
+            String::from_utf8(header[..end].to_vec()).unwrap()
+        };
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while archive[offset..offset + BLOCK] != [0; BLOCK] {
+            let header = &archive[offset..offset + BLOCK];
+            let mut sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+            sum -= header[148..156].iter().map(|&b| u32::from(b)).sum::<u32>();
+            sum += 8 * u32::from(b' ');
+            let checksum = u32::from_str_radix(&field(&header[148..155]), 8).unwrap();
+            assert_eq!(checksum, sum, "checksum of {}", field(&header[0..100]));
+
+            let size = usize::from_str_radix(&field(&header[124..135]), 8).unwrap();
+            let start = offset + BLOCK;
+            content.extend_from_slice(&archive[start..start + size]);
+            entries.push((
+                field(&header[0..100]),
+                header[156],
+                size,
+                field(&header[157..257]),
+            ));
+            offset = start + size.div_ceil(BLOCK) * BLOCK;
+        }
+        assert_eq!(archive.len(), offset + 2 * BLOCK);
+        entries
+    }
+
+    #[test]
+    fn archives_directory_tree() {
+        let hello = b"hello".as_slice();
+        let hello_cid = sha256_cid(RAW, hello);
+        let link = unixfs_node(4, b"../hello.txt", &[]);
+        let link_cid = sha256_cid(DAG_PB, &link);
+        let docs = unixfs_node(1, b"", &[("link", &link_cid)]);
+        let docs_cid = sha256_cid(DAG_PB, &docs);
+        let root_node = unixfs_node(1, b"", &[("docs", &docs_cid), ("hello.txt", &hello_cid)]);
+        let root = sha256_cid(DAG_PB, &root_node);
+
+        let mut car = build_car(
+            &root,
+            &[
+                (&root, &root_node),
+                (&docs_cid, &docs),
+                (&link_cid, &link),
+                (&hello_cid, hello),
+            ],
+        );
+        let mut archive = Vec::new();
+        to_tar(&mut car, &mut archive).unwrap();
+
+        let mut content = Vec::new();
+        assert_eq!(
+            read_archive(&archive, &mut content),
+            vec![
+                (format!("{root}/"), b'5', 0, String::new()),
+                (format!("{root}/docs/"), b'5', 0, String::new()),
+                (format!("{root}/docs/link"), b'2', 0, "../hello.txt".into()),
+                (format!("{root}/hello.txt"), b'0', 5, String::new()),
+            ]
+        );
+        assert_eq!(content, b"hello");
+    }
+
+    #[test]
+    fn stores_long_paths_in_pax_headers() {
+        let name = "a".repeat(120);
+        let file = sha256_cid(RAW, b"x");
+        let root_node = unixfs_node(1, b"", &[(&name, &file)]);
+        let root = sha256_cid(DAG_PB, &root_node);
+        let mut car = build_car(&root, &[(&root, &root_node), (&file, b"x")]);
+        let mut archive = Vec::new();
+        to_tar(&mut car, &mut archive).unwrap();
+
+        let mut content = Vec::new();
+        let entries = read_archive(&archive, &mut content);
+        assert_eq!(entries[1].1, b'x');
+        let path = format!("{root}/{name}");
+        let record = String::from_utf8(content[..entries[1].2].to_vec()).unwrap();
+        assert_eq!(record, format!("{} path={path}\n", record.len()));
+        assert_eq!(entries[2].1, b'0');
+    }
+
+    #[test]
+    fn rejects_unsafe_entry_names() {
+        let file = sha256_cid(RAW, b"evil");
+        for name in ["../evil", "C:evil", "C:..", "..", "NUL", "com1.txt"] {
+            let root_node = unixfs_node(1, b"", &[(name, &file)]);
+            let root = sha256_cid(DAG_PB, &root_node);
+            let mut car = build_car(&root, &[(&root, &root_node), (&file, b"evil")]);
+            assert!(
+                matches!(
+                    to_tar(&mut car, &mut io::sink()),
+                    Err(UnixFsError::InvalidBlock { cid, .. }) if cid == root
+                ),
+                "{name}"
+            );
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::supervisor::Supervisor;
+use crate::{FailureEvent, LassieResult};
+
+go_lassie! {
+    fn PingDaemon() -> LassieResult;
+}
+
+/// Configuration of the watchdog, see [`DaemonConfig::watchdog`](crate::DaemonConfig::watchdog).
+///
+/// Every `interval`, the watchdog sends a `GET /healthz` request to the HTTP listener, or pings
+/// the Go side via FFI when the listener is disabled. After `failures` probes in a row did not get
+/// an answer within `timeout`, the daemon is reported as unhealthy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct WatchdogConfig {
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::config_de::duration")
+    )]
+    pub interval: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::config_de::duration")
+    )]
+    pub timeout: Duration,
+    pub failures: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+            failures: 3,
+        }
+    }
+}
+
+/// The health of the daemon, see [`Daemon::health`](crate::Daemon::health).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Health {
+    /// The daemon is running and, if the watchdog is enabled, answered its last probe.
+    Healthy,
+    /// The daemon is running but did not answer the watchdog probes. The value describes the last
+    /// failed probe.
+    Unhealthy(String),
+    /// The daemon stopped serving requests, see [`Daemon::is_running`](crate::Daemon::is_running).
+    Stopped,
+}
+
+/// What the watchdog probes.
+pub(crate) enum Probe {
+    /// The HTTP listener at this address
+    Listener(SocketAddr),
+    /// The Go side via FFI, the receiver is set while a ping is still waiting for an answer
+    Ffi(Option<mpsc::Receiver<Option<String>>>),
+}
+
+impl Probe {
+    fn run(&mut self, timeout: Duration) -> Result<(), String> {
+        match self {
+            Probe::Listener(addr) => probe_listener(*addr, timeout)
+                .map_err(|err| format!("HTTP listener on {addr} is not responding: {err}")),
+            Probe::Ffi(pending) => {
+                // Don't pile up threads while Go is wedged, wait for the ping already sent
+                let answer = pending.take().unwrap_or_else(|| {
+                    let (tx, rx) = mpsc::channel();
+                    std::thread::spawn(move || {
+                        // SAFETY:
+                        // We can call this FFI function as it does not have any special safety
+                        // requirements.
+                        let result = unsafe { PingDaemon() };
+                        let _ = tx.send(result.error());
+                    });
+                    rx
+                });
+                match answer.recv_timeout(timeout) {
+                    Ok(None) => Ok(()),
+                    Ok(Some(msg)) => Err(msg),
+                    Err(RecvTimeoutError::Timeout) => {
+                        *pending = Some(answer);
+                        Err(format!("Lassie did not answer a ping within {timeout:?}"))
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        Err("Lassie ping thread panicked".to_string())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Send a health check request, every step must finish within `timeout`.
+fn probe_listener(addr: SocketAddr, timeout: Duration) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(format!("GET /healthz HTTP/1.0\r\nHost: {addr}\r\n\r\n").as_bytes())?;
+    let mut status_line = [0u8; 12];
+    stream.read_exact(&mut status_line)?;
+    // Any HTTP response shows that the server is serving, e.g. `403` from allowed_client_ips
+    if status_line.starts_with(b"HTTP/1.") {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not an HTTP response",
+        ))
+    }
+}
+
+/// The background thread enforcing [`WatchdogConfig`]. Dropping the task stops the thread.
+pub(crate) struct WatchdogTask {
+    unhealthy: Arc<Mutex<Option<String>>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchdogTask {
+    pub(crate) fn start(
+        config: &WatchdogConfig,
+        mut probe: Probe,
+        running: Arc<AtomicBool>,
+        supervisor: Arc<Supervisor>,
+    ) -> Self {
+        let WatchdogConfig {
+            interval,
+            timeout,
+            failures,
+        } = *config;
+        let unhealthy = Arc::new(Mutex::new(None));
+        let thread_unhealthy = Arc::clone(&unhealthy);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut failed = 0;
+            // The loop ends when the task is dropped or the daemon stops
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if !running.load(Ordering::Acquire) {
+                    break;
+                }
+                match probe.run(timeout) {
+                    Ok(()) => {
+                        failed = 0;
+                        if lock(&thread_unhealthy).take().is_some() {
+                            supervisor.emit(&FailureEvent::Recovered);
+                        }
+                    }
+                    Err(message) => {
+                        failed += 1;
+                        log::debug!("Lassie watchdog probe {failed} failed: {message}");
+                        if failed < failures {
+                            continue;
+                        }
+                        let was_healthy =
+                            lock(&thread_unhealthy).replace(message.clone()).is_none();
+                        if was_healthy {
+                            supervisor.emit(&FailureEvent::Unresponsive { message });
+                        }
+                    }
+                }
+            }
+        });
+        WatchdogTask {
+            unhealthy,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// The reason of the last failed probe while the daemon is unhealthy.
+    pub(crate) fn unhealthy(&self) -> Option<String> {
+        lock(&self.unhealthy).clone()
+    }
+}
+
+fn lock(unhealthy: &Mutex<Option<String>>) -> MutexGuard<'_, Option<String>> {
+    unhealthy.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Drop for WatchdogTask {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the thread, a probe in progress finishes within its timeout
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Lassie watchdog panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[test]
+    fn probes_http_listener() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 16];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(&request, b"GET /healthz HTT");
+            stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+            // Accept a connection and never answer, like a wedged server
+            listener.accept().unwrap()
+        });
+
+        probe_listener(addr, Duration::from_secs(5)).unwrap();
+        let err = probe_listener(addr, Duration::from_millis(100)).unwrap_err();
+        assert!(
+            matches!(
+                err.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ),
+            "unexpected error {err:?}"
+        );
+        drop(server.join().unwrap());
+    }
+}
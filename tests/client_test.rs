@@ -0,0 +1,378 @@
+#![cfg(feature = "client")]
+
+use pretty_assertions::assert_eq;
+use std::io::{Read, Write};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use lassie::{Client, Daemon, DaemonConfig, RetrievalError, RetrievalRequest, RetryPolicy};
+
+// Rust runs tests in parallel. Since Lassie Daemon is a singleton,
+// we must synchronise the tests to ensure they run sequentially
+static TEST_GUARD: Mutex<()> = Mutex::new(());
+
+const TEST_CID: &str = "bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq";
+const TEST_PROVIDER: &str = "/dns4/frisbii.fly.dev/https";
+
+#[test]
+fn client_fetches_cid() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let client = Client::new(&daemon);
+
+    let request = RetrievalRequest::new(TEST_CID)
+        .protocols(["http"])
+        .providers([TEST_PROVIDER.parse().unwrap()]);
+    let response = client.fetch(&request).expect("cannot fetch CID");
+    assert_eq!(
+        response.content_type(),
+        Some("application/vnd.ipld.car;version=1;order=dfs;dups=y")
+    );
+
+    let content = response.read_to_end().expect("cannot read response body");
+    assert_eq!(
+        content,
+        include_bytes!("testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car")
+    );
+}
+
+#[test]
+fn client_applies_block_limit() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let client = Client::new(&daemon);
+
+    let request = RetrievalRequest::new(TEST_CID)
+        .protocols(["http"])
+        .providers([TEST_PROVIDER.parse().unwrap()])
+        .block_limit(1);
+    let content = client
+        .fetch(&request)
+        .and_then(lassie::RetrievalResponse::read_to_end)
+        .expect("cannot fetch CID");
+    assert_eq!(
+        content,
+        include_bytes!("testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car")
+    );
+}
+
+#[test]
+fn client_reports_bad_request() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let client = Client::new(&daemon);
+
+    match client.fetch(&RetrievalRequest::new("not-a-cid")) {
+        Ok(_) => panic!("request for an invalid CID should have failed"),
+        Err(err) => assert!(
+            matches!(err, RetrievalError::BadRequest(_)),
+            "unexpected error: {err:?}"
+        ),
+    }
+}
+
+#[test]
+fn client_talks_to_daemon_url() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig {
+        access_token: Some("secret".to_string()),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let base_url = format!("{}/", daemon.handle().base_url());
+
+    let request = RetrievalRequest::new("not-a-cid");
+    let err = Client::from_url(&base_url, None)
+        .fetch(&request)
+        .err()
+        .expect("request without the access token should have failed");
+    assert!(
+        matches!(err, RetrievalError::Unauthorized),
+        "unexpected error: {err:?}"
+    );
+
+    let err = Client::from_url(&base_url, Some("secret".to_string()))
+        .fetch(&request)
+        .err()
+        .expect("request for an invalid CID should have failed");
+    assert!(
+        matches!(err, RetrievalError::BadRequest(_)),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[test]
+fn client_enforces_request_timeout() {
+    // A daemon that accepts the connection and never answers
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || listener.accept().unwrap());
+
+    let started = Instant::now();
+    let err = Client::from_url(&base_url, None)
+        .fetch(&RetrievalRequest::new(TEST_CID).timeout(Duration::from_millis(200)))
+        .err()
+        .expect("request to a stuck daemon should have failed");
+    assert!(
+        matches!(err, RetrievalError::Timeout(_)),
+        "unexpected error: {err:?}"
+    );
+    assert!(started.elapsed() < Duration::from_secs(10));
+    drop(server.join().unwrap());
+}
+
+#[test]
+fn client_retries_transient_failures() {
+    // A daemon failing the first retrieval with 502 and serving the second one
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let responses = [
+            "HTTP/1.1 502 Bad Gateway\r\nContent-Length: 21\r\nConnection: close\r\n\r\nall retrievals failed",
+            "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.ipld.car;version=1\r\nContent-Length: 3\r\nConnection: close\r\n\r\ncar",
+        ];
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    };
+    let outcome = Client::from_url(&base_url, None)
+        .fetch_with_retry(&RetrievalRequest::new(TEST_CID), &policy);
+    server.join().unwrap();
+
+    assert_eq!(outcome.result, Ok(b"car".to_vec()));
+    let errors: Vec<_> = outcome.attempts.into_iter().map(|a| a.error).collect();
+    assert_eq!(
+        errors,
+        vec![
+            Some(RetrievalError::ProviderFailure {
+                msg: "all retrievals failed".to_string()
+            }),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn client_classifies_errors_by_error_code() {
+    // The message mentions a timeout, the error code tells what happened
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 502 Bad Gateway\r\nX-Lassie-Error: no-candidates\r\nContent-Length: 28\r\nConnection: close\r\n\r\nindexer lookup timeout: none")
+            .unwrap();
+    });
+
+    let result = Client::from_url(&base_url, None).fetch(&RetrievalRequest::new(TEST_CID));
+    server.join().unwrap();
+    assert_eq!(result.err(), Some(RetrievalError::NoCandidates));
+}
+
+#[test]
+fn client_sends_traceparent() {
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 1024];
+        let len = stream.read(&mut request).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        String::from_utf8_lossy(&request[..len]).to_lowercase()
+    });
+
+    let request = RetrievalRequest::new(TEST_CID).traceparent(TRACEPARENT);
+    let _ = Client::from_url(&base_url, None).fetch(&request);
+    let request = server.join().unwrap();
+    assert!(
+        request.contains(&format!("traceparent: {TRACEPARENT}\r\n")),
+        "{request}"
+    );
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn client_fetches_many_cids() {
+    use lassie::testing::{Fixture, MockProvider};
+
+    let _lock = setup_test_env();
+
+    let fixtures: Vec<_> = (0..5u8).map(|i| Fixture::raw_block(&[i; 16])).collect();
+    let provider = MockProvider::start(fixtures.clone()).expect("cannot start the provider");
+    let daemon = Daemon::start(DaemonConfig {
+        disable_ipni: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let client = Client::new(&daemon);
+
+    let requests = fixtures.iter().map(|fixture| {
+        RetrievalRequest::new(fixture.root().to_string())
+            .protocols(["http"])
+            .providers([provider.multiaddr()])
+    });
+    let mut results: Vec<_> = client.fetch_many(requests, 2).collect();
+    results.sort_by_key(|(index, _)| *index);
+
+    assert_eq!(results.len(), fixtures.len());
+    for ((index, result), fixture) in results.into_iter().zip(&fixtures) {
+        let content = result.unwrap_or_else(|err| panic!("request {index} failed: {err}"));
+        assert_eq!(content, fixture.car());
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn fetch_pool_runs_and_cancels_jobs() {
+    use lassie::testing::{Fixture, MockProvider};
+    use lassie::{FetchPool, JobStatus};
+
+    let _lock = setup_test_env();
+
+    let fixtures: Vec<_> = (0..3u8).map(|i| Fixture::raw_block(&[i; 16])).collect();
+    let provider = MockProvider::start(fixtures.clone()).expect("cannot start the provider");
+    let daemon = Daemon::start(DaemonConfig {
+        disable_ipni: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let request = |fixture: &Fixture| {
+        RetrievalRequest::new(fixture.root().to_string())
+            .protocols(["http"])
+            .providers([provider.multiaddr()])
+    };
+
+    // Without workers running in parallel, the last job stays queued long enough to cancel it
+    let pool = FetchPool::new(&daemon.handle(), 1);
+    let first = pool.submit(request(&fixtures[0]), 0);
+    let second = pool.submit(request(&fixtures[1]), 10);
+    let cancelled = pool.submit(request(&fixtures[2]), -10);
+    assert!(pool.cancel(cancelled));
+    assert_eq!(pool.status(cancelled), Some(JobStatus::Cancelled));
+
+    let content = pool
+        .wait(second)
+        .expect("unknown job")
+        .expect("retrieval failed");
+    assert_eq!(content, fixtures[1].car());
+    let content = pool
+        .wait(first)
+        .expect("unknown job")
+        .expect("retrieval failed");
+    assert_eq!(content, fixtures[0].car());
+    assert_eq!(pool.wait(cancelled), None);
+    assert_eq!(pool.status(first), None);
+}
+
+#[cfg(all(feature = "testing", feature = "car"))]
+#[test]
+fn client_resumes_interrupted_download() {
+    use lassie::testing::{Fixture, MockProvider};
+
+    let _lock = setup_test_env();
+
+    // No two chunks are the same, so that each leaf is a distinct block
+    let content: Vec<u8> = (0..=250u8).cycle().take(64 * 1024).collect();
+    let fixture = Fixture::unixfs_file(&content, 1024);
+    let provider = MockProvider::start(vec![fixture.clone()]).expect("cannot start the provider");
+    let daemon = Daemon::start(DaemonConfig {
+        disable_ipni: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let client = Client::new(&daemon);
+    let request = RetrievalRequest::new(fixture.root().to_string())
+        .protocols(["http"])
+        .providers([provider.multiaddr()]);
+
+    let path = std::env::temp_dir().join("rusty-lassie-download-test.car");
+    let _ = std::fs::remove_file(&path);
+    let report = client
+        .download_to(&request, &path)
+        .expect("cannot download");
+    assert_eq!(report.resumed_blocks, 0);
+    let complete = std::fs::read(&path).unwrap();
+    assert_eq!(report.bytes, complete.len() as u64);
+
+    // Simulate an interrupted download by cutting the file in the middle of a block
+    std::fs::write(&path, &complete[..complete.len() / 2]).unwrap();
+    let resumed = client
+        .download_to(&request, &path)
+        .expect("cannot resume the download");
+    assert!(resumed.resumed_blocks > 0, "report: {resumed:?}");
+    assert_eq!(resumed.blocks, report.blocks);
+
+    let file = std::fs::File::open(&path).unwrap();
+    let verified = lassie::car::verify(std::io::BufReader::new(file), fixture.root())
+        .expect("cannot read the downloaded CAR");
+    assert!(verified.is_complete(), "issues: {:?}", verified.issues);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(all(feature = "testing", feature = "car"))]
+#[test]
+fn client_restarts_download_of_a_different_root() {
+    use lassie::testing::{Fixture, MockProvider};
+    use lassie::DagScope;
+
+    let _lock = setup_test_env();
+
+    let content: Vec<u8> = (0..=250u8).cycle().take(16 * 1024).collect();
+    let old = Fixture::unixfs_file(&content[..8 * 1024], 1024);
+    let new = Fixture::unixfs_file(&content, 1024);
+    let provider =
+        MockProvider::start(vec![old.clone(), new.clone()]).expect("cannot start the provider");
+    let daemon = Daemon::start(DaemonConfig {
+        disable_ipni: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let client = Client::new(&daemon);
+    let request = |fixture: &Fixture| {
+        RetrievalRequest::new(fixture.root().to_string())
+            .dag_scope(DagScope::Entity)
+            .protocols(["http"])
+            .providers([provider.multiaddr()])
+    };
+
+    let path = std::env::temp_dir().join("rusty-lassie-download-root-test.car");
+    let _ = std::fs::remove_file(&path);
+    client
+        .download_to(&request(&old), &path)
+        .expect("cannot download");
+
+    // The file on disk holds the first half of the new content, under a different root
+    let report = client
+        .download_to(&request(&new), &path)
+        .expect("cannot download the new root");
+    assert_eq!(report.resumed_blocks, 0);
+
+    let file = std::fs::File::open(&path).unwrap();
+    let verified = lassie::car::verify(std::io::BufReader::new(file), new.root())
+        .expect("cannot read the downloaded CAR");
+    assert!(verified.is_complete(), "issues: {:?}", verified.issues);
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn setup_test_env() -> MutexGuard<'static, ()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let lock = TEST_GUARD.lock().expect("cannot obtain global test lock. This typically happens when one of the test fails; the problem should go away after you fix the test failure.");
+    lock
+}
@@ -0,0 +1,116 @@
+#![cfg(all(feature = "front", feature = "testing"))]
+
+use pretty_assertions::assert_eq;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use lassie::front::{FrontConfig, FrontListener, RateLimitConfig};
+use lassie::testing::{Fixture, MockProvider};
+use lassie::{Daemon, DaemonConfig};
+
+const SMALL_CAR: &[u8] =
+    include_bytes!("testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car");
+
+// Rust runs tests in parallel. Since Lassie Daemon is a singleton,
+// we must synchronise the tests to ensure they run sequentially
+static TEST_GUARD: Mutex<()> = Mutex::new(());
+
+#[test]
+fn front_listener_forwards_authorized_requests() {
+    let _lock = setup_test_env();
+    let small = Fixture::from_car(SMALL_CAR).expect("cannot parse the test CAR file");
+    let provider = MockProvider::start(vec![small.clone()]).expect("cannot start the provider");
+    let daemon = start_daemon();
+
+    let runtime = tokio::runtime::Runtime::new().expect("cannot create tokio runtime");
+    let addr = start_front(
+        &runtime,
+        &daemon,
+        FrontConfig {
+            access_token: Some("secret".to_string()),
+            ..FrontConfig::new(localhost())
+        },
+    );
+    let url = format!("http://{addr}/ipfs/{}?{}", small.root(), provider.query());
+
+    match ureq::get(&url).call() {
+        Err(ureq::Error::Status(code, _)) => assert_eq!(code, 401),
+        other => panic!("requests without the access token should be rejected: {other:?}"),
+    }
+
+    let response = ureq::get(&url)
+        .set("Authorization", "Bearer secret")
+        .set("Accept", "application/vnd.ipld.car")
+        .call()
+        .expect("cannot fetch the CID through the front listener");
+    assert_eq!(response.status(), 200);
+    let mut content = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+fn front_listener_limits_request_rate() {
+    let _lock = setup_test_env();
+    let daemon = start_daemon();
+
+    let runtime = tokio::runtime::Runtime::new().expect("cannot create tokio runtime");
+    let addr = start_front(
+        &runtime,
+        &daemon,
+        FrontConfig {
+            rate_limit: Some(RateLimitConfig {
+                requests_per_second: 1,
+                burst: 1,
+            }),
+            ..FrontConfig::new(localhost())
+        },
+    );
+    // Paths outside of /ipfs/ and /ipns/ are rejected without starting a retrieval
+    let url = format!("http://{addr}/");
+
+    let status = |response: Result<ureq::Response, ureq::Error>| match response {
+        Ok(response) => response.status(),
+        Err(ureq::Error::Status(code, _)) => code,
+        Err(err) => panic!("cannot reach the front listener: {err}"),
+    };
+    assert_eq!(status(ureq::get(&url).call()), 404);
+    assert_eq!(status(ureq::get(&url).call()), 429);
+}
+
+fn start_daemon() -> Arc<Daemon> {
+    Arc::new(
+        Daemon::start(DaemonConfig {
+            disable_listener: true,
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie"),
+    )
+}
+
+fn start_front(
+    runtime: &tokio::runtime::Runtime,
+    daemon: &Arc<Daemon>,
+    config: FrontConfig,
+) -> SocketAddr {
+    let front = runtime
+        .block_on(FrontListener::bind(config))
+        .expect("cannot bind the front listener");
+    let addr = front.local_addr().expect("cannot get the listener address");
+    runtime.spawn(front.serve(Arc::clone(daemon)));
+    addr
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+fn setup_test_env() -> MutexGuard<'static, ()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let lock = TEST_GUARD.lock().expect("cannot obtain global test lock. This typically happens when one of the test fails; the problem should go away after you fix the test failure.");
+    lock
+}
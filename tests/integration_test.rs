@@ -1,57 +1,1276 @@
 use pretty_assertions::assert_eq;
-use std::sync::{Mutex, MutexGuard};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
-use lassie::{Daemon, DaemonConfig};
+use lassie::testing::{Fixture, MockProvider};
+use lassie::{
+    AccessLogRecord, AdminAddress, AdminListenerConfig, BlockCacheConfig, CircuitBreakerConfig,
+    ConfigError, ConnectionManagerConfig, Daemon, DaemonConfig, ExtraListenerConfig, Health,
+    LazyDaemon, Libp2pTransport, Measurement, OtlpConfig, Priority, RequestOutcome, ResponseSink,
+    RetrievalError, RetrievalEvent, RetrievalEventKind, StartError, WatchdogConfig,
+    ERROR_CODE_HEADER, PRIORITY_HEADER, REQUEST_ID_HEADER, TIMEOUT_HEADER, TRACEPARENT_HEADER,
+};
+
+const SMALL_CAR: &[u8] =
+    include_bytes!("testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car");
 
 // Rust runs tests in parallel. Since Lassie Daemon is a singleton,
 // we must synchronise the tests to ensure they run sequentially
 static TEST_GUARD: Mutex<()> = Mutex::new(());
 
 #[test]
-fn start_daemon_and_request_cid() {
+fn start_daemon_and_request_cid() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let port = daemon.port();
+    assert!(port > 0, "Lassie is listening on non-zero port number");
+
+    let url = format!("http://127.0.0.1:{port}{}", provider.small_path());
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    let response = assert_ok_response(response);
+
+    println!("response headers: {:?}", response.headers_names());
+    for hn in &response.headers_names() {
+        println!("\t{hn}: {}", response.header(hn).unwrap_or("<empty>"));
+    }
+
+    assert_eq!(
+        response.header("Content-Type"),
+        Some("application/vnd.ipld.car;version=1;order=dfs;dups=y")
+    );
+
+    let mut content = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+#[cfg(unix)]
+fn serve_requests_on_inherited_listener() {
+    use std::os::fd::AsRawFd;
+
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("cannot bind the listener");
+    let listener_port = listener.local_addr().unwrap().port();
+    let daemon = Daemon::start(DaemonConfig {
+        listener_fd: Some(listener.as_raw_fd()),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    // The daemon keeps its own copy of the socket
+    drop(listener);
+    assert_eq!(daemon.port(), listener_port);
+
+    let url = format!("http://127.0.0.1:{listener_port}{}", provider.small_path());
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    let mut content = Vec::new();
+    assert_ok_response(response)
+        .into_reader()
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+#[cfg(unix)]
+fn listen_with_reuse_port() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("cannot find a free port")
+        .port();
+    let daemon = Daemon::start(DaemonConfig {
+        port,
+        reuse_port: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    assert_eq!(daemon.port(), port);
+
+    let url = format!("http://127.0.0.1:{port}{}", provider.small_path());
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    assert_ok_response(response);
+}
+
+#[test]
+fn fail_when_startup_times_out() {
+    let _lock = setup_test_env();
+
+    let result = Daemon::start(DaemonConfig {
+        startup_timeout: Some(Duration::from_nanos(1)),
+        ..DaemonConfig::default()
+    });
+    assert_eq!(
+        result.err(),
+        Some(StartError::StartupTimedOut(Duration::from_nanos(1)))
+    );
+
+    // The abandoned initialization stops its daemon in the background
+    let deadline = std::time::Instant::now() + Duration::from_secs(30);
+    let daemon = loop {
+        match Daemon::start(DaemonConfig::default()) {
+            Err(StartError::OnlyOneInstanceAllowed) if std::time::Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            other => break other.expect("cannot start Lassie after the startup timeout"),
+        }
+    };
+    assert!(daemon.port() > 0);
+}
+
+#[test]
+fn lazy_daemon_starts_on_first_use() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let lazy = LazyDaemon::new(DaemonConfig::default());
+    assert!(lazy.get_if_started().is_none(), "the daemon starts lazily");
+    // Lassie is a singleton, another daemon can run until the lazy one starts
+    drop(Daemon::start(DaemonConfig::default()).expect("cannot start Lassie"));
+
+    let handle = lazy.handle().expect("cannot start the lazy daemon");
+    assert!(handle.port() > 0);
+    assert_eq!(
+        lazy.get().unwrap().port(),
+        handle.port(),
+        "started only once"
+    );
+
+    let response = ureq::get(&handle.url(&provider.small_path()))
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    assert_ok_response(response);
+
+    let daemon = lazy.into_inner().expect("the daemon was started");
+    daemon.try_shutdown().expect("cannot stop Lassie");
+}
+
+#[test]
+fn lazy_daemon_restarts_after_idle_shutdown() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let lazy = LazyDaemon::new(DaemonConfig {
+        idle_shutdown: Some(Duration::from_millis(200)),
+        ..DaemonConfig::default()
+    });
+    let handle = lazy.handle().expect("cannot start the lazy daemon");
+    let response = ureq::get(&handle.url(&provider.small_path()))
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    assert_ok_response(response);
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while handle.is_running() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "the idle daemon did not stop"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(lazy.get_if_started().is_none());
+
+    let handle = lazy.handle().expect("cannot restart the lazy daemon");
+    let response = ureq::get(&handle.url(&provider.small_path()))
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    assert_ok_response(response);
+}
+
+#[test]
+fn share_daemon_between_callers() {
+    let _lock = setup_test_env();
+
+    let first = Daemon::get_or_start(DaemonConfig::default()).expect("cannot start Lassie");
+    let second = Daemon::get_or_start(DaemonConfig {
+        max_blocks: Some(1),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot share Lassie");
+    assert_eq!(first.port(), second.port(), "the running daemon is reused");
+
+    let third = second.clone();
+    drop(first);
+    drop(second);
+    assert!(third.is_running(), "a handle keeps the daemon running");
+    assert_eq!(
+        Daemon::start(DaemonConfig::default()).err(),
+        Some(StartError::OnlyOneInstanceAllowed)
+    );
+
+    drop(third);
+    let _daemon = Daemon::start(DaemonConfig::default())
+        .expect("the daemon stops when the last handle is dropped");
+}
+
+#[test]
+fn report_health_with_watchdog() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig {
+        watchdog: Some(WatchdogConfig {
+            interval: Duration::from_millis(50),
+            ..WatchdogConfig::default()
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let events = daemon.failure_events();
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(daemon.health(), Health::Healthy);
+    assert!(events.try_recv().is_err(), "the probes succeed");
+
+    let response = ureq::get(&format!("http://127.0.0.1:{}/healthz", daemon.port())).call();
+    assert_ok_response(response);
+}
+
+#[test]
+fn serve_request_in_process_without_listener() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        disable_listener: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    assert_eq!(daemon.port(), 0, "Lassie should not listen on any port");
+
+    let mut response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.header("Content-Type"),
+        Some("application/vnd.ipld.car;version=1;order=dfs;dups=y")
+    );
+
+    let mut content = Vec::new();
+    response
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+fn serve_request_through_pipe() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+
+    let mut response = daemon
+        .serve_request_to_pipe(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+
+    let mut content = Vec::new();
+    response
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+fn serve_request_with_sink() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+
+    let mut sink = CollectingSink::default();
+    daemon
+        .serve_request_with_sink(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+            &mut sink,
+        )
+        .expect("cannot serve the request in-process");
+
+    assert_eq!(sink.status, Some(200));
+    assert_eq!(sink.content, SMALL_CAR);
+}
+
+#[test]
+fn serve_request_to_file() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+
+    let dest = std::env::temp_dir().join("rusty-lassie-serve-to-file-test.car");
+    let _ = std::fs::remove_file(&dest);
+    let response = daemon
+        .serve_request_to_file(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+            &dest,
+        )
+        .expect("cannot serve the request to a file");
+    assert_eq!(response.bytes(), SMALL_CAR.len() as u64);
+    assert!(response.retrieval_id().is_some());
+    assert_eq!(std::fs::read(&dest).unwrap(), SMALL_CAR);
+    std::fs::remove_file(&dest).unwrap();
+
+    let err = daemon
+        .serve_request_to_file(
+            "/ipfs/not-a-cid",
+            &[("Accept", "application/vnd.ipld.car")],
+            &dest,
+        )
+        .expect_err("invalid CIDs should be rejected");
+    assert!(
+        matches!(
+            err.get_ref()
+                .and_then(|e| e.downcast_ref::<RetrievalError>()),
+            Some(RetrievalError::BadRequest(_))
+        ),
+        "unexpected error: {err:?}"
+    );
+    assert!(!dest.exists(), "error responses should not create the file");
+}
+
+#[test]
+#[cfg(unix)]
+fn retrieve_with_mmap_car_store() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        mmap_car_store: Some(64 << 20),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with the memory-mapped CAR store");
+
+    let mut response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+
+    let mut content = Vec::new();
+    response
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+fn serve_repeated_retrievals_from_block_cache() {
+    let _lock = setup_test_env();
+    let dir = std::env::temp_dir().join(format!(
+        "rusty-lassie-block-cache-test-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let fixture = Fixture::unixfs_file(&file_content(16 * 1024), 1024);
+    let provider = MockProvider::start(vec![fixture.clone()]).expect("cannot start the provider");
+    let path = format!("/ipfs/{}?{}", fixture.root(), provider.query());
+
+    let daemon = Daemon::start(DaemonConfig {
+        block_cache: Some(BlockCacheConfig {
+            dir: dir.clone(),
+            max_size: 1 << 20,
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with a block cache");
+    let fetch = || {
+        let mut response = daemon
+            .serve_request(&path, &[("Accept", "application/vnd.ipld.car")])
+            .expect("cannot serve the request in-process");
+        assert_eq!(response.status(), 200);
+        let mut content = Vec::new();
+        response
+            .read_to_end(&mut content)
+            .expect("cannot read response body");
+        content
+    };
+    assert_eq!(fetch(), fixture.car());
+
+    // The provider is gone, the second retrieval succeeds from the cache only
+    drop(provider);
+    assert_eq!(fetch(), fixture.car());
+
+    drop(daemon);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn retrieve_with_max_download_rate() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        max_download_rate: Some(1 << 20),
+        max_download_rate_per_retrieval: Some(64 << 10),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with a download rate limit");
+
+    let mut response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+
+    let mut content = Vec::new();
+    response
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+    assert_eq!(content, SMALL_CAR);
+}
+
+#[test]
+fn cancel_retrieval() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    assert!(
+        !daemon.cancel("unknown-retrieval-id"),
+        "cancelling an unknown retrieval should return false"
+    );
+
+    // This archive contains many blocks and takes long to download
+    let mut response = daemon
+        .serve_request(
+            &provider.large_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    let retrieval_id = response
+        .retrieval_id()
+        .expect("response should include the retrieval ID")
+        .to_string();
+
+    assert!(
+        daemon.cancel(&retrieval_id),
+        "cancelling a running retrieval should return true"
+    );
+
+    // The stream ends (possibly with an error) shortly after the retrieval was cancelled
+    let mut content = Vec::new();
+    let _ = response.read_to_end(&mut content);
+
+    assert!(
+        !daemon.cancel(&retrieval_id),
+        "cancelling a finished retrieval should return false"
+    );
+}
+
+#[test]
+fn shutdown_gracefully_finishes_running_retrievals() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let url = daemon.handle().url(&provider.large_path());
+
+    // The large archive takes a few seconds to download, both requests are running when the
+    // daemon starts draining
+    let response = assert_ok_response(ureq::get(&url).call());
+    let over_http = std::thread::spawn(move || {
+        let mut content = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut content)
+            .map(|_| content)
+    });
+    let mut response = daemon
+        .serve_request(
+            &provider.large_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    let in_process = std::thread::spawn(move || {
+        let mut content = Vec::new();
+        response.read_to_end(&mut content).map(|_| content)
+    });
+
+    daemon
+        .shutdown_gracefully(Duration::from_secs(60))
+        .expect("cannot stop Lassie");
+
+    let expected = provider.large.car();
+    let content = over_http
+        .join()
+        .unwrap()
+        .expect("cannot read the HTTP response");
+    assert_eq!(content.len(), expected.len());
+    let content = in_process
+        .join()
+        .unwrap()
+        .expect("cannot read the in-process response");
+    assert_eq!(content.len(), expected.len());
+
+    assert!(
+        ureq::get(&url).call().is_err(),
+        "the daemon should not accept requests after the shutdown"
+    );
+}
+
+#[test]
+fn list_active_retrievals() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    assert_eq!(daemon.active_retrievals(), vec![]);
+
+    let mut response = daemon
+        .serve_request(
+            &provider.large_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    let retrieval_id = response
+        .retrieval_id()
+        .expect("response should include the retrieval ID")
+        .to_string();
+
+    let active = daemon.active_retrievals();
+    assert_eq!(active.len(), 1, "active retrievals: {active:?}");
+    assert_eq!(active[0].id, retrieval_id);
+    assert_eq!(active[0].cid, provider.large.root().to_string());
+
+    assert!(daemon.cancel(&retrieval_id));
+    let mut content = Vec::new();
+    let _ = response.read_to_end(&mut content);
+    assert_eq!(daemon.active_retrievals(), vec![]);
+}
+
+#[test]
+fn collect_provider_stats() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    drop(response);
+
+    let succeeded = |stats: &[lassie::ProviderStats]| {
+        stats.iter().any(|s| {
+            s.successes > 0 && s.protocol.as_deref() == Some("transport-ipfs-gateway-http")
+        })
+    };
+    // Lassie delivers the retrieval events asynchronously
+    let mut stats = daemon.provider_stats();
+    for _ in 0..50 {
+        if succeeded(&stats) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        stats = daemon.provider_stats();
+    }
+    assert!(succeeded(&stats), "provider stats: {stats:?}");
+    let http = stats.iter().find(|s| s.successes > 0).unwrap();
+    assert!(http.bytes_received > 0, "{http:?}");
+    assert!(http.success_rate().unwrap() > 0.0, "{http:?}");
+}
+
+#[test]
+fn collect_metrics() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let before = daemon.metrics();
+
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    drop(response);
+
+    let successes = |metrics: &lassie::MetricsSnapshot| {
+        metrics
+            .protocol("transport-ipfs-gateway-http")
+            .map_or(0, |m| m.successes)
+    };
+    // Lassie delivers the retrieval events asynchronously
+    let mut after = daemon.metrics();
+    for _ in 0..50 {
+        if successes(&after) > successes(&before) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        after = daemon.metrics();
+    }
+    assert_eq!(after.total_retrievals, before.total_retrievals + 1);
+    assert_eq!(successes(&after), successes(&before) + 1);
+    let http = after
+        .protocol("transport-ipfs-gateway-http")
+        .expect("no metrics for HTTP retrievals");
+    assert!(http.bytes_received > 0);
+}
+
+#[test]
+fn access_log_callback() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let access_log = daemon.access_log({
+        let records = Arc::clone(&records);
+        move |record: &AccessLogRecord| records.lock().unwrap().push(record.clone())
+    });
+
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.small_path()
+    );
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    let response = assert_ok_response(response);
+    let mut content = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut content)
+        .expect("cannot read response body");
+
+    let response = daemon
+        .serve_request("/ipfs/not-a-cid", &[("Accept", "application/vnd.ipld.car")])
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 400);
+    drop(response);
+    drop(access_log);
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 2, "records: {records:?}");
+    let ok = &records[0];
+    assert_eq!(ok.method, "GET");
+    assert_eq!(ok.cid, Some(provider.small.root().to_string()));
+    assert_eq!(ok.status, 200);
+    assert_eq!(ok.bytes, content.len() as u64);
+    assert_eq!(ok.outcome, RequestOutcome::Succeeded);
+    assert!(
+        ok.remote_addr.is_some(),
+        "HTTP requests have a client address"
+    );
+    assert!(ok.retrieval_id.is_some());
+
+    let failed = &records[1];
+    assert_eq!(failed.cid.as_deref(), Some("not-a-cid"));
+    assert_eq!(failed.status, 400);
+    assert_eq!(failed.outcome, RequestOutcome::Failed);
+    assert_eq!(
+        failed.remote_addr, None,
+        "in-process requests have no client address"
+    );
+}
+
+#[test]
+fn retrieval_events_callback() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let subscription = daemon.retrieval_events({
+        let events = Arc::clone(&events);
+        move |event: &RetrievalEvent| events.lock().unwrap().push(event.clone())
+    });
+
+    let mut response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    std::io::copy(&mut response, &mut std::io::sink()).expect("cannot read response body");
+    drop(response);
+
+    // Lassie dispatches the events asynchronously
+    let succeeded = |events: &[RetrievalEvent]| {
+        events
+            .iter()
+            .any(|e| matches!(e.kind, RetrievalEventKind::Success { .. }))
+    };
+    for _ in 0..50 {
+        if succeeded(&events.lock().unwrap()) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    drop(subscription);
+
+    let events = events.lock().unwrap();
+    assert!(succeeded(&events), "events: {events:?}");
+    assert!(
+        matches!(events[0].kind, RetrievalEventKind::Started),
+        "the first event should be Started, events: {events:?}"
+    );
+    let root = provider.small.root().to_string();
+    assert!(events
+        .iter()
+        .all(|e| e.root_cid.as_deref() == Some(root.as_str())));
+    for event in events.iter() {
+        if let RetrievalEventKind::Success {
+            bytes, protocol, ..
+        } = &event.kind
+        {
+            assert!(*bytes > 0, "{event:?}");
+            assert_eq!(protocol, "transport-ipfs-gateway-http");
+        }
+    }
+}
+
+#[test]
+fn correlate_events_of_concurrent_retrievals_of_the_same_cid() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let subscription = daemon.retrieval_events({
+        let events = Arc::clone(&events);
+        move |event: &RetrievalEvent| events.lock().unwrap().push(event.clone())
+    });
+
+    // Both retrievals of the large file run at the same time
+    let mut responses: Vec<_> = (0..2)
+        .map(|_| {
+            daemon
+                .serve_request(
+                    &provider.large_path(),
+                    &[("Accept", "application/vnd.ipld.car")],
+                )
+                .expect("cannot serve the request in-process")
+        })
+        .collect();
+    let retrieval_ids: Vec<String> = responses
+        .iter()
+        .map(|response| response.retrieval_id().unwrap().to_string())
+        .collect();
+    let mut buf = vec![0u8; 64 * 1024];
+    for response in &mut responses {
+        response
+            .read_exact(&mut buf)
+            .expect("cannot read response body");
+    }
+
+    let started_both = |events: &[RetrievalEvent]| {
+        retrieval_ids.iter().all(|id| {
+            events.iter().any(|e| {
+                e.retrieval_id.as_ref() == Some(id)
+                    && matches!(e.kind, RetrievalEventKind::FirstByte { .. })
+            })
+        })
+    };
+    for _ in 0..50 {
+        if started_both(&events.lock().unwrap()) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    drop(responses);
+    drop(subscription);
+
+    let events = events.lock().unwrap();
+    assert!(started_both(&events), "events: {events:?}");
+    // Each Lassie retrieval belongs to exactly one of our requests
+    let lassie_ids: Vec<Vec<&str>> = retrieval_ids
+        .iter()
+        .map(|id| {
+            let mut ids: Vec<&str> = events
+                .iter()
+                .filter(|e| e.retrieval_id.as_ref() == Some(id))
+                .map(|e| e.lassie_id.as_str())
+                .collect();
+            ids.dedup();
+            ids
+        })
+        .collect();
+    assert_eq!(lassie_ids[0].len(), 1, "events: {events:?}");
+    assert_eq!(lassie_ids[1].len(), 1, "events: {events:?}");
+    assert_ne!(lassie_ids[0], lassie_ids[1]);
+}
+
+#[test]
+fn writes_event_log() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+    let event_log = std::env::temp_dir().join("rusty-lassie-event-log-test.jsonl");
+    let _ = std::fs::remove_file(&event_log);
+
+    let daemon = Daemon::start(DaemonConfig {
+        event_log: Some(event_log.clone()),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with an event log");
+    let mut response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    std::io::copy(&mut response, &mut std::io::sink()).expect("cannot read response body");
+    drop(response);
+
+    // Lassie dispatches the events asynchronously
+    let read_log = || std::fs::read_to_string(&event_log).unwrap_or_default();
+    for _ in 0..50 {
+        if read_log().contains(r#""event":"success""#) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    drop(daemon);
+
+    let log = read_log();
+    assert!(log.contains(r#""event":"success""#), "event log: {log}");
+    let root = format!(r#""root_cid":"{}""#, provider.small.root());
+    for line in log.lines() {
+        assert!(
+            line.starts_with(r#"{"ts":""#) && line.ends_with('}'),
+            "not a JSON record: {line}"
+        );
+        assert!(line.contains(&root), "unexpected root: {line}");
+    }
+}
+
+#[test]
+fn reports_measurements() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let measurements = daemon.measurements(move |m: &Measurement| {
+        let _ = tx.send(m.clone());
+    });
+
+    let mut response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    let retrieval_id = response.retrieval_id().unwrap().to_string();
+    let mut body = Vec::new();
+    response
+        .read_to_end(&mut body)
+        .expect("cannot read response body");
+    drop(response);
+
+    let measurement = rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("no measurement reported");
+    drop(measurements);
+
+    let digest = Sha256::digest(&body)
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        });
+    assert_eq!(measurement.retrieval_id, retrieval_id);
+    assert_eq!(measurement.cid, provider.small.root().to_string());
+    assert_eq!(measurement.status, 200);
+    assert_eq!(measurement.byte_length, body.len() as u64);
+    assert_eq!(measurement.car_checksum, Some(format!("1220{digest}")));
+    assert!(!measurement.aborted);
+    let ttfb = measurement.ttfb.expect("the response has a body");
+    assert!(ttfb <= measurement.duration, "{measurement:?}");
+}
+
+#[test]
+fn propagate_request_id() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[
+                ("Accept", "application/vnd.ipld.car"),
+                (REQUEST_ID_HEADER, "gateway-trace-42"),
+            ],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header(REQUEST_ID_HEADER), Some("gateway-trace-42"));
+    assert_eq!(response.retrieval_id(), Some("gateway-trace-42"));
+    drop(response);
+
+    // Malformed IDs are replaced with the generated retrieval ID
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[
+                ("Accept", "application/vnd.ipld.car"),
+                (REQUEST_ID_HEADER, "not a valid id"),
+            ],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    let retrieval_id = response.retrieval_id().expect("missing retrieval ID");
+    assert_ne!(retrieval_id, "not a valid id");
+    assert_eq!(response.header(REQUEST_ID_HEADER), Some(retrieval_id));
+}
+
+#[test]
+fn report_retrieval_progress() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let watcher = daemon
+        .watch_progress(Duration::from_millis(50), move |retrieval| {
+            let _ = tx.send(retrieval.clone());
+        })
+        .expect("cannot watch progress");
+
+    let mut response = daemon
+        .serve_request(
+            &provider.large_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    let retrieval_id = response
+        .retrieval_id()
+        .expect("response should include the retrieval ID")
+        .to_string();
+
+    // Read enough data to receive the CAR header and at least one block
+    let mut buf = vec![0u8; 64 * 1024];
+    response
+        .read_exact(&mut buf)
+        .expect("cannot read response body");
+
+    let progress = std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(10)).ok())
+        .find(|p| p.id == retrieval_id && p.blocks_received > 0)
+        .expect("progress report with received blocks");
+    assert!(progress.bytes_sent > 0, "progress: {progress:?}");
+
+    drop(watcher);
+    assert!(daemon.cancel(&retrieval_id));
+}
+
+#[test]
+fn disable_ipni() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        disable_ipni: true,
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+
+    // No providers specified and no candidate discovery
+    let response = daemon
+        .serve_request(
+            "/ipfs/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq",
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 502);
+    assert_eq!(response.header(ERROR_CODE_HEADER), Some("no-candidates"));
+
+    // Explicitly specified providers are still used
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn skip_providers_with_open_circuit() {
+    let _lock = setup_test_env();
+    let fixture = Fixture::raw_block(b"circuit breaker test");
+    let provider = MockProvider::start(vec![fixture.clone()]).expect("cannot start the provider");
+
+    // The circuit breaker applies to discovered candidates only, not to `providers=`
+    let daemon = Daemon::start(DaemonConfig {
+        delegated_routing_url: Some(provider.routing_url()),
+        circuit_breaker: Some(CircuitBreakerConfig {
+            failures: 2,
+            cool_down: Duration::from_secs(300),
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with a circuit breaker");
+    let fetch = |cid: String| {
+        daemon
+            .serve_request(
+                &format!("/ipfs/{cid}?protocols=http"),
+                &[("Accept", "application/vnd.ipld.car")],
+            )
+            .expect("cannot serve the request in-process")
+            .status()
+    };
+    assert_eq!(fetch(fixture.root().to_string()), 200);
+
+    // The provider answers 404 for the CIDs it doesn't have
+    for data in [b"missing 1", b"missing 2"] {
+        let missing = lassie::testing::raw_cid(data);
+        assert_eq!(fetch(missing.to_string()), 502);
+    }
+    // Lassie delivers the retrieval events asynchronously
+    let is_open = || {
+        daemon
+            .provider_stats()
+            .iter()
+            .any(|s| s.provider == provider.peer_id() && s.circuit_open_until.is_some())
+    };
+    for _ in 0..50 {
+        if is_open() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(is_open(), "provider stats: {:?}", daemon.provider_stats());
+
+    // The provider has the content, but it's skipped now
+    assert_eq!(fetch(fixture.root().to_string()), 502);
+}
+
+#[test]
+fn configure_max_blocks() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        max_blocks: Some(1),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let port = daemon.port();
+    assert!(port > 0, "Lassie is listening on non-zero port number");
+
+    // This archive contains many blocks and takes long to download unless the block limit is applied
+    let url = format!("http://127.0.0.1:{port}{}", provider.large_path());
+    let response = ureq::get(&url).call();
+    let response = assert_ok_response(response);
+
+    let mut content = Vec::new();
+    let error = response
+        .into_reader()
+        .read_to_end(&mut content)
+        .expect_err("response stream should have been aborted by the server");
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn reject_requests_over_max_concurrent_requests() {
     let _lock = setup_test_env();
+    let provider = TestProvider::start();
 
-    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
-    let port = daemon.port();
-    assert!(port > 0, "Lassie is listening on non-zero port number");
+    let daemon = Daemon::start(DaemonConfig {
+        max_concurrent_requests: Some(1),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
 
+    // The large archive takes a few seconds to download, the request keeps the only slot
     let url = format!(
-        "http://127.0.0.1:{port}/ipfs/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq?protocol=http&providers=/dns4/frisbii.fly.dev/https"
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.large_path()
     );
-    let response = ureq::get(&url)
-        .set("Accept", "application/vnd.ipld.car")
-        .call();
-    let response = assert_ok_response(response);
+    let _running = assert_ok_response(ureq::get(&url).call());
 
-    println!("response headers: {:?}", response.headers_names());
-    for hn in &response.headers_names() {
-        println!("\t{hn}: {}", response.header(hn).unwrap_or("<empty>"));
-    }
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.header("Retry-After"), Some("1"));
+}
 
-    assert_eq!(
-        response.header("Content-Type"),
-        Some("application/vnd.ipld.car;version=1;order=dfs;dups=y")
+#[test]
+fn queue_retrievals_over_max_concurrent_retrievals() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Arc::new(
+        Daemon::start(DaemonConfig {
+            max_concurrent_retrievals: Some(1),
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie"),
     );
 
-    let mut content = Vec::new();
-    response
-        .into_reader()
-        .read_to_end(&mut content)
-        .expect("cannot read response body");
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.large_path()
+    );
+    let running = assert_ok_response(ureq::get(&url).call());
+
+    let queued = {
+        let daemon = Arc::clone(&daemon);
+        let path = provider.small_path();
+        std::thread::spawn(move || {
+            let mut response = daemon
+                .serve_request(&path, &[("Accept", "application/vnd.ipld.car")])
+                .expect("cannot serve the request in-process");
+            let mut content = Vec::new();
+            response
+                .read_to_end(&mut content)
+                .expect("cannot read response body");
+            (response.status(), content)
+        })
+    };
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!queued.is_finished(), "the retrieval waits for a free slot");
+
+    std::io::copy(&mut running.into_reader(), &mut std::io::sink())
+        .expect("cannot read the running retrieval");
+    let (status, content) = queued.join().unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(content, SMALL_CAR);
+}
 
+#[test]
+fn start_queued_retrievals_by_priority() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Arc::new(
+        Daemon::start(DaemonConfig {
+            max_concurrent_retrievals: Some(1),
+            ..DaemonConfig::default()
+        })
+        .expect("cannot start Lassie"),
+    );
+
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.large_path()
+    );
+    let running = assert_ok_response(ureq::get(&url).call());
+
+    let finished = Arc::new(Mutex::new(Vec::new()));
+    let queue = |priority: Priority| {
+        let daemon = Arc::clone(&daemon);
+        let finished = Arc::clone(&finished);
+        let path = provider.small_path();
+        let thread = std::thread::spawn(move || {
+            let mut response = daemon
+                .serve_request(
+                    &path,
+                    &[
+                        ("Accept", "application/vnd.ipld.car"),
+                        (PRIORITY_HEADER, priority.as_str()),
+                    ],
+                )
+                .expect("cannot serve the request in-process");
+            assert_eq!(response.status(), 200);
+            std::io::copy(&mut response, &mut std::io::sink()).expect("cannot read response body");
+            finished.lock().unwrap().push(priority);
+        });
+        // Let the request reach the queue before the next one
+        std::thread::sleep(Duration::from_millis(300));
+        thread
+    };
+    let low = queue(Priority::Low);
+    let high = queue(Priority::High);
+
+    std::io::copy(&mut running.into_reader(), &mut std::io::sink())
+        .expect("cannot read the running retrieval");
+    low.join().unwrap();
+    high.join().unwrap();
     assert_eq!(
-        content,
-        include_bytes!("testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car")
+        *finished.lock().unwrap(),
+        vec![Priority::High, Priority::Low]
     );
 }
 
 #[test]
-fn configure_max_blocks() {
+fn reject_retrievals_over_max_queued_retrievals() {
     let _lock = setup_test_env();
+    let provider = TestProvider::start();
 
     let daemon = Daemon::start(DaemonConfig {
-        max_blocks: Some(1),
+        max_concurrent_retrievals: Some(1),
+        max_queued_retrievals: Some(0),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.large_path()
+    );
+    let _running = assert_ok_response(ureq::get(&url).call());
+
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.header("Retry-After"), Some("1"));
+}
+
+#[test]
+fn configure_global_timeout() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        global_timeout: Some(Duration::from_millis(1000)),
         ..DaemonConfig::default()
     })
     .expect("cannot start Lassie");
@@ -59,9 +1278,7 @@ fn configure_max_blocks() {
     assert!(port > 0, "Lassie is listening on non-zero port number");
 
     // This archive contains many blocks and takes long to download unless the block limit is applied
-    let url = format!(
-        "http://127.0.0.1:{port}/ipfs/bafybeih5zasorm4tlfga4ztwvm2dlnw6jxwwuvgnokyt3mjamfn3svvpyy?protocol=http&providers=/dns4/frisbii.fly.dev/https"
-    );
+    let url = format!("http://127.0.0.1:{port}{}", provider.large_path());
     let response = ureq::get(&url).call();
     let response = assert_ok_response(response);
 
@@ -75,22 +1292,104 @@ fn configure_max_blocks() {
 }
 
 #[test]
-fn configure_global_timeout() {
+fn configure_http_provider_timeout() {
     let _lock = setup_test_env();
+    let provider = TestProvider::start();
 
     let daemon = Daemon::start(DaemonConfig {
-        global_timeout: Some(Duration::from_millis(1000)),
+        // The provider pauses for 50ms before each block after the first one
+        http_provider_timeout: Some(Duration::from_millis(20)),
         ..DaemonConfig::default()
     })
     .expect("cannot start Lassie");
-    let port = daemon.port();
-    assert!(port > 0, "Lassie is listening on non-zero port number");
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.large_path()
+    );
 
-    // This archive contains many blocks and takes long to download unless the block limit is applied
+    // Depending on when Lassie gives up, the response fails or the stream is aborted
+    if let Ok(response) = ureq::get(&url).call() {
+        let mut content = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut content)
+            .expect_err("response stream should have been aborted by the server");
+    }
+}
+
+#[test]
+fn reject_graphsync_provider_timeout() {
+    let _lock = setup_test_env();
+
+    let result = Daemon::start(DaemonConfig {
+        graphsync_provider_timeout: Some(Duration::from_secs(20)),
+        ..DaemonConfig::default()
+    });
+    assert_eq!(
+        result.err(),
+        Some(StartError::InvalidConfig(vec![ConfigError::Unsupported(
+            "graphsync_provider_timeout"
+        )]))
+    );
+}
+
+#[test]
+fn export_traces_to_otlp_collector() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+    let (endpoint, exports) = start_otlp_collector();
+
+    let daemon = Daemon::start(DaemonConfig {
+        otlp: Some(OtlpConfig {
+            endpoint,
+            service_name: Some("lassie-test".to_string()),
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
     let url = format!(
-        "http://127.0.0.1:{port}/ipfs/bafybeih5zasorm4tlfga4ztwvm2dlnw6jxwwuvgnokyt3mjamfn3svvpyy?protocol=http&providers=/dns4/frisbii.fly.dev/https"
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.small_path()
     );
-    let response = ureq::get(&url).call();
+    let response = ureq::get(&url)
+        .set(
+            TRACEPARENT_HEADER,
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .call();
+    let mut content = Vec::new();
+    assert_ok_response(response)
+        .into_reader()
+        .read_to_end(&mut content)
+        .expect("cannot read the response body");
+    // Stopping the daemon exports the remaining spans
+    drop(daemon);
+
+    let exports = exports.lock().unwrap().concat();
+    for expected in [
+        r#""stringValue":"lassie-test""#,
+        r#""traceId":"4bf92f3577b34da6a3ce929d0e0e4736""#,
+        r#""parentSpanId":"00f067aa0ba902b7","name":"retrieval""#,
+        r#""name":"candidate discovery""#,
+    ] {
+        assert!(exports.contains(expected), "{expected} not in {exports}");
+    }
+}
+
+#[test]
+fn apply_request_timeout_header() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig::default()).expect("cannot start Lassie");
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.large_path()
+    );
+    let response = ureq::get(&url).set(TIMEOUT_HEADER, "1s").call();
     let response = assert_ok_response(response);
 
     let mut content = Vec::new();
@@ -98,13 +1397,25 @@ fn configure_global_timeout() {
         .into_reader()
         .read_to_end(&mut content)
         .expect_err("response stream should have been aborted by the server");
-
     assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    let response = ureq::get(&url).set(TIMEOUT_HEADER, "soon").call();
+    match response {
+        Err(ureq::Error::Status(400, response)) => {
+            let body = response.into_string().unwrap();
+            assert_eq!(
+                RetrievalError::from_response(400, &body),
+                RetrievalError::BadRequest(body.trim().to_string())
+            );
+        }
+        other => panic!("unexpected response {other:?}"),
+    }
 }
 
 #[test]
 fn it_rejects_anonymous_requests_when_configured_with_access_token() {
     let _lock = setup_test_env();
+    let provider = TestProvider::start();
 
     let daemon = Daemon::start(DaemonConfig {
         access_token: Some("super_secret".to_string()),
@@ -114,19 +1425,66 @@ fn it_rejects_anonymous_requests_when_configured_with_access_token() {
     let port = daemon.port();
     assert!(port > 0, "Lassie is listening on non-zero port number");
 
+    let url = format!("http://127.0.0.1:{port}{}", provider.small_path());
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+
+    assert_response_error(response, 401);
+}
+
+#[test]
+fn it_rejects_clients_outside_of_allowed_client_ips() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        allowed_client_ips: Some(vec!["10.0.0.0/8".parse().unwrap()]),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+
     let url = format!(
-        "http://127.0.0.1:{port}/ipfs/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq?protocol=http&providers=/dns4/frisbii.fly.dev/https"
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.small_path()
     );
     let response = ureq::get(&url)
         .set("Accept", "application/vnd.ipld.car")
         .call();
+    assert_response_error(response, 403);
 
-    assert_response_error(response, 401);
+    // In-process requests are not restricted
+    let response = daemon
+        .serve_request(
+            &provider.small_path(),
+            &[("Accept", "application/vnd.ipld.car")],
+        )
+        .expect("cannot serve the request in-process");
+    assert_eq!(response.status(), 200);
+    drop(response);
+    drop(daemon);
+
+    let daemon = Daemon::start(DaemonConfig {
+        allowed_client_ips: Some(vec!["127.0.0.0/8".parse().unwrap()]),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let url = format!(
+        "http://127.0.0.1:{}{}",
+        daemon.port(),
+        provider.small_path()
+    );
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .call();
+    assert_ok_response(response);
 }
 
 #[test]
 fn it_allows_authorized_requests_when_configured_with_access_token() {
     let _lock = setup_test_env();
+    let provider = TestProvider::start();
 
     let daemon = Daemon::start(DaemonConfig {
         access_token: Some("super_secret".to_string()),
@@ -136,9 +1494,7 @@ fn it_allows_authorized_requests_when_configured_with_access_token() {
     let port = daemon.port();
     assert!(port > 0, "Lassie is listening on non-zero port number");
 
-    let url = format!(
-        "http://127.0.0.1:{port}/ipfs/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq?protocol=http&providers=/dns4/frisbii.fly.dev/https"
-    );
+    let url = format!("http://127.0.0.1:{port}{}", provider.small_path());
     let response = ureq::get(&url)
         .set("Accept", "application/vnd.ipld.car")
         .set(
@@ -149,9 +1505,43 @@ fn it_allows_authorized_requests_when_configured_with_access_token() {
     assert_ok_response(response);
 }
 
+#[test]
+fn extra_listeners_have_their_own_access_token() {
+    let _lock = setup_test_env();
+    let provider = TestProvider::start();
+
+    let daemon = Daemon::start(DaemonConfig {
+        access_token: Some("super_secret".to_string()),
+        extra_listeners: vec![ExtraListenerConfig {
+            address: AdminAddress::Port(0),
+            access_token: Some("proxy_secret".to_string()),
+        }],
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let extra_addr = daemon.extra_listen_addrs()[0]
+        .connect_addr()
+        .expect("the extra listener uses TCP");
+    assert_ne!(extra_addr.port(), daemon.port());
+
+    let url = format!("http://{extra_addr}{}", provider.small_path());
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .set("Authorization", "Bearer super_secret")
+        .call();
+    assert_response_error(response, 401);
+
+    let response = ureq::get(&url)
+        .set("Accept", "application/vnd.ipld.car")
+        .set("Authorization", "Bearer proxy_secret")
+        .call();
+    assert_ok_response(response);
+}
+
 #[test]
 fn it_rejects_incorrect_authorization_when_configured_with_access_token() {
     let _lock = setup_test_env();
+    let provider = TestProvider::start();
 
     let daemon = Daemon::start(DaemonConfig {
         access_token: Some("super_secret".to_string()),
@@ -161,9 +1551,7 @@ fn it_rejects_incorrect_authorization_when_configured_with_access_token() {
     let port = daemon.port();
     assert!(port > 0, "Lassie is listening on non-zero port number");
 
-    let url = format!(
-        "http://127.0.0.1:{port}/ipfs/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq?protocol=http&providers=/dns4/frisbii.fly.dev/https"
-    );
+    let url = format!("http://127.0.0.1:{port}{}", provider.small_path());
     let response = ureq::get(&url)
         .set("Accept", "application/vnd.ipld.car")
         .set("Authorization", "Bearer wrong-token")
@@ -172,6 +1560,269 @@ fn it_rejects_incorrect_authorization_when_configured_with_access_token() {
     assert_response_error(response, 401);
 }
 
+#[test]
+fn admin_listener() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig {
+        admin_listener: Some(AdminListenerConfig {
+            address: AdminAddress::Port(0),
+            access_token: Some("admin-secret".to_string()),
+            pprof: false,
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let admin_port = daemon.admin_port().expect("admin listener is not running");
+    assert!(
+        admin_port > 0,
+        "admin listener is listening on non-zero port number"
+    );
+    assert!(
+        admin_port != daemon.port(),
+        "admin listener must use its own port"
+    );
+
+    let admin_url = format!("http://127.0.0.1:{admin_port}");
+    assert_response_error(ureq::get(&format!("{admin_url}/stats")).call(), 401);
+
+    for endpoint in ["stats", "retrievals", "config"] {
+        let response = ureq::get(&format!("{admin_url}/{endpoint}"))
+            .set("Authorization", "Bearer admin-secret")
+            .call();
+        let response = assert_ok_response(response);
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+    }
+
+    // Control endpoints are not available on the public port
+    let public_url = format!("http://127.0.0.1:{}/stats", daemon.port());
+    assert_response_error(ureq::get(&public_url).call(), 404);
+
+    // Profiling is disabled by default
+    let response = ureq::get(&format!("{admin_url}/debug/pprof/"))
+        .set("Authorization", "Bearer admin-secret")
+        .call();
+    assert_response_error(response, 404);
+
+    let response = ureq::post(&format!("{admin_url}/shutdown"))
+        .set("Authorization", "Bearer admin-secret")
+        .call()
+        .expect("cannot request shutdown");
+    assert_eq!(response.status(), 202);
+}
+
+#[test]
+fn configure_connection_manager() {
+    let _lock = setup_test_env();
+    let daemon = Daemon::start(DaemonConfig {
+        connection_manager: Some(ConnectionManagerConfig {
+            low_water: 10,
+            high_water: 20,
+            grace_period: Duration::from_secs(5),
+        }),
+        admin_listener: Some(AdminListenerConfig {
+            address: AdminAddress::Port(0),
+            access_token: None,
+            pprof: false,
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with a custom connection manager");
+
+    let libp2p = admin_libp2p_info(&daemon);
+    let conn_manager = &libp2p["conn_manager"];
+    assert_eq!(conn_manager["low_water"], 10, "libp2p: {libp2p}");
+    assert_eq!(conn_manager["high_water"], 20, "libp2p: {libp2p}");
+    assert_eq!(conn_manager["grace_period"], "5s", "libp2p: {libp2p}");
+}
+
+#[test]
+fn listen_on_selected_libp2p_transports() {
+    let _lock = setup_test_env();
+    let daemon = Daemon::start(DaemonConfig {
+        libp2p_transports: Some(vec![
+            Libp2pTransport::Tcp,
+            Libp2pTransport::WebTransport,
+            Libp2pTransport::WebRtcDirect,
+        ]),
+        admin_listener: Some(AdminListenerConfig {
+            address: AdminAddress::Port(0),
+            access_token: None,
+            pprof: false,
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie with selected libp2p transports");
+
+    // E.g. `/ip4/0.0.0.0/tcp/4001` or `/ip6/::/udp/4001/quic-v1/webtransport`
+    let transport = |addr: &str| {
+        let parts: Vec<&str> = addr.split('/').skip(3).collect();
+        match parts[..] {
+            ["tcp", _] => "tcp",
+            ["tcp", _, "ws"] => "websocket",
+            ["udp", _, "quic-v1"] => "quic",
+            ["udp", _, "quic-v1", "webtransport", ..] => "webtransport",
+            ["udp", _, "webrtc-direct", ..] => "webrtc-direct",
+            _ => panic!("unexpected listen address {addr}"),
+        }
+    };
+    let libp2p = admin_libp2p_info(&daemon);
+    let transports: BTreeSet<&str> = libp2p["listen_addrs"]
+        .as_array()
+        .expect("listen_addrs is not an array")
+        .iter()
+        .filter_map(serde_json::Value::as_str)
+        .map(transport)
+        .collect();
+    assert_eq!(
+        transports,
+        BTreeSet::from(["tcp", "webtransport", "webrtc-direct"])
+    );
+}
+
+#[test]
+fn admin_listener_pprof() {
+    let _lock = setup_test_env();
+
+    let daemon = Daemon::start(DaemonConfig {
+        admin_listener: Some(AdminListenerConfig {
+            address: AdminAddress::Port(0),
+            access_token: None,
+            pprof: true,
+        }),
+        ..DaemonConfig::default()
+    })
+    .expect("cannot start Lassie");
+    let admin_port = daemon.admin_port().expect("admin listener is not running");
+
+    let response = ureq::get(&format!(
+        "http://127.0.0.1:{admin_port}/debug/pprof/heap?debug=1"
+    ))
+    .call();
+    let response = assert_ok_response(response);
+    let mut profile = String::new();
+    response
+        .into_reader()
+        .read_to_string(&mut profile)
+        .expect("cannot read the heap profile");
+    assert!(
+        profile.contains("heap profile"),
+        "unexpected profile: {profile}"
+    );
+}
+
+#[derive(Default)]
+struct CollectingSink {
+    status: Option<u16>,
+    content: Vec<u8>,
+}
+
+impl ResponseSink for CollectingSink {
+    fn head(&mut self, status: u16, _headers: &[(String, String)]) -> std::io::Result<()> {
+        self.status = Some(status);
+        Ok(())
+    }
+
+    fn chunk(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.content.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// A local HTTP provider serving a single-block CAR and a large multi-block file.
+///
+/// The provider pauses between blocks, so that retrievals of the large file take a few seconds.
+/// That gives the tests enough time to cancel them or to observe their progress.
+struct TestProvider {
+    provider: MockProvider,
+    small: Fixture,
+    large: Fixture,
+}
+
+impl TestProvider {
+    fn start() -> Self {
+        let small = Fixture::from_car(SMALL_CAR).expect("cannot parse the test CAR file");
+        let large = Fixture::unixfs_file(&file_content(64 * 1024 * 64), 64 * 1024);
+        let provider = MockProvider::start_with_block_delay(
+            vec![small.clone(), large.clone()],
+            Duration::from_millis(50),
+        )
+        .expect("cannot start the provider");
+        TestProvider {
+            provider,
+            small,
+            large,
+        }
+    }
+
+    fn small_path(&self) -> String {
+        format!("/ipfs/{}?{}", self.small.root(), self.provider.query())
+    }
+
+    fn large_path(&self) -> String {
+        format!("/ipfs/{}?{}", self.large.root(), self.provider.query())
+    }
+}
+
+/// Read `GET /libp2p` from the admin listener of `daemon`, which must not require a token.
+fn admin_libp2p_info(daemon: &Daemon) -> serde_json::Value {
+    let port = daemon.admin_port().expect("admin listener is not running");
+    let response = ureq::get(&format!("http://127.0.0.1:{port}/libp2p")).call();
+    let response = assert_ok_response(response);
+    serde_json::from_reader(response.into_reader()).expect("cannot parse the libp2p info")
+}
+
+/// Start a minimal OTLP/HTTP collector recording the bodies of the export requests, returns its
+/// endpoint.
+fn start_otlp_collector() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let exports = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&exports);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut content_length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && !line.trim_end().is_empty() {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0; content_length];
+            if reader.read_exact(&mut body).is_err() {
+                continue;
+            }
+            // Record the export before responding, the daemon waits for the response when it stops
+            recorded
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(&body).into_owned());
+            let _ = reader
+                .into_inner()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}");
+        }
+    });
+    (endpoint, exports)
+}
+
+/// Pseudo-random content, so that no two chunks are the same.
+#[allow(clippy::cast_possible_truncation)]
+fn file_content(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
 fn setup_test_env() -> MutexGuard<'static, ()> {
     let _ = env_logger::builder().is_test(true).try_init();
     let lock = TEST_GUARD.lock().expect("cannot obtain global test lock. This typically happens when one of the test fails; the problem should go away after you fix the test failure.");
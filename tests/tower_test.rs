@@ -0,0 +1,77 @@
+#![cfg(feature = "tower")]
+
+use http_body_util::BodyExt;
+use pretty_assertions::assert_eq;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tower::ServiceExt;
+
+use lassie::tower::LassieService;
+use lassie::{Daemon, DaemonConfig};
+
+// Rust runs tests in parallel. Since Lassie Daemon is a singleton,
+// we must synchronise the tests to ensure they run sequentially
+static TEST_GUARD: Mutex<()> = Mutex::new(());
+
+#[test]
+fn service_serves_ipfs_requests() {
+    let _lock = setup_test_env();
+    block_on(async {
+        let daemon = Arc::new(
+            Daemon::start(DaemonConfig {
+                disable_listener: true,
+                ..DaemonConfig::default()
+            })
+            .expect("cannot start Lassie"),
+        );
+        let service = LassieService::new(daemon);
+
+        let request = http::Request::builder()
+        .uri("/ipfs/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq?protocol=http&providers=/dns4/frisbii.fly.dev/https")
+        .header("Accept", "application/vnd.ipld.car")
+        .body(())
+        .expect("cannot build the request");
+        let response = service.oneshot(request).await.expect("infallible");
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let content = response
+            .into_body()
+            .collect()
+            .await
+            .expect("cannot read response body")
+            .to_bytes();
+        assert_eq!(
+            content.as_ref(),
+            include_bytes!(
+                "testdata/bafkreih25dih6ug3xtj73vswccw423b56ilrwmnos4cbwhrceudopdp5sq.car"
+            )
+        );
+    });
+}
+
+#[test]
+fn service_rejects_other_paths() {
+    let _lock = setup_test_env();
+    block_on(async {
+        let daemon = Arc::new(Daemon::start(DaemonConfig::default()).expect("cannot start Lassie"));
+        let service = LassieService::new(daemon);
+
+        let request = http::Request::builder()
+            .uri("/ipns/example.com")
+            .body(())
+            .expect("cannot build the request");
+        let response = service.oneshot(request).await.expect("infallible");
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    });
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Runtime::new()
+        .expect("cannot create tokio runtime")
+        .block_on(future)
+}
+
+fn setup_test_env() -> MutexGuard<'static, ()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let lock = TEST_GUARD.lock().expect("cannot obtain global test lock. This typically happens when one of the test fails; the problem should go away after you fix the test failure.");
+    lock
+}